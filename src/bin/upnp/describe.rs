@@ -0,0 +1,348 @@
+use crate::output::{print_document, print_records, Format};
+use serde_json::{json, Value};
+use std::str::FromStr;
+use upnp_rs::common::uri::URL;
+use upnp_rs::description::device::{fetch as fetch_device, Device, Service};
+use upnp_rs::description::service::{fetch as fetch_service, AllowedValue, Direction, Spcd};
+use upnp_rs::discovery::search::{search_once, Options, SearchTarget};
+use upnp_rs::error::Error;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Fetch and print the description of the device at `target`, which is either the URL of a
+/// device description or a UDN (`uuid:...`) which is first found using `search_options`.
+///
+pub fn do_describe(
+    target: String,
+    search_options: Options,
+    with_scpd: bool,
+    format: Format,
+) -> Result<(), String> {
+    let location = find_location(&target, search_options)?;
+    let root = fetch_device(&location).map_err(|e| e.to_string())?;
+    let described = DescribedDevice::new(&root.device, with_scpd);
+    match format {
+        Format::Text => {
+            println!("{} (UPnP/{})", location, root.spec_version);
+            print_device(&described, 1);
+        }
+        Format::Json | Format::JsonLines => print_document(
+            format,
+            &json!({
+                "location": location.to_string(),
+                "specVersion": root.spec_version.to_string(),
+                "configId": root.config_id,
+                "device": device_to_json(&described),
+            }),
+        ),
+        Format::Markdown | Format::Csv => {
+            // The tabular formats list each service, with the device that provides it.
+            let mut records: Vec<Value> = Default::default();
+            services_to_records(&described, &mut records);
+            print_records(format, &SERVICE_COLUMNS, &records);
+        }
+    }
+    Ok(())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const SERVICE_COLUMNS: [&str; 9] = [
+    "UDN",
+    "deviceType",
+    "friendlyName",
+    "serviceId",
+    "serviceType",
+    "SCPDURL",
+    "controlURL",
+    "eventSubURL",
+    "actions",
+];
+
+///
+/// A device, with the service descriptions of each of its services if they were requested.
+///
+#[derive(Debug)]
+struct DescribedDevice<'a> {
+    device: &'a Device,
+    services: Vec<(&'a Service, Option<Result<Spcd, Error>>)>,
+    devices: Vec<DescribedDevice<'a>>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl<'a> DescribedDevice<'a> {
+    fn new(device: &'a Device, with_scpd: bool) -> Self {
+        Self {
+            device,
+            services: device
+                .service_list
+                .iter()
+                .map(|service| {
+                    (
+                        service,
+                        if with_scpd {
+                            Some(fetch_service(&service.scpd_url))
+                        } else {
+                            None
+                        },
+                    )
+                })
+                .collect(),
+            devices: device
+                .device_list
+                .iter()
+                .map(|device| DescribedDevice::new(device, with_scpd))
+                .collect(),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the description URL for `target`, either the URL itself or, for a UDN, the location
+/// returned by a search for that device.
+///
+pub fn find_location(target: &str, mut search_options: Options) -> Result<URL, String> {
+    if let Some(uuid) = target.strip_prefix("uuid:") {
+        search_options.search_target = SearchTarget::Device(uuid.to_string());
+        let responses = search_once(search_options).map_err(|e| e.to_string())?;
+        match responses.first() {
+            Some(response) => Ok(response.location.clone()),
+            None => Err(format!("no device responded to a search for '{}'", target)),
+        }
+    } else {
+        match URL::from_str(target) {
+            Ok(url) if url.is_absolute() => Ok(url),
+            _ => Err(format!("'{}' is neither an absolute URL nor a UDN", target)),
+        }
+    }
+}
+
+fn print_device(described: &DescribedDevice<'_>, depth: usize) {
+    let device = described.device;
+    let indent = "  ".repeat(depth);
+    println!("{}{}", indent, device.friendly_name);
+    print_field(depth + 1, "device type", &device.device_type.to_string());
+    print_field(depth + 1, "UDN", &device.unique_device_name);
+    print_field(depth + 1, "manufacturer", &device.manufacturer);
+    print_field(
+        depth + 1,
+        "model",
+        &match &device.model_number {
+            Some(number) => format!("{} {}", device.model_name, number),
+            None => device.model_name.to_string(),
+        },
+    );
+    if let Some(serial_number) = &device.serial_number {
+        print_field(depth + 1, "serial number", serial_number);
+    }
+    if let Some(presentation_url) = &device.presentation_url {
+        print_field(depth + 1, "presentation", &presentation_url.to_string());
+    }
+    if !described.services.is_empty() {
+        println!("{}  services:", indent);
+        for (service, scpd) in &described.services {
+            print_service(service, scpd, depth + 2);
+        }
+    }
+    if !described.devices.is_empty() {
+        println!("{}  devices:", indent);
+        for embedded in &described.devices {
+            print_device(embedded, depth + 2);
+        }
+    }
+}
+
+fn print_service(service: &Service, scpd: &Option<Result<Spcd, Error>>, depth: usize) {
+    println!("{}{}", "  ".repeat(depth), service.service_id);
+    print_field(depth + 1, "service type", &service.service_type.to_string());
+    print_field(depth + 1, "SCPD URL", &service.scpd_url.to_string());
+    print_field(depth + 1, "control URL", &service.control_url.to_string());
+    print_field(depth + 1, "event URL", &service.event_sub_url.to_string());
+    match scpd {
+        None => {}
+        Some(Err(error)) => print_field(depth + 1, "error", &error.to_string()),
+        Some(Ok(scpd)) => {
+            println!("{}actions:", "  ".repeat(depth + 1));
+            for action in &scpd.action_list {
+                println!("{}{}", "  ".repeat(depth + 2), action.name);
+                for argument in &action.argument_list {
+                    let (data_type, allowed) =
+                        argument_type(scpd, &argument.related_state_variable);
+                    println!(
+                        "{}{:<4} {:<24} {:<12} {}",
+                        "  ".repeat(depth + 3),
+                        direction(&argument.direction),
+                        argument.name,
+                        data_type,
+                        allowed.unwrap_or_default()
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn print_field(depth: usize, label: &str, value: &str) {
+    println!(
+        "{}{:<14}{}",
+        "  ".repeat(depth),
+        format!("{}:", label),
+        value
+    );
+}
+
+fn device_to_json(described: &DescribedDevice<'_>) -> Value {
+    let device = described.device;
+    json!({
+        "deviceType": device.device_type.to_string(),
+        "friendlyName": device.friendly_name,
+        "manufacturer": device.manufacturer,
+        "manufacturerURL": device.manufacturer_url.as_ref().map(|url| url.to_string()),
+        "modelDescription": device.model_description,
+        "modelName": device.model_name,
+        "modelNumber": device.model_number,
+        "modelURL": device.model_url.as_ref().map(|url| url.to_string()),
+        "serialNumber": device.serial_number,
+        "UDN": device.unique_device_name,
+        "UPC": device.upc,
+        "presentationURL": device.presentation_url.as_ref().map(|url| url.to_string()),
+        "services": described
+            .services
+            .iter()
+            .map(|(service, scpd)| service_to_json(service, scpd))
+            .collect::<Vec<Value>>(),
+        "devices": described
+            .devices
+            .iter()
+            .map(device_to_json)
+            .collect::<Vec<Value>>(),
+    })
+}
+
+fn services_to_records(described: &DescribedDevice<'_>, records: &mut Vec<Value>) {
+    let device = described.device;
+    for (service, scpd) in &described.services {
+        records.push(json!({
+            "UDN": device.unique_device_name,
+            "deviceType": device.device_type.to_string(),
+            "friendlyName": device.friendly_name,
+            "serviceId": service.service_id,
+            "serviceType": service.service_type.to_string(),
+            "SCPDURL": service.scpd_url.to_string(),
+            "controlURL": service.control_url.to_string(),
+            "eventSubURL": service.event_sub_url.to_string(),
+            "actions": match scpd {
+                Some(Ok(scpd)) => Some(
+                    scpd.action_list
+                        .iter()
+                        .map(|action| action.name.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(" "),
+                ),
+                Some(Err(error)) => Some(error.to_string()),
+                None => None,
+            },
+        }));
+    }
+    for embedded in &described.devices {
+        services_to_records(embedded, records);
+    }
+}
+
+fn service_to_json(service: &Service, scpd: &Option<Result<Spcd, Error>>) -> Value {
+    let mut value = json!({
+        "serviceId": service.service_id,
+        "serviceType": service.service_type.to_string(),
+        "SCPDURL": service.scpd_url.to_string(),
+        "controlURL": service.control_url.to_string(),
+        "eventSubURL": service.event_sub_url.to_string(),
+    });
+    match scpd {
+        None => {}
+        Some(Err(error)) => {
+            value["error"] = json!(error.to_string());
+        }
+        Some(Ok(scpd)) => {
+            value["actions"] = scpd
+                .action_list
+                .iter()
+                .map(|action| {
+                    json!({
+                        "name": action.name,
+                        "arguments": action
+                            .argument_list
+                            .iter()
+                            .map(|argument| {
+                                let variable = scpd.state_variable(&argument.related_state_variable);
+                                json!({
+                                    "name": argument.name,
+                                    "direction": direction(&argument.direction),
+                                    "retval": argument.return_value,
+                                    "relatedStateVariable": argument.related_state_variable,
+                                    "dataType": variable.map(|v| v.data_type.clone()),
+                                    "allowedValues": variable
+                                        .and_then(|v| v.allowed_values.as_ref())
+                                        .map(allowed_to_json),
+                                })
+                            })
+                            .collect::<Vec<Value>>(),
+                    })
+                })
+                .collect();
+        }
+    }
+    value
+}
+
+fn allowed_to_json(allowed: &AllowedValue) -> Value {
+    match allowed {
+        AllowedValue::List { values } => json!(values),
+        AllowedValue::Range {
+            minimum,
+            maximum,
+            step,
+        } => json!({ "minimum": minimum, "maximum": maximum, "step": step }),
+    }
+}
+
+fn argument_type(scpd: &Spcd, related_state_variable: &str) -> (String, Option<String>) {
+    match scpd.state_variable(related_state_variable) {
+        None => ("?".to_string(), None),
+        Some(variable) => (
+            variable.data_type.clone(),
+            variable
+                .allowed_values
+                .as_ref()
+                .map(|allowed| match allowed {
+                    AllowedValue::List { values } => format!("[{}]", values.join(", ")),
+                    AllowedValue::Range {
+                        minimum,
+                        maximum,
+                        step,
+                    } => match step {
+                        Some(step) => format!("[{}..{} step {}]", minimum, maximum, step),
+                        None => format!("[{}..{}]", minimum, maximum),
+                    },
+                }),
+        ),
+    }
+}
+
+fn direction(direction: &Direction) -> &'static str {
+    match direction {
+        Direction::In => "in",
+        Direction::Out => "out",
+    }
+}
@@ -0,0 +1,123 @@
+use crate::describe::find_location;
+use crate::output::{print_document, print_records, Format};
+use crate::CommandLineError;
+use serde_json::{json, Value};
+use upnp_rs::common::soap::Action;
+use upnp_rs::control::{invoke, validate_arguments};
+use upnp_rs::description::device::{fetch as fetch_device, Device, Service};
+use upnp_rs::description::service::fetch as fetch_service;
+use upnp_rs::discovery::search::Options;
+use upnp_rs::error::Error;
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Parse a command-line argument of the form `Name=Value`.
+///
+pub fn parse_argument(s: &str) -> Result<(String, String), CommandLineError> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(CommandLineError::InvalidParameterValue(
+            "argument".to_string(),
+            s.to_string(),
+        )),
+    }
+}
+
+///
+/// Invoke `action_name` on the service identified by `service` (a service ID, service type, or
+/// service type name) of the device at `device`, which is either a description URL or a UDN.
+///
+pub fn do_invoke(
+    device: String,
+    service: String,
+    action_name: String,
+    arguments: Vec<(String, String)>,
+    search_options: Options,
+    format: Format,
+) -> Result<(), String> {
+    let out_arguments = invoke_action(&device, &service, &action_name, arguments, search_options)?;
+    let records: Vec<Value> = out_arguments
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect();
+    match format {
+        Format::Text => {
+            for (name, value) in out_arguments {
+                println!("{}={}", name, value);
+            }
+        }
+        Format::Json | Format::JsonLines => print_document(
+            format,
+            &json!({ "action": action_name, "arguments": records }),
+        ),
+        Format::Markdown | Format::Csv => print_records(format, &["name", "value"], &records),
+    }
+    Ok(())
+}
+
+///
+/// Find the service identified by `service`, a service ID, service type, or service type name,
+/// in `device` or any of its embedded devices.
+///
+pub fn find_service<'a>(device: &'a Device, service: &str) -> Option<&'a Service> {
+    device
+        .service_list
+        .iter()
+        .find(|candidate| service_matches(candidate, service))
+        .or_else(|| {
+            device
+                .device_list
+                .iter()
+                .find_map(|embedded| find_service(embedded, service))
+        })
+}
+
+///
+/// Returns `true` if `candidate` is identified by `service`, a service ID, service type, or
+/// service type name.
+///
+pub fn service_matches(candidate: &Service, service: &str) -> bool {
+    candidate.service_id == service
+        || candidate.service_type.to_string() == service
+        || candidate.service_type.name() == service
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn invoke_action(
+    device: &str,
+    service: &str,
+    action_name: &str,
+    arguments: Vec<(String, String)>,
+    search_options: Options,
+) -> Result<Vec<(String, String)>, String> {
+    let location = find_location(device, search_options)?;
+    let root = fetch_device(&location).map_err(|e| e.to_string())?;
+    let service = match find_service(&root.device, service) {
+        Some(found) => found,
+        None => {
+            return Err(format!(
+                "device '{}' has no service '{}'",
+                root.device.friendly_name, service
+            ))
+        }
+    };
+    let spcd = fetch_service(&service.scpd_url).map_err(|e| e.to_string())?;
+    let arguments =
+        validate_arguments(&spcd, action_name, &arguments).map_err(|e| e.to_string())?;
+    let action = Action::new(service.service_type.clone(), action_name.to_string());
+    match invoke(&service.control_url, &action, arguments) {
+        Ok(out_arguments) => Ok(out_arguments),
+        Err(Error::Upnp(error)) => Err(format!(
+            "device returned UPnP error {}: {}",
+            error.code(),
+            error.description()
+        )),
+        Err(error) => Err(error.to_string()),
+    }
+}
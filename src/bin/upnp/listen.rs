@@ -0,0 +1,341 @@
+use crate::output::{epoch_millis, headers_to_json, print_status, Format, RecordWriter};
+use crate::timestamp;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use upnp_rs::discovery::listen::{Listener, Message, Options};
+use upnp_rs::discovery::notify::{Notification, NotificationSubType};
+use upnp_rs::discovery::search::{SearchRequest, SearchTarget};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The set of filters applied to each message; a message is only shown if it passes all those
+/// that are present.
+///
+#[derive(Debug, Default)]
+pub struct Filter {
+    pub target: Option<SearchTarget>,
+    pub usn: Option<String>,
+    pub from: Option<IpAddr>,
+    pub nts: Option<NotificationSubType>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+pub fn parse_nts(s: &str) -> Result<NotificationSubType, String> {
+    NotificationSubType::from_str(s)
+        .or_else(|_| NotificationSubType::from_str(&format!("ssdp:{}", s)))
+        .map_err(|_| format!("Value '{}' invalid for parameter nts", s))
+}
+
+pub fn do_listen(
+    options: Options,
+    filter: Filter,
+    summary: bool,
+    format: Format,
+) -> Result<(), String> {
+    if summary && format != Format::Text {
+        return Err("the summary table is only available in the text format".to_string());
+    }
+    let stopped = Arc::new(AtomicBool::new(false));
+    let handler_stopped = stopped.clone();
+    ctrlc::set_handler(move || handler_stopped.store(true, Ordering::SeqCst))
+        .map_err(|e| e.to_string())?;
+
+    let listener = Listener::new(&options).map_err(|error| error.to_string())?;
+    if !summary {
+        print_status(
+            format,
+            format!(
+                "Listening on {}, press Ctrl-C to stop",
+                options
+                    .group_address()
+                    .map(|address| address.to_string())
+                    .unwrap_or_default()
+            ),
+        );
+    }
+    let mut writer = RecordWriter::new(format, &MESSAGE_COLUMNS);
+    let mut devices: HashMap<String, DeviceEntry> = Default::default();
+    while !stopped.load(Ordering::SeqCst) {
+        match listener.next_message(REDRAW_INTERVAL) {
+            Ok(Some((from, message))) if filter.accepts(&from, &message) => {
+                if summary {
+                    if let Message::Notification(notification) = message {
+                        update_summary(&mut devices, from, notification);
+                    }
+                } else if format == Format::Text {
+                    print_message(&from, &message);
+                } else {
+                    writer.write(&message_to_json(&from, &message));
+                }
+            }
+            Ok(_) => {}
+            Err(error) => {
+                writer.finish();
+                return Err(error.to_string());
+            }
+        }
+        if summary {
+            print_summary(&devices);
+        }
+    }
+    writer.finish();
+    Ok(())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
+const MESSAGE_COLUMNS: [&str; 13] = [
+    "time",
+    "from",
+    "method",
+    "nts",
+    "target",
+    "usn",
+    "location",
+    "maxAge",
+    "server",
+    "bootId",
+    "configId",
+    "maxWait",
+    "userAgent",
+];
+
+#[derive(Debug, PartialEq)]
+enum DeviceState {
+    Alive,
+    ByeBye,
+}
+
+#[derive(Debug)]
+struct DeviceEntry {
+    notification_type: SearchTarget,
+    location: Option<String>,
+    from: SocketAddr,
+    state: DeviceState,
+    last_seen: Instant,
+    expires: Option<Instant>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Filter {
+    fn accepts(&self, from: &SocketAddr, message: &Message) -> bool {
+        if let Some(address) = &self.from {
+            if from.ip() != *address {
+                return false;
+            }
+        }
+        match message {
+            Message::Notification(notification) => {
+                self.target
+                    .as_ref()
+                    .map(|target| target.matches(&notification.notification_type))
+                    .unwrap_or(true)
+                    && self
+                        .usn
+                        .as_ref()
+                        .map(|usn| notification.service_name.to_string().contains(usn))
+                        .unwrap_or(true)
+                    && self
+                        .nts
+                        .map(|nts| notification.sub_type == nts)
+                        .unwrap_or(true)
+            }
+            Message::Search(search) => {
+                // Searches have no USN or NTS, so they are only shown if neither is filtered on.
+                self.usn.is_none()
+                    && self.nts.is_none()
+                    && self
+                        .target
+                        .as_ref()
+                        .map(|target| {
+                            target.matches(&search.search_target)
+                                || search.search_target.matches(target)
+                        })
+                        .unwrap_or(true)
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn print_message(from: &SocketAddr, message: &Message) {
+    match message {
+        Message::Notification(notification) => print_notification(from, notification),
+        Message::Search(search) => print_search(from, search),
+    }
+}
+
+fn print_notification(from: &SocketAddr, notification: &Notification) {
+    println!(
+        "{} {} NOTIFY {} NT={} USN={}",
+        timestamp(),
+        from,
+        notification.sub_type,
+        notification.notification_type,
+        notification.service_name
+    );
+    if let Some(location) = &notification.location {
+        println!("    LOCATION={}", location);
+    }
+    if let Some(max_age) = &notification.max_age {
+        println!("    max-age={}", max_age.as_secs());
+    }
+    if let Some(server) = &notification.server {
+        println!("    SERVER={}", server);
+    }
+    if let Some(boot_id) = &notification.boot_id {
+        println!("    BOOTID={}", boot_id);
+    }
+    if let Some(config_id) = &notification.config_id {
+        println!("    CONFIGID={}", config_id);
+    }
+}
+
+fn print_search(from: &SocketAddr, search: &SearchRequest) {
+    println!(
+        "{} {} M-SEARCH ST={} MX={}",
+        timestamp(),
+        from,
+        search.search_target,
+        search
+            .max_wait_time
+            .map(|mx| mx.to_string())
+            .unwrap_or_else(|| "-".to_string())
+    );
+    if let Some(user_agent) = &search.user_agent {
+        println!("    USER-AGENT={}", user_agent);
+    }
+    if let Some(control_point) = &search.control_point {
+        println!("    CPFN={}", control_point.friendly_name);
+    }
+}
+
+///
+/// The stable JSON form of a message; notifications use `target` for `NT` and searches use it
+/// for `ST`, and fields that do not apply to a message are `null`.
+///
+fn message_to_json(from: &SocketAddr, message: &Message) -> Value {
+    match message {
+        Message::Notification(notification) => json!({
+            "time": epoch_millis(),
+            "from": from.to_string(),
+            "method": "NOTIFY",
+            "nts": notification.sub_type.to_string(),
+            "target": notification.notification_type.to_string(),
+            "usn": notification.service_name.to_string(),
+            "location": notification.location.as_ref().map(|location| location.to_string()),
+            "maxAge": notification.max_age.map(|max_age| max_age.as_secs()),
+            "server": notification.server,
+            "bootId": notification.boot_id,
+            "nextBootId": notification.next_boot_id,
+            "configId": notification.config_id,
+            "searchPort": notification.search_port,
+            "secureLocation": notification
+                .secure_location
+                .as_ref()
+                .map(|location| location.to_string()),
+            "otherHeaders": headers_to_json(&notification.other_headers),
+        }),
+        Message::Search(search) => json!({
+            "time": epoch_millis(),
+            "from": from.to_string(),
+            "method": "M-SEARCH",
+            "target": search.search_target.to_string(),
+            "maxWait": search.max_wait_time,
+            "userAgent": search.user_agent,
+            "controlPoint": search
+                .control_point
+                .as_ref()
+                .map(|control_point| control_point.friendly_name.clone()),
+            "otherHeaders": headers_to_json(&search.other_headers),
+        }),
+    }
+}
+
+fn update_summary(
+    devices: &mut HashMap<String, DeviceEntry>,
+    from: SocketAddr,
+    notification: Notification,
+) {
+    let now = Instant::now();
+    let usn = notification.service_name.to_string();
+    let state = if notification.sub_type == NotificationSubType::ByeBye {
+        DeviceState::ByeBye
+    } else {
+        DeviceState::Alive
+    };
+    let previous = devices.remove(&usn);
+    let entry = DeviceEntry {
+        notification_type: notification.notification_type,
+        location: notification
+            .location
+            .map(|location| location.to_string())
+            .or_else(|| previous.as_ref().and_then(|p| p.location.clone())),
+        from,
+        state,
+        last_seen: now,
+        expires: notification
+            .max_age
+            .map(|max_age| now + max_age)
+            .or_else(|| previous.as_ref().and_then(|p| p.expires)),
+    };
+    let _ = devices.insert(usn, entry);
+}
+
+fn print_summary(devices: &HashMap<String, DeviceEntry>) {
+    let now = Instant::now();
+    let mut usns: Vec<&String> = devices.keys().collect();
+    usns.sort();
+    // Clear the screen and move the cursor home before redrawing the table.
+    print!("\x1b[2J\x1b[H");
+    println!("{} - {} device(s)\n", timestamp(), devices.len());
+    println!(
+        "{:<8} {:>6} {:>8}  {:<22} {:<40} USN / LOCATION",
+        "STATE", "SEEN", "EXPIRES", "FROM", "NT"
+    );
+    for usn in usns {
+        let entry = devices.get(usn).unwrap();
+        let state = match (&entry.state, entry.expires) {
+            (DeviceState::ByeBye, _) => "byebye",
+            (DeviceState::Alive, Some(expires)) if expires <= now => "expired",
+            (DeviceState::Alive, _) => "alive",
+        };
+        let expires = match entry.expires {
+            Some(expires) if expires > now => format!("{}s", (expires - now).as_secs()),
+            _ => "-".to_string(),
+        };
+        println!(
+            "{:<8} {:>5}s {:>8}  {:<22} {:<40} {}",
+            state,
+            (now - entry.last_seen).as_secs(),
+            expires,
+            entry.from.to_string(),
+            entry.notification_type.to_string(),
+            usn
+        );
+        if let Some(location) = &entry.location {
+            println!("{:>90}{}", "", location);
+        }
+    }
+}
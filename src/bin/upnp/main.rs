@@ -0,0 +1,732 @@
+use human_panic::setup_panic;
+use ipnetwork::IpNetwork;
+use output::{headers_to_json, print_error, print_records, Format};
+use serde_json::{json, Value};
+use std::fmt::Display;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use structopt::StructOpt;
+use tracing::info;
+use upnp_rs::common::http::http_date;
+use upnp_rs::common::interface::IP;
+use upnp_rs::discovery::guard::GuardOptions;
+use upnp_rs::discovery::listen::Options as ListenOptions;
+use upnp_rs::discovery::notify::{NotificationSubType, Options as NotifyOptions};
+use upnp_rs::discovery::search::*;
+use upnp_rs::SpecVersion;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(StructOpt)]
+#[structopt(name = "upnp", about = "UPnP simple qery/discovery tool.")]
+struct CommandLine {
+    /// The level of logging to perform, from off to trace; the default is off
+    #[structopt(long, short = "v", parse(from_occurrences))]
+    verbose: i8,
+
+    /// The network interface name to bind to; the default is all
+    #[structopt(long)]
+    interface: Option<String>,
+
+    /// Use IPv6 instead of the default v4
+    #[structopt(long, short = "6")]
+    use_ipv6: bool,
+
+    /// The UPnP version to use, 1.0, 1.1, or 2.0; the default is 1.0
+    #[structopt(long, short = "V")]
+    spec_version: Option<String>,
+
+    /// The output format, text, markdown, json, jsonl, or csv; the default is text
+    #[structopt(long, default_value = "text")]
+    format: Format,
+
+    #[structopt(subcommand)]
+    cmd: Command,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Issue a multicast search to find devices
+    Search {
+        /// The UPnP search target (all, root, device:{id}, device-type:{id}, service-type:{id});
+        /// the default is root
+        #[structopt(long, short)]
+        search_target: Option<CLSearchTarget>,
+
+        /// A domain to use in constructing device and service type targets; the default is the UPnP
+        /// domain
+        #[structopt(long, short)]
+        domain: Option<String>,
+
+        /// The maximum wait time, in seconds, for devices to respond to multicast; the default is 2
+        #[structopt(long, short = "w")]
+        max_wait: Option<u8>,
+
+        /// Multicast address, default: 239.255.255.250
+        #[structopt(long, short = "a")]
+        address: Option<String>,
+
+        /// Multicast port, default: 1900
+        #[structopt(long, short = "p")]
+        port: Option<u16>,
+
+        /// Multicast bind port, default: random
+        #[structopt(long, short = "b")]
+        bind_port: Option<u16>,
+
+        /// Send a unicast search to this device address (ip or ip:port, default port 1900)
+        /// rather than multicast
+        #[structopt(long, parse(try_from_str = parse_device_address))]
+        device: Option<SocketAddr>,
+
+        /// An additional header to send, in the form Name:Value; may be repeated
+        #[structopt(long = "header", short = "H", parse(try_from_str = parse_header))]
+        headers: Vec<(String, String)>,
+    },
+    /// Fetch and print the description of a device, and optionally its services
+    Describe {
+        /// The URL of the device description, or the device UDN (uuid:{id}) to search for
+        target: String,
+
+        /// Also fetch the description of each service, listing its actions and arguments
+        #[structopt(long, short)]
+        scpd: bool,
+
+        /// The maximum wait time, in seconds, for the device to respond when searching by UDN; the
+        /// default is 2
+        #[structopt(long, short = "w")]
+        max_wait: Option<u8>,
+    },
+    /// Invoke an action on a device service, printing the out arguments
+    Invoke {
+        /// The URL of the device description, or the device UDN (uuid:{id}) to search for
+        #[structopt(long, short)]
+        device: String,
+
+        /// The service ID, service type, or service type name (such as SwitchPower) to invoke the
+        /// action on
+        #[structopt(long, short)]
+        service: String,
+
+        /// The maximum wait time, in seconds, for the device to respond when searching by UDN; the
+        /// default is 2
+        #[structopt(long, short = "w")]
+        max_wait: Option<u8>,
+
+        /// The name of the action to invoke
+        action: String,
+
+        /// The in arguments of the action, each in the form Name=Value
+        #[structopt(parse(try_from_str = invoke::parse_argument))]
+        arguments: Vec<(String, String)>,
+    },
+    /// Subscribe to events from a device service, printing each property change until Ctrl-C
+    Subscribe {
+        /// The URL of the device description, or the device UDN (uuid:{id}) to search for
+        #[structopt(long, short)]
+        device: String,
+
+        /// The service ID, service type, or service type name (such as SwitchPower) to subscribe
+        /// to
+        #[structopt(long, short)]
+        service: String,
+
+        /// The requested subscription duration, in seconds; the default is 1800
+        #[structopt(long, short, default_value = "1800")]
+        timeout: u64,
+
+        /// The local port to receive events on; the default is random
+        #[structopt(long, short)]
+        port: Option<u16>,
+
+        /// The maximum wait time, in seconds, for the device to respond when searching by UDN; the
+        /// default is 2
+        #[structopt(long, short = "w")]
+        max_wait: Option<u8>,
+    },
+    /// Host a simulated device, serving its description, advertising it, and answering searches
+    /// and actions until Ctrl-C
+    #[structopt(alias = "advertise")]
+    Serve {
+        /// The device description document to serve
+        #[structopt(long, short, parse(from_os_str))]
+        description: PathBuf,
+
+        /// The directory containing the service descriptions; the default is the directory
+        /// containing the device description
+        #[structopt(long, short, parse(from_os_str))]
+        scpd: Option<PathBuf>,
+
+        /// A JSON file defining the responses to actions; by default each out argument takes the
+        /// default value of its state variable
+        #[structopt(long, short, parse(from_os_str))]
+        config: Option<PathBuf>,
+
+        /// The HTTP port to serve on; the default is random
+        #[structopt(long, short)]
+        port: Option<u16>,
+
+        /// The number of seconds advertisements remain valid; the default is 1800
+        #[structopt(long, short)]
+        max_age: Option<u16>,
+
+        /// Only answer searches from this network, for example 192.168.1.0/24; may be repeated.
+        /// The default is the networks of this host's interfaces
+        #[structopt(long, short)]
+        allow: Vec<IpNetwork>,
+    },
+    /// Listen for device notifications and searches on the multicast group
+    Listen {
+        /// Only show messages with an NT, or ST, that matches this target (ssdp:all,
+        /// upnp:rootdevice, uuid:{id}, urn:{domain}:device:{type}:{ver}, ...)
+        #[structopt(long, short)]
+        target: Option<SearchTarget>,
+
+        /// Only show notifications with a USN that contains this value
+        #[structopt(long, short)]
+        usn: Option<String>,
+
+        /// Only show messages sent from this IP address
+        #[structopt(long, short)]
+        from: Option<IpAddr>,
+
+        /// Only show notifications of this kind (alive, byebye, update)
+        #[structopt(long, short, parse(try_from_str = listen::parse_nts))]
+        nts: Option<NotificationSubType>,
+
+        /// Show a live table of devices rather than each message as it arrives
+        #[structopt(long, short = "S")]
+        summary: bool,
+
+        /// Multicast address, default: 239.255.255.250, or FF02::C with -6
+        #[structopt(long, short = "a")]
+        address: Option<String>,
+
+        /// Multicast port, default: 1900
+        #[structopt(long, short = "p")]
+        port: Option<u16>,
+
+        /// The size, in bytes, of the buffer each message is read into; the default is 8192
+        #[structopt(long)]
+        buffer_size: Option<usize>,
+    },
+}
+
+#[derive(Debug)]
+pub enum CLSearchTarget {
+    All,
+    RootDevice,
+    Device(String),
+    DeviceType(String),
+    ServiceType(String),
+    Raw(String),
+}
+
+#[derive(Debug)]
+pub enum CommandLineError {
+    MissingParameter(String),
+    UnexpectedParameter(String),
+    InvalidParameterValue(String, String),
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const SEARCH_COLUMNS: [&str; 16] = [
+    "from",
+    "interface",
+    "searchTarget",
+    "usn",
+    "location",
+    "maxAge",
+    "date",
+    "product",
+    "upnp",
+    "platform",
+    "bootId",
+    "configId",
+    "searchPort",
+    "secureLocation",
+    "extension",
+    "otherHeaders",
+];
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl FromStr for CLSearchTarget {
+    type Err = CommandLineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Ok(CLSearchTarget::RootDevice)
+        } else if s == "all" {
+            Ok(CLSearchTarget::All)
+        } else if s == "root" {
+            Ok(CLSearchTarget::RootDevice)
+        } else if let Some(stripped) = s.strip_prefix("raw:") {
+            Ok(CLSearchTarget::Raw(stripped.to_string()))
+        } else if let Some(stripped) = s.strip_prefix("device:") {
+            Ok(CLSearchTarget::Device(stripped.to_string()))
+        } else if let Some(stripped) = s.strip_prefix("device-type:") {
+            Ok(CLSearchTarget::DeviceType(stripped.to_string()))
+        } else if let Some(stripped) = s.strip_prefix("service-type:") {
+            Ok(CLSearchTarget::ServiceType(stripped.to_string()))
+        } else {
+            Err(CommandLineError::InvalidParameterValue(
+                "search_target".to_string(),
+                s.to_string(),
+            ))
+        }
+    }
+}
+
+impl Display for CommandLineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandLineError::MissingParameter(p) => write!(f, "Parameter {} required", p),
+            CommandLineError::UnexpectedParameter(p) => write!(f, "Parameter {} unnecessary", p),
+            CommandLineError::InvalidParameterValue(p, v) => {
+                write!(f, "Value '{}' invalid for parameter {}", v, p)
+            }
+        }
+    }
+}
+
+pub fn main() {
+    setup_panic!();
+
+    let args = CommandLine::from_args();
+
+    init_tracing(args.verbose);
+
+    let format = args.format;
+    let (command, result) = match args.cmd {
+        Command::Search {
+            search_target,
+            domain,
+            max_wait,
+            address,
+            port,
+            bind_port,
+            device,
+            headers,
+        } => (
+            "search",
+            do_search(
+                parse_version(args.spec_version),
+                args.interface,
+                if args.use_ipv6 { IP::V6 } else { IP::V4 },
+                search_target,
+                domain,
+                max_wait,
+                address,
+                port,
+                bind_port,
+                device,
+                headers,
+                format,
+            ),
+        ),
+        Command::Describe {
+            target,
+            scpd,
+            max_wait,
+        } => (
+            "describe",
+            describe::do_describe(
+                target,
+                search_options(
+                    parse_version(args.spec_version),
+                    args.interface,
+                    args.use_ipv6,
+                    max_wait,
+                ),
+                scpd,
+                format,
+            ),
+        ),
+        Command::Invoke {
+            device,
+            service,
+            max_wait,
+            action,
+            arguments,
+        } => (
+            "invoke",
+            invoke::do_invoke(
+                device,
+                service,
+                action,
+                arguments,
+                search_options(
+                    parse_version(args.spec_version),
+                    args.interface,
+                    args.use_ipv6,
+                    max_wait,
+                ),
+                format,
+            ),
+        ),
+        Command::Subscribe {
+            device,
+            service,
+            timeout,
+            port,
+            max_wait,
+        } => (
+            "subscribe",
+            subscribe::do_subscribe(
+                device,
+                service,
+                timeout,
+                port,
+                search_options(
+                    parse_version(args.spec_version),
+                    args.interface,
+                    args.use_ipv6,
+                    max_wait,
+                ),
+                format,
+            ),
+        ),
+        Command::Serve {
+            description,
+            scpd,
+            config,
+            port,
+            max_age,
+            allow,
+        } => {
+            let mut options = NotifyOptions::default_for(parse_version(args.spec_version));
+            options.network_interface = args.interface.clone();
+            options.network_version = Some(if args.use_ipv6 { IP::V6 } else { IP::V4 });
+            if let Some(max_age) = max_age {
+                options.max_age = max_age;
+            }
+            (
+                "serve",
+                serve::do_serve(
+                    description,
+                    scpd,
+                    config,
+                    port,
+                    options,
+                    ListenOptions {
+                        network_interface: args.interface,
+                        network_version: Some(if args.use_ipv6 { IP::V6 } else { IP::V4 }),
+                        address: None,
+                        port: None,
+                        buffer_size: None,
+                    },
+                    GuardOptions {
+                        allowed_networks: allow,
+                        ..Default::default()
+                    },
+                    format,
+                ),
+            )
+        }
+        Command::Listen {
+            target,
+            usn,
+            from,
+            nts,
+            summary,
+            address,
+            port,
+            buffer_size,
+        } => (
+            "listen",
+            listen::do_listen(
+                ListenOptions {
+                    network_interface: args.interface,
+                    network_version: Some(if args.use_ipv6 { IP::V6 } else { IP::V4 }),
+                    address,
+                    port,
+                    buffer_size,
+                },
+                listen::Filter {
+                    target,
+                    usn,
+                    from,
+                    nts,
+                },
+                summary,
+                format,
+            ),
+        ),
+    };
+    if let Err(error) = result {
+        print_error(format, command, error);
+        std::process::exit(1);
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn init_tracing(verbosity: i8) {
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::{EnvFilter, FmtSubscriber};
+
+    let log_level = match verbosity {
+        0 => LevelFilter::OFF,
+        1 => LevelFilter::ERROR,
+        2 => LevelFilter::WARN,
+        3 => LevelFilter::INFO,
+        4 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+
+    let filter = EnvFilter::from_default_env()
+        .add_directive(
+            format!("{}={}", module_path!(), log_level)
+                .parse()
+                .expect("Issue with command-line trace directive"),
+        )
+        .add_directive(
+            format!("upnp={}", log_level)
+                .parse()
+                .expect("Issue with library trace directive"),
+        );
+    let subscriber = FmtSubscriber::builder().with_env_filter(filter).finish();
+
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("Unable to set global default tracing subscriber");
+    info!("Log level set to `LevelFilter::{:?}`", log_level);
+}
+
+fn timestamp() -> String {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = since_epoch.as_secs() % 86_400;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}Z",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+fn parse_version(version: Option<String>) -> SpecVersion {
+    if let Some(s) = version {
+        if &s == "1.0" {
+            SpecVersion::V10
+        } else if &s == "1.1" {
+            SpecVersion::V11
+        } else if &s == "2.0" {
+            SpecVersion::V20
+        } else {
+            SpecVersion::default()
+        }
+    } else {
+        SpecVersion::default()
+    }
+}
+
+fn parse_header(s: &str) -> Result<(String, String), CommandLineError> {
+    match s.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(CommandLineError::InvalidParameterValue(
+            "header".to_string(),
+            s.to_string(),
+        )),
+    }
+}
+
+fn parse_device_address(s: &str) -> Result<SocketAddr, String> {
+    match SocketAddr::from_str(s) {
+        Ok(address) => Ok(address),
+        Err(_) => IpAddr::from_str(s)
+            .map(|address| SocketAddr::new(address, 1900))
+            .map_err(|error| error.to_string()),
+    }
+}
+
+fn search_options(
+    spec_version: SpecVersion,
+    bind_to_interface: Option<String>,
+    use_ipv6: bool,
+    max_wait_time: Option<u8>,
+) -> Options {
+    let mut options = Options::default_for(spec_version);
+    options.network_interface = bind_to_interface;
+    options.network_version = Some(if use_ipv6 { IP::V6 } else { IP::V4 });
+    if let Some(max_wait_time) = max_wait_time {
+        options.max_wait_time = max_wait_time;
+    }
+    options
+}
+
+#[allow(clippy::too_many_arguments)]
+fn do_search(
+    spec_version: SpecVersion,
+    bind_to_interface: Option<String>,
+    ip_version: IP,
+    search_target: Option<CLSearchTarget>,
+    domain: Option<String>,
+    max_wait_time: Option<u8>,
+    address: Option<String>,
+    port: Option<u16>,
+    bind_port: Option<u16>,
+    device: Option<SocketAddr>,
+    headers: Vec<(String, String)>,
+    format: Format,
+) -> Result<(), String> {
+    let mut options = Options::default_for(spec_version);
+    options.extra_headers = headers.into_iter().collect();
+    options.address = address;
+    options.port = port;
+    options.bind_port = bind_port;
+    options.network_interface = bind_to_interface;
+    options.network_version = Some(ip_version);
+    if let Some(search_target) = search_target {
+        options.search_target = match search_target {
+            CLSearchTarget::All => SearchTarget::All,
+            CLSearchTarget::RootDevice => SearchTarget::RootDevice,
+            CLSearchTarget::Device(d) => SearchTarget::Device(d),
+            CLSearchTarget::DeviceType(dt) => {
+                if let Some(domain) = domain {
+                    SearchTarget::DomainDeviceType(domain, dt)
+                } else {
+                    SearchTarget::DeviceType(dt)
+                }
+            }
+            CLSearchTarget::ServiceType(st) => {
+                if let Some(domain) = domain {
+                    SearchTarget::DomainServiceType(domain, st)
+                } else {
+                    SearchTarget::ServiceType(st)
+                }
+            }
+            CLSearchTarget::Raw(st) => SearchTarget::Raw(st),
+        }
+    }
+    if let Some(max_wait_time) = max_wait_time {
+        options.max_wait_time = max_wait_time;
+    }
+    let network_interface = options.network_interface.clone();
+    let search_target = options.search_target.clone();
+    let max_wait_time = options.max_wait_time;
+    let responses = match device {
+        Some(device) => search_once_to_device(options, device),
+        None => search_once(options),
+    }
+    .map_err(|error| error.to_string())?;
+    match format {
+        Format::Text => {
+            for response in responses.iter() {
+                println!("{} {}", response.service_name, response.location);
+                println!(
+                    "    ST={} FROM={} SERVER={}",
+                    response.search_target,
+                    response
+                        .from
+                        .map(|from| from.to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    response.versions
+                );
+            }
+        }
+        Format::Markdown => {
+            println!(
+                r#"
+# UPnP Search Response
+
+Search parameters
+
+* UPnP version: {}
+* Search Target: `{}`
+* Network interface: {}
+* Wait time: {} seconds
+
+## Results "#,
+                spec_version,
+                search_target,
+                match &network_interface {
+                    None => "all".to_string(),
+                    Some(s) => s.to_string(),
+                },
+                max_wait_time
+            );
+            for response in responses.iter() {
+                println!("\n**[{}]({})**\n", response.service_name, response.location);
+                println!("* Product Version: {}", response.versions.product_version());
+                println!("* UPnP Version:    {}", response.versions.upnp_version());
+                println!(
+                    "* O/S Version:     {}",
+                    response.versions.platform_version()
+                );
+            }
+        }
+        _ => print_records(
+            format,
+            &SEARCH_COLUMNS,
+            &responses
+                .iter()
+                .map(|response| search_response_to_json(response, &network_interface))
+                .collect::<Vec<Value>>(),
+        ),
+    }
+    Ok(())
+}
+
+///
+/// The stable JSON form of a search response; `other_headers` holds every header that does
+/// not have a field of its own.
+///
+fn search_response_to_json(response: &Response, network_interface: &Option<String>) -> Value {
+    json!({
+        "from": response.from.map(|from| from.to_string()),
+        "interface": network_interface,
+        "searchTarget": response.search_target.to_string(),
+        "usn": response.service_name.to_string(),
+        "location": response.location.to_string(),
+        "maxAge": response.max_age.as_secs(),
+        "date": response.date.map(http_date),
+        "product": response.versions.product_version().to_string(),
+        "upnp": response.versions.upnp_version().to_string(),
+        "platform": response.versions.platform_version().to_string(),
+        "bootId": response.boot_id,
+        "configId": response.config_id,
+        "searchPort": response.search_port,
+        "secureLocation": response
+            .secure_location
+            .as_ref()
+            .map(|location| location.to_string()),
+        "extension": response.extension.as_ref().map(|extension| json!({
+            "uri": extension.uri,
+            "namespace": extension.namespace,
+            "nls": extension.network_location_signature,
+        })),
+        "otherHeaders": headers_to_json(&response.other_headers),
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Modules
+// ------------------------------------------------------------------------------------------------
+
+mod describe;
+
+mod invoke;
+
+mod listen;
+
+mod output;
+
+mod serve;
+
+mod subscribe;
@@ -0,0 +1,264 @@
+use crate::CommandLineError;
+use serde_json::{json, Map, Value};
+use std::fmt::Display;
+use std::io::{stdout, Write};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use upnp_rs::common::httpu::Headers;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The output format, selected with the global `--format` option and applied by every command.
+/// `Text` is the human-readable form specific to each command; the other formats render each
+/// command's records using a common set of rules so that they can be consumed by other tools.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Markdown,
+    Json,
+    JsonLines,
+    Csv,
+}
+
+///
+/// A writer for commands that produce records as a stream, such as `listen`, rather than all at
+/// once. Each record is written as soon as it is available; CSV and Markdown headers are written
+/// before the first record. JSON lines are one record per line, while JSON is a single array
+/// that is only closed by `finish`, when the command stops.
+///
+#[derive(Debug)]
+pub struct RecordWriter {
+    format: Format,
+    columns: &'static [&'static str],
+    started: bool,
+    finished: bool,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Print a complete set of records, each a JSON object, in `format`; `columns` names the fields
+/// used, in order, for the CSV and Markdown tables. JSON output is a single array.
+///
+pub fn print_records(format: Format, columns: &[&str], records: &[Value]) {
+    match format {
+        Format::Text => {}
+        Format::Json => println!("{}", to_pretty_string(&json!(records))),
+        Format::JsonLines => {
+            for record in records {
+                println!("{}", record);
+            }
+        }
+        Format::Csv => {
+            println!("{}", csv_row(columns.iter().map(|c| c.to_string())));
+            for record in records {
+                println!("{}", csv_row(fields(columns, record)));
+            }
+        }
+        Format::Markdown => {
+            print_markdown_header(columns);
+            for record in records {
+                println!("{}", markdown_row(fields(columns, record)));
+            }
+        }
+    }
+}
+
+///
+/// Print a single document, such as a device description, in `format`; JSON output is the
+/// object itself rather than an array.
+///
+pub fn print_document(format: Format, document: &Value) {
+    match format {
+        Format::Json => println!("{}", to_pretty_string(document)),
+        Format::JsonLines => println!("{}", document),
+        _ => {}
+    }
+}
+
+///
+/// Report that `command` failed. In the JSON formats this prints an object of the form
+/// `{"command": "...", "error": "..."}` to standard output so that it can be parsed along with
+/// any other output, otherwise the message is printed to standard error.
+///
+pub fn print_error(format: Format, command: &str, error: impl Display) {
+    let error = json!({ "command": command, "error": error.to_string() });
+    match format {
+        Format::Json => println!("{}", to_pretty_string(&error)),
+        Format::JsonLines => println!("{}", error),
+        _ => eprintln!(
+            "{} failed with error: {}",
+            command,
+            error["error"].as_str().unwrap()
+        ),
+    }
+}
+
+///
+/// Print an informational message, such as a banner or progress note, that is not part of a
+/// command's records; it goes to standard error in any format other than text so that it does
+/// not interfere with parsing.
+///
+pub fn print_status(format: Format, message: impl Display) {
+    if format == Format::Text {
+        println!("{}", message);
+    } else {
+        eprintln!("{}", message);
+    }
+}
+
+///
+/// The JSON form of a set of message headers, an object keyed by header name; the values of a
+/// repeated header are joined with `", "`, as HTTP allows.
+///
+pub fn headers_to_json(headers: &Headers) -> Value {
+    let mut object = Map::new();
+    for (name, value) in headers.iter() {
+        let joined = match object.get(name) {
+            Some(Value::String(previous)) => format!("{}, {}", previous, value),
+            _ => value.clone(),
+        };
+        let _ = object.insert(name.clone(), Value::String(joined));
+    }
+    Value::Object(object)
+}
+
+///
+/// The current time as milliseconds since the Unix epoch, the form used for times in records.
+///
+pub fn epoch_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl FromStr for Format {
+    type Err = CommandLineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::JsonLines),
+            "csv" => Ok(Format::Csv),
+            _ => Err(CommandLineError::InvalidParameterValue(
+                "format".to_string(),
+                s.to_string(),
+            )),
+        }
+    }
+}
+
+impl RecordWriter {
+    pub fn new(format: Format, columns: &'static [&'static str]) -> Self {
+        Self {
+            format,
+            columns,
+            started: false,
+            finished: false,
+        }
+    }
+
+    pub fn write(&mut self, record: &Value) {
+        if self.finished {
+            return;
+        }
+        match self.format {
+            Format::Text => {}
+            Format::Json => {
+                // The separator is written with the next record, or the close with `finish`.
+                print!("{}\n  {}", if self.started { "," } else { "[" }, record);
+                let _ = stdout().flush();
+            }
+            Format::JsonLines => println!("{}", record),
+            Format::Csv => {
+                if !self.started {
+                    println!("{}", csv_row(self.columns.iter().map(|c| c.to_string())));
+                }
+                println!("{}", csv_row(fields(self.columns, record)));
+            }
+            Format::Markdown => {
+                if !self.started {
+                    print_markdown_header(self.columns);
+                }
+                println!("{}", markdown_row(fields(self.columns, record)));
+            }
+        }
+        self.started = true;
+    }
+
+    ///
+    /// Complete the output, closing the JSON array; any later records are ignored.
+    ///
+    pub fn finish(&mut self) {
+        if self.format == Format::Json && !self.finished {
+            println!("{}", if self.started { "\n]" } else { "[]" });
+        }
+        self.finished = true;
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn to_pretty_string(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_default()
+}
+
+///
+/// Flatten the named fields of `record` into strings; missing and null fields are empty, and
+/// nested objects or arrays are written as compact JSON.
+///
+fn fields<'a>(columns: &'a [&str], record: &'a Value) -> impl Iterator<Item = String> + 'a {
+    let empty = Map::new();
+    let object = record.as_object().cloned().unwrap_or(empty);
+    columns.iter().map(move |column| match object.get(*column) {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    })
+}
+
+fn csv_row(fields: impl Iterator<Item = String>) -> String {
+    fields
+        .map(|field| {
+            if field.contains(&[',', '"', '\n', '\r'][..]) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn print_markdown_header(columns: &[&str]) {
+    println!("{}", markdown_row(columns.iter().map(|c| c.to_string())));
+    println!(
+        "{}",
+        markdown_row(columns.iter().map(|_| "---".to_string()))
+    );
+}
+
+fn markdown_row(fields: impl Iterator<Item = String>) -> String {
+    format!(
+        "| {} |",
+        fields
+            .map(|field| field.replace('|', "\\|").replace(['\r', '\n'], " "))
+            .collect::<Vec<String>>()
+            .join(" | ")
+    )
+}
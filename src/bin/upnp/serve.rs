@@ -0,0 +1,841 @@
+use crate::invoke::service_matches;
+use crate::output::{epoch_millis, print_status, Format, RecordWriter};
+use crate::timestamp;
+use serde_json::{json, Value};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::{from_utf8, FromStr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use upnp_rs::common::http::{read_request, write_response};
+use upnp_rs::common::httpu::{
+    Options as MulticastOptions, Request, Response, ResponseBuilder, UdpTransport,
+};
+use upnp_rs::common::interface::{ip_address_for_interface, local_address_for, IP};
+use upnp_rs::common::monitor::InterfaceMonitor;
+use upnp_rs::common::soap::{Action, Body, Envelope};
+use upnp_rs::common::uri::URL;
+use upnp_rs::control::validate_arguments;
+use upnp_rs::description::device::{to_writer_for, Device, DeviceRoot, Service};
+use upnp_rs::description::service::{Direction, Spcd};
+use upnp_rs::discovery::guard::GuardOptions;
+use upnp_rs::discovery::identity::{DeviceIdentity, MemoryBootIdStore};
+use upnp_rs::discovery::listen::Options as ListenOptions;
+use upnp_rs::discovery::location::LocationTemplate;
+use upnp_rs::discovery::notify::{device_available_using, device_unavailable_using, Options};
+use upnp_rs::discovery::respond::Responder;
+use upnp_rs::error::{UpnpError, UpnpErrorCode};
+use upnp_rs::syntax::{HTTP_HEADER_CONTENT_TYPE, SOAP_CONTENT_TYPE};
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Host a simulated device from the description in `description`, serving it and the service
+/// descriptions found in `scpd_dir` (by default the directory containing the description) over
+/// HTTP, advertising it with SSDP, answering searches allowed by `guard_options`, and answering
+/// actions with the responses in the `config` file, if any, until the user presses Ctrl-C.
+///
+#[allow(clippy::too_many_arguments)]
+pub fn do_serve(
+    description: PathBuf,
+    scpd_dir: Option<PathBuf>,
+    config: Option<PathBuf>,
+    port: Option<u16>,
+    options: Options,
+    listen_options: ListenOptions,
+    guard_options: GuardOptions,
+    format: Format,
+) -> Result<(), String> {
+    serve(
+        description,
+        scpd_dir,
+        config,
+        port,
+        options,
+        listen_options,
+        guard_options,
+        format,
+    )
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Everything served over HTTP; the device description, written with the address of the
+/// interface each request arrives on, the service descriptions, exactly as read, and the services
+/// whose actions are answered.
+///
+#[derive(Debug)]
+struct Hosted {
+    description_path: String,
+    description: DeviceRoot,
+    location_template: LocationTemplate,
+    services: Vec<HostedService>,
+    responses: Vec<CannedResponse>,
+    log: ActivityLog,
+}
+
+#[derive(Debug)]
+struct HostedService {
+    service: Service,
+    spcd: Spcd,
+    document: Vec<u8>,
+}
+
+///
+/// A response to an action, from the config file.
+///
+#[derive(Debug)]
+struct CannedResponse {
+    service: Option<String>,
+    action: String,
+    reply: Reply,
+}
+
+///
+/// The log of requests served and messages sent, shared by the HTTP, search, and advertising
+/// threads; each entry is printed as a line of text or as a record in the selected format.
+///
+#[derive(Debug)]
+struct ActivityLog {
+    format: Format,
+    writer: Mutex<RecordWriter>,
+}
+
+#[derive(Debug)]
+enum Reply {
+    /// Out argument values; any out arguments not listed take their default value.
+    Arguments(Vec<(String, String)>),
+    /// A UPnP error to return as a SOAP fault.
+    Fault(UpnpError),
+    /// A shell command to run; in arguments are passed as `UPNP_ARG_{name}` environment
+    /// variables and out arguments are read, as `Name=Value` lines, from its output.
+    Script(String),
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(1);
+
+const STREAM_TIMEOUT: Duration = Duration::from_secs(5);
+
+const ACTIVITY_COLUMNS: [&str; 13] = [
+    "time",
+    "event",
+    "from",
+    "method",
+    "path",
+    "status",
+    "service",
+    "action",
+    "arguments",
+    "target",
+    "responses",
+    "nts",
+    "location",
+];
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Hosted {
+    fn load(
+        description: &Path,
+        scpd_dir: &Path,
+        config: Option<&Path>,
+        location_template: &LocationTemplate,
+        location: &URL,
+        format: Format,
+    ) -> Result<(Self, DeviceRoot), String> {
+        let document = read_file(description)?;
+        let unresolved = DeviceRoot::from_str(from_utf8(&document).map_err(|e| e.to_string())?)
+            .map_err(|e| format!("could not parse {}: {}", description.display(), e))?;
+        let root = unresolved.resolve(location);
+        let mut services = Vec::new();
+        let mut devices = vec![&root.device];
+        while let Some(device) = devices.pop() {
+            for service in &device.service_list {
+                let path = service.scpd_url.path().trim_start_matches('/');
+                let file = vec![scpd_dir.join(path), scpd_dir.join(file_name(path))]
+                    .into_iter()
+                    .find(|file| file.is_file())
+                    .ok_or_else(|| {
+                        format!(
+                            "no service description '{}' in {}",
+                            path,
+                            scpd_dir.display()
+                        )
+                    })?;
+                let scpd_document = read_file(&file)?;
+                let spcd = Spcd::from_str(from_utf8(&scpd_document).map_err(|e| e.to_string())?)
+                    .map_err(|e| format!("could not parse {}: {}", file.display(), e))?;
+                services.push(HostedService {
+                    service: service.clone(),
+                    spcd,
+                    document: scpd_document,
+                });
+            }
+            devices.extend(device.device_list.iter());
+        }
+        let responses = match config {
+            None => Vec::new(),
+            Some(config) => load_config(config)?,
+        };
+        Ok((
+            Self {
+                description_path: location.path().to_string(),
+                description: unresolved,
+                location_template: location_template.clone(),
+                services,
+                responses,
+                log: ActivityLog::new(format),
+            },
+            root,
+        ))
+    }
+
+    fn respond_to(&self, request: &Request, local_address: &IpAddr) -> Response {
+        let path = request
+            .resource()
+            .map(|resource| resource.split('?').next().unwrap().to_string())
+            .unwrap_or_default();
+        match request.method().as_str() {
+            "GET" if path == self.description_path => {
+                match self
+                    .location_template
+                    .location_for(local_address)
+                    .and_then(|location| to_writer_for(&self.description, &location, Vec::new()))
+                {
+                    Ok(document) => xml_document(&document),
+                    Err(_) => ResponseBuilder::new(500, "Internal Server Error").into(),
+                }
+            }
+            "GET" => match self
+                .services
+                .iter()
+                .find(|hosted| hosted.service.scpd_url.path() == &path)
+            {
+                Some(hosted) => xml_document(&hosted.document),
+                None => ResponseBuilder::new(404, "Not Found").into(),
+            },
+            "POST" => match self
+                .services
+                .iter()
+                .find(|hosted| hosted.service.control_url.path() == &path)
+            {
+                Some(hosted) => self.invoke(hosted, request),
+                None => ResponseBuilder::new(404, "Not Found").into(),
+            },
+            _ => ResponseBuilder::new(405, "Method Not Allowed").into(),
+        }
+    }
+
+    fn invoke(&self, hosted: &HostedService, request: &Request) -> Response {
+        let envelope = request
+            .body()
+            .and_then(|body| from_utf8(body).ok())
+            .and_then(|body| Envelope::from_str(body).ok());
+        let (action, arguments) = match envelope.as_ref().map(|envelope| envelope.body()) {
+            Some(Body::Action { action, arguments }) => (action, arguments),
+            _ => return fault(UpnpError::new(UpnpErrorCode::InvalidAction)),
+        };
+        let action_name = action.name();
+        if hosted.spcd.action(action_name).is_none() {
+            return fault(UpnpError::new(UpnpErrorCode::InvalidAction));
+        }
+        self.log.add(
+            || {
+                format!(
+                    "{}   {}#{} {}",
+                    timestamp(),
+                    hosted.service.service_id,
+                    action_name,
+                    arguments
+                        .iter()
+                        .map(|(name, value)| format!("{}={}", name, value))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                )
+            },
+            json!({
+                "event": "action",
+                "service": hosted.service.service_id,
+                "action": action_name,
+                "arguments": arguments
+                    .iter()
+                    .map(|(name, value)| json!({ "name": name, "value": value }))
+                    .collect::<Vec<Value>>(),
+            }),
+        );
+        let canned = self.responses.iter().find(|canned| {
+            &canned.action == action_name
+                && canned
+                    .service
+                    .as_ref()
+                    .map(|service| service_matches(&hosted.service, service))
+                    .unwrap_or(true)
+        });
+        let values = match canned.map(|canned| &canned.reply) {
+            Some(Reply::Fault(error)) => return fault(error.clone()),
+            Some(Reply::Arguments(values)) => values.clone(),
+            Some(Reply::Script(script)) => {
+                match run_script(script, &hosted.service, action_name, arguments) {
+                    Ok(values) => values,
+                    Err(error) => return fault(error),
+                }
+            }
+            None => {
+                if validate_arguments(&hosted.spcd, action_name, arguments).is_err() {
+                    return fault(UpnpError::new(UpnpErrorCode::InvalidArgs));
+                }
+                Vec::new()
+            }
+        };
+        let out_arguments = out_arguments(&hosted.spcd, action_name, &values);
+        let response = Envelope::new_response(
+            Action::new(hosted.service.service_type.clone(), action_name.clone()),
+            out_arguments,
+        );
+        match response.to_writer(Vec::new()) {
+            Ok(body) => {
+                let mut builder = ResponseBuilder::ok();
+                builder
+                    .add_header(HTTP_HEADER_CONTENT_TYPE, SOAP_CONTENT_TYPE)
+                    .body(&body);
+                builder.into()
+            }
+            Err(_) => fault(UpnpError::new(UpnpErrorCode::ActionFailed)),
+        }
+    }
+}
+
+impl ActivityLog {
+    fn new(format: Format) -> Self {
+        Self {
+            format,
+            writer: Mutex::new(RecordWriter::new(format, &ACTIVITY_COLUMNS)),
+        }
+    }
+
+    ///
+    /// Add an entry, printing the line returned by `text` in the text format, or otherwise
+    /// writing `record` with the current time added.
+    ///
+    fn add<F>(&self, text: F, mut record: Value)
+    where
+        F: FnOnce() -> String,
+    {
+        if self.format == Format::Text {
+            println!("{}", text());
+        } else {
+            record["time"] = json!(epoch_millis());
+            if let Ok(mut writer) = self.writer.lock() {
+                writer.write(&record);
+            }
+        }
+    }
+
+    ///
+    /// Complete the output; activity after this, such as a late search, is not logged.
+    ///
+    fn finish(&self) {
+        if let Ok(mut writer) = self.writer.lock() {
+            writer.finish();
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+#[allow(clippy::too_many_arguments)]
+fn serve(
+    description: PathBuf,
+    scpd_dir: Option<PathBuf>,
+    config: Option<PathBuf>,
+    port: Option<u16>,
+    options: Options,
+    listen_options: ListenOptions,
+    guard_options: GuardOptions,
+    format: Format,
+) -> Result<(), String> {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let handler_stopped = stopped.clone();
+    ctrlc::set_handler(move || handler_stopped.store(true, Ordering::SeqCst))
+        .map_err(|e| e.to_string())?;
+
+    let group = listen_options.group_address().map_err(|e| e.to_string())?;
+    let use_ipv6 = matches!(options.network_version, Some(IP::V6));
+    let listener = TcpListener::bind(SocketAddr::new(
+        if use_ipv6 {
+            IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+        } else {
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        },
+        port.unwrap_or_default(),
+    ))
+    .map_err(|e| e.to_string())?;
+    let http_port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let location_template = location_template_for(http_port, &description)?;
+    let mut address = advertised_address(&options, &group)?;
+    let mut location = location_template
+        .location_for(&address)
+        .map_err(|e| e.to_string())?;
+
+    let scpd_dir = scpd_dir.unwrap_or_else(|| {
+        description
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    });
+    let (hosted, root) = Hosted::load(
+        &description,
+        &scpd_dir,
+        config.as_deref(),
+        &location_template,
+        &location,
+        format,
+    )?;
+    let hosted = Arc::new(hosted);
+
+    // Nothing is persisted, so the boot ID is taken from the time the device starts.
+    let mut identity =
+        DeviceIdentity::start(Box::new(MemoryBootIdStore::default())).map_err(|e| e.to_string())?;
+    let mut advertisements = identity
+        .advertisements_for(&root, &location)
+        .map_err(|e| e.to_string())?;
+
+    let http_hosted = hosted.clone();
+    let _ = thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let hosted = http_hosted.clone();
+            let _ = thread::spawn(move || handle_connection(stream, &hosted));
+        }
+    });
+
+    let mut responder = Responder::new(&listen_options, advertisements.clone(), options.clone())
+        .map_err(|e| e.to_string())?;
+    // Searches may arrive on any interface, each answered with the address on the route back.
+    responder.set_location_template(Some(location_template.clone()));
+    responder.set_guard_options(guard_options);
+    let responder = Arc::new(Mutex::new(responder));
+    let search_responder = responder.clone();
+    let search_hosted = hosted.clone();
+    let _ = thread::spawn(move || loop {
+        let result = search_responder.lock().unwrap().next_search(SEARCH_TIMEOUT);
+        match result {
+            Ok(Some((from, search, sent))) => search_hosted.log.add(
+                || {
+                    format!(
+                        "{} {} M-SEARCH ST={} -> {} response(s)",
+                        timestamp(),
+                        from,
+                        search.search_target,
+                        sent
+                    )
+                },
+                json!({
+                    "event": "search",
+                    "from": from.to_string(),
+                    "target": search.search_target.to_string(),
+                    "responses": sent,
+                }),
+            ),
+            Ok(None) => {}
+            Err(error) => {
+                print_status(
+                    format,
+                    format!("search responder failed with error: {}", error),
+                );
+                return;
+            }
+        }
+    });
+
+    let mut transport = UdpTransport::new(&group, &MulticastOptions::from(options.clone()))
+        .map_err(|e| e.to_string())?;
+    let mut monitor = InterfaceMonitor::new();
+    print_status(
+        format,
+        format!(
+            "Serving '{}' at {} with {} advertisement(s); press Ctrl-C to stop",
+            root.device.friendly_name,
+            location,
+            advertisements.len()
+        ),
+    );
+    print_services(&root.device, format);
+
+    // Advertisements must be renewed well before they expire.
+    let interval = Duration::from_secs(u64::from(options.max_age / 3).max(1));
+    let mut advertise_at = Instant::now();
+    // Sends fail while the network is down, so failures are retried rather than ending the
+    // server; at the next interval, or sooner if the interfaces change.
+    let mut advertise_failed = false;
+    let mut rejoin_failed = false;
+    while !stopped.load(Ordering::SeqCst) {
+        if Instant::now() >= advertise_at {
+            match advertisements.iter().try_for_each(|advertisement| {
+                device_available_using(advertisement, options.clone(), &transport)
+            }) {
+                Ok(()) => {
+                    advertise_failed = false;
+                    hosted.log.add(
+                        || format!("{} NOTIFY ssdp:alive", timestamp()),
+                        json!({ "event": "notify", "nts": "ssdp:alive" }),
+                    );
+                }
+                Err(error) => {
+                    advertise_failed = true;
+                    print_status(format, format!("could not advertise: {}", error));
+                }
+            }
+            advertise_at = Instant::now() + interval;
+        }
+        let changes = monitor
+            .next_changes(POLL_INTERVAL)
+            .map_err(|e| e.to_string())?;
+        if !changes
+            .iter()
+            .any(|change| change.affects(&options.network_interface))
+        {
+            continue;
+        }
+        // Without an address there is nothing to advertise until the network returns.
+        match advertised_address(&options, &group) {
+            Ok(new_address) if new_address != address => {
+                transport =
+                    match UdpTransport::new(&group, &MulticastOptions::from(options.clone())) {
+                        Ok(transport) => transport,
+                        Err(error) => {
+                            print_status(format, format!("could not re-advertise: {}", error));
+                            continue;
+                        }
+                    };
+                address = new_address;
+                location = location_template
+                    .location_for(&address)
+                    .map_err(|e| e.to_string())?;
+                if let Err(error) =
+                    identity.relocate_using(&mut advertisements, &location, &options, &transport)
+                {
+                    print_status(format, format!("could not re-advertise: {}", error));
+                }
+                let mut responder = responder.lock().unwrap();
+                responder.set_advertisements(advertisements.clone());
+                rejoin_failed = rejoin(&mut responder, format);
+                hosted.log.add(
+                    || format!("{} NOTIFY ssdp:update, now at {}", timestamp(), location),
+                    json!({
+                        "event": "notify",
+                        "nts": "ssdp:update",
+                        "location": location.to_string(),
+                    }),
+                );
+                advertise_at = Instant::now() + interval;
+            }
+            Ok(_) => {
+                if rejoin_failed {
+                    rejoin_failed = rejoin(&mut responder.lock().unwrap(), format);
+                }
+                if advertise_failed {
+                    advertise_at = Instant::now();
+                }
+            }
+            Err(error) => print_status(format, format!("no address to advertise: {}", error)),
+        }
+    }
+    match advertisements.iter().try_for_each(|advertisement| {
+        device_unavailable_using(advertisement, options.clone(), &transport)
+    }) {
+        Ok(()) => hosted.log.add(
+            || format!("{} NOTIFY ssdp:byebye", timestamp()),
+            json!({ "event": "notify", "nts": "ssdp:byebye" }),
+        ),
+        Err(error) => print_status(format, format!("could not send byebye: {}", error)),
+    }
+    hosted.log.finish();
+    let counters = responder.lock().unwrap().counters();
+    print_status(
+        format,
+        format!(
+            "Answered {} search(es), dropped {} (not allowed {}, rate limited {}, over budget {}, invalid {})",
+            counters.accepted,
+            counters.dropped(),
+            counters.not_allowed,
+            counters.rate_limited,
+            counters.over_budget,
+            counters.invalid
+        ),
+    );
+    Ok(())
+}
+
+///
+/// The address advertised in `LOCATION`; the first address of the chosen interface, or that of the
+/// interface on the route to the multicast group.
+///
+fn advertised_address(options: &Options, group: &SocketAddr) -> Result<IpAddr, String> {
+    match ip_address_for_interface(&options.network_interface, &options.network_version) {
+        Some(address) => Ok(address),
+        None => local_address_for(group).map_err(|e| e.to_string()),
+    }
+}
+
+///
+/// Join the multicast group again after the interfaces change, returning `true` if this failed
+/// and should be tried again.
+///
+fn rejoin(responder: &mut Responder, format: Format) -> bool {
+    match responder.rejoin() {
+        Ok(()) => false,
+        Err(error) => {
+            print_status(
+                format,
+                format!("could not rejoin the multicast group: {}", error),
+            );
+            true
+        }
+    }
+}
+
+fn location_template_for(port: u16, description: &Path) -> Result<LocationTemplate, String> {
+    LocationTemplate::from_str(&format!(
+        "http://{{host}}:{}/{}",
+        port,
+        file_name(&description.to_string_lossy())
+    ))
+    .map_err(|e| e.to_string())
+}
+
+fn handle_connection(mut stream: TcpStream, hosted: &Hosted) {
+    let from = stream
+        .peer_addr()
+        .map(|address| address.to_string())
+        .unwrap_or_default();
+    let local_address = match stream.local_addr() {
+        Ok(address) => address.ip(),
+        Err(error) => {
+            print_status(
+                hosted.log.format,
+                format!("could not serve {}: {}", from, error),
+            );
+            return;
+        }
+    };
+    let _ = stream.set_read_timeout(Some(STREAM_TIMEOUT));
+    let (method, path, response) = match read_request(&mut stream) {
+        Ok(request) => (
+            request.method().to_string(),
+            request.resource().cloned().unwrap_or_default(),
+            hosted.respond_to(&request, &local_address),
+        ),
+        Err(error) => (
+            "invalid request".to_string(),
+            format!("({})", error),
+            ResponseBuilder::new(400, "Bad Request").into(),
+        ),
+    };
+    hosted.log.add(
+        || {
+            format!(
+                "{} {} {} {} -> {}",
+                timestamp(),
+                from,
+                method,
+                path,
+                response.status().code()
+            )
+        },
+        json!({
+            "event": "http",
+            "from": from,
+            "method": method,
+            "path": path,
+            "status": response.status().code(),
+        }),
+    );
+    if let Err(error) = write_response(&mut stream, &response) {
+        print_status(
+            hosted.log.format,
+            format!("could not respond to {}: {}", from, error),
+        );
+    }
+}
+
+fn print_services(device: &Device, format: Format) {
+    for service in &device.service_list {
+        print_status(
+            format,
+            format!(
+                "    {} SCPD={} control={}",
+                service.service_id,
+                service.scpd_url.path(),
+                service.control_url.path()
+            ),
+        );
+    }
+    for embedded in &device.device_list {
+        print_services(embedded, format);
+    }
+}
+
+fn xml_document(document: &[u8]) -> Response {
+    let mut builder = ResponseBuilder::ok();
+    builder
+        .add_header(HTTP_HEADER_CONTENT_TYPE, SOAP_CONTENT_TYPE)
+        .body(document);
+    builder.into()
+}
+
+fn fault(error: UpnpError) -> Response {
+    match Envelope::new_fault(error).to_writer(Vec::new()) {
+        Ok(body) => {
+            let mut builder = ResponseBuilder::new(500, "Internal Server Error");
+            builder
+                .add_header(HTTP_HEADER_CONTENT_TYPE, SOAP_CONTENT_TYPE)
+                .body(&body);
+            builder.into()
+        }
+        Err(_) => ResponseBuilder::new(500, "Internal Server Error").into(),
+    }
+}
+
+///
+/// Return the out arguments of `action_name`, in the order the service description defines,
+/// taking each value from `values` or else the default value of its related state variable.
+///
+fn out_arguments(
+    spcd: &Spcd,
+    action_name: &str,
+    values: &[(String, String)],
+) -> Vec<(String, String)> {
+    spcd.action(action_name)
+        .map(|action| {
+            action
+                .argument_list
+                .iter()
+                .filter(|argument| argument.direction == Direction::Out)
+                .map(|argument| {
+                    let value = values
+                        .iter()
+                        .find(|(name, _)| name == &argument.name)
+                        .map(|(_, value)| value.clone())
+                        .or_else(|| {
+                            spcd.state_variable(&argument.related_state_variable)
+                                .and_then(|variable| variable.default_value.clone())
+                        })
+                        .unwrap_or_default();
+                    (argument.name.clone(), value)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn run_script(
+    script: &str,
+    service: &Service,
+    action_name: &str,
+    arguments: &[(String, String)],
+) -> Result<Vec<(String, String)>, UpnpError> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .env("UPNP_SERVICE", &service.service_id)
+        .env("UPNP_ACTION", action_name)
+        .envs(
+            arguments
+                .iter()
+                .map(|(name, value)| (format!("UPNP_ARG_{}", name), value)),
+        )
+        .output()
+        .map_err(|e| UpnpError::with_description(UpnpErrorCode::ActionFailed, &e.to_string()))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(if message.is_empty() {
+            UpnpError::new(UpnpErrorCode::ActionFailed)
+        } else {
+            UpnpError::with_description(UpnpErrorCode::ActionFailed, &message)
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.to_string()))
+        .collect())
+}
+
+///
+/// Read the config file, a JSON object with an `actions` array; each entry names an `action`,
+/// and optionally a `service`, and has one of `arguments` (an object of out argument values),
+/// `fault` (an object with a `code` and optional `description`), or `script` (a shell command).
+///
+fn load_config(path: &Path) -> Result<Vec<CannedResponse>, String> {
+    let invalid = |message: &str| format!("invalid config {}: {}", path.display(), message);
+    let config: Value =
+        serde_json::from_slice(&read_file(path)?).map_err(|e| invalid(&e.to_string()))?;
+    let actions = config["actions"]
+        .as_array()
+        .ok_or_else(|| invalid("expected an 'actions' array"))?;
+    actions
+        .iter()
+        .map(|entry| {
+            let action = entry["action"]
+                .as_str()
+                .ok_or_else(|| invalid("each entry requires an 'action'"))?;
+            let reply = if let Some(arguments) = entry["arguments"].as_object() {
+                Reply::Arguments(
+                    arguments
+                        .iter()
+                        .map(|(name, value)| {
+                            (
+                                name.clone(),
+                                match value {
+                                    Value::String(value) => value.clone(),
+                                    value => value.to_string(),
+                                },
+                            )
+                        })
+                        .collect(),
+                )
+            } else if let Some(code) = entry["fault"]["code"].as_u64() {
+                let code = UpnpErrorCode::from(code as u16);
+                Reply::Fault(match entry["fault"]["description"].as_str() {
+                    Some(description) => UpnpError::with_description(code, description),
+                    None => UpnpError::new(code),
+                })
+            } else if let Some(script) = entry["script"].as_str() {
+                Reply::Script(script.to_string())
+            } else {
+                return Err(invalid(&format!(
+                    "action '{}' requires one of 'arguments', 'fault', or 'script'",
+                    action
+                )));
+            };
+            Ok(CannedResponse {
+                service: entry["service"].as_str().map(str::to_string),
+                action: action.to_string(),
+                reply,
+            })
+        })
+        .collect()
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("could not read {}: {}", path.display(), e))
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
@@ -0,0 +1,163 @@
+use crate::describe::find_location;
+use crate::invoke::find_service;
+use crate::output::{epoch_millis, print_status, Format, RecordWriter};
+use crate::timestamp;
+use serde_json::json;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use upnp_rs::common::interface::IP;
+use upnp_rs::description::device::fetch as fetch_device;
+use upnp_rs::discovery::search::Options;
+use upnp_rs::eventing::{renew, subscribe, unsubscribe, CallbackListener, Subscription};
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Subscribe to events from the service identified by `service` (a service ID, service type, or
+/// service type name) of the device at `device`, which is either a description URL or a UDN.
+/// Each event is printed as it arrives, the subscription is renewed before it expires, and it is
+/// cancelled when the user presses Ctrl-C.
+///
+pub fn do_subscribe(
+    device: String,
+    service: String,
+    timeout: u64,
+    port: Option<u16>,
+    search_options: Options,
+    format: Format,
+) -> Result<(), String> {
+    watch_events(&device, &service, timeout, port, search_options, format)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const MINIMUM_RENEWAL: Duration = Duration::from_secs(5);
+
+const EVENT_COLUMNS: [&str; 5] = ["time", "sid", "seq", "name", "value"];
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn watch_events(
+    device: &str,
+    service: &str,
+    timeout: u64,
+    port: Option<u16>,
+    search_options: Options,
+    format: Format,
+) -> Result<(), String> {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let handler_stopped = stopped.clone();
+    ctrlc::set_handler(move || handler_stopped.store(true, Ordering::SeqCst))
+        .map_err(|e| e.to_string())?;
+
+    let use_ipv6 = matches!(search_options.network_version, Some(IP::V6));
+    let location = find_location(device, search_options)?;
+    let root = fetch_device(&location).map_err(|e| e.to_string())?;
+    let service = match find_service(&root.device, service) {
+        Some(found) => found,
+        None => {
+            return Err(format!(
+                "device '{}' has no service '{}'",
+                root.device.friendly_name, service
+            ))
+        }
+    };
+
+    let bind_address = if use_ipv6 {
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+    } else {
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+    };
+    let listener = CallbackListener::new(SocketAddr::new(bind_address, port.unwrap_or_default()))
+        .map_err(|e| e.to_string())?;
+    let callback = listener
+        .callback_url_for(&service.event_sub_url)
+        .map_err(|e| e.to_string())?;
+    let requested = Some(Duration::from_secs(timeout));
+    let mut subscription = subscribe(
+        &service.event_sub_url,
+        std::slice::from_ref(&callback),
+        requested,
+    )
+    .map_err(|e| e.to_string())?;
+    print_status(
+        format,
+        format!(
+            "Subscribed to {} with {}, callback {}; press Ctrl-C to stop",
+            service.service_id, subscription.sid, callback
+        ),
+    );
+    let mut writer = RecordWriter::new(format, &EVENT_COLUMNS);
+    let mut renew_at = renewal_time(&subscription);
+
+    while !stopped.load(Ordering::SeqCst) {
+        match listener.next_event(POLL_INTERVAL) {
+            Ok(Some(event)) if event.sid == subscription.sid => {
+                let (now, millis) = (timestamp(), epoch_millis());
+                for (name, value) in &event.properties {
+                    if format == Format::Text {
+                        println!("{} SEQ={} {}={}", now, event.seq, name, value);
+                    } else {
+                        writer.write(&json!({
+                            "time": millis,
+                            "sid": event.sid,
+                            "seq": event.seq,
+                            "name": name,
+                            "value": value,
+                        }));
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(error) => {
+                writer.finish();
+                let _ = cancel(&subscription, format);
+                return Err(error.to_string());
+            }
+        }
+        if let Some(at) = renew_at {
+            if Instant::now() >= at {
+                subscription = match renew(&subscription, requested) {
+                    Ok(renewed) => renewed,
+                    Err(error) => {
+                        writer.finish();
+                        return Err(format!("renewal failed: {}", error));
+                    }
+                };
+                print_status(
+                    format,
+                    format!("{} renewed {}", timestamp(), subscription.sid),
+                );
+                renew_at = renewal_time(&subscription);
+            }
+        }
+    }
+    writer.finish();
+    cancel(&subscription, format)
+}
+
+fn renewal_time(subscription: &Subscription) -> Option<Instant> {
+    // Renew half way through the subscription to leave plenty of time for retries.
+    subscription
+        .timeout
+        .map(|timeout| Instant::now() + (timeout / 2).max(MINIMUM_RENEWAL))
+}
+
+fn cancel(subscription: &Subscription, format: Format) -> Result<(), String> {
+    unsubscribe(subscription).map_err(|error| format!("unsubscribe failed: {}", error))?;
+    print_status(
+        format,
+        format!("{} unsubscribed {}", timestamp(), subscription.sid),
+    );
+    Ok(())
+}
@@ -1,17 +1,15 @@
+use crate::common::httpu::Headers;
+use crate::common::uri::URL;
 use crate::error::{invalid_header_value, missing_required_header, MessageFormatError};
 use regex::Regex;
-use std::collections::HashMap;
 use std::str::FromStr;
 use tracing::error;
 
-pub fn check_required(
-    headers: &HashMap<String, String>,
-    required: &[&str],
-) -> Result<(), MessageFormatError> {
+pub fn check_required(headers: &Headers, required: &[&str]) -> Result<(), MessageFormatError> {
     let missing_headers: Vec<String> = required
         .iter()
         .cloned()
-        .take_while(|h| !headers.contains_key(*h))
+        .filter(|h| !headers.contains(h))
         .map(String::from)
         .collect();
     if missing_headers.is_empty() {
@@ -40,6 +38,37 @@ where
     }
 }
 
+pub fn check_optional_value<T>(
+    header_value: Option<&String>,
+    name: &str,
+) -> Result<Option<T>, MessageFormatError>
+where
+    T: FromStr,
+{
+    header_value
+        .map(|value| check_parsed_value::<T>(value, name))
+        .transpose()
+}
+
+pub fn check_optional_url(
+    header_value: Option<&String>,
+    name: &str,
+) -> Result<Option<URL>, MessageFormatError> {
+    match header_value {
+        None => Ok(None),
+        Some(value) => match URL::from_str(value) {
+            Ok(url) if url.is_absolute() => Ok(Some(url)),
+            _ => {
+                error!(
+                    "check_optional_url - header '{}' is not an absolute URL '{}'",
+                    name, value
+                );
+                invalid_header_value(name, value).into()
+            }
+        },
+    }
+}
+
 pub fn check_regex(
     header_value: &str,
     name: &str,
@@ -1,15 +1,286 @@
-use crate::error::{unsupported_operation, Error};
+use crate::common::httpu::{Request, Response};
+use crate::common::uri::URL;
+use crate::error::{invalid_header_value, Error};
+use crate::syntax::HTTP_HEADER_CONTENT_LENGTH;
+use regex::Regex;
 use reqwest::blocking::Client;
-use tracing::info;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, trace};
 
-pub fn fetch<T>(url: String) -> Result<T, Error> {
+///
+/// Fetch the document at `url` and parse it as `T`, using a new HTTP client.
+///
+pub fn fetch<T>(url: &URL) -> Result<T, Error>
+where
+    T: FromStr<Err = Error>,
+{
     let client = Client::new();
     fetch_with(url, &client)
 }
 
-pub fn fetch_with<T>(url: String, client: &Client) -> Result<T, Error> {
+///
+/// Fetch the document at `url` and parse it as `T`, using the provided HTTP client. Any
+/// response status other than success is returned as `Error::OperationFailed`.
+///
+pub fn fetch_with<T>(url: &URL, client: &Client) -> Result<T, Error>
+where
+    T: FromStr<Err = Error>,
+{
     info!("fetch_with - fetching {}", url);
-    let response = client.get(&url).send()?;
+    let response = client.get(url.to_string()).send()?;
     info!("fetch_with - received {:?}", &response);
-    unsupported_operation(url).into()
+    if !response.status().is_success() {
+        error!(
+            "fetch_with - GET {} returned status {}",
+            url,
+            response.status()
+        );
+        return Err(Error::OperationFailed {
+            operation: format!("GET {}", url),
+            status: response.status().to_string(),
+        });
+    }
+    response.text()?.parse()
+}
+
+///
+/// Read a single HTTP request, including any body of the length given by `CONTENT-LENGTH`, from
+/// `stream`; this is used by the simple servers that receive events or serve descriptions.
+///
+pub fn read_request<S: Read>(stream: &mut S) -> Result<Request, Error> {
+    Ok(Request::try_from(read_message(stream)?.as_slice())?)
+}
+
+///
+/// Read a single HTTP response, including any body of the length given by `CONTENT-LENGTH`,
+/// from `stream`; this is used to receive search responses sent over TCP.
+///
+pub fn read_response<S: Read>(stream: &mut S) -> Result<Response, Error> {
+    Ok(Response::try_from(read_message(stream)?.as_slice())?)
+}
+
+///
+/// Write `response`, and any body it has, to `stream`.
+///
+pub fn write_response<S: Write>(stream: &mut S, response: &Response) -> Result<(), Error> {
+    let bytes: Vec<u8> = response.into();
+    stream.write_all(&bytes)?;
+    stream.flush()?;
+    Ok(())
+}
+
+///
+/// Format `time` as an HTTP date, in the RFC 1123 form `Sun, 06 Nov 1994 08:49:37 GMT`, as used
+/// in the `DATE` header.
+///
+pub fn http_date(time: SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days = since_epoch / 86_400;
+    let seconds = since_epoch % 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[((days + 4) % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+///
+/// Parse an HTTP date, as sent in the `DATE` header, in the RFC 1123 form
+/// `Sun, 06 Nov 1994 08:49:37 GMT`. The day name is not checked, and the obsolete RFC 850 form
+/// `Sunday, 06-Nov-94 08:49:37 GMT`, which recipients must also accept, is allowed.
+///
+pub fn parse_http_date(s: &str) -> Option<SystemTime> {
+    lazy_static! {
+        static ref DATE: Regex = Regex::new(
+            r"^\s*(?:[A-Za-z]+,\s*)?(\d{1,2})[ -]([A-Za-z]{3})[ -](\d{2}|\d{4})\s+(\d{2}):(\d{2}):(\d{2})\s+GMT\s*$"
+        )
+        .unwrap();
+    }
+    let captures = DATE.captures(s)?;
+    let number = |index: usize| captures.get(index).unwrap().as_str().parse::<u64>().ok();
+    let day = number(1)?;
+    let month = MONTHS
+        .iter()
+        .position(|month| month.eq_ignore_ascii_case(captures.get(2).unwrap().as_str()))?
+        as u32
+        + 1;
+    let year = match number(3)? {
+        year if year < 70 => year + 2000,
+        year if year < 100 => year + 1900,
+        year => year,
+    };
+    let (hours, minutes, seconds) = (number(4)?, number(5)?, number(6)?);
+    if !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let days = days_from_civil(year as i64, month, day as u32);
+    if days < 0 {
+        return None;
+    }
+    Some(
+        UNIX_EPOCH
+            + Duration::from_secs(days as u64 * 86_400 + hours * 3600 + minutes * 60 + seconds),
+    )
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+const MAX_HEADER_SIZE: usize = 65536;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+///
+/// Convert a count of days since the Unix epoch into a `(year, month, day)` civil date in the
+/// proleptic Gregorian calendar; see <http://howardhinnant.github.io/date_algorithms.html>.
+///
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+///
+/// Convert a `(year, month, day)` civil date in the proleptic Gregorian calendar into a count of
+/// days since the Unix epoch; the inverse of `civil_from_days`.
+///
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let day_of_year = (153 * mp + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn read_message<S: Read>(stream: &mut S) -> Result<Vec<u8>, Error> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let mut expected_length: Option<usize> = None;
+    loop {
+        if let Some(length) = expected_length {
+            if buffer.len() >= length {
+                break;
+            }
+        } else if let Some(end) = header_end(&buffer) {
+            let length = end + content_length(&buffer[..end])?;
+            expected_length = Some(length);
+            if buffer.len() >= length {
+                break;
+            }
+        } else if buffer.len() > MAX_HEADER_SIZE {
+            error!("read_message - headers exceed {} bytes", MAX_HEADER_SIZE);
+            return invalid_header_value("MESSAGE", "headers too large").into();
+        }
+        let read = stream.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    trace!("read_message - read {} bytes", buffer.len());
+    Ok(buffer)
+}
+
+fn header_end(buffer: &[u8]) -> Option<usize> {
+    buffer
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|start| start + 4)
+}
+
+fn content_length(headers: &[u8]) -> Result<usize, Error> {
+    let headers = String::from_utf8_lossy(headers);
+    match headers.split("\r\n").find_map(|line| {
+        line.split_once(':')
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case(HTTP_HEADER_CONTENT_LENGTH))
+    }) {
+        None => Ok(0),
+        Some((_, value)) => match value.trim().parse::<usize>() {
+            Ok(length) => Ok(length),
+            Err(_) => {
+                error!("content_length - invalid value '{}'", value);
+                invalid_header_value(HTTP_HEADER_CONTENT_LENGTH, value).into()
+            }
+        },
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_http_date() {
+        assert_eq!(http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(
+            http_date(UNIX_EPOCH + Duration::from_secs(784_111_777)),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert_eq!(
+            http_date(UNIX_EPOCH + Duration::from_secs(951_782_400)),
+            "Tue, 29 Feb 2000 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        let expected = Some(UNIX_EPOCH + Duration::from_secs(784_111_777));
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(parse_http_date("sun, 6 nov 1994 08:49:37 GMT"), expected);
+        assert_eq!(
+            parse_http_date("Tue, 29 Feb 2000 00:00:00 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(951_782_400))
+        );
+        for time in [0, 951_782_400, 1_601_656_521, 4_102_444_799] {
+            let time = UNIX_EPOCH + Duration::from_secs(time);
+            assert_eq!(parse_http_date(&http_date(time)), Some(time));
+        }
+        assert_eq!(parse_http_date(""), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nox 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn test_read_request_with_body() {
+        let mut stream: &[u8] =
+            b"POST /control HTTP/1.1\r\nHOST: 127.0.0.1\r\ncontent-length: 5\r\n\r\nhello";
+        let request = read_request(&mut stream).unwrap();
+        assert_eq!(request.method(), "POST");
+        assert_eq!(request.resource().unwrap(), "/control");
+        assert_eq!(request.body().unwrap(), b"hello");
+    }
 }
@@ -1,11 +1,23 @@
 use crate::common::httpu::request::Request;
-use std::collections::HashMap;
+use crate::common::httpu::response::{Response, ResponseStatus};
+use crate::common::httpu::Headers;
 
+///
+/// Constructs a `Request`, such as an `M-SEARCH` or `NOTIFY` message, to be sent.
+///
 #[derive(Debug)]
 pub struct RequestBuilder {
     request: Request,
 }
 
+///
+/// Constructs a `Response`, such as the answer to an `M-SEARCH`, to be sent.
+///
+#[derive(Debug)]
+pub struct ResponseBuilder {
+    response: Response,
+}
+
 impl RequestBuilder {
     pub fn new(message: &str) -> Self {
         RequestBuilder {
@@ -13,6 +25,7 @@ impl RequestBuilder {
                 message: message.to_string(),
                 resource: None,
                 headers: Default::default(),
+                body: None,
             },
         }
     }
@@ -32,15 +45,38 @@ impl RequestBuilder {
         self
     }
 
-    pub fn headers(&mut self, headers: HashMap<String, String>) -> &mut Self {
+    pub fn headers(&mut self, headers: Headers) -> &mut Self {
         self.request.headers = headers;
         self
     }
 
     pub fn add_header(&mut self, name: &str, value: &str) -> &mut Self {
-        self.request
-            .headers
-            .insert(name.to_string(), value.to_string());
+        self.request.headers.insert(name, value);
+        self
+    }
+
+    pub fn append_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.request.headers.append(name, value);
+        self
+    }
+
+    ///
+    /// Add each of `headers`, in order, after those already set; any header already set with
+    /// the same name as one of `headers` is removed first, so that these replace, for example,
+    /// a default `USER-AGENT`.
+    ///
+    pub fn extra_headers(&mut self, headers: &Headers) -> &mut Self {
+        for (name, _) in headers.iter() {
+            let _ = self.request.headers.remove(name);
+        }
+        for (name, value) in headers.iter() {
+            self.request.headers.append(name, value);
+        }
+        self
+    }
+
+    pub fn body(&mut self, body: &[u8]) -> &mut Self {
+        self.request.body = Some(body.to_vec());
         self
     }
 }
@@ -50,3 +86,52 @@ impl From<RequestBuilder> for Request {
         rb.request
     }
 }
+
+// ------------------------------------------------------------------------------------------------
+
+impl ResponseBuilder {
+    pub fn new(code: u16, message: &str) -> Self {
+        ResponseBuilder {
+            response: Response {
+                status: ResponseStatus::new(code, message),
+                headers: Default::default(),
+                body: None,
+            },
+        }
+    }
+
+    pub fn ok() -> Self {
+        Self::new(200, "OK")
+    }
+
+    pub fn no_headers(&mut self) -> &mut Self {
+        self.response.headers = Default::default();
+        self
+    }
+
+    pub fn headers(&mut self, headers: Headers) -> &mut Self {
+        self.response.headers = headers;
+        self
+    }
+
+    pub fn add_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.response.headers.insert(name, value);
+        self
+    }
+
+    pub fn append_header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.response.headers.append(name, value);
+        self
+    }
+
+    pub fn body(&mut self, body: &[u8]) -> &mut Self {
+        self.response.body = Some(body.to_vec());
+        self
+    }
+}
+
+impl From<ResponseBuilder> for Response {
+    fn from(rb: ResponseBuilder) -> Self {
+        rb.response
+    }
+}
@@ -0,0 +1,265 @@
+/*!
+An ordered collection of HTTP headers shared by requests and responses.
+*/
+
+use crate::error::{invalid_header_value, MessageFormatError};
+use crate::syntax::{HTTP_HEADER_CONTENT_LENGTH, HTTP_HEADER_LINE_SEP, HTTP_HEADER_SEP};
+use regex::Regex;
+use std::iter::FromIterator;
+use tracing::error;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A set of HTTP headers. Headers are kept in the order they were added or received, duplicate
+/// headers are preserved, and the original case of each header name is retained for output.
+/// All lookups by name are case-insensitive.
+///
+/// With the `serde` feature headers are serialized as a list of `[name, value]` pairs.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Headers {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    ///
+    /// Returns `true` if at least one header with the given name is present.
+    ///
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case(name))
+    }
+
+    ///
+    /// Return the value of the first header with the given name.
+    ///
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    ///
+    /// Return the values of all headers with the given name, in order.
+    ///
+    pub fn get_all(&self, name: &str) -> Vec<&String> {
+        self.entries
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    ///
+    /// Set the value of the named header. If one or more headers with this name exist, the
+    /// first is replaced in place and any others removed, otherwise the header is added at the
+    /// end.
+    ///
+    pub fn insert(&mut self, name: &str, value: &str) {
+        match self
+            .entries
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(name))
+        {
+            None => self.append(name, value),
+            Some(index) => {
+                self.entries[index].1 = value.to_string();
+                let mut next = index + 1;
+                while next < self.entries.len() {
+                    if self.entries[next].0.eq_ignore_ascii_case(name) {
+                        let _ = self.entries.remove(next);
+                    } else {
+                        next += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    ///
+    /// Add a header at the end, regardless of whether a header with this name already exists.
+    ///
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.entries.push((name.to_string(), value.to_string()));
+    }
+
+    ///
+    /// Remove all headers with the given name, returning their values.
+    ///
+    pub fn remove(&mut self, name: &str) -> Vec<String> {
+        let (removed, retained) = self
+            .entries
+            .drain(..)
+            .partition(|(k, _)| k.eq_ignore_ascii_case(name));
+        self.entries = retained;
+        removed.into_iter().map(|(_, v)| v).collect()
+    }
+
+    ///
+    /// Return a copy of these headers, in order, without any of those named in `names`.
+    ///
+    pub fn without(&self, names: &[&str]) -> Self {
+        self.entries
+            .iter()
+            .filter(|(k, _)| !names.iter().any(|name| k.eq_ignore_ascii_case(name)))
+            .cloned()
+            .collect()
+    }
+
+    ///
+    /// Iterate over all headers, in order, as `(name, value)` pairs.
+    ///
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+impl FromIterator<(String, String)> for Headers {
+    fn from_iter<T: IntoIterator<Item = (String, String)>>(iter: T) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for Headers {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl From<&Headers> for String {
+    fn from(headers: &Headers) -> Self {
+        headers
+            .entries
+            .iter()
+            .map(|(k, v)| format!("{}{}{}{}", k, HTTP_HEADER_SEP, v, HTTP_HEADER_LINE_SEP))
+            .collect()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Crate Functions
+// ------------------------------------------------------------------------------------------------
+
+pub(crate) fn split_at_body(all: &[u8]) -> (&[u8], &[u8]) {
+    static BLANK_LINE: &[u8] = b"\r\n\r\n";
+    match all
+        .windows(BLANK_LINE.len())
+        .position(|window| window == BLANK_LINE)
+    {
+        None => (all, &[]),
+        Some(start) => (&all[..start], &all[start + BLANK_LINE.len()..]),
+    }
+}
+
+pub(crate) fn decode_headers(lines: Vec<String>) -> Result<Headers, MessageFormatError> {
+    let mut headers = Headers::new();
+    for line in lines {
+        let (key, value) = match decode_header(line)? {
+            Some(x) => x,
+            None => continue,
+        };
+        headers.append(&key, &value);
+    }
+    Ok(headers)
+}
+
+pub(crate) fn decode_body(
+    headers: &Headers,
+    body: &[u8],
+) -> Result<Option<Vec<u8>>, MessageFormatError> {
+    match headers.get(HTTP_HEADER_CONTENT_LENGTH) {
+        None => Ok(if body.is_empty() {
+            None
+        } else {
+            Some(body.into())
+        }),
+        Some(length) => match length.trim().parse::<usize>() {
+            Ok(0) => Ok(None),
+            Ok(length) if length <= body.len() => Ok(Some(body[..length].into())),
+            _ => {
+                error!(
+                    "decode_body - content length '{}' invalid for body of {} bytes",
+                    length,
+                    body.len()
+                );
+                invalid_header_value(HTTP_HEADER_CONTENT_LENGTH, length).into()
+            }
+        },
+    }
+}
+
+pub(crate) fn encode_headers_and_body(headers: &Headers, body: &Option<Vec<u8>>) -> Vec<u8> {
+    let mut bytes: Vec<u8> = String::from(headers).into_bytes();
+    if let Some(body) = body {
+        if !headers.contains(HTTP_HEADER_CONTENT_LENGTH) {
+            bytes.extend_from_slice(
+                format!(
+                    "{}{}{}{}",
+                    HTTP_HEADER_CONTENT_LENGTH,
+                    HTTP_HEADER_SEP,
+                    body.len(),
+                    HTTP_HEADER_LINE_SEP
+                )
+                .as_bytes(),
+            );
+        }
+        bytes.extend_from_slice(HTTP_HEADER_LINE_SEP.as_bytes());
+        bytes.extend_from_slice(body);
+    } else {
+        bytes.extend_from_slice(HTTP_HEADER_LINE_SEP.as_bytes());
+    }
+    bytes
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn decode_header(line: String) -> Result<Option<(String, String)>, MessageFormatError> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r"^([!#$%&'*+\-.^_`|~0-9A-Za-z]+)[ \t]*:[ \t]*(.*?)[ \t]*$").unwrap();
+    }
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    match RE.captures(&line) {
+        None => {
+            error!("decode_header - could not decode header '{}'", line);
+            invalid_header_value("?", line).into()
+        }
+        Some(captured) => Ok(Some((
+            captured.get(1).unwrap().as_str().to_string(),
+            captured.get(2).unwrap().as_str().to_string(),
+        ))),
+    }
+}
@@ -6,11 +6,11 @@ components.
 use crate::common::interface;
 use crate::common::interface::IP;
 use crate::error::{invalid_socket_value, Error};
+use socket2::{Domain, Protocol, Socket, Type};
 use std::convert::TryFrom;
-use std::io::ErrorKind as IOErrorKind;
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, UdpSocket};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
 use std::time::Duration;
-use tracing::{debug, error, trace};
+use tracing::{debug, error, trace, warn};
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -18,46 +18,79 @@ use tracing::{debug, error, trace};
 
 //type CallbackFn = fn(&Response) -> bool;
 
+///
+/// The configuration of the UDP socket used to send, and receive, HTTPMU/HTTPU messages. The
+/// discovery `Options` types convert into this type, so these values are usually set there.
+///
 #[derive(Clone, Debug)]
 pub struct Options {
-    pub(crate) network_interface: Option<String>,
-    pub(crate) network_version: Option<IP>,
-    pub(crate) local_port: u16,
-    pub(crate) recv_timeout: u64,
-    pub(crate) packet_ttl: u32,
-    pub(crate) local_network_only: bool,
-    pub(crate) loop_back_also: bool,
+    /// A specific network interface to bind to; if specified the default address for the
+    /// interface will be used. Default: `None`.
+    pub network_interface: Option<String>,
+    /// Denotes whether to use IPv4 or IPv6 when no address is otherwise given. Default: `None`,
+    /// which is IPv4.
+    pub network_version: Option<IP>,
+    /// An explicit local address to bind to, this takes precedence over `network_interface`.
+    /// Default: `None`.
+    pub source_address: Option<IpAddr>,
+    /// The local port to bind to, `0` lets the system choose. Default: `0`.
+    pub local_port: u16,
+    /// The time to wait for each response. Default: `DEFAULT_RECV_TIMEOUT`.
+    pub recv_timeout: Duration,
+    /// The TTL, or for IPv6 the hop limit, of sent packets, both unicast and multicast.
+    /// Default: `2`.
+    pub packet_ttl: u32,
+    /// Whether multicast packets sent are also delivered to the sending host. Default: `false`.
+    pub loop_back_also: bool,
+    /// The size of the buffer each received datagram is read into; anything larger is
+    /// truncated. Default: `DEFAULT_BUFFER_SIZE`.
+    pub buffer_size: usize,
+    /// Set `SO_REUSEADDR` on the socket. Default: `false`.
+    pub reuse_address: bool,
+    /// Set `SO_REUSEPORT` on the socket; this is ignored on platforms without it. Default:
+    /// `false`.
+    pub reuse_port: bool,
+    /// The Differentiated Services code point, `0..=63`, to mark sent packets with. Default:
+    /// `None`, which leaves the system default.
+    pub dscp: Option<u8>,
     //    pub callback: Option<CallbackFn>,
 }
 
-pub const DEFAULT_BUFFER_SIZE: usize = 1500;
+///
+/// The default size of the receive buffer, large enough for any SSDP response seen in practice;
+/// the specification allows messages to exceed a single Ethernet frame.
+///
+pub const DEFAULT_BUFFER_SIZE: usize = 8192;
 
-pub const DEFAULT_RECV_TIMEOUT: u64 = 2;
+///
+/// The default time to wait for each response.
+///
+pub const DEFAULT_RECV_TIMEOUT: Duration = Duration::from_secs(2);
 
 // ------------------------------------------------------------------------------------------------
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Create a socket, configured according to `options`, for sending messages to `to_address` and
+/// receiving any responses. If `to_address` is a multicast group it is joined on the bound
+/// interface.
+///
 pub fn create_multicast_socket(
     to_address: &SocketAddr,
     options: &Options,
 ) -> Result<UdpSocket, Error> {
     debug!("create_multicast_socket - options: {:?}", options);
-    let local_address = match interface::ip_address_for_interface(
-        &options.network_interface,
-        &options.network_version,
-    ) {
+    if options.buffer_size == 0 {
+        error!("create_multicast_socket - buffer_size must not be zero");
+        return invalid_socket_value("buffer_size", "0").into();
+    }
+    let local_address = match options.source_address.or_else(|| {
+        interface::ip_address_for_interface(&options.network_interface, &options.network_version)
+    }) {
         None => match &options.network_version {
-            Some(IP::V6) => SocketAddr::V6(SocketAddrV6::new(
-                Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0),
-                options.local_port,
-                0,
-                0,
-            )),
-            _ => SocketAddr::V4(SocketAddrV4::new(
-                Ipv4Addr::new(0, 0, 0, 0),
-                options.local_port,
-            )),
+            Some(IP::V6) => SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), options.local_port),
+            _ => SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), options.local_port),
         },
         Some(address) => SocketAddr::new(address, options.local_port),
     };
@@ -65,49 +98,120 @@ pub fn create_multicast_socket(
         "create_multicast_socket - binding to local_address: {:?}",
         local_address
     );
-    let socket = UdpSocket::bind(local_address)?;
+    let socket = Socket::new(
+        Domain::for_address(local_address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
 
     trace!("create_multicast_socket - setting socket options");
+    socket.set_reuse_address(options.reuse_address)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(options.reuse_port)?;
+    socket.bind(&local_address.into())?;
     socket.set_nonblocking(false)?;
-    socket.set_ttl(options.packet_ttl)?;
-    socket.set_read_timeout(Some(Duration::from_secs(options.recv_timeout)))?;
+    socket.set_read_timeout(Some(options.recv_timeout))?;
     match (to_address, local_address) {
         (SocketAddr::V4(to_address), SocketAddr::V4(local_address)) => {
-            socket.join_multicast_v4(to_address.ip(), local_address.ip())?;
-            socket.set_multicast_ttl_v4(if options.local_network_only { 1 } else { 10 })?;
+            // Unicast messages, such as a search sent to a single device, have no group to join.
+            if to_address.ip().is_multicast() {
+                socket.join_multicast_v4(to_address.ip(), local_address.ip())?;
+            }
+            socket.set_ttl_v4(options.packet_ttl)?;
+            socket.set_multicast_ttl_v4(options.packet_ttl)?;
             socket.set_multicast_loop_v4(options.loop_back_also)?;
+            if let Some(dscp) = options.dscp {
+                socket.set_tos_v4(u32::from(dscp_to_traffic_class(dscp)?))?;
+            }
         }
         (SocketAddr::V6(_), SocketAddr::V6(_)) => {
+            socket.set_unicast_hops_v6(options.packet_ttl)?;
+            socket.set_multicast_hops_v6(options.packet_ttl)?;
             socket.set_multicast_loop_v6(options.loop_back_also)?;
+            if let Some(dscp) = options.dscp {
+                set_traffic_class_v6(&socket, dscp_to_traffic_class(dscp)?)?;
+            }
         }
         _ => {
-            return invalid_socket_value(
-                "to, local",
-                format!("{}, {}", to_address, local_address),
-            )
-            .into();
+            return invalid_socket_value("to, local", format!("{}, {}", to_address, local_address))
+                .into();
         }
     }
 
+    let socket: UdpSocket = socket.into();
     trace!(
-        "create_multicast_socket - socket: {:?}, read_timeout: {:?}, ttl: {:?}, multicast_ttl: {}",
+        "create_multicast_socket - socket: {:?}, read_timeout: {:?}",
         socket,
         socket.read_timeout()?,
-        socket.ttl()?,
-        socket.multicast_ttl_v4()?
     );
 
     Ok(socket)
 }
 
+///
+/// Create a socket that receives all traffic sent to the multicast group `group_address`. The
+/// socket is bound to the group's port on all addresses, with address (and, where supported,
+/// port) reuse enabled so that it may coexist with other SSDP listeners on the same host, and
+/// joins the group on the interface selected by `options`.
+///
+pub fn create_listener_socket(
+    group_address: &SocketAddr,
+    options: &Options,
+) -> Result<UdpSocket, Error> {
+    debug!(
+        "create_listener_socket - group: {:?}, options: {:?}",
+        group_address, options
+    );
+    let socket = Socket::new(
+        Domain::for_address(*group_address),
+        Type::DGRAM,
+        Some(Protocol::UDP),
+    )?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+
+    match group_address.ip() {
+        IpAddr::V4(group) => {
+            let interface_address = match interface::ip_address_for_interface(
+                &options.network_interface,
+                &Some(IP::V4),
+            ) {
+                Some(IpAddr::V4(address)) => address,
+                _ => Ipv4Addr::UNSPECIFIED,
+            };
+            socket.bind(
+                &SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), group_address.port()).into(),
+            )?;
+            socket.join_multicast_v4(&group, &interface_address)?;
+            socket.set_multicast_loop_v4(true)?;
+        }
+        IpAddr::V6(group) => {
+            socket.set_only_v6(true)?;
+            socket.bind(
+                &SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), group_address.port()).into(),
+            )?;
+            socket.join_multicast_v6(
+                &group,
+                interface::index_for_interface(&options.network_interface).unwrap_or(0),
+            )?;
+            socket.set_multicast_loop_v6(true)?;
+        }
+    }
+
+    let socket: UdpSocket = socket.into();
+    trace!("create_listener_socket - socket: {:?}", socket);
+    Ok(socket)
+}
+
 pub fn multicast(
     message: &Request,
     to_address: &SocketAddr,
     options: &Options,
-) -> Result<Vec<Response>, Error> {
-    let socket = create_multicast_socket(to_address, options)?;
+) -> Result<Vec<(SocketAddr, Response)>, Error> {
+    let transport = UdpTransport::new(to_address, options)?;
 
-    multicast_using(message, to_address, &socket)
+    multicast_using(message, to_address, &transport, options)
 }
 
 pub fn multicast_once(
@@ -115,43 +219,50 @@ pub fn multicast_once(
     to_address: &SocketAddr,
     options: &Options,
 ) -> Result<(), Error> {
-    let socket = create_multicast_socket(to_address, options)?;
+    let transport = UdpTransport::new(to_address, options)?;
 
-    multicast_once_using(message, to_address, &socket)
+    multicast_once_using(message, to_address, &transport)
 }
 
+///
+/// Send `message` to `to_address` using `transport`, then collect responses until none arrives
+/// within `options.recv_timeout`; each response is read into a buffer of `options.buffer_size`
+/// bytes.
+///
 pub fn multicast_using(
     message: &Request,
     to_address: &SocketAddr,
-    socket: &UdpSocket,
-) -> Result<Vec<Response>, Error> {
-    multicast_send_using(message, to_address, socket)?;
+    transport: &dyn Transport,
+    options: &Options,
+) -> Result<Vec<(SocketAddr, Response)>, Error> {
+    multicast_send_using(message, to_address, transport)?;
 
-    let mut responses: Vec<Response> = Default::default();
+    let mut responses: Vec<(SocketAddr, Response)> = Default::default();
 
+    let mut buf = vec![0u8; options.buffer_size];
     loop {
-        let mut buf = [0u8; DEFAULT_BUFFER_SIZE];
         trace!(
             "multicast_using - blocking on recv_from, buffer size {}",
-            DEFAULT_BUFFER_SIZE
+            buf.len()
         );
-        match socket.recv_from(&mut buf) {
-            Ok((received, from)) => {
+        match transport.recv_from(&mut buf, options.recv_timeout)? {
+            Some((received, from)) => {
                 trace!(
                     "multicast_using - received {} bytes from {:?}",
                     received,
                     from,
                 );
-                responses.push(Response::try_from(&buf[..received])?);
-            }
-            Err(e) => {
-                if e.kind() == IOErrorKind::WouldBlock {
-                    trace!("multicast_using - socket timed out, no data");
-                    break;
-                } else {
-                    error!("multicast_using - socket read returned error: {:?}", e);
-                    return Err(Error::NetworkTransport(e));
+                if received == buf.len() {
+                    warn!(
+                        "multicast_using - response from {:?} filled the buffer, it may be truncated",
+                        from
+                    );
                 }
+                responses.push((from, Response::try_from(&buf[..received])?));
+            }
+            None => {
+                trace!("multicast_using - transport timed out, no data");
+                break;
             }
         }
     }
@@ -161,9 +272,9 @@ pub fn multicast_using(
 pub fn multicast_once_using(
     message: &Request,
     to_address: &SocketAddr,
-    socket: &UdpSocket,
+    transport: &dyn Transport,
 ) -> Result<(), Error> {
-    multicast_send_using(message, to_address, socket)
+    multicast_send_using(message, to_address, transport)
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -175,11 +286,15 @@ impl Default for Options {
         Options {
             network_interface: None,
             network_version: None,
+            source_address: None,
             local_port: 0,
             recv_timeout: DEFAULT_RECV_TIMEOUT,
             packet_ttl: 2,
-            local_network_only: false,
             loop_back_also: false,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            reuse_address: false,
+            reuse_port: false,
+            dscp: None,
             //callback: None,
         }
     }
@@ -189,14 +304,56 @@ impl Default for Options {
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// The DSCP occupies the upper six bits of the IPv4 TOS, and IPv6 traffic class, octet.
+///
+fn dscp_to_traffic_class(dscp: u8) -> Result<u8, Error> {
+    if dscp > 63 {
+        error!(
+            "dscp_to_traffic_class - DSCP must be between 0..63 ({})",
+            dscp
+        );
+        return invalid_socket_value("dscp", dscp.to_string()).into();
+    }
+    Ok(dscp << 2)
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn set_traffic_class_v6(socket: &Socket, traffic_class: u8) -> Result<(), Error> {
+    Ok(socket.set_tclass_v6(u32::from(traffic_class))?)
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn set_traffic_class_v6(_socket: &Socket, traffic_class: u8) -> Result<(), Error> {
+    warn!(
+        "set_traffic_class_v6 - not supported on this platform, ignoring {}",
+        traffic_class
+    );
+    Ok(())
+}
+
 #[inline]
 fn multicast_send_using(
     message: &Request,
     to_address: &SocketAddr,
-    socket: &UdpSocket,
+    transport: &dyn Transport,
 ) -> Result<(), Error> {
     let message: String = message.into();
-    socket.send_to(message.as_bytes(), to_address)?;
+    transport.send_to(message.as_bytes(), to_address)?;
     Ok(())
 }
 
@@ -204,14 +361,146 @@ fn multicast_send_using(
 // Modules
 // ------------------------------------------------------------------------------------------------
 
-#[doc(hidden)]
 mod builder;
-pub use builder::RequestBuilder;
+pub use builder::{RequestBuilder, ResponseBuilder};
+
+mod headers;
+pub use headers::Headers;
 
-#[doc(hidden)]
 mod request;
 pub use request::Request;
 
-#[doc(hidden)]
 mod response;
-pub use response::Response;
+pub use response::{Response, ResponseStatus};
+
+mod transport;
+pub use transport::{MemoryTransport, Transport, UdpTransport};
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const EX_SEARCH: &str = "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: ssdp:all\r\n\r\n";
+
+    const EX_NOTIFY: &str = "NOTIFY * HTTP/1.1\r\nHost: 239.255.255.250:1900\r\nNT: upnp:rootdevice\r\nNTS: ssdp:alive\r\nX-Vendor: one\r\nBOOTID.UPNP.ORG: 7\r\nX-Vendor: two\r\n\r\n";
+
+    #[test]
+    fn test_create_multicast_socket_options() {
+        let options = Options {
+            source_address: Some(Ipv4Addr::LOCALHOST.into()),
+            recv_timeout: Duration::from_millis(250),
+            packet_ttl: 4,
+            loop_back_also: true,
+            reuse_address: true,
+            dscp: Some(46),
+            ..Default::default()
+        };
+        let socket = create_multicast_socket(&"127.0.0.1:1900".parse().unwrap(), &options).unwrap();
+        assert_eq!(socket.local_addr().unwrap().ip(), Ipv4Addr::LOCALHOST);
+        assert_eq!(socket.ttl().unwrap(), 4);
+        // The system may round the timeout to its clock resolution.
+        let read_timeout = socket.read_timeout().unwrap().unwrap();
+        assert!(
+            read_timeout >= Duration::from_millis(250) && read_timeout < Duration::from_secs(1)
+        );
+        let socket = Socket::from(socket);
+        assert_eq!(socket.multicast_ttl_v4().unwrap(), 4);
+        assert!(socket.multicast_loop_v4().unwrap());
+        assert!(socket.reuse_address().unwrap());
+        assert_eq!(socket.tos_v4().unwrap(), 46 << 2);
+
+        let invalid = Options {
+            dscp: Some(64),
+            ..options.clone()
+        };
+        assert!(create_multicast_socket(&"127.0.0.1:1900".parse().unwrap(), &invalid).is_err());
+        let invalid = Options {
+            buffer_size: 0,
+            ..options
+        };
+        assert!(create_multicast_socket(&"127.0.0.1:1900".parse().unwrap(), &invalid).is_err());
+    }
+
+    #[test]
+    fn test_parse_search_request() {
+        let request = Request::try_from(EX_SEARCH.as_bytes()).unwrap();
+        assert_eq!(request.method(), "M-SEARCH");
+        assert_eq!(request.resource(), None);
+        assert_eq!(request.headers().get("st").unwrap(), "ssdp:all");
+        assert_eq!(request.headers().get("MAN").unwrap(), "\"ssdp:discover\"");
+        assert!(request.body().is_none());
+    }
+
+    #[test]
+    fn test_parse_notify_request_preserves_headers() {
+        let request = Request::try_from(EX_NOTIFY.as_bytes()).unwrap();
+        assert_eq!(request.method(), "NOTIFY");
+        assert_eq!(request.headers().len(), 6);
+        assert_eq!(
+            request.headers().get("HOST").unwrap(),
+            "239.255.255.250:1900"
+        );
+        assert_eq!(request.headers().get("bootid.upnp.org").unwrap(), "7");
+        assert_eq!(request.headers().get_all("x-vendor"), vec!["one", "two"]);
+        let names: Vec<&String> = request.headers().iter().map(|(k, _)| k).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Host",
+                "NT",
+                "NTS",
+                "X-Vendor",
+                "BOOTID.UPNP.ORG",
+                "X-Vendor"
+            ]
+        );
+        let written = String::from(&request);
+        assert!(written.starts_with("NOTIFY * HTTP/1.1\r\nHost:239.255.255.250:1900\r\n"));
+        let reparsed = Request::try_from(written.as_bytes()).unwrap();
+        assert_eq!(reparsed.headers(), request.headers());
+    }
+
+    #[test]
+    fn test_request_round_trip_with_body() {
+        let mut builder = RequestBuilder::new("NOTIFY");
+        builder
+            .for_resource("/event")
+            .add_header("NT", "upnp:event")
+            .body(b"<e:propertyset/>");
+        let request: Request = builder.into();
+        let bytes: Vec<u8> = (&request).into();
+        assert_eq!(
+            String::from_utf8(bytes.clone()).unwrap(),
+            "NOTIFY /event HTTP/1.1\r\nNT:upnp:event\r\nCONTENT-LENGTH:16\r\n\r\n<e:propertyset/>"
+        );
+        let parsed = Request::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(parsed.resource().unwrap(), "/event");
+        assert_eq!(parsed.body().unwrap(), b"<e:propertyset/>");
+    }
+
+    #[test]
+    fn test_response_round_trip_non_success() {
+        let mut builder = ResponseBuilder::new(412, "Precondition Failed");
+        builder.add_header("SERVER", "test/1.0").body(b"nope");
+        let response: Response = builder.into();
+        let bytes: Vec<u8> = (&response).into();
+        let parsed = Response::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(parsed.status().code(), 412);
+        assert_eq!(parsed.status().message(), "Precondition Failed");
+        assert!(!parsed.status().is_success());
+        assert_eq!(parsed.headers().get("server").unwrap(), "test/1.0");
+        assert_eq!(parsed.body().unwrap(), b"nope");
+    }
+
+    #[test]
+    fn test_response_invalid_content_length() {
+        let response =
+            Response::try_from("HTTP/1.1 200 OK\r\nCONTENT-LENGTH: 10\r\n\r\nshort".as_bytes());
+        assert!(response.is_err());
+    }
+}
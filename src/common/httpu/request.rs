@@ -2,21 +2,33 @@
 What's this all about then?
 */
 
+use crate::common::httpu::headers::{
+    decode_body, decode_headers, encode_headers_and_body, split_at_body,
+};
+use crate::common::httpu::Headers;
+use crate::error::{invalid_header_value, MessageFormatError};
 use crate::syntax::{
-    HTTP_HEADER_LINE_SEP, HTTP_HEADER_SEP, HTTP_MATCH_ANY_RESOURCE, HTTP_PROTOCOL_NAME,
-    HTTP_PROTOCOL_VERSION,
+    HTTP_HEADER_LINE_SEP, HTTP_MATCH_ANY_RESOURCE, HTTP_PROTOCOL_NAME, HTTP_PROTOCOL_VERSION,
 };
-use std::collections::HashMap;
+use regex::Regex;
+use std::convert::TryFrom;
+use std::str::from_utf8;
+use tracing::{error, trace};
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
+///
+/// An HTTPU/HTTPMU request, such as `M-SEARCH` or `NOTIFY`, with all its headers as sent or
+/// received.
+///
 #[derive(Clone, Debug)]
 pub struct Request {
     pub(crate) message: String,
     pub(crate) resource: Option<String>,
-    pub(crate) headers: HashMap<String, String>,
+    pub(crate) headers: Headers,
+    pub(crate) body: Option<Vec<u8>>,
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -24,6 +36,28 @@ pub struct Request {
 // ------------------------------------------------------------------------------------------------
 
 impl Request {
+    ///
+    /// The request method, for example `M-SEARCH` or `NOTIFY`.
+    ///
+    pub fn method(&self) -> &String {
+        &self.message
+    }
+
+    ///
+    /// The requested resource, `None` denotes the match-any resource `*`.
+    ///
+    pub fn resource(&self) -> Option<&String> {
+        self.resource.as_ref()
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    pub fn body(&self) -> Option<&Vec<u8>> {
+        self.body.as_ref()
+    }
+
     fn request_line(&self) -> String {
         format!(
             "{} {} {}/{}{}",
@@ -37,22 +71,77 @@ impl Request {
             HTTP_HEADER_LINE_SEP,
         )
     }
+}
 
-    fn all_headers(&self) -> String {
-        self.headers
-            .iter()
-            .map(|(k, v)| format!("{}{}{}", k, HTTP_HEADER_SEP, v))
-            .collect::<Vec<String>>()
-            .join(HTTP_HEADER_LINE_SEP)
-    }
-
-    fn body(&self) -> String {
-        format!("{}{}", HTTP_HEADER_LINE_SEP, HTTP_HEADER_LINE_SEP)
+impl From<&Request> for Vec<u8> {
+    fn from(rq: &Request) -> Self {
+        let mut bytes = rq.request_line().into_bytes();
+        bytes.extend(encode_headers_and_body(&rq.headers, &rq.body));
+        bytes
     }
 }
 
 impl From<&Request> for String {
     fn from(rq: &Request) -> Self {
-        format!("{}{}{}", rq.request_line(), rq.all_headers(), rq.body())
+        String::from_utf8_lossy(&Vec::from(rq)).to_string()
+    }
+}
+
+impl TryFrom<&[u8]> for Request {
+    type Error = MessageFormatError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let (raw_headers, body) = split_at_body(bytes);
+
+        let headers = from_utf8(raw_headers)?;
+        let mut lines = headers
+            .split(HTTP_HEADER_LINE_SEP)
+            .map(String::from)
+            .collect::<Vec<String>>();
+
+        let (message, resource) = decode_request_line(lines.remove(0))?;
+
+        let headers = decode_headers(lines)?;
+
+        trace!("{:?}", headers);
+
+        let body = decode_body(&headers, body)?;
+
+        Ok(Request {
+            message,
+            resource,
+            headers,
+            body,
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn decode_request_line(line: String) -> Result<(String, Option<String>), MessageFormatError> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^([A-Za-z\-_]+) (\S+) HTTP/([\d\.]+)$").unwrap();
+    }
+    match RE.captures(&line) {
+        None => {
+            error!(
+                "decode_request_line - could not decode request line '{}'",
+                line
+            );
+            invalid_header_value("REQUEST", line).into()
+        }
+        Some(captured) => {
+            let resource = captured.get(2).unwrap().as_str();
+            Ok((
+                captured.get(1).unwrap().as_str().to_string(),
+                if resource == HTTP_MATCH_ANY_RESOURCE {
+                    None
+                } else {
+                    Some(resource.to_string())
+                },
+            ))
+        }
     }
 }
@@ -2,10 +2,13 @@
 What's this all about then?
 */
 
+use crate::common::httpu::headers::{
+    decode_body, decode_headers, encode_headers_and_body, split_at_body,
+};
+use crate::common::httpu::Headers;
 use crate::error::{invalid_header_value, MessageFormatError};
-use crate::syntax::HTTP_HEADER_LINE_SEP;
+use crate::syntax::{HTTP_HEADER_LINE_SEP, HTTP_PROTOCOL_NAME, HTTP_PROTOCOL_VERSION};
 use regex::Regex;
-use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::str::from_utf8;
 use std::str::FromStr;
@@ -17,29 +20,102 @@ use tracing::{error, trace};
 
 #[derive(Clone, Debug)]
 pub struct ResponseStatus {
-    #[allow(dead_code)]
     protocol: String,
-    #[allow(dead_code)]
     version: String,
-    #[allow(dead_code)]
     code: u16,
-    #[allow(dead_code)]
     message: String,
 }
 
+///
+/// An HTTPU response, such as the answer to an `M-SEARCH`, with all its headers as sent or
+/// received.
+///
 #[derive(Clone, Debug)]
 pub struct Response {
-    #[allow(dead_code)]
-    status: ResponseStatus,
-    pub(crate) headers: HashMap<String, String>,
-    #[allow(dead_code)]
-    body: Option<Vec<u8>>,
+    pub(crate) status: ResponseStatus,
+    pub(crate) headers: Headers,
+    pub(crate) body: Option<Vec<u8>>,
 }
 
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
 
+impl ResponseStatus {
+    pub fn new(code: u16, message: &str) -> Self {
+        Self {
+            protocol: HTTP_PROTOCOL_NAME.to_string(),
+            version: HTTP_PROTOCOL_VERSION.to_string(),
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn protocol(&self) -> &String {
+        &self.protocol
+    }
+
+    pub fn version(&self) -> &String {
+        &self.version
+    }
+
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    pub fn message(&self) -> &String {
+        &self.message
+    }
+
+    ///
+    /// Returns `true` if the status code is in the 2xx, success, range.
+    ///
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.code)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Response {
+    pub fn status(&self) -> &ResponseStatus {
+        &self.status
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    pub fn body(&self) -> Option<&Vec<u8>> {
+        self.body.as_ref()
+    }
+
+    fn status_line(&self) -> String {
+        format!(
+            "{}/{} {} {}{}",
+            self.status.protocol,
+            self.status.version,
+            self.status.code,
+            self.status.message,
+            HTTP_HEADER_LINE_SEP,
+        )
+    }
+}
+
+impl From<&Response> for Vec<u8> {
+    fn from(rs: &Response) -> Self {
+        let mut bytes = rs.status_line().into_bytes();
+        bytes.extend(encode_headers_and_body(&rs.headers, &rs.body));
+        bytes
+    }
+}
+
+impl From<&Response> for String {
+    fn from(rs: &Response) -> Self {
+        String::from_utf8_lossy(&Vec::from(rs)).to_string()
+    }
+}
+
 impl TryFrom<&[u8]> for Response {
     type Error = MessageFormatError;
 
@@ -58,14 +134,12 @@ impl TryFrom<&[u8]> for Response {
 
         trace!("{:?}", headers);
 
+        let body = decode_body(&headers, body)?;
+
         Ok(Response {
             status,
             headers,
-            body: if body.is_empty() {
-                None
-            } else {
-                Some(body.into())
-            },
+            body,
         })
     }
 }
@@ -74,20 +148,9 @@ impl TryFrom<&[u8]> for Response {
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
-fn split_at_body(all: &[u8]) -> (&[u8], &[u8]) {
-    static BLANK_LINE: &[u8] = b"\r\n\r\n";
-    match all
-        .windows(BLANK_LINE.len())
-        .position(|window| window == BLANK_LINE)
-    {
-        None => (all, &[]),
-        Some(start) => (&all[..start], &all[start + BLANK_LINE.len()..]),
-    }
-}
-
 fn decode_status_line(line: String) -> Result<ResponseStatus, MessageFormatError> {
     lazy_static! {
-        static ref RE: Regex = Regex::new(r"^HTTP/([\d\.]+) (\d+) (.*)$").unwrap();
+        static ref RE: Regex = Regex::new(r"^(HTTP)/([\d\.]+) (\d{3})(?: (.*))?$").unwrap();
     }
     match RE.captures(&line) {
         None => {
@@ -97,51 +160,14 @@ fn decode_status_line(line: String) -> Result<ResponseStatus, MessageFormatError
             );
             invalid_header_value("STATUS", line).into()
         }
-        Some(captured) => {
-            let status_code = u16::from_str(captured.get(2).unwrap().as_str()).unwrap();
-            if status_code == 200 {
-                Ok(ResponseStatus {
-                    protocol: String::from("HTTP"),
-                    version: captured.get(1).unwrap().as_str().to_string(),
-                    code: status_code,
-                    message: captured.get(3).unwrap().as_str().to_string(),
-                })
-            } else {
-                error!("server returned error '{}'", status_code);
-                invalid_header_value("STATUS", status_code.to_string()).into()
-            }
-        }
-    }
-}
-
-fn decode_headers(lines: Vec<String>) -> Result<HashMap<String, String>, MessageFormatError> {
-    let mut headers: HashMap<String, String> = HashMap::new();
-    for line in lines {
-        let (key, value) = match decode_header(line)? {
-            Some(x) => x,
-            None => continue,
-        };
-        headers.insert(key, value);
-    }
-    Ok(headers)
-}
-
-fn decode_header(line: String) -> Result<Option<(String, String)>, MessageFormatError> {
-    lazy_static! {
-        static ref RE: Regex = Regex::new(r"^([a-zA-Z0-9\-_]*)[ ]*:[ ]*(.*)$").unwrap();
-    }
-    if line.is_empty() {
-        return Ok(None);
-    }
-
-    match RE.captures(&line) {
-        None => {
-            error!("decode_header - could not decode header '{}'", line);
-            invalid_header_value("?", line).into()
-        }
-        Some(captured) => Ok(Some((
-            captured.get(1).unwrap().as_str().to_uppercase(),
-            captured.get(2).unwrap().as_str().to_string(),
-        ))),
+        Some(captured) => Ok(ResponseStatus {
+            protocol: captured.get(1).unwrap().as_str().to_string(),
+            version: captured.get(2).unwrap().as_str().to_string(),
+            code: u16::from_str(captured.get(3).unwrap().as_str()).unwrap(),
+            message: captured
+                .get(4)
+                .map(|m| m.as_str().to_string())
+                .unwrap_or_default(),
+        }),
     }
 }
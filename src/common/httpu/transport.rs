@@ -0,0 +1,220 @@
+/*!
+This module provides the `Transport` abstraction over which HTTPMU/HTTPU messages are sent and
+received. The default implementation, `UdpTransport`, wraps a standard UDP socket, while
+`MemoryTransport` allows tests to script responses and inspect sent messages without touching the
+network.
+*/
+
+use crate::common::httpu::{create_multicast_socket, Options};
+use crate::error::Error;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::io::ErrorKind as IOErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, trace};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A datagram transport used to send requests and receive responses.
+///
+pub trait Transport: Debug + Send + Sync {
+    ///
+    /// Send the bytes in `buffer` as a single datagram to `to_address`, returning the number of
+    /// bytes sent.
+    ///
+    fn send_to(&self, buffer: &[u8], to_address: &SocketAddr) -> Result<usize, Error>;
+
+    ///
+    /// Receive a single datagram into `buffer`, waiting at most `timeout`. Returns `None` if the
+    /// timeout expired before any data was received, otherwise the number of bytes received and
+    /// the address of the sender.
+    ///
+    fn recv_from(
+        &self,
+        buffer: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, Error>;
+
+    ///
+    /// The local address this transport is bound to.
+    ///
+    fn local_addr(&self) -> Result<SocketAddr, Error>;
+}
+
+///
+/// The default transport, sending and receiving over a UDP socket.
+///
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+///
+/// An in-memory transport; responses are queued ahead of time with `push_response` and all
+/// datagrams sent are captured and may be retrieved with `sent`. Once all queued responses have
+/// been consumed `recv_from` behaves as if the timeout expired.
+///
+#[derive(Debug)]
+pub struct MemoryTransport {
+    local_address: SocketAddr,
+    responses: Mutex<VecDeque<(SocketAddr, Vec<u8>)>>,
+    sent: Mutex<Vec<(SocketAddr, Vec<u8>)>>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl UdpTransport {
+    ///
+    /// Create a new multicast socket, configured according to `options`, for sending messages to
+    /// `to_address`.
+    ///
+    pub fn new(to_address: &SocketAddr, options: &Options) -> Result<Self, Error> {
+        Ok(Self {
+            socket: create_multicast_socket(to_address, options)?,
+        })
+    }
+
+    ///
+    /// Return the underlying socket.
+    ///
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+}
+
+impl From<UdpSocket> for UdpTransport {
+    fn from(socket: UdpSocket) -> Self {
+        Self { socket }
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send_to(&self, buffer: &[u8], to_address: &SocketAddr) -> Result<usize, Error> {
+        Ok(self.socket.send_to(buffer, to_address)?)
+    }
+
+    fn recv_from(
+        &self,
+        buffer: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, Error> {
+        self.socket.set_read_timeout(Some(timeout))?;
+        match self.socket.recv_from(buffer) {
+            Ok(received) => Ok(Some(received)),
+            Err(e) => {
+                if e.kind() == IOErrorKind::WouldBlock || e.kind() == IOErrorKind::TimedOut {
+                    trace!("recv_from - socket timed out, no data");
+                    Ok(None)
+                } else {
+                    error!("recv_from - socket read returned error: {:?}", e);
+                    Err(Error::NetworkTransport(e))
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.socket.local_addr()?)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A shared transport, this allows the owner of a transport given to a listener, for example,
+/// to still inspect it.
+///
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn send_to(&self, buffer: &[u8], to_address: &SocketAddr) -> Result<usize, Error> {
+        (**self).send_to(buffer, to_address)
+    }
+
+    fn recv_from(
+        &self,
+        buffer: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, Error> {
+        (**self).recv_from(buffer, timeout)
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, Error> {
+        (**self).local_addr()
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl MemoryTransport {
+    ///
+    /// Create a new, empty, transport that reports `local_address` as its bound address.
+    ///
+    pub fn new(local_address: SocketAddr) -> Self {
+        Self {
+            local_address,
+            responses: Default::default(),
+            sent: Default::default(),
+        }
+    }
+
+    ///
+    /// Queue a datagram to be returned, as if sent from `from_address`, by a later call to
+    /// `recv_from`.
+    ///
+    pub fn push_response(&self, from_address: SocketAddr, response: &[u8]) -> &Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .push_back((from_address, response.to_vec()));
+        self
+    }
+
+    ///
+    /// Return all datagrams sent on this transport, along with the address they were sent to.
+    ///
+    pub fn sent(&self) -> Vec<(SocketAddr, Vec<u8>)> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    ///
+    /// Returns `true` if there are no more queued responses.
+    ///
+    pub fn is_drained(&self) -> bool {
+        self.responses.lock().unwrap().is_empty()
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send_to(&self, buffer: &[u8], to_address: &SocketAddr) -> Result<usize, Error> {
+        self.sent
+            .lock()
+            .unwrap()
+            .push((*to_address, buffer.to_vec()));
+        Ok(buffer.len())
+    }
+
+    fn recv_from(
+        &self,
+        buffer: &mut [u8],
+        _timeout: Duration,
+    ) -> Result<Option<(usize, SocketAddr)>, Error> {
+        match self.responses.lock().unwrap().pop_front() {
+            None => Ok(None),
+            Some((from_address, response)) => {
+                let received = response.len().min(buffer.len());
+                buffer[..received].copy_from_slice(&response[..received]);
+                Ok(Some((received, from_address)))
+            }
+        }
+    }
+
+    fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.local_address)
+    }
+}
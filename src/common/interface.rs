@@ -1,7 +1,10 @@
+use crate::error::Error;
 use pnet::datalink;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 #[allow(dead_code)]
 pub enum IP {
     V4,
@@ -26,6 +29,16 @@ pub fn ip_address_for_interface(
     }
 }
 
+pub fn index_for_interface(network_interface: &Option<String>) -> Option<u32> {
+    match network_interface {
+        None => None,
+        Some(name) => datalink::interfaces()
+            .into_iter()
+            .find(|ni| &ni.name == name)
+            .map(|ni| ni.index),
+    }
+}
+
 pub fn ip_addresses_for_interface(interface: String, version: Option<IP>) -> Vec<IpAddr> {
     let interfaces = datalink::interfaces();
     match &interfaces.into_iter().find(|ni| ni.name == interface) {
@@ -53,3 +66,17 @@ pub fn ip_addresses_for_interface(interface: String, version: Option<IP>) -> Vec
             .collect(),
     }
 }
+
+///
+/// Return the local address the system would use to send to `remote`; this is the address of
+/// the interface on the route to `remote`, no packets are sent.
+///
+pub fn local_address_for(remote: &SocketAddr) -> Result<IpAddr, Error> {
+    let socket = if remote.is_ipv4() {
+        UdpSocket::bind("0.0.0.0:0")?
+    } else {
+        UdpSocket::bind("[::]:0")?
+    };
+    socket.connect(remote)?;
+    Ok(socket.local_addr()?.ip())
+}
@@ -6,6 +6,11 @@ pub mod httpu;
 
 pub mod interface;
 
+pub mod monitor;
+
+#[cfg(feature = "serde")]
+pub(crate) mod seconds;
+
 pub mod soap;
 
 pub mod uri;
@@ -0,0 +1,407 @@
+/*!
+This module provides an `InterfaceMonitor` that reports changes to the host's network interfaces:
+links going up or down and addresses being added or removed. Devices use these to re-advertise
+with a new `LOCATION`, see `DeviceIdentity::relocate_using`, and control points to rejoin the
+multicast group, see `Listener::rejoin`.
+
+On Linux the monitor is woken by the kernel's routing (netlink) notifications and so reports
+changes as they happen; on other platforms, or if netlink is not available, it polls the list of
+interfaces.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::monitor::InterfaceMonitor;
+use std::time::Duration;
+
+let mut monitor = InterfaceMonitor::new();
+loop {
+    for change in monitor.next_changes(Duration::from_secs(5)).unwrap() {
+        println!("{}", change);
+    }
+}
+```
+*/
+
+use crate::error::Error;
+use pnet::datalink;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, trace, warn};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A single change to a network interface; each carries the interface name.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InterfaceEvent {
+    LinkUp(String),
+    LinkDown(String),
+    AddressAdded(String, IpAddr),
+    AddressRemoved(String, IpAddr),
+}
+
+///
+/// The state of a network interface, as last seen by the monitor.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InterfaceState {
+    pub index: u32,
+    pub up: bool,
+    pub addresses: BTreeSet<IpAddr>,
+}
+
+///
+/// Watches the host's network interfaces, see the [module documentation](index.html).
+///
+#[derive(Debug)]
+pub struct InterfaceMonitor {
+    wake: Wake,
+    checked: Instant,
+    interfaces: BTreeMap<String, InterfaceState>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for InterfaceEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterfaceEvent::LinkUp(name) => write!(f, "{} up", name),
+            InterfaceEvent::LinkDown(name) => write!(f, "{} down", name),
+            InterfaceEvent::AddressAdded(name, address) => write!(f, "{} added {}", name, address),
+            InterfaceEvent::AddressRemoved(name, address) => {
+                write!(f, "{} removed {}", name, address)
+            }
+        }
+    }
+}
+
+impl InterfaceEvent {
+    pub fn interface(&self) -> &String {
+        match self {
+            InterfaceEvent::LinkUp(name)
+            | InterfaceEvent::LinkDown(name)
+            | InterfaceEvent::AddressAdded(name, _)
+            | InterfaceEvent::AddressRemoved(name, _) => name,
+        }
+    }
+
+    ///
+    /// Returns `true` if this change is to `network_interface`, or to any interface if it is
+    /// `None`; this takes the same value as the `network_interface` field of the various
+    /// `Options` types.
+    ///
+    pub fn affects(&self, network_interface: &Option<String>) -> bool {
+        match network_interface {
+            None => true,
+            Some(name) => self.interface() == name,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Default for InterfaceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InterfaceMonitor {
+    ///
+    /// Create a new monitor, using netlink notifications where available and otherwise polling
+    /// every 5 seconds.
+    ///
+    pub fn new() -> Self {
+        #[cfg(target_os = "linux")]
+        match netlink::RouteSocket::open() {
+            Ok(socket) => return Self::with_wake(Wake::Netlink(socket)),
+            Err(e) => warn!("new - netlink not available, polling instead: {}", e),
+        }
+        Self::polling(DEFAULT_POLL_INTERVAL)
+    }
+
+    ///
+    /// Create a new monitor that checks the list of interfaces every `interval`.
+    ///
+    pub fn polling(interval: Duration) -> Self {
+        Self::with_wake(Wake::Poll(interval))
+    }
+
+    ///
+    /// Returns `true` if this monitor polls for changes rather than being notified of them.
+    ///
+    pub fn is_polling(&self) -> bool {
+        matches!(self.wake, Wake::Poll(_))
+    }
+
+    ///
+    /// The interfaces, by name, as of the last check.
+    ///
+    pub fn interfaces(&self) -> &BTreeMap<String, InterfaceState> {
+        &self.interfaces
+    }
+
+    ///
+    /// Wait up to `timeout` for the interfaces to change, returning the changes, or an empty list
+    /// if there were none in time.
+    ///
+    pub fn next_changes(&mut self, timeout: Duration) -> Result<Vec<InterfaceEvent>, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let woken = match &self.wake {
+                Wake::Poll(interval) => {
+                    let due = self.checked + *interval;
+                    let now = Instant::now();
+                    if now < due {
+                        thread::sleep(remaining.min(due - now));
+                    }
+                    Instant::now() >= due
+                }
+                #[cfg(target_os = "linux")]
+                Wake::Netlink(socket) => socket.wait(remaining)?,
+            };
+            if woken {
+                self.checked = Instant::now();
+                let current = interface_states();
+                let changes = changes_between(&self.interfaces, &current);
+                self.interfaces = current;
+                if !changes.is_empty() {
+                    info!("next_changes - {} interface change(s)", changes.len());
+                    return Ok(changes);
+                }
+                trace!("next_changes - woken, but no changes");
+            }
+            if Instant::now() >= deadline {
+                return Ok(Vec::new());
+            }
+        }
+    }
+
+    fn with_wake(wake: Wake) -> Self {
+        Self {
+            wake,
+            checked: Instant::now(),
+            interfaces: interface_states(),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+enum Wake {
+    Poll(Duration),
+    #[cfg(target_os = "linux")]
+    Netlink(netlink::RouteSocket),
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn interface_states() -> BTreeMap<String, InterfaceState> {
+    datalink::interfaces()
+        .into_iter()
+        .map(|interface| {
+            (
+                interface.name.clone(),
+                InterfaceState {
+                    index: interface.index,
+                    up: interface.is_up(),
+                    addresses: interface.ips.iter().map(|network| network.ip()).collect(),
+                },
+            )
+        })
+        .collect()
+}
+
+///
+/// The changes that turn `before` into `after`; for each interface removals are listed before
+/// additions, and a link comes up before its addresses are added.
+///
+fn changes_between(
+    before: &BTreeMap<String, InterfaceState>,
+    after: &BTreeMap<String, InterfaceState>,
+) -> Vec<InterfaceEvent> {
+    let empty = InterfaceState::default();
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    let mut changes = Vec::new();
+    for name in names {
+        let old = before.get(name).unwrap_or(&empty);
+        let new = after.get(name).unwrap_or(&empty);
+        changes.extend(
+            old.addresses
+                .difference(&new.addresses)
+                .map(|address| InterfaceEvent::AddressRemoved(name.clone(), *address)),
+        );
+        if old.up && !new.up {
+            changes.push(InterfaceEvent::LinkDown(name.clone()));
+        } else if !old.up && new.up {
+            changes.push(InterfaceEvent::LinkUp(name.clone()));
+        }
+        changes.extend(
+            new.addresses
+                .difference(&old.addresses)
+                .map(|address| InterfaceEvent::AddressAdded(name.clone(), *address)),
+        );
+    }
+    changes
+}
+
+// ------------------------------------------------------------------------------------------------
+// Modules
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(target_os = "linux")]
+mod netlink {
+    use socket2::{Domain, Protocol, SockAddr, SockAddrStorage, Socket, Type};
+    use std::io::{ErrorKind, Read};
+    use std::mem::size_of;
+    use std::time::Duration;
+
+    ///
+    /// A socket subscribed to the kernel's link and address notifications; the messages are
+    /// only used to know when to look at the interfaces again, not parsed.
+    ///
+    #[derive(Debug)]
+    pub(super) struct RouteSocket {
+        socket: Socket,
+    }
+
+    impl RouteSocket {
+        pub(super) fn open() -> std::io::Result<Self> {
+            let socket = Socket::new(
+                Domain::from(libc::AF_NETLINK),
+                Type::RAW,
+                Some(Protocol::from(libc::NETLINK_ROUTE)),
+            )?;
+            let mut storage = SockAddrStorage::zeroed();
+            // SAFETY: `sockaddr_nl` is a socket address type for this platform, and the length
+            // given for the address is its size.
+            let address = unsafe {
+                let address = storage.view_as::<libc::sockaddr_nl>();
+                address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+                address.nl_groups = (libc::RTMGRP_LINK
+                    | libc::RTMGRP_IPV4_IFADDR
+                    | libc::RTMGRP_IPV6_IFADDR) as u32;
+                SockAddr::new(storage, size_of::<libc::sockaddr_nl>() as libc::socklen_t)
+            };
+            socket.bind(&address)?;
+            Ok(Self { socket })
+        }
+
+        ///
+        /// Wait up to `timeout` for notifications, returning `true` if any arrived; all those
+        /// waiting are read so that a burst of them only wakes the monitor once.
+        ///
+        pub(super) fn wait(&self, timeout: Duration) -> std::io::Result<bool> {
+            let mut buffer = [0u8; 8192];
+            self.socket.set_nonblocking(false)?;
+            self.socket
+                .set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+            match (&self.socket).read(&mut buffer) {
+                Ok(_) => {}
+                // A signal, such as Ctrl-C, interrupting the wait is treated as a timeout so
+                // that the caller can decide whether to stop.
+                Err(e)
+                    if e.kind() == ErrorKind::WouldBlock
+                        || e.kind() == ErrorKind::TimedOut
+                        || e.kind() == ErrorKind::Interrupted =>
+                {
+                    return Ok(false)
+                }
+                Err(e) => return Err(e),
+            }
+            self.socket.set_nonblocking(true)?;
+            loop {
+                match (&self.socket).read(&mut buffer) {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(true),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn state(up: bool, addresses: &[&str]) -> InterfaceState {
+        InterfaceState {
+            index: 2,
+            up,
+            addresses: addresses
+                .iter()
+                .map(|address| address.parse().unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_changes_between() {
+        let mut before = BTreeMap::new();
+        let _ = before.insert("eth0".to_string(), state(true, &["192.168.1.10"]));
+        let _ = before.insert("wlan0".to_string(), state(true, &["10.0.0.5", "fe80::1"]));
+
+        let mut after = BTreeMap::new();
+        let _ = after.insert("eth0".to_string(), state(true, &["192.168.1.10"]));
+        let _ = after.insert("wlan0".to_string(), state(true, &["10.0.1.7", "fe80::1"]));
+        let _ = after.insert("usb0".to_string(), state(true, &["172.16.0.2"]));
+
+        assert!(changes_between(&before, &before).is_empty());
+        assert_eq!(
+            changes_between(&before, &after),
+            vec![
+                InterfaceEvent::LinkUp("usb0".to_string()),
+                InterfaceEvent::AddressAdded("usb0".to_string(), "172.16.0.2".parse().unwrap()),
+                InterfaceEvent::AddressRemoved("wlan0".to_string(), "10.0.0.5".parse().unwrap()),
+                InterfaceEvent::AddressAdded("wlan0".to_string(), "10.0.1.7".parse().unwrap()),
+            ]
+        );
+
+        let _ = after.insert("eth0".to_string(), state(false, &[]));
+        let changes = changes_between(&before, &after);
+        assert_eq!(
+            changes[..2],
+            [
+                InterfaceEvent::AddressRemoved("eth0".to_string(), "192.168.1.10".parse().unwrap()),
+                InterfaceEvent::LinkDown("eth0".to_string()),
+            ]
+        );
+        assert!(changes[0].affects(&Some("eth0".to_string())));
+        assert!(!changes[0].affects(&Some("wlan0".to_string())));
+        assert!(changes[0].affects(&None));
+    }
+
+    #[test]
+    fn test_polling_monitor() {
+        let mut monitor = InterfaceMonitor::polling(Duration::from_millis(10));
+        assert!(monitor.is_polling());
+        assert!(!monitor.interfaces().is_empty());
+        let started = Instant::now();
+        let _ = monitor.next_changes(Duration::from_millis(50)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
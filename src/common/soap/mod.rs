@@ -1,6 +1,4 @@
-use crate::description::TypeID;
-use crate::syntax::{SOAP_NS_ENCODING, SOAP_NS_ENVELOPE};
-/**
+/*!
 
 ```http
 POST path of control URL HTTP/1.1
@@ -21,35 +19,77 @@ SOAPACTION: "urn:schemas-upnp-org:service:serviceType:v#actionName"
    </s:Body>
 </s:Envelope>
 ```
+
+If an action fails the device responds with a SOAP fault, carrying the UPnP error code and
+description in a `<UPnPError>` detail element; see `fault_to_writer` and `fault_from_str`.
+
+```xml
+<s:Envelope
+   xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"
+   s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+   <s:Body>
+      <s:Fault>
+         <faultcode>s:Client</faultcode>
+         <faultstring>UPnPError</faultstring>
+         <detail>
+            <UPnPError xmlns="urn:schemas-upnp-org:control-1-0">
+               <errorCode>error code</errorCode>
+               <errorDescription>error string</errorDescription>
+            </UPnPError>
+         </detail>
+      </s:Fault>
+   </s:Body>
+</s:Envelope>
+```
 */
-use std::collections::HashMap;
-use std::fmt::{Display, Error, Formatter};
+use crate::common::xml::read::{read_document, Node};
+use crate::common::xml::write::RootWritable;
+use crate::description::TypeID;
+use crate::error::{invalid_field_value, missing_required_field, Error, UpnpError};
+use crate::syntax::{
+    SOAP_ACTION_PREFIX, SOAP_ATTR_ENCODING_STYLE, SOAP_ELEM_BODY, SOAP_ELEM_DETAIL,
+    SOAP_ELEM_ENVELOPE, SOAP_ELEM_ERROR_CODE, SOAP_ELEM_ERROR_DESCRIPTION, SOAP_ELEM_FAULT,
+    SOAP_ELEM_FAULT_CODE, SOAP_ELEM_FAULT_STRING, SOAP_ELEM_UPNP_ERROR, SOAP_FAULT_CODE_CLIENT,
+    SOAP_FAULT_STRING_UPNP, SOAP_NS_CONTROL, SOAP_NS_ENCODING, SOAP_NS_ENVELOPE, SOAP_PREFIX,
+    SOAP_RESPONSE_SUFFIX, XML_ATTR_NAMESPACE,
+};
+use std::convert::TryFrom;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+use tracing::error;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
-#[derive(Clone, Debug)]
+///
+/// Identifies an action by its name and the type of the service that provides it.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Action {
     service: TypeID,
     action: String,
 }
 
+///
+/// The content of an envelope. Arguments are held in order as `(name, value)` pairs, as the
+/// UDA requires that they appear in the order defined in the service description.
+///
 #[derive(Clone, Debug)]
 pub enum Body {
     Action {
         action: Action,
-        argumments: HashMap<String, String>,
+        arguments: Vec<(String, String)>,
     },
     Response {
         action: Action,
-        argumments: HashMap<String, String>,
+        arguments: Vec<(String, String)>,
     },
     Fault {
         code: String,
         string: String,
-        upnp_code: String,
-        upnp_description: String,
+        error: UpnpError,
     },
 }
 
@@ -59,10 +99,42 @@ pub struct Envelope {
     schema: String,
     #[allow(dead_code)]
     encoding_style: String,
-    #[allow(dead_code)]
     body: Body,
 }
 
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Write a complete SOAP envelope containing a fault that carries `error`.
+///
+pub fn fault_to_writer<T: Write>(error: &UpnpError, writer: T) -> Result<T, Error> {
+    Envelope::new_fault(error.clone()).to_writer(writer)
+}
+
+///
+/// Parse a complete SOAP envelope, returning the UPnP error carried in its fault. An error is
+/// returned if the document is not a SOAP envelope, or its body does not contain a fault.
+///
+pub fn fault_from_str(xml: &str) -> Result<UpnpError, Error> {
+    let document = read_document(xml)?;
+    if document.local_name() != SOAP_ELEM_ENVELOPE {
+        error!(
+            "fault_from_str - expecting envelope element, not '{}'",
+            document.name
+        );
+        return invalid_field_value(SOAP_ELEM_ENVELOPE, &document.name).into();
+    }
+    UpnpError::try_from(
+        document
+            .required_child(SOAP_ELEM_BODY)?
+            .required_child(SOAP_ELEM_FAULT)?
+            .required_child(SOAP_ELEM_DETAIL)?
+            .required_child(SOAP_ELEM_UPNP_ERROR)?,
+    )
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -78,10 +150,26 @@ impl Action {
             action,
         }
     }
+
+    pub fn service(&self) -> &TypeID {
+        &self.service
+    }
+
+    pub fn name(&self) -> &String {
+        &self.action
+    }
+
+    ///
+    /// The value of the `SOAPACTION` header for a request invoking this action, the quoted
+    /// service type and action name.
+    ///
+    pub fn soap_action(&self) -> String {
+        format!("\"{}\"", self)
+    }
 }
 
 impl Display for Action {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
         write!(f, "{}#{}", self.service, self.action)
     }
 }
@@ -93,20 +181,176 @@ impl Envelope {
         Self::new_with(action, Default::default())
     }
 
-    pub fn new_with(action: Action, argumments: HashMap<String, String>) -> Self {
+    pub fn new_with(action: Action, arguments: Vec<(String, String)>) -> Self {
         Envelope {
             schema: SOAP_NS_ENVELOPE.to_string(),
             encoding_style: SOAP_NS_ENCODING.to_string(),
-            body: Body::Action { action, argumments },
+            body: Body::Action { action, arguments },
         }
     }
 
-    pub fn new_response(action: Action, argumments: HashMap<String, String>) -> Self {
+    pub fn new_response(action: Action, arguments: Vec<(String, String)>) -> Self {
         Envelope {
             schema: SOAP_NS_ENVELOPE.to_string(),
             encoding_style: SOAP_NS_ENCODING.to_string(),
-            body: Body::Response { action, argumments },
+            body: Body::Response { action, arguments },
+        }
+    }
+
+    pub fn new_fault(error: UpnpError) -> Self {
+        Envelope {
+            schema: SOAP_NS_ENVELOPE.to_string(),
+            encoding_style: SOAP_NS_ENCODING.to_string(),
+            body: Body::Fault {
+                code: prefixed(SOAP_FAULT_CODE_CLIENT),
+                string: SOAP_FAULT_STRING_UPNP.to_string(),
+                error,
+            },
+        }
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    ///
+    /// Write this envelope as a complete XML document.
+    ///
+    pub fn to_writer<T: Write>(&self, writer: T) -> Result<T, Error> {
+        envelope(Node::from(&self.body)).write_root(writer)
+    }
+}
+
+impl FromStr for Envelope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let document = read_document(s)?;
+        if document.local_name() != SOAP_ELEM_ENVELOPE {
+            error!(
+                "from_str - expecting envelope element, not '{}'",
+                document.name
+            );
+            return invalid_field_value(SOAP_ELEM_ENVELOPE, &document.name).into();
+        }
+        let body = document.required_child(SOAP_ELEM_BODY)?;
+        let content = match body.children.first() {
+            Some(content) => content,
+            None => {
+                error!("from_str - envelope body is empty");
+                return missing_required_field(SOAP_ELEM_BODY).into();
+            }
+        };
+        Ok(Envelope {
+            schema: SOAP_NS_ENVELOPE.to_string(),
+            encoding_style: SOAP_NS_ENCODING.to_string(),
+            body: Body::try_from(content)?,
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl From<&Body> for Node {
+    fn from(body: &Body) -> Self {
+        match body {
+            Body::Action { action, arguments } => action_node(action, &action.action, arguments),
+            Body::Response { action, arguments } => action_node(
+                action,
+                &format!("{}{}", action.action, SOAP_RESPONSE_SUFFIX),
+                arguments,
+            ),
+            Body::Fault {
+                code,
+                string,
+                error,
+            } => Node::new(&prefixed(SOAP_ELEM_FAULT))
+                .with_child(Node::new_text(SOAP_ELEM_FAULT_CODE, code))
+                .with_child(Node::new_text(SOAP_ELEM_FAULT_STRING, string))
+                .with_child(Node::new(SOAP_ELEM_DETAIL).with_child(Node::from(error))),
+        }
+    }
+}
+
+impl TryFrom<&Node> for Body {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        if node.local_name() == SOAP_ELEM_FAULT {
+            return Ok(Body::Fault {
+                code: node.required_child_text(SOAP_ELEM_FAULT_CODE)?.clone(),
+                string: node.required_child_text(SOAP_ELEM_FAULT_STRING)?.clone(),
+                error: UpnpError::try_from(
+                    node.required_child(SOAP_ELEM_DETAIL)?
+                        .required_child(SOAP_ELEM_UPNP_ERROR)?,
+                )?,
+            });
         }
+        let namespace_attribute = match node.prefix() {
+            Some(prefix) => format!("{}:{}", XML_ATTR_NAMESPACE, prefix),
+            None => XML_ATTR_NAMESPACE.to_string(),
+        };
+        let service = match node.attribute(&namespace_attribute) {
+            Some(namespace) => TypeID::from_str(namespace)?,
+            None => {
+                error!(
+                    "try_from - no service type namespace on action element '{}'",
+                    node.name
+                );
+                return missing_required_field(namespace_attribute).into();
+            }
+        };
+        let arguments = node
+            .children
+            .iter()
+            .map(|child| (child.local_name().to_string(), child.text.clone()))
+            .collect();
+        Ok(match node.local_name().strip_suffix(SOAP_RESPONSE_SUFFIX) {
+            Some(name) => Body::Response {
+                action: Action::new(service, name.to_string()),
+                arguments,
+            },
+            None => Body::Action {
+                action: Action::new(service, node.local_name().to_string()),
+                arguments,
+            },
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl From<&UpnpError> for Node {
+    fn from(error: &UpnpError) -> Self {
+        Node::new(SOAP_ELEM_UPNP_ERROR)
+            .with_attribute(XML_ATTR_NAMESPACE, SOAP_NS_CONTROL)
+            .with_child(Node::new_text(
+                SOAP_ELEM_ERROR_CODE,
+                &error.code().to_string(),
+            ))
+            .with_child(Node::new_text(
+                SOAP_ELEM_ERROR_DESCRIPTION,
+                error.description(),
+            ))
+    }
+}
+
+impl TryFrom<&Node> for UpnpError {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        let code = node.required_child_text(SOAP_ELEM_ERROR_CODE)?;
+        let code = match code.trim().parse::<u16>() {
+            Ok(code) => code,
+            Err(_) => {
+                error!("try_from - invalid UPnP error code '{}'", code);
+                return invalid_field_value(SOAP_ELEM_ERROR_CODE, code).into();
+            }
+        };
+        Ok(match node.child_text(SOAP_ELEM_ERROR_DESCRIPTION) {
+            Some(description) => UpnpError::with_description(code.into(), description),
+            None => UpnpError::new(code.into()),
+        })
     }
 }
 
@@ -114,6 +358,111 @@ impl Envelope {
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+fn prefixed(name: &str) -> String {
+    format!("{}:{}", SOAP_PREFIX, name)
+}
+
+fn action_node(action: &Action, name: &str, arguments: &[(String, String)]) -> Node {
+    arguments.iter().fold(
+        Node::new(&format!("{}:{}", SOAP_ACTION_PREFIX, name)).with_attribute(
+            &format!("{}:{}", XML_ATTR_NAMESPACE, SOAP_ACTION_PREFIX),
+            &action.service.to_string(),
+        ),
+        |node, (name, value)| node.with_child(Node::new_text(name, value)),
+    )
+}
+
+fn envelope(body_content: Node) -> Node {
+    Node::new(&prefixed(SOAP_ELEM_ENVELOPE))
+        .with_attribute(
+            &format!("{}:{}", XML_ATTR_NAMESPACE, SOAP_PREFIX),
+            SOAP_NS_ENVELOPE,
+        )
+        .with_attribute(&prefixed(SOAP_ATTR_ENCODING_STYLE), SOAP_NS_ENCODING)
+        .with_child(Node::new(&prefixed(SOAP_ELEM_BODY)).with_child(body_content))
+}
+
 // ------------------------------------------------------------------------------------------------
 // Modules
 // ------------------------------------------------------------------------------------------------
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::UpnpErrorCode;
+    use pretty_assertions::assert_eq;
+    use std::str::from_utf8;
+
+    const EX_FAULT: &str = "<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body><s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring><detail><UPnPError xmlns=\"urn:schemas-upnp-org:control-1-0\"><errorCode>718</errorCode><errorDescription>ConflictInMappingEntry</errorDescription></UPnPError></detail></s:Fault></s:Body></s:Envelope>";
+
+    #[test]
+    fn test_write_fault() {
+        let error = UpnpError::for_service("WANIPConnection", 718);
+        let written = fault_to_writer(&error, Vec::new()).unwrap();
+        assert_eq!(from_utf8(&written).unwrap(), EX_FAULT);
+    }
+
+    #[test]
+    fn test_read_fault() {
+        let error = fault_from_str(EX_FAULT).unwrap();
+        assert_eq!(error.code(), UpnpErrorCode::CONFLICT_IN_MAPPING_ENTRY);
+        assert_eq!(error.description(), "ConflictInMappingEntry");
+
+        let error = fault_from_str(&EX_FAULT.replace("718", "402")).unwrap();
+        assert_eq!(error.code(), UpnpErrorCode::InvalidArgs);
+
+        assert!(fault_from_str("<root/>").is_err());
+    }
+
+    const EX_ACTION: &str = "<?xml version=\"1.0\"?><s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body><u:SetTarget xmlns:u=\"urn:schemas-upnp-org:service:SwitchPower:1\"><newTargetValue>1</newTargetValue></u:SetTarget></s:Body></s:Envelope>";
+
+    #[test]
+    fn test_write_and_read_action() {
+        let action = Action::new(
+            TypeID::new_service("SwitchPower".to_string(), 1),
+            "SetTarget".to_string(),
+        );
+        assert_eq!(
+            action.soap_action(),
+            "\"urn:schemas-upnp-org:service:SwitchPower:1#SetTarget\""
+        );
+        let envelope = Envelope::new_with(
+            action.clone(),
+            vec![("newTargetValue".to_string(), "1".to_string())],
+        );
+        let written = envelope.to_writer(Vec::new()).unwrap();
+        assert_eq!(from_utf8(&written).unwrap(), EX_ACTION);
+
+        match Envelope::from_str(EX_ACTION).unwrap().body() {
+            Body::Action {
+                action: read,
+                arguments,
+            } => {
+                assert_eq!(read, &action);
+                assert_eq!(arguments.len(), 1);
+            }
+            _ => panic!("expected an action"),
+        }
+
+        let response = "<s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\"><s:Body><u:GetStatusResponse xmlns:u=\"urn:schemas-upnp-org:service:SwitchPower:1\"><ResultStatus>0</ResultStatus></u:GetStatusResponse></s:Body></s:Envelope>";
+        match Envelope::from_str(response).unwrap().body() {
+            Body::Response { action, arguments } => {
+                assert_eq!(action.name(), "GetStatus");
+                assert_eq!(
+                    arguments,
+                    &vec![("ResultStatus".to_string(), "0".to_string())]
+                );
+            }
+            _ => panic!("expected a response"),
+        }
+
+        assert!(matches!(
+            Envelope::from_str(EX_FAULT).unwrap().body(),
+            Body::Fault { .. }
+        ));
+    }
+}
@@ -1,47 +1,389 @@
-// ------------------------------------------------------------------------------------------------
-// Public Types
-// ------------------------------------------------------------------------------------------------
+/*!
+Provides a URI type that parses and validates references according to
+[RFC 3986](https://tools.ietf.org/html/rfc3986), and resolves relative references against a base.
+
+UPnP descriptions commonly contain relative URLs; for example the `SCPDURL`, `controlURL`, and
+`eventSubURL` of a service are usually paths relative to the location of the device description.
+The `resolve` method implements the reference resolution algorithm in section 5.2 of the RFC.
 
-use std::fmt::{Display, Error, Formatter};
+# Example
+
+```rust
+use upnp_rs::common::uri::URL;
 use std::str::FromStr;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct URI(String);
+let base = URL::from_str("http://192.168.1.10:49152/desc/device.xml").unwrap();
+let control = URL::from_str("control/switch").unwrap();
+assert!(!control.is_absolute());
+assert_eq!(
+    control.resolve(&base).to_string(),
+    "http://192.168.1.10:49152/desc/control/switch"
+);
+```
+*/
 
-pub type URL = URI;
+use crate::error::{invalid_value_for_type, Error};
+use regex::Regex;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::str::FromStr;
+use tracing::error;
 
 // ------------------------------------------------------------------------------------------------
-// Public Functions
+// Public Types
 // ------------------------------------------------------------------------------------------------
 
+///
+/// A URI reference, either absolute or relative, held as its five RFC 3986 components.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct URI {
+    scheme: Option<String>,
+    authority: Option<String>,
+    path: String,
+    query: Option<String>,
+    fragment: Option<String>,
+}
+
+///
+/// URLs are URIs that identify a resource by its network location, the same type is used for
+/// both.
+///
+pub type URL = URI;
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
 
 impl Display for URI {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(f, "{}", self.0)
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        if let Some(scheme) = &self.scheme {
+            write!(f, "{}:", scheme)?;
+        }
+        if let Some(authority) = &self.authority {
+            write!(f, "//{}", authority)?;
+        }
+        write!(f, "{}", self.path)?;
+        if let Some(query) = &self.query {
+            write!(f, "?{}", query)?;
+        }
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{}", fragment)?;
+        }
+        Ok(())
     }
 }
 
 impl FromStr for URI {
-    type Err = ();
+    type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(URI(s.to_string()))
+        lazy_static! {
+            static ref REFERENCE: Regex =
+                Regex::new(r"^(?:([^:/?#]+):)?(?://([^/?#]*))?([^?#]*)(?:\?([^#]*))?(?:#(.*))?$")
+                    .unwrap();
+            static ref SCHEME: Regex = Regex::new(r"^[A-Za-z][A-Za-z0-9+\-.]*$").unwrap();
+            static ref CHARACTERS: Regex =
+                Regex::new(r"^(?:[A-Za-z0-9\-._~!$&'()*+,;=:@/?#\[\]]|%[0-9A-Fa-f]{2})*$").unwrap();
+        }
+        let s = s.trim();
+        let captures = match REFERENCE.captures(s) {
+            Some(captures) if CHARACTERS.is_match(s) => captures,
+            _ => {
+                error!("from_str - invalid characters in URI '{}'", s);
+                return invalid_value_for_type("URI", s).into();
+            }
+        };
+        let uri = URI {
+            scheme: captures.get(1).map(|m| m.as_str().to_string()),
+            authority: captures.get(2).map(|m| m.as_str().to_string()),
+            path: captures.get(3).unwrap().as_str().to_string(),
+            query: captures.get(4).map(|m| m.as_str().to_string()),
+            fragment: captures.get(5).map(|m| m.as_str().to_string()),
+        };
+        if let Some(scheme) = &uri.scheme {
+            if !SCHEME.is_match(scheme) {
+                error!("from_str - invalid scheme '{}' in URI '{}'", scheme, s);
+                return invalid_value_for_type("URI", s).into();
+            }
+        }
+        if let Some(authority) = &uri.authority {
+            if !is_valid_authority(authority) {
+                error!(
+                    "from_str - invalid authority '{}' in URI '{}'",
+                    authority, s
+                );
+                return invalid_value_for_type("URI", s).into();
+            }
+        }
+        Ok(uri)
     }
 }
 
-impl URI {}
+serde_as_string!(URI);
 
-// ------------------------------------------------------------------------------------------------
-// Private Types
-// ------------------------------------------------------------------------------------------------
+impl URI {
+    ///
+    /// Returns `true` if this URI has a scheme, and so may be used as a base for resolution.
+    ///
+    pub fn is_absolute(&self) -> bool {
+        self.scheme.is_some()
+    }
+
+    ///
+    /// Returns `true` if this is the empty reference, which refers to the base URI itself.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.scheme.is_none()
+            && self.authority.is_none()
+            && self.path.is_empty()
+            && self.query.is_none()
+            && self.fragment.is_none()
+    }
+
+    pub fn scheme(&self) -> Option<&String> {
+        self.scheme.as_ref()
+    }
+
+    pub fn authority(&self) -> Option<&String> {
+        self.authority.as_ref()
+    }
+
+    ///
+    /// The host portion of the authority, without any user information or port. IPv6 literal
+    /// addresses are returned without the enclosing brackets.
+    ///
+    pub fn host(&self) -> Option<&str> {
+        self.authority.as_ref().map(|authority| {
+            let host_and_port = authority.rsplit('@').next().unwrap();
+            if host_and_port.starts_with('[') {
+                let end = host_and_port.find(']').unwrap();
+                &host_and_port[1..end]
+            } else {
+                match host_and_port.find(':') {
+                    None => host_and_port,
+                    Some(colon) => &host_and_port[..colon],
+                }
+            }
+        })
+    }
+
+    ///
+    /// The port portion of the authority, if one is present and not empty.
+    ///
+    pub fn port(&self) -> Option<u16> {
+        self.authority.as_ref().and_then(|authority| {
+            let host_and_port = authority.rsplit('@').next().unwrap();
+            let after_host = match host_and_port.rfind(']') {
+                Some(end) => &host_and_port[end + 1..],
+                None => host_and_port,
+            };
+            after_host
+                .rfind(':')
+                .and_then(|colon| after_host[colon + 1..].parse::<u16>().ok())
+        })
+    }
+
+    pub fn path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn query(&self) -> Option<&String> {
+        self.query.as_ref()
+    }
+
+    pub fn fragment(&self) -> Option<&String> {
+        self.fragment.as_ref()
+    }
+
+    ///
+    /// Resolve this, possibly relative, reference against `base` as described in section 5.2.2
+    /// of RFC 3986. If this reference is already absolute it is returned with any dot segments
+    /// removed from its path.
+    ///
+    pub fn resolve(&self, base: &URI) -> URI {
+        if self.scheme.is_some() {
+            return URI {
+                path: remove_dot_segments(&self.path),
+                ..self.clone()
+            };
+        }
+        let (authority, path, query) = if self.authority.is_some() {
+            (
+                self.authority.clone(),
+                remove_dot_segments(&self.path),
+                self.query.clone(),
+            )
+        } else if self.path.is_empty() {
+            (
+                base.authority.clone(),
+                base.path.clone(),
+                self.query.clone().or_else(|| base.query.clone()),
+            )
+        } else if self.path.starts_with('/') {
+            (
+                base.authority.clone(),
+                remove_dot_segments(&self.path),
+                self.query.clone(),
+            )
+        } else {
+            (
+                base.authority.clone(),
+                remove_dot_segments(&merge_paths(base, &self.path)),
+                self.query.clone(),
+            )
+        };
+        URI {
+            scheme: base.scheme.clone(),
+            authority,
+            path,
+            query,
+            fragment: self.fragment.clone(),
+        }
+    }
+}
 
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+fn is_valid_authority(authority: &str) -> bool {
+    lazy_static! {
+        static ref HOST_AND_PORT: Regex =
+            Regex::new(r"^(?:\[[0-9A-Fa-f:.]+\]|[^\[\]:]*)(?::[0-9]*)?$").unwrap();
+    }
+    HOST_AND_PORT.is_match(authority.rsplit('@').next().unwrap())
+}
+
+fn merge_paths(base: &URI, path: &str) -> String {
+    if base.authority.is_some() && base.path.is_empty() {
+        format!("/{}", path)
+    } else {
+        match base.path.rfind('/') {
+            None => path.to_string(),
+            Some(last) => format!("{}{}", &base.path[..=last], path),
+        }
+    }
+}
+
+fn remove_dot_segments(path: &str) -> String {
+    let mut input = path.to_string();
+    let mut output = String::with_capacity(path.len());
+    while !input.is_empty() {
+        if input.starts_with("../") {
+            input.replace_range(..3, "");
+        } else if input.starts_with("./") {
+            input.replace_range(..2, "");
+        } else if input.starts_with("/./") {
+            input.replace_range(..3, "/");
+        } else if input == "/." {
+            input = "/".to_string();
+        } else if input.starts_with("/../") || input == "/.." {
+            if input == "/.." {
+                input = "/".to_string();
+            } else {
+                input.replace_range(..3, "");
+            }
+            match output.rfind('/') {
+                None => output.clear(),
+                Some(last) => output.truncate(last),
+            }
+        } else if input == "." || input == ".." {
+            input.clear();
+        } else {
+            let start = usize::from(input.starts_with('/'));
+            let end = match input[start..].find('/') {
+                None => input.len(),
+                Some(next) => next + start,
+            };
+            output.push_str(&input[..end]);
+            input.replace_range(..end, "");
+        }
+    }
+    output
+}
+
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_components() {
+        let uri = URI::from_str("http://user@[fe80::1]:49152/desc/device.xml?a=1#top").unwrap();
+        assert!(uri.is_absolute());
+        assert_eq!(uri.scheme().unwrap(), "http");
+        assert_eq!(uri.host(), Some("fe80::1"));
+        assert_eq!(uri.port(), Some(49152));
+        assert_eq!(uri.path(), "/desc/device.xml");
+        assert_eq!(uri.query().unwrap(), "a=1");
+        assert_eq!(uri.fragment().unwrap(), "top");
+        assert_eq!(
+            uri.to_string(),
+            "http://user@[fe80::1]:49152/desc/device.xml?a=1#top"
+        );
+
+        let usn =
+            URI::from_str("uuid:2f402f80-da50-11e1-9b23-00178829d301::upnp:rootdevice").unwrap();
+        assert_eq!(usn.scheme().unwrap(), "uuid");
+        assert!(usn.authority().is_none());
+
+        assert!(URI::from_str("").unwrap().is_empty());
+        assert!(URI::from_str("/upnp/control").is_ok());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(URI::from_str("http://host/a path").is_err());
+        assert!(URI::from_str("http://host:port/").is_err());
+        assert!(URI::from_str("http://host/%zz").is_err());
+        assert!(URI::from_str("1http://host/").is_err());
+        assert!(URI::from_str("http://[fe80::1/").is_err());
+    }
+
+    #[test]
+    fn test_resolve_rfc3986_examples() {
+        let base = URI::from_str("http://a/b/c/d;p?q").unwrap();
+        for (reference, expected) in &[
+            ("g:h", "g:h"),
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../..", "http://a/"),
+            ("../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("g/../h", "http://a/b/c/h"),
+        ] {
+            assert_eq!(
+                URI::from_str(reference).unwrap().resolve(&base).to_string(),
+                *expected,
+                "resolving '{}'",
+                reference
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_against_authority_only() {
+        let base = URI::from_str("http://192.168.1.1:5000").unwrap();
+        assert_eq!(
+            URI::from_str("scpd.xml")
+                .unwrap()
+                .resolve(&base)
+                .to_string(),
+            "http://192.168.1.1:5000/scpd.xml"
+        );
+    }
+}
@@ -1,3 +1,3 @@
-//pub mod read;
+pub mod read;
 
 pub mod write;
@@ -1,229 +1,253 @@
 /*!
-One-line description.
-
-More detailed description, with
-
-# Example
+Provides a minimal, read-only, XML element tree used when parsing description and control
+documents.
 
+Elements keep their qualified names, attributes are kept in document order, and all text content
+directly within an element is collected into a single, trimmed, string. Comments, processing
+instructions, and the document type declaration are discarded.
 */
 
-use crate::common::xml::udom::{Document, Error as DOMError, Name, Node, NodeType};
-use crate::{Error, MessageErrorKind};
-use quick_xml::events::{BytesStart, BytesText, Event};
+use crate::error::{
+    invalid_field_value, missing_required_field, xml_error, Error, MessageFormatError,
+};
+use crate::syntax::{XML_ELEM_MAJOR, XML_ELEM_MINOR};
+use crate::SpecVersion;
+use quick_xml::events::{BytesStart, Event};
 use quick_xml::Reader;
-use std::borrow::BorrowMut;
 use std::convert::TryFrom;
-use std::rc::Rc;
 use std::str::from_utf8;
+use tracing::{error, trace};
 
 // ------------------------------------------------------------------------------------------------
-// Public Functions
+// Public Types
 // ------------------------------------------------------------------------------------------------
 
-pub fn read_xml(xml: &str) -> Result<Document, Error> {
-    let mut reader = Reader::from_str(xml);
-    reader.trim_text(true);
-
-    let event_buffer: Vec<u8> = Vec::new();
-
-    read_loop_outer(reader, event_buffer)
+///
+/// A single XML element, along with its attributes, text, and child elements.
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Node {
+    /// The qualified name of the element, including any namespace prefix.
+    pub name: String,
+    /// The element's attributes, in document order, as `(qualified name, value)` pairs.
+    pub attributes: Vec<(String, String)>,
+    /// Any text content directly within this element.
+    pub text: String,
+    /// Child elements, in document order.
+    pub children: Vec<Node>,
 }
 
-fn read_loop_outer(
-    mut reader: Reader<&[u8]>,
-    mut event_buffer: Vec<u8>,
-) -> Result<Document, Error> {
-    let mut document = Document::default();
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
 
+///
+/// Parse the provided string and return the document element.
+///
+pub fn read_document(xml: &str) -> Result<Node, Error> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut stack: Vec<Node> = Vec::new();
     loop {
-        match reader.read_event(&mut event_buffer) {
-            Ok(Event::Start(ev)) => {
-                trace!("read_loop_outer - Event::Start ({:?})", ev);
-                document.document_element = Some(try_element_from(ev)?);
-                let document = read_loop_inner(reader, event_buffer, document)?;
-                return Ok(document);
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(ev) => {
+                trace!("read_document - Event::Start ({:?})", ev);
+                stack.push(node_from(&ev)?);
             }
-            Ok(Event::Empty(ev)) => {
-                document.document_element = Some(try_element_from(ev)?);
-                let document = read_loop_inner(reader, event_buffer, document)?;
-                return Ok(document);
-            }
-            Ok(Event::Decl(ev)) => {
-                trace!("read_loop_outer - Event::Decl ({:?})", ev);
-                let pi_node = Document::create_xml_declaration("", Some(""), Some(true))?;
-                document.children.push(Rc::new(pi_node));
+            Event::Empty(ev) => {
+                trace!("read_document - Event::Empty ({:?})", ev);
+                let node = node_from(&ev)?;
+                match stack.last_mut() {
+                    None => return Ok(node),
+                    Some(parent) => parent.children.push(node),
+                }
             }
-            Ok(Event::PI(ev)) => {
-                trace!("read_loop_outer - Event::PI");
-                ev.
-                let pi_node = Document::create_processing_instruction("", "")?;
-                document.children.push(Rc::new(pi_node));
+            Event::End(ev) => {
+                trace!("read_document - Event::End ({:?})", ev);
+                let node = stack.pop().unwrap();
+                match stack.last_mut() {
+                    None => return Ok(node),
+                    Some(parent) => parent.children.push(node),
+                }
             }
-            Ok(Event::DocType(ev)) => {
-                trace!("read_loop_outer - Event::DocType ({:?})", ev);
+            Event::Text(ev) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&ev.unescape().map_err(xml_error)?);
+                }
             }
-            Ok(Event::Eof) => break,
-            Ok(ev) => {
-                error!("read_loop_outer, unexpected event {:?}", ev);
-                return Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError));
+            Event::CData(ev) => {
+                if let Some(current) = stack.last_mut() {
+                    current
+                        .text
+                        .push_str(from_utf8(&ev).map_err(MessageFormatError::from)?);
+                }
             }
-            Err(err) => {
-                error!(
-                    "read_loop_outer error at position {}: {:?}",
-                    reader.buffer_position(),
-                    err
-                );
-                return Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError));
+            Event::Eof => {
+                error!("read_document - unexpected end of document");
+                return missing_required_field("document element").into();
             }
+            _ => {}
         }
     }
-    Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError))
 }
 
-fn read_loop_inner(
-    mut reader: Reader<&[u8]>,
-    mut event_buffer: Vec<u8>,
-    document: Document,
-) -> Result<Document, Error> {
-    let mut current_element = document.document_element.unwrap();
-    loop {
-        let child_node = match reader.read_event(&mut event_buffer) {
-            Ok(Event::Start(ev)) => {
-                trace!("read_loop_inner - Event::Start ({:?})", ev);
-                let mut new_element = try_element_from(ev)?;
-                let c = &mut current_element;
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
 
-                c.borrow_mut().append_child(c, &mut new_element);
-                current_element = new_element;
-                None
-            }
-            Ok(Event::End(ev)) => {
-                trace!("read_loop_inner - Event::End ({:?})", ev);
-                let name = Name::try_from(from_utf8(ev.name())?)?;
-                if name != current_element.name {
-                    return Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError));
-                } else {
-                    match current_element.parent_node.clone() {
-                        Some(parent) => current_element = parent,
-                        None => return Ok(document),
-                    }
-                }
-                None
-            }
-            Ok(Event::Empty(ev)) => {
-                trace!("read_loop_inner - Event::Empty ({:?})", ev);
-                Some(try_element_from(ev)?)
-            }
-            Ok(Event::Text(ev)) => {
-                trace!("read_loop_inner - Event::Text ({:?})", ev);
-                Some(Rc::new(Document::create_text_node(&string_from_bytes(
-                    ev, &reader,
-                )?)))
-            }
-            Ok(Event::Comment(ev)) => {
-                trace!("read_loop_inner - Event::Comment ({:?})", ev);
-                Some(Rc::new(Document::create_comment(&string_from_bytes(
-                    ev, &reader,
-                )?)))
-            }
-            Ok(Event::CData(ev)) => {
-                trace!("read_loop_inner - Event::CData ({:?})", ev);
-                Some(Rc::new(Document::create_cdata_section(
-                    &string_from_bytes(ev, &reader)?,
-                )?))
-            }
-            Ok(Event::Decl(ev)) => {
-                trace!("read_loop_inner - Event::Decl ({:?})", ev);
-                None
-            }
-            Ok(Event::PI(ev)) => {
-                trace!("read_loop_inner - Event::PI ({:?})", ev);
-                None
-            }
-            Ok(Event::DocType(ev)) => {
-                trace!("read_loop_inner - Event::DocType ({:?})", ev);
-                None
-            }
-            Err(err) => {
-                error!(
-                    "read_loop_inner error at position {}: {:?}",
-                    reader.buffer_position(),
-                    err
-                );
-                return Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError));
-            }
-            Ok(Event::Eof) => break,
-        };
-        if let Some(mut child_node) = child_node {
-            Rc::make_mut(&mut current_element).append_child(&mut current_element, &mut child_node);
+impl Node {
+    ///
+    /// Create a new element with the given qualified name.
+    ///
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
         }
     }
 
-    Err(Error::MessageFormat(MessageErrorKind::XmlFormattingError))
-}
+    ///
+    /// Create a new element with the given qualified name and text content.
+    ///
+    pub fn new_text(name: &str, text: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
 
-// ------------------------------------------------------------------------------------------------
-// Private Types
-// ------------------------------------------------------------------------------------------------
+    ///
+    /// Add an attribute, returning this element.
+    ///
+    pub fn with_attribute(mut self, name: &str, value: &str) -> Self {
+        self.attributes.push((name.to_string(), value.to_string()));
+        self
+    }
 
-// ------------------------------------------------------------------------------------------------
-// Private Functions
-// ------------------------------------------------------------------------------------------------
+    ///
+    /// Add a child element, returning this element.
+    ///
+    pub fn with_child(mut self, child: Node) -> Self {
+        self.children.push(child);
+        self
+    }
 
-#[inline]
-fn string_from_bytes(bytes: BytesText<'_>, reader: &Reader<&[u8]>) -> Result<String, Error> {
-    bytes
-        .unescape_and_decode(reader)
-        .map_err(|_| Error::MessageFormat(MessageErrorKind::InvalidEncoding))
-}
+    ///
+    /// The namespace prefix, if any, of this element's name.
+    ///
+    pub fn prefix(&self) -> Option<&str> {
+        self.name.find(':').map(|i| &self.name[..i])
+    }
 
-fn string_from_utf8(value: &[u8]) -> Result<&str, DOMError> {
-    match from_utf8(value) {
-        Ok(value) => Ok(value),
-        Err(e) => {
-            error!("Could not convert from UTF-8, error {:?}", e);
-            Err(DOMError::InvalidCharacter)
+    ///
+    /// The name of this element without any namespace prefix.
+    ///
+    pub fn local_name(&self) -> &str {
+        match self.name.find(':') {
+            None => &self.name,
+            Some(i) => &self.name[i + 1..],
         }
     }
-}
 
-fn try_element_from(ev: BytesStart<'_>) -> Result<Node, DOMError> {
-    trace!("try_element_from");
-    let name = string_from_utf8(ev.name())?;
-    let element = Rc::new(Document::create_element(name)?);
+    ///
+    /// Return the value of the named attribute.
+    ///
+    pub fn attribute(&self, name: &str) -> Option<&String> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v)
+    }
+
+    ///
+    /// Return the first child element with the given local name.
+    ///
+    pub fn child(&self, local_name: &str) -> Option<&Node> {
+        self.children
+            .iter()
+            .find(|child| child.local_name() == local_name)
+    }
+
+    ///
+    /// Return all child elements with the given local name.
+    ///
+    pub fn children_named<'a>(&'a self, local_name: &'a str) -> impl Iterator<Item = &'a Node> {
+        self.children
+            .iter()
+            .filter(move |child| child.local_name() == local_name)
+    }
+
+    ///
+    /// Return the text of the first child element with the given local name.
+    ///
+    pub fn child_text(&self, local_name: &str) -> Option<&String> {
+        self.child(local_name).map(|child| &child.text)
+    }
 
-    for maybe in ev.attributes() {
-        let attribute = maybe.unwrap();
-        let mut attribute_node = Document::create_attribute(string_from_utf8(attribute.key)?)?;
-        match &attribute_node.rc_node.node {
-            NodeType::Attribute {
-                mut value,
-                mut owner_element,
-                ..
-            } => {
-                value = string_from_utf8(attribute.value.as_ref())?.to_string();
-                owner_element = Some(element);
+    ///
+    /// Return the first child element with the given local name, or an error if it is missing.
+    ///
+    pub fn required_child(&self, local_name: &str) -> Result<&Node, Error> {
+        match self.child(local_name) {
+            None => {
+                error!(
+                    "required_child - element '{}' missing required child '{}'",
+                    self.name, local_name
+                );
+                missing_required_field(local_name).into()
             }
-            _ => return Err(DOMError::Syntax),
+            Some(child) => Ok(child),
         }
-        element
-            .attributes
-            .insert(attribute_node.name, Rc::new(attribute_node));
     }
-    Ok(element)
+
+    ///
+    /// Return the text of the first child element with the given local name, or an error if it
+    /// is missing.
+    ///
+    pub fn required_child_text(&self, local_name: &str) -> Result<&String, Error> {
+        self.required_child(local_name).map(|child| &child.text)
+    }
 }
 
-impl From<DOMError> for Error {
-    fn from(e: DOMError) -> Self {
-        error!("XML DOM error: {:?}", e);
-        Error::MessageFormat(MessageErrorKind::XmlFormattingError)
+// ------------------------------------------------------------------------------------------------
+
+impl TryFrom<&Node> for SpecVersion {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        let major = node.required_child_text(XML_ELEM_MAJOR)?;
+        let minor = node.required_child_text(XML_ELEM_MINOR)?;
+        match (major.trim(), minor.trim()) {
+            ("1", "0") => Ok(SpecVersion::V10),
+            ("1", _) => Ok(SpecVersion::V11),
+            ("2", _) => Ok(SpecVersion::V20),
+            _ => invalid_field_value("specVersion", format!("{}.{}", major, minor)).into(),
+        }
     }
 }
 
 // ------------------------------------------------------------------------------------------------
-// Modules
+// Private Functions
 // ------------------------------------------------------------------------------------------------
 
+fn node_from(ev: &BytesStart<'_>) -> Result<Node, Error> {
+    let mut node = Node::new(from_utf8(ev.name().as_ref()).map_err(MessageFormatError::from)?);
+    for attribute in ev.attributes() {
+        let attribute = attribute.map_err(|e| xml_error(e.into()))?;
+        node.attributes.push((
+            from_utf8(attribute.key.as_ref())
+                .map_err(MessageFormatError::from)?
+                .to_string(),
+            attribute.unescape_value().map_err(xml_error)?.to_string(),
+        ));
+    }
+    Ok(node)
+}
+
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
@@ -231,61 +255,37 @@ impl From<DOMError> for Error {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use log::LevelFilter;
-
-    #[allow(unused_must_use)]
-    fn setup_logging() {
-        env_logger::Builder::from_default_env()
-            .filter_module("upnp_rs::common::xml::read", LevelFilter::Trace)
-            .try_init();
-    }
 
     #[test]
-    fn test_xml_read_minimal() {
-        const TEST_DOC: &str = "<root></root>";
-        setup_logging();
-        let doc = read_xml(TEST_DOC);
-        assert!(doc.is_ok());
-        let doc = doc.unwrap();
-        assert!(doc.document_element.is_some());
-        assert_eq!(doc.document_element.unwrap().name.to_string(), "root");
-        println!("{:#?}", doc);
+    fn test_read_minimal() {
+        let doc = read_document("<root></root>").unwrap();
+        assert_eq!(doc.name, "root");
+        assert!(doc.children.is_empty());
     }
 
     #[test]
-    fn test_xml_read_minimal_with_namespace() {
-        const TEST_DOC: &str = "<root xmlns=\"urn:schemas-upnp-org:device-1-0\"></root>";
-        setup_logging();
-        let doc = read_xml(TEST_DOC);
-        assert!(doc.is_ok());
-        let doc = doc.unwrap();
-        assert!(doc.document_element.is_some());
-        assert_eq!(doc.document_element.unwrap().name.to_string(), "root");
-        assert_eq!(doc.document_element.unwrap().attributes.len(), 1);
-        let ns = doc.document_element.unwrap().attributes.get(0).unwrap();
-        assert_eq!(ns.name.to_string(), "xmlns");
-        assert_eq!(ns.value, "urn:schemas-upnp-org:device-1-0");
-        println!("{:#?}", doc);
+    fn test_read_with_decl_and_namespaces() {
+        let doc = read_document(
+            "<?xml version=\"1.0\"?>\n<root xmlns=\"urn:schemas-upnp-org:device-1-0\" xmlns:dlna=\"urn:schemas-dlna-org:device-1-0\">\n  <dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC>\n  <empty/>\n  <text>a &amp; b</text>\n</root>",
+        )
+        .unwrap();
+        assert_eq!(doc.name, "root");
+        assert_eq!(doc.attributes.len(), 2);
+        assert_eq!(
+            doc.attribute("xmlns").unwrap(),
+            "urn:schemas-upnp-org:device-1-0"
+        );
+        assert_eq!(doc.children.len(), 3);
+        let ext = doc.child("X_DLNADOC").unwrap();
+        assert_eq!(ext.prefix(), Some("dlna"));
+        assert_eq!(ext.text, "DMS-1.50");
+        assert_eq!(doc.child_text("text").unwrap(), "a & b");
+        assert!(doc.child("empty").unwrap().text.is_empty());
     }
 
     #[test]
-    fn test_xml_read_minimal_with_decl() {
-        const TEST_DOC: &str =
-            "<?xml version=\"1.0\"?><root xmlns=\"urn:schemas-upnp-org:device-1-0\"></root>";
-        setup_logging();
-        let doc = read_xml(TEST_DOC);
-        assert!(doc.is_ok());
-        let doc = doc.unwrap();
-        assert!(doc.document_element.is_some());
-        assert_eq!(doc.document_element.unwrap().name.to_string(), "root");
-        assert_eq!(doc.document_element.unwrap().attributes.len(), 1);
-        let ns = doc.document_element.unwrap().attributes.get(0).unwrap();
-        assert_eq!(ns.name.to_string(), "xmlns");
-        assert_eq!(ns.value, "urn:schemas-upnp-org:device-1-0");
-        assert_eq!(doc.processing_instructions.len(), 1);
-        let pi = doc.processing_instructions.get(0).unwrap();
-        assert_eq!(pi.target, "xml");
-        // TODO: fix this assert_eq!(pi.attributes.len(), 3);
-        println!("{:#?}", doc);
+    fn test_read_unbalanced() {
+        assert!(read_document("<root><a></root>").is_err());
+        assert!(read_document("<root>").is_err());
     }
 }
@@ -7,6 +7,7 @@ More detailed description, with
 
 */
 
+use crate::common::xml::read::Node;
 use crate::error::{xml_error, Error};
 use crate::syntax::{
     XML_ATTR_NAMESPACE, XML_DECL_VERSION, XML_ELEM_MAJOR, XML_ELEM_MINOR, XML_ELEM_SPEC_VERSION,
@@ -67,8 +68,9 @@ pub fn start_element<T: Write>(
     writer: &mut Writer<T>,
     name: &'static str,
 ) -> Result<Element, quick_xml::Error> {
-    writer.write_event(Event::Start(BytesStart::new(name)))
-    .map_err(|e| quick_xml::Error::Io(e.into()))?;
+    writer
+        .write_event(Event::Start(BytesStart::new(name)))
+        .map_err(|e| quick_xml::Error::Io(e.into()))?;
     Ok(Element { name })
 }
 
@@ -109,6 +111,13 @@ pub fn end_element<T: Write>(writer: &mut Writer<T>, name: &str) -> Result<(), q
         .map_err(|e| quick_xml::Error::Io(e.into()))
 }
 
+pub fn text<T: Write>(writer: &mut Writer<T>, content: &str) -> Result<(), quick_xml::Error> {
+    writer
+        .write_event(Event::Text(BytesText::new(content)))
+        .map(|_| ())
+        .map_err(|e| quick_xml::Error::Io(e.into()))
+}
+
 pub fn text_element<T: Write>(
     writer: &mut Writer<T>,
     name: &'static str,
@@ -131,6 +140,31 @@ impl Element {
 
 // ------------------------------------------------------------------------------------------------
 
+impl<T: Write> RootWritable<T> for Node {}
+
+impl<T: Write> Writable<T> for Node {
+    fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
+        let mut element = BytesStart::new(self.name.as_str());
+        for (name, value) in &self.attributes {
+            element.push_attribute(Attribute::from((name.as_str(), value.as_str())));
+        }
+        writer
+            .write_event(Event::Start(element))
+            .map_err(|e| xml_error(e.into()))?;
+        if !self.text.is_empty() {
+            writer
+                .write_event(Event::Text(BytesText::new(&self.text)))
+                .map_err(|e| xml_error(e.into()))?;
+        }
+        for child in &self.children {
+            child.write(writer)?;
+        }
+        writer
+            .write_event(Event::End(BytesEnd::new(self.name.as_str())))
+            .map_err(|e| xml_error(e.into()))
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 
 impl<T: Write> Writable<T> for SpecVersion {
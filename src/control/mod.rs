@@ -1,7 +1,40 @@
 /*!
 This module implements the UPnP device and service control capabilities.
+
+A control point invokes an action by sending a SOAP envelope, naming the action and carrying its
+in arguments, to the service's control URL; the device replies with the out arguments, or a SOAP
+fault carrying a UPnP error. The `invoke` function performs this exchange, while
+`validate_arguments` can be used first to check the arguments against the service description.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::soap::Action;
+use upnp_rs::common::uri::URL;
+use upnp_rs::control::invoke;
+use upnp_rs::description::TypeID;
+use std::str::FromStr;
+
+let action = Action::new(
+    TypeID::new_service("SwitchPower".to_string(), 1),
+    "SetTarget".to_string(),
+);
+let control_url = URL::from_str("http://192.168.1.10:49152/upnp/control/power").unwrap();
+let out_arguments =
+    invoke(&control_url, &action, vec![("newTargetValue".to_string(), "1".to_string())]);
+```
 */
 
+use crate::common::soap::{Action, Body, Envelope};
+use crate::common::uri::URL;
+use crate::description::service::{AllowedValue, Direction, Spcd, StateVariable};
+use crate::error::{invalid_field_value, invalid_value_for_type, missing_required_field, Error};
+use crate::syntax::{HTTP_HEADER_CONTENT_TYPE, SOAP_CONTENT_TYPE, SOAP_HTTP_HEADER_ACTION};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use std::str::FromStr;
+use tracing::{error, info};
+
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
@@ -10,6 +43,172 @@ This module implements the UPnP device and service control capabilities.
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Invoke `action` on the service at `control_url` with the given in arguments, returning the
+/// out arguments from the device's response. If the device responds with a SOAP fault the UPnP
+/// error it carries is returned as `Error::Upnp`.
+///
+pub fn invoke(
+    control_url: &URL,
+    action: &Action,
+    arguments: Vec<(String, String)>,
+) -> Result<Vec<(String, String)>, Error> {
+    let client = Client::new();
+    invoke_with(control_url, action, arguments, &client)
+}
+
+///
+/// Invoke an action, as `invoke`, using the provided HTTP client.
+///
+pub fn invoke_with(
+    control_url: &URL,
+    action: &Action,
+    arguments: Vec<(String, String)>,
+    client: &Client,
+) -> Result<Vec<(String, String)>, Error> {
+    info!("invoke_with - invoking {} at {}", action, control_url);
+    let request = Envelope::new_with(action.clone(), arguments).to_writer(Vec::new())?;
+    let response = client
+        .post(control_url.to_string())
+        .header(HTTP_HEADER_CONTENT_TYPE, SOAP_CONTENT_TYPE)
+        .header(SOAP_HTTP_HEADER_ACTION, action.soap_action())
+        .body(request)
+        .send()?;
+    let status = response.status();
+    info!("invoke_with - received status {}", status);
+    let body = response.text()?;
+    if status.is_success() || status == StatusCode::INTERNAL_SERVER_ERROR {
+        match Envelope::from_str(&body) {
+            Ok(envelope) => match envelope.body() {
+                Body::Response {
+                    action: responded,
+                    arguments,
+                } if responded.name() == action.name() => return Ok(arguments.clone()),
+                Body::Fault { error, .. } => return Err(error.clone().into()),
+                _ => {
+                    error!(
+                        "invoke_with - unexpected response body {:?}",
+                        envelope.body()
+                    );
+                }
+            },
+            Err(e) => {
+                error!("invoke_with - could not parse response envelope: {}", e);
+            }
+        }
+    }
+    Err(Error::OperationFailed {
+        operation: action.to_string(),
+        status: status.to_string(),
+    })
+}
+
+///
+/// Validate the in arguments for the action `action_name` against the service description,
+/// returning them in the order the description defines. Every in argument must be present,
+/// no unknown arguments may be given, and each value must be valid for the data type, and any
+/// allowed values, of the argument's related state variable.
+///
+pub fn validate_arguments(
+    spcd: &Spcd,
+    action_name: &str,
+    arguments: &[(String, String)],
+) -> Result<Vec<(String, String)>, Error> {
+    let action = match spcd.action(action_name) {
+        Some(action) => action,
+        None => {
+            error!("validate_arguments - no action named '{}'", action_name);
+            return invalid_field_value("action", action_name).into();
+        }
+    };
+    for (name, _) in arguments {
+        if !action
+            .argument_list
+            .iter()
+            .any(|argument| argument.direction == Direction::In && &argument.name == name)
+        {
+            error!(
+                "validate_arguments - action '{}' has no in argument '{}'",
+                action_name, name
+            );
+            return invalid_field_value("argument", name).into();
+        }
+    }
+    let mut ordered = Vec::new();
+    for argument in action
+        .argument_list
+        .iter()
+        .filter(|argument| argument.direction == Direction::In)
+    {
+        let value = match arguments.iter().find(|(name, _)| name == &argument.name) {
+            Some((_, value)) => value,
+            None => {
+                error!(
+                    "validate_arguments - action '{}' requires argument '{}'",
+                    action_name, argument.name
+                );
+                return missing_required_field(&argument.name).into();
+            }
+        };
+        if let Some(variable) = spcd.state_variable(&argument.related_state_variable) {
+            check_value(variable, value)?;
+        }
+        ordered.push((argument.name.clone(), value.clone()));
+    }
+    Ok(ordered)
+}
+
+///
+/// Check that `value` is valid for the data type, and any allowed values, of `variable`. Only
+/// the numeric, `boolean`, and `char` types, and allowed value lists and ranges, are checked;
+/// values of other types are accepted as-is.
+///
+pub fn check_value(variable: &StateVariable, value: &str) -> Result<(), Error> {
+    let valid_type = match variable.data_type.as_str() {
+        "ui1" => value.parse::<u8>().is_ok(),
+        "ui2" => value.parse::<u16>().is_ok(),
+        "ui4" => value.parse::<u32>().is_ok(),
+        "ui8" => value.parse::<u64>().is_ok(),
+        "i1" => value.parse::<i8>().is_ok(),
+        "i2" => value.parse::<i16>().is_ok(),
+        "i4" | "int" => value.parse::<i32>().is_ok(),
+        "i8" => value.parse::<i64>().is_ok(),
+        "r4" | "r8" | "number" | "fixed.14.4" | "float" => value.parse::<f64>().is_ok(),
+        "boolean" => ["0", "1", "true", "false", "yes", "no"].contains(&value),
+        "char" => value.chars().count() == 1,
+        _ => true,
+    };
+    if !valid_type {
+        error!(
+            "check_value - '{}' is not a valid {} for '{}'",
+            value, variable.data_type, variable.name
+        );
+        return invalid_value_for_type(&variable.data_type, value).into();
+    }
+    let allowed = match &variable.allowed_values {
+        None => true,
+        Some(AllowedValue::List { values }) => values.iter().any(|allowed| allowed == value),
+        Some(AllowedValue::Range {
+            minimum, maximum, ..
+        }) => match (
+            value.parse::<f64>(),
+            minimum.trim().parse::<f64>(),
+            maximum.trim().parse::<f64>(),
+        ) {
+            (Ok(value), Ok(minimum), Ok(maximum)) => value >= minimum && value <= maximum,
+            _ => true,
+        },
+    };
+    if !allowed {
+        error!(
+            "check_value - '{}' is not an allowed value for '{}'",
+            value, variable.name
+        );
+        return invalid_field_value(&variable.name, value).into();
+    }
+    Ok(())
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -25,3 +224,89 @@ This module implements the UPnP device and service control capabilities.
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const EX_SCPD: &str = r#"<?xml version="1.0"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0">
+  <specVersion><major>1</major><minor>0</minor></specVersion>
+  <actionList>
+    <action>
+      <name>SetVolume</name>
+      <argumentList>
+        <argument><name>InstanceID</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_InstanceID</relatedStateVariable></argument>
+        <argument><name>Channel</name><direction>in</direction><relatedStateVariable>A_ARG_TYPE_Channel</relatedStateVariable></argument>
+        <argument><name>DesiredVolume</name><direction>in</direction><relatedStateVariable>Volume</relatedStateVariable></argument>
+      </argumentList>
+    </action>
+  </actionList>
+  <serviceStateTable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_InstanceID</name><dataType>ui4</dataType></stateVariable>
+    <stateVariable sendEvents="no"><name>A_ARG_TYPE_Channel</name><dataType>string</dataType>
+      <allowedValueList><allowedValue>Master</allowedValue></allowedValueList></stateVariable>
+    <stateVariable sendEvents="no"><name>Volume</name><dataType>ui2</dataType>
+      <allowedValueRange><minimum>0</minimum><maximum>100</maximum></allowedValueRange></stateVariable>
+  </serviceStateTable>
+</scpd>"#;
+
+    fn arguments(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_arguments() {
+        let spcd = Spcd::from_str(EX_SCPD).unwrap();
+        let ordered = validate_arguments(
+            &spcd,
+            "SetVolume",
+            &arguments(&[
+                ("DesiredVolume", "50"),
+                ("InstanceID", "0"),
+                ("Channel", "Master"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            ordered,
+            arguments(&[
+                ("InstanceID", "0"),
+                ("Channel", "Master"),
+                ("DesiredVolume", "50")
+            ])
+        );
+
+        for invalid in &[
+            arguments(&[("InstanceID", "0"), ("Channel", "Master")]),
+            arguments(&[
+                ("InstanceID", "-1"),
+                ("Channel", "Master"),
+                ("DesiredVolume", "5"),
+            ]),
+            arguments(&[
+                ("InstanceID", "0"),
+                ("Channel", "LF"),
+                ("DesiredVolume", "5"),
+            ]),
+            arguments(&[
+                ("InstanceID", "0"),
+                ("Channel", "Master"),
+                ("DesiredVolume", "101"),
+            ]),
+            arguments(&[
+                ("InstanceID", "0"),
+                ("Channel", "Master"),
+                ("DesiredVolume", "5"),
+                ("Mute", "1"),
+            ]),
+        ] {
+            assert!(validate_arguments(&spcd, "SetVolume", invalid).is_err());
+        }
+        assert!(validate_arguments(&spcd, "GetVolume", &[]).is_err());
+    }
+}
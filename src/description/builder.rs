@@ -0,0 +1,850 @@
+/*!
+This module provides builders for device and service descriptions. Each builder starts from the
+values that are required and fills in sensible defaults for the rest: a device is given a new
+UDN, a service takes its `serviceId` from its type and its SCPD, control, and event URLs from a
+[`PathScheme`](struct.PathScheme.html), and a description root sets `configId` when the
+specification version requires it.
+
+Every builder validates the value it builds when `build` is called, and fails with
+`Error::InvalidDescription` if there are any errors; see the [`validate`](../validate/index.html)
+module. The root builders, [`DeviceRootBuilder`](struct.DeviceRootBuilder.html) and
+[`SpcdBuilder`](struct.SpcdBuilder.html), validate the complete description, including the rules
+that depend on its specification version.
+
+# Example
+
+```rust
+use upnp_rs::description::builder::*;
+use upnp_rs::description::TypeID;
+use upnp_rs::SpecVersion;
+
+let service = ServiceBuilder::new(TypeID::new_service("SwitchPower".to_string(), 1))
+    .build()
+    .unwrap();
+assert_eq!(service.service_id, "urn:upnp-org:serviceId:SwitchPower");
+assert_eq!(service.control_url.to_string(), "/SwitchPower/control");
+
+let device = DeviceBuilder::new(TypeID::new_device("BinaryLight".to_string(), 1), "Hall Light")
+    .manufacturer("Example")
+    .model_name("Light")
+    .service(service)
+    .build()
+    .unwrap();
+
+let root = DeviceRootBuilder::new(device)
+    .spec_version(SpecVersion::V11)
+    .build()
+    .unwrap();
+assert_eq!(root.config_id, Some(1));
+```
+*/
+
+use crate::common::uri::URL;
+use crate::common::xml::read::Node;
+use crate::description::device::{Device, DeviceRoot, Icon, Service};
+use crate::description::service::{Action, AllowedValue, Argument, Direction, Spcd, StateVariable};
+use crate::description::validate::{
+    validate_action, validate_device, validate_device_root, validate_icon, validate_service,
+    validate_spcd, validate_state_variable, Finding, Severity,
+};
+use crate::description::TypeID;
+use crate::error::{invalid_description, invalid_value_for_type, Error};
+use crate::SpecVersion;
+use std::str::FromStr;
+use tracing::error;
+use uuid::Uuid;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Templates for the default SCPD, control, and event URLs of a service; in each `{name}` is
+/// replaced by the service type name and `{version}` by its version. The defaults are
+/// `/{name}/scpd.xml`, `/{name}/control`, and `/{name}/event`.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathScheme {
+    pub scpd: String,
+    pub control: String,
+    pub event: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeviceRootBuilder {
+    root: DeviceRoot,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeviceBuilder {
+    device: Device,
+}
+
+#[derive(Clone, Debug)]
+pub struct ServiceBuilder {
+    service_type: TypeID,
+    service_id: String,
+    scheme: PathScheme,
+    scpd_url: Option<URL>,
+    control_url: Option<URL>,
+    event_sub_url: Option<URL>,
+}
+
+#[derive(Clone, Debug)]
+pub struct IconBuilder {
+    icon: Icon,
+}
+
+#[derive(Clone, Debug)]
+pub struct SpcdBuilder {
+    spcd: Spcd,
+}
+
+#[derive(Clone, Debug)]
+pub struct ActionBuilder {
+    action: Action,
+    service_state_table: Vec<StateVariable>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StateVariableBuilder {
+    spec_version: SpecVersion,
+    variable: StateVariable,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return a new, random, unique device name in the form `uuid:{UUID}`.
+///
+/// A UDN must not change over the lifetime of a device, including across restarts, so a device
+/// should persist the value it is first given rather than calling this each time it starts.
+///
+pub fn new_unique_device_name() -> String {
+    format!("uuid:{}", Uuid::new_v4())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for PathScheme {
+    fn default() -> Self {
+        Self {
+            scpd: "/{name}/scpd.xml".to_string(),
+            control: "/{name}/control".to_string(),
+            event: "/{name}/event".to_string(),
+        }
+    }
+}
+
+impl PathScheme {
+    fn url_for(template: &str, service_type: &TypeID) -> Result<URL, Error> {
+        let path = template
+            .replace("{name}", service_type.name())
+            .replace("{version}", &service_type.version().to_string());
+        URL::from_str(&path).map_err(|_| {
+            error!("url_for - '{}' is not a valid URL path", path);
+            invalid_value_for_type("URL", path).into()
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl DeviceRootBuilder {
+    pub fn new(device: Device) -> Self {
+        Self {
+            root: DeviceRoot {
+                spec_version: SpecVersion::default(),
+                config_id: None,
+                namespaces: Default::default(),
+                url_base: None,
+                device,
+            },
+        }
+    }
+
+    pub fn spec_version(&mut self, spec_version: SpecVersion) -> &mut Self {
+        self.root.spec_version = spec_version;
+        self
+    }
+
+    ///
+    /// Set the `configId`; if not set it defaults to `1` for UPnP 1.1 and later, which require
+    /// it.
+    ///
+    pub fn config_id(&mut self, config_id: u32) -> &mut Self {
+        self.root.config_id = Some(config_id);
+        self
+    }
+
+    pub fn url_base(&mut self, url_base: URL) -> &mut Self {
+        self.root.url_base = Some(url_base);
+        self
+    }
+
+    pub fn namespace(&mut self, prefix: &str, uri: &str) -> &mut Self {
+        self.root
+            .namespaces
+            .push((prefix.to_string(), uri.to_string()));
+        self
+    }
+
+    pub fn device(&mut self, device: Device) -> &mut Self {
+        self.root.device = device;
+        self
+    }
+
+    ///
+    /// Return the description, if it is valid for its specification version.
+    ///
+    pub fn build(&self) -> Result<DeviceRoot, Error> {
+        let mut root = self.root.clone();
+        if root.spec_version >= SpecVersion::V11 && root.config_id.is_none() {
+            root.config_id = Some(DEFAULT_CONFIG_ID);
+        }
+        check_findings(validate_device_root(&root))?;
+        Ok(root)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl DeviceBuilder {
+    ///
+    /// Start a new device of the given type with a new UDN; the manufacturer and model name are
+    /// also required and should be set before the device is built.
+    ///
+    pub fn new(device_type: TypeID, friendly_name: &str) -> Self {
+        Self {
+            device: Device {
+                device_type,
+                friendly_name: friendly_name.to_string(),
+                manufacturer: Default::default(),
+                manufacturer_url: None,
+                model_description: None,
+                model_name: Default::default(),
+                model_number: None,
+                model_url: None,
+                serial_number: None,
+                unique_device_name: new_unique_device_name(),
+                upc: None,
+                icon_list: Default::default(),
+                service_list: Default::default(),
+                device_list: Default::default(),
+                presentation_url: None,
+                extensions: Default::default(),
+            },
+        }
+    }
+
+    pub fn manufacturer(&mut self, manufacturer: &str) -> &mut Self {
+        self.device.manufacturer = manufacturer.to_string();
+        self
+    }
+
+    pub fn manufacturer_url(&mut self, manufacturer_url: URL) -> &mut Self {
+        self.device.manufacturer_url = Some(manufacturer_url);
+        self
+    }
+
+    pub fn model_description(&mut self, model_description: &str) -> &mut Self {
+        self.device.model_description = Some(model_description.to_string());
+        self
+    }
+
+    pub fn model_name(&mut self, model_name: &str) -> &mut Self {
+        self.device.model_name = model_name.to_string();
+        self
+    }
+
+    pub fn model_number(&mut self, model_number: &str) -> &mut Self {
+        self.device.model_number = Some(model_number.to_string());
+        self
+    }
+
+    pub fn model_url(&mut self, model_url: URL) -> &mut Self {
+        self.device.model_url = Some(model_url);
+        self
+    }
+
+    pub fn serial_number(&mut self, serial_number: &str) -> &mut Self {
+        self.device.serial_number = Some(serial_number.to_string());
+        self
+    }
+
+    ///
+    /// Replace the generated UDN, for example with one persisted from an earlier run.
+    ///
+    pub fn unique_device_name(&mut self, unique_device_name: &str) -> &mut Self {
+        self.device.unique_device_name = unique_device_name.to_string();
+        self
+    }
+
+    pub fn upc(&mut self, upc: &str) -> &mut Self {
+        self.device.upc = Some(upc.to_string());
+        self
+    }
+
+    pub fn presentation_url(&mut self, presentation_url: URL) -> &mut Self {
+        self.device.presentation_url = Some(presentation_url);
+        self
+    }
+
+    pub fn icon(&mut self, icon: Icon) -> &mut Self {
+        self.device.icon_list.push(icon);
+        self
+    }
+
+    pub fn service(&mut self, service: Service) -> &mut Self {
+        self.device.service_list.push(service);
+        self
+    }
+
+    pub fn device(&mut self, device: Device) -> &mut Self {
+        self.device.device_list.push(device);
+        self
+    }
+
+    pub fn extension(&mut self, extension: Node) -> &mut Self {
+        self.device.extensions.push(extension);
+        self
+    }
+
+    ///
+    /// Return the device, and all embedded devices and services, if it is valid.
+    ///
+    pub fn build(&self) -> Result<Device, Error> {
+        let device = self.device.clone();
+        check_findings(validate_device(&device))?;
+        Ok(device)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl ServiceBuilder {
+    ///
+    /// Start a new service of the given type, its `serviceId` and URLs are derived from the type
+    /// using `TypeID::default_id`, in the type's own domain for vendor services, and the default
+    /// `PathScheme`.
+    ///
+    pub fn new(service_type: TypeID) -> Self {
+        Self::with_scheme(service_type, &PathScheme::default())
+    }
+
+    ///
+    /// Start a new service of the given type, as `new`, with URLs derived using `scheme`.
+    ///
+    pub fn with_scheme(service_type: TypeID, scheme: &PathScheme) -> Self {
+        Self {
+            service_id: service_type.default_id(),
+            service_type,
+            scheme: scheme.clone(),
+            scpd_url: None,
+            control_url: None,
+            event_sub_url: None,
+        }
+    }
+
+    pub fn service_id(&mut self, service_id: &str) -> &mut Self {
+        self.service_id = service_id.to_string();
+        self
+    }
+
+    pub fn scpd_url(&mut self, scpd_url: URL) -> &mut Self {
+        self.scpd_url = Some(scpd_url);
+        self
+    }
+
+    pub fn control_url(&mut self, control_url: URL) -> &mut Self {
+        self.control_url = Some(control_url);
+        self
+    }
+
+    pub fn event_sub_url(&mut self, event_sub_url: URL) -> &mut Self {
+        self.event_sub_url = Some(event_sub_url);
+        self
+    }
+
+    ///
+    /// Return the service if it is valid, failing also if a URL derived from the path scheme is
+    /// not valid.
+    ///
+    pub fn build(&self) -> Result<Service, Error> {
+        let url_or_default = |url: &Option<URL>, template: &str| match url {
+            Some(url) => Ok(url.clone()),
+            None => PathScheme::url_for(template, &self.service_type),
+        };
+        let service = Service {
+            service_type: self.service_type.clone(),
+            service_id: self.service_id.clone(),
+            scpd_url: url_or_default(&self.scpd_url, &self.scheme.scpd)?,
+            control_url: url_or_default(&self.control_url, &self.scheme.control)?,
+            event_sub_url: url_or_default(&self.event_sub_url, &self.scheme.event)?,
+        };
+        check_findings(validate_service(&service))?;
+        Ok(service)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl IconBuilder {
+    ///
+    /// Start a new icon, which defaults to 48x48 pixels with a color depth of 24.
+    ///
+    pub fn new(mime_type: &str, url: URL) -> Self {
+        Self {
+            icon: Icon {
+                mime_type: mime_type.to_string(),
+                width: DEFAULT_ICON_SIZE,
+                height: DEFAULT_ICON_SIZE,
+                depth: DEFAULT_ICON_DEPTH,
+                url,
+            },
+        }
+    }
+
+    pub fn size(&mut self, width: u16, height: u16) -> &mut Self {
+        self.icon.width = width;
+        self.icon.height = height;
+        self
+    }
+
+    pub fn depth(&mut self, depth: u16) -> &mut Self {
+        self.icon.depth = depth;
+        self
+    }
+
+    ///
+    /// Return the icon, if it is valid.
+    ///
+    pub fn build(&self) -> Result<Icon, Error> {
+        let icon = self.icon.clone();
+        check_findings(validate_icon(&icon))?;
+        Ok(icon)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Default for SpcdBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpcdBuilder {
+    pub fn new() -> Self {
+        Self {
+            spcd: Spcd {
+                spec_version: SpecVersion::default(),
+                config_id: None,
+                action_list: Default::default(),
+                service_state_table: Default::default(),
+            },
+        }
+    }
+
+    pub fn spec_version(&mut self, spec_version: SpecVersion) -> &mut Self {
+        self.spcd.spec_version = spec_version;
+        self
+    }
+
+    ///
+    /// Set the `configId`; if not set it defaults to `1` for UPnP 1.1 and later, which require
+    /// it.
+    ///
+    pub fn config_id(&mut self, config_id: u32) -> &mut Self {
+        self.spcd.config_id = Some(config_id);
+        self
+    }
+
+    pub fn action(&mut self, action: Action) -> &mut Self {
+        self.spcd.action_list.push(action);
+        self
+    }
+
+    pub fn state_variable(&mut self, variable: StateVariable) -> &mut Self {
+        self.spcd.service_state_table.push(variable);
+        self
+    }
+
+    ///
+    /// Return the service description, if it is valid for its specification version.
+    ///
+    pub fn build(&self) -> Result<Spcd, Error> {
+        let mut spcd = self.spcd.clone();
+        if spcd.spec_version >= SpecVersion::V11 && spcd.config_id.is_none() {
+            spcd.config_id = Some(DEFAULT_CONFIG_ID);
+        }
+        check_findings(validate_spcd(&spcd))?;
+        Ok(spcd)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl ActionBuilder {
+    ///
+    /// Start a new action; the related state variable of each argument must be one of
+    /// `service_state_table`.
+    ///
+    pub fn new(name: &str, service_state_table: &[StateVariable]) -> Self {
+        Self {
+            action: Action {
+                name: name.to_string(),
+                argument_list: Default::default(),
+            },
+            service_state_table: service_state_table.to_vec(),
+        }
+    }
+
+    pub fn in_argument(&mut self, name: &str, related_state_variable: &str) -> &mut Self {
+        self.argument(name, Direction::In, false, related_state_variable)
+    }
+
+    pub fn out_argument(&mut self, name: &str, related_state_variable: &str) -> &mut Self {
+        self.argument(name, Direction::Out, false, related_state_variable)
+    }
+
+    ///
+    /// Add an out argument that is the return value of the action; this must be the first out
+    /// argument.
+    ///
+    pub fn return_value(&mut self, name: &str, related_state_variable: &str) -> &mut Self {
+        self.argument(name, Direction::Out, true, related_state_variable)
+    }
+
+    ///
+    /// Return the action, if it is valid.
+    ///
+    pub fn build(&self) -> Result<Action, Error> {
+        let action = self.action.clone();
+        check_findings(validate_action(&action, &self.service_state_table))?;
+        Ok(action)
+    }
+
+    fn argument(
+        &mut self,
+        name: &str,
+        direction: Direction,
+        return_value: bool,
+        related_state_variable: &str,
+    ) -> &mut Self {
+        self.action.argument_list.push(Argument {
+            name: name.to_string(),
+            direction,
+            return_value,
+            related_state_variable: related_state_variable.to_string(),
+        });
+        self
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl StateVariableBuilder {
+    ///
+    /// Start a new state variable with the given UDA data type, such as `ui4` or `string`; it
+    /// sends events, as is the default in the specification, but is not multicast.
+    ///
+    pub fn new(name: &str, data_type: &str) -> Self {
+        Self {
+            spec_version: SpecVersion::default(),
+            variable: StateVariable {
+                send_events: true,
+                multicast: false,
+                name: name.to_string(),
+                data_type: data_type.to_string(),
+                extended_data_type: None,
+                default_value: None,
+                allowed_values: None,
+            },
+        }
+    }
+
+    ///
+    /// Set the specification version the data type and allowed values are validated against;
+    /// this should match the version of the service description.
+    ///
+    pub fn spec_version(&mut self, spec_version: SpecVersion) -> &mut Self {
+        self.spec_version = spec_version;
+        self
+    }
+
+    pub fn send_events(&mut self, send_events: bool) -> &mut Self {
+        self.variable.send_events = send_events;
+        self
+    }
+
+    pub fn multicast(&mut self, multicast: bool) -> &mut Self {
+        self.variable.multicast = multicast;
+        self
+    }
+
+    pub fn extended_data_type(&mut self, extended_data_type: &str) -> &mut Self {
+        self.variable.extended_data_type = Some(extended_data_type.to_string());
+        self
+    }
+
+    pub fn default_value(&mut self, default_value: &str) -> &mut Self {
+        self.variable.default_value = Some(default_value.to_string());
+        self
+    }
+
+    pub fn allowed_values(&mut self, values: &[&str]) -> &mut Self {
+        self.variable.allowed_values = Some(AllowedValue::List {
+            values: values.iter().map(|value| value.to_string()).collect(),
+        });
+        self
+    }
+
+    pub fn allowed_range(&mut self, minimum: &str, maximum: &str, step: Option<&str>) -> &mut Self {
+        self.variable.allowed_values = Some(AllowedValue::Range {
+            minimum: minimum.to_string(),
+            maximum: maximum.to_string(),
+            step: step.map(str::to_string),
+        });
+        self
+    }
+
+    ///
+    /// Return the state variable, if it is valid.
+    ///
+    pub fn build(&self) -> Result<StateVariable, Error> {
+        let variable = self.variable.clone();
+        check_findings(validate_state_variable(&variable, self.spec_version))?;
+        Ok(variable)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+const DEFAULT_CONFIG_ID: u32 = 1;
+
+const DEFAULT_ICON_SIZE: u16 = 48;
+
+const DEFAULT_ICON_DEPTH: u16 = 24;
+
+fn check_findings(findings: Vec<Finding>) -> Result<(), Error> {
+    let errors: Vec<Finding> = findings
+        .into_iter()
+        .filter(|finding| finding.severity == Severity::Error)
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        error!("check_findings - description has {} error(s)", errors.len());
+        Err(invalid_description(errors))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn light() -> DeviceBuilder {
+        let mut builder = DeviceBuilder::new(
+            TypeID::new_device("BinaryLight".to_string(), 1),
+            "Hall Light",
+        );
+        builder
+            .manufacturer("Example")
+            .model_name("Light")
+            .serial_number("0001")
+            .service(
+                ServiceBuilder::new(TypeID::new_service("SwitchPower".to_string(), 1))
+                    .build()
+                    .unwrap(),
+            );
+        builder
+    }
+
+    fn invalid_findings<T: std::fmt::Debug>(result: Result<T, Error>) -> Vec<Finding> {
+        match result {
+            Err(Error::InvalidDescription { findings }) => findings,
+            _ => panic!("expected an invalid description, not {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_build_device_root() {
+        let builder = light();
+        let device = builder.build().unwrap();
+        assert!(device.unique_device_name.starts_with("uuid:"));
+        assert_eq!(
+            builder.build().unwrap().unique_device_name,
+            device.unique_device_name
+        );
+
+        let service = &device.service_list[0];
+        assert_eq!(service.service_id, "urn:upnp-org:serviceId:SwitchPower");
+        assert_eq!(service.scpd_url.to_string(), "/SwitchPower/scpd.xml");
+        assert_eq!(service.event_sub_url.to_string(), "/SwitchPower/event");
+
+        let root = DeviceRootBuilder::new(device)
+            .spec_version(SpecVersion::V20)
+            .build()
+            .unwrap();
+        assert_eq!(root.config_id, Some(1));
+        assert_eq!(root.device.friendly_name, "Hall Light");
+    }
+
+    #[test]
+    fn test_build_with_scheme() {
+        let scheme = PathScheme {
+            scpd: "/upnp/{name}{version}.xml".to_string(),
+            control: "/upnp/control/{name}".to_string(),
+            event: "/upnp/event/{name}".to_string(),
+        };
+        let service =
+            ServiceBuilder::with_scheme(TypeID::new_service("Dimming".to_string(), 2), &scheme)
+                .control_url(URL::from_str("/dim").unwrap())
+                .build()
+                .unwrap();
+        assert_eq!(service.scpd_url.to_string(), "/upnp/Dimming2.xml");
+        assert_eq!(service.control_url.to_string(), "/dim");
+        assert_eq!(service.event_sub_url.to_string(), "/upnp/event/Dimming");
+
+        let service = ServiceBuilder::new(TypeID::new_service_with_domain(
+            "example-com".to_string(),
+            "Fan".to_string(),
+            1,
+        ))
+        .build()
+        .unwrap();
+        assert_eq!(service.service_id, "urn:example-com:serviceId:Fan");
+    }
+
+    #[test]
+    fn test_build_invalid_device() {
+        let findings = invalid_findings(
+            DeviceBuilder::new(TypeID::new_device("BinaryLight".to_string(), 1), "")
+                .manufacturer("Example")
+                .model_name("Light")
+                .build(),
+        );
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].path, "device");
+        assert!(findings[0].message.contains("friendlyName"));
+    }
+
+    #[test]
+    fn test_build_invalid_device_root() {
+        let mut device = light().build().unwrap();
+        device.manufacturer = String::new();
+        let findings = invalid_findings(DeviceRootBuilder::new(device).build());
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains("manufacturer"));
+    }
+
+    #[test]
+    fn test_build_invalid_service() {
+        let findings = invalid_findings(
+            ServiceBuilder::new(TypeID::new_service("SwitchPower".to_string(), 1))
+                .service_id("SwitchPower")
+                .build(),
+        );
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains("serviceId 'SwitchPower'"));
+    }
+
+    #[test]
+    fn test_build_icon() {
+        let url = URL::from_str("/icons/light.png").unwrap();
+        let icon = IconBuilder::new("image/png", url.clone()).build().unwrap();
+        assert_eq!((icon.width, icon.height, icon.depth), (48, 48, 24));
+
+        let findings = invalid_findings(IconBuilder::new("image/png", url).size(0, 0).build());
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains("width and height"));
+    }
+
+    #[test]
+    fn test_build_invalid_action() {
+        let status = StateVariableBuilder::new("Status", "boolean")
+            .build()
+            .unwrap();
+        let findings = invalid_findings(
+            ActionBuilder::new("GetLevel", &[status])
+                .out_argument("Level", "LoadLevel")
+                .build(),
+        );
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0]
+            .message
+            .contains("relatedStateVariable 'LoadLevel'"));
+    }
+
+    #[test]
+    fn test_build_invalid_state_variable() {
+        let findings = invalid_findings(StateVariableBuilder::new("Level", "ui8").build());
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains("requires UPnP 2.0"));
+
+        let variable = StateVariableBuilder::new("Level", "ui8")
+            .spec_version(SpecVersion::V20)
+            .build()
+            .unwrap();
+        assert_eq!(variable.data_type, "ui8");
+    }
+
+    #[test]
+    fn test_build_spcd() {
+        let target = StateVariableBuilder::new("Target", "boolean")
+            .send_events(false)
+            .default_value("0")
+            .build()
+            .unwrap();
+        let status = StateVariableBuilder::new("Status", "boolean")
+            .default_value("0")
+            .build()
+            .unwrap();
+        let variables = [target.clone(), status.clone()];
+        let spcd = SpcdBuilder::new()
+            .spec_version(SpecVersion::V11)
+            .action(
+                ActionBuilder::new("SetTarget", &variables)
+                    .in_argument("newTargetValue", "Target")
+                    .build()
+                    .unwrap(),
+            )
+            .action(
+                ActionBuilder::new("GetStatus", &variables)
+                    .return_value("ResultStatus", "Status")
+                    .build()
+                    .unwrap(),
+            )
+            .state_variable(target)
+            .state_variable(status.clone())
+            .build()
+            .unwrap();
+        assert_eq!(spcd.config_id, Some(1));
+        assert_eq!(spcd.action_list.len(), 2);
+        assert!(spcd.action_list[1].argument_list[0].return_value);
+        assert!(spcd.state_variable("Status").unwrap().send_events);
+
+        let invalid = SpcdBuilder::new()
+            .action(
+                ActionBuilder::new("SetTarget", &variables)
+                    .in_argument("newTargetValue", "Target")
+                    .build()
+                    .unwrap(),
+            )
+            .state_variable(status)
+            .build();
+        assert!(invalid.is_err());
+    }
+}
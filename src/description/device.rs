@@ -6,64 +6,91 @@ What's this all about then?
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
+use crate::common::http;
+use crate::common::uri::URL;
+use crate::common::xml::read::{read_document, Node};
 use crate::common::xml::write::*;
 use crate::description::TypeID;
-use crate::error::{xml_error, Error};
+use crate::error::{invalid_field_value, xml_error, Error};
 use crate::syntax::{
-    XML_ELEM_DEVICE, XML_ELEM_DEVICE_LIST, XML_ELEM_DEVICE_TYPE, XML_ELEM_FRIENDLY_NAME,
-    XML_ELEM_ICON, XML_ELEM_ICON_DEPTH, XML_ELEM_ICON_HEIGHT, XML_ELEM_ICON_LIST,
-    XML_ELEM_ICON_MIME_TYPE, XML_ELEM_ICON_URL, XML_ELEM_ICON_WIDTH, XML_ELEM_MANUFACTURER,
-    XML_ELEM_MANUFACTURER_URL, XML_ELEM_MODEL_DESCR, XML_ELEM_MODEL_NAME, XML_ELEM_MODEL_NUMBER,
-    XML_ELEM_MODEL_URL, XML_ELEM_PRESENTATION_URL, XML_ELEM_ROOT, XML_ELEM_SERIAL_NUMBER,
-    XML_ELEM_SERVICE, XML_ELEM_SERVICE_CONTROL_URL, XML_ELEM_SERVICE_EVENT_URL,
-    XML_ELEM_SERVICE_ID, XML_ELEM_SERVICE_LIST, XML_ELEM_SERVICE_SCPD_URL, XML_ELEM_SERVICE_TYPE,
-    XML_ELEM_UDN, XML_ELEM_UPC, XML_ELEM_URL_BASE, XML_NS_DEVICE, XML_NS_SERVICE,
+    XML_ATTR_CONFIG_ID, XML_ATTR_NAMESPACE, XML_ELEM_DEVICE, XML_ELEM_DEVICE_LIST,
+    XML_ELEM_DEVICE_TYPE, XML_ELEM_FRIENDLY_NAME, XML_ELEM_ICON, XML_ELEM_ICON_DEPTH,
+    XML_ELEM_ICON_HEIGHT, XML_ELEM_ICON_LIST, XML_ELEM_ICON_MIME_TYPE, XML_ELEM_ICON_URL,
+    XML_ELEM_ICON_WIDTH, XML_ELEM_MANUFACTURER, XML_ELEM_MANUFACTURER_URL, XML_ELEM_MODEL_DESCR,
+    XML_ELEM_MODEL_NAME, XML_ELEM_MODEL_NUMBER, XML_ELEM_MODEL_URL, XML_ELEM_PRESENTATION_URL,
+    XML_ELEM_ROOT, XML_ELEM_SERIAL_NUMBER, XML_ELEM_SERVICE, XML_ELEM_SERVICE_CONTROL_URL,
+    XML_ELEM_SERVICE_EVENT_URL, XML_ELEM_SERVICE_ID, XML_ELEM_SERVICE_LIST,
+    XML_ELEM_SERVICE_SCPD_URL, XML_ELEM_SERVICE_TYPE, XML_ELEM_SPEC_VERSION, XML_ELEM_UDN,
+    XML_ELEM_UPC, XML_ELEM_URL_BASE, XML_NS_DEVICE, XML_NS_SERVICE,
 };
 use crate::SpecVersion;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesEnd, BytesStart, Event};
 use quick_xml::Writer;
-use std::io::Write;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use tracing::error;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Icon {
     pub mime_type: String,
     pub width: u16,
     pub height: u16,
     pub depth: u16,
-    pub url: String, /* URL */
+    pub url: URL,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Service {
     pub service_type: TypeID,
-    pub service_id: String,    /* URI */
-    pub scpd_url: String,      /* URL */
-    pub control_url: String,   /* URL */
-    pub event_sub_url: String, /* URL */
+    pub service_id: String, /* URI */
+    pub scpd_url: URL,
+    pub control_url: URL,
+    pub event_sub_url: URL,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub device_type: TypeID,
     pub friendly_name: String,
     pub manufacturer: String,
-    pub manufacturer_url: Option<String>, /* URL */
+    pub manufacturer_url: Option<URL>,
     pub model_description: Option<String>,
     pub model_name: String,
     pub model_number: Option<String>,
-    pub model_url: Option<String>, /* URL */
+    pub model_url: Option<URL>,
     pub serial_number: Option<String>,
     pub unique_device_name: String,
     pub upc: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub icon_list: Vec<Icon>,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub service_list: Vec<Service>,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub device_list: Vec<Device>,
-    pub presentation_url: Option<String>, /* URL */
+    pub presentation_url: Option<URL>,
+    /// Vendor or forum extension elements, such as `dlna:X_DLNADOC`, and any other elements
+    /// not defined by the UDA; these are written, unchanged, after the standard elements.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extensions: Vec<Node>,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeviceRoot {
     pub spec_version: SpecVersion,
-    pub url_base: String, /* URL */
+    /// The `configId` attribute of the root element, required for UPnP 1.1 and later.
+    pub config_id: Option<u32>,
+    /// Additional namespace declarations on the root element, as `(prefix, URI)` pairs, used by
+    /// any extension elements in the description.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub namespaces: Vec<(String, String)>,
+    /// The base for relative URLs in the description, deprecated since UPnP 1.1.
+    pub url_base: Option<URL>,
     pub device: Device,
 }
 
@@ -75,6 +102,34 @@ pub fn to_writer<T: Write>(root: &DeviceRoot, writer: T) -> Result<T, Error> {
     root.write_root(writer)
 }
 
+///
+/// Write the description as served from `location`, usually on the interface a request arrived
+/// on, so that its URLs are reachable by the requester; see `DeviceRoot::located_at`.
+///
+pub fn to_writer_for<T: Write>(root: &DeviceRoot, location: &URL, writer: T) -> Result<T, Error> {
+    root.located_at(location).write_root(writer)
+}
+
+///
+/// Read a complete device description document from the provided reader.
+///
+pub fn from_reader<R: Read>(mut reader: R) -> Result<DeviceRoot, Error> {
+    let mut xml = String::new();
+    let _ = reader
+        .read_to_string(&mut xml)
+        .map_err(|e| xml_error(e.into()))?;
+    xml.parse()
+}
+
+///
+/// Fetch the device description at `location`, usually the `LOCATION` from a search response or
+/// notification, and resolve all URLs within it to absolute URLs.
+///
+pub fn fetch(location: &URL) -> Result<DeviceRoot, Error> {
+    let root: DeviceRoot = http::fetch(location)?;
+    Ok(root.resolve(location))
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -83,21 +138,32 @@ impl<T: Write> RootWritable<T> for DeviceRoot {}
 
 impl<T: Write> Writable<T> for DeviceRoot {
     fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
-        let root =
-            start_ns_element(writer, XML_ELEM_ROOT, XML_NS_DEVICE, None).map_err(xml_error)?;
+        let mut root = BytesStart::new(XML_ELEM_ROOT);
+        root.push_attribute(Attribute::from((XML_ATTR_NAMESPACE, XML_NS_DEVICE)));
+        for (prefix, namespace) in &self.namespaces {
+            let name = format!("{}:{}", XML_ATTR_NAMESPACE, prefix);
+            root.push_attribute(Attribute::from((name.as_str(), namespace.as_str())));
+        }
+        if let Some(config_id) = &self.config_id {
+            let config_id = config_id.to_string();
+            root.push_attribute(Attribute::from((XML_ATTR_CONFIG_ID, config_id.as_str())));
+        }
+        writer
+            .write_event(Event::Start(root))
+            .map_err(|e| xml_error(e.into()))?;
 
         self.spec_version.write(writer)?;
 
-        text_element(
-            writer,
-            XML_ELEM_URL_BASE,
-            self.url_base.to_string().as_str(),
-        )
-        .map_err(xml_error)?;
+        if let Some(url_base) = &self.url_base {
+            text_element(writer, XML_ELEM_URL_BASE, url_base.to_string().as_str())
+                .map_err(xml_error)?;
+        }
 
         self.device.write(writer)?;
 
-        root.end(writer).map_err(xml_error)
+        writer
+            .write_event(Event::End(BytesEnd::new(XML_ELEM_ROOT)))
+            .map_err(|e| xml_error(e.into()))
     }
 }
 
@@ -112,18 +178,15 @@ impl<T: Write> Writable<T> for Device {
         )
         .map_err(xml_error)?;
 
-        text_element(
-            writer,
-            XML_ELEM_FRIENDLY_NAME,
-            self.friendly_name.as_str(),
-        )
-        .map_err(xml_error)?;
+        text_element(writer, XML_ELEM_FRIENDLY_NAME, self.friendly_name.as_str())
+            .map_err(xml_error)?;
 
         text_element(writer, XML_ELEM_MANUFACTURER, self.manufacturer.as_str())
             .map_err(xml_error)?;
 
         if let Some(s) = &self.manufacturer_url {
-            text_element(writer, XML_ELEM_MANUFACTURER_URL, s.as_str()).map_err(xml_error)?;
+            text_element(writer, XML_ELEM_MANUFACTURER_URL, s.to_string().as_str())
+                .map_err(xml_error)?;
         }
 
         if let Some(s) = &self.model_description {
@@ -137,15 +200,14 @@ impl<T: Write> Writable<T> for Device {
         }
 
         if let Some(s) = &self.model_url {
-            text_element(writer, XML_ELEM_MODEL_URL, s.as_str()).map_err(xml_error)?;
+            text_element(writer, XML_ELEM_MODEL_URL, s.to_string().as_str()).map_err(xml_error)?;
         }
 
         if let Some(s) = &self.serial_number {
             text_element(writer, XML_ELEM_SERIAL_NUMBER, s.as_str()).map_err(xml_error)?;
         }
 
-        text_element(writer, XML_ELEM_UDN, self.unique_device_name.as_str())
-            .map_err(xml_error)?;
+        text_element(writer, XML_ELEM_UDN, self.unique_device_name.as_str()).map_err(xml_error)?;
 
         if let Some(s) = &self.upc {
             text_element(writer, XML_ELEM_UPC, s.as_str()).map_err(xml_error)?;
@@ -176,7 +238,12 @@ impl<T: Write> Writable<T> for Device {
         }
 
         if let Some(s) = &self.presentation_url {
-            text_element(writer, XML_ELEM_PRESENTATION_URL, s.as_str()).map_err(xml_error)?;
+            text_element(writer, XML_ELEM_PRESENTATION_URL, s.to_string().as_str())
+                .map_err(xml_error)?;
+        }
+
+        for extension in &self.extensions {
+            extension.write(writer)?;
         }
 
         top.end(writer).map_err(xml_error)
@@ -189,25 +256,18 @@ impl<T: Write> Writable<T> for Icon {
 
         text_element(writer, XML_ELEM_ICON_MIME_TYPE, self.mime_type.as_str())
             .map_err(xml_error)?;
-        text_element(
-            writer,
-            XML_ELEM_ICON_WIDTH,
-            self.width.to_string().as_str(),
-        )
-        .map_err(xml_error)?;
+        text_element(writer, XML_ELEM_ICON_WIDTH, self.width.to_string().as_str())
+            .map_err(xml_error)?;
         text_element(
             writer,
             XML_ELEM_ICON_HEIGHT,
             self.height.to_string().as_str(),
         )
         .map_err(xml_error)?;
-        text_element(
-            writer,
-            XML_ELEM_ICON_DEPTH,
-            self.depth.to_string().as_str(),
-        )
-        .map_err(xml_error)?;
-        text_element(writer, XML_ELEM_ICON_URL, self.url.as_str()).map_err(xml_error)?;
+        text_element(writer, XML_ELEM_ICON_DEPTH, self.depth.to_string().as_str())
+            .map_err(xml_error)?;
+        text_element(writer, XML_ELEM_ICON_URL, self.url.to_string().as_str())
+            .map_err(xml_error)?;
 
         element.end(writer).map_err(xml_error)
     }
@@ -227,20 +287,24 @@ impl<T: Write> Writable<T> for Service {
 
         text_element(writer, XML_ELEM_SERVICE_ID, self.service_id.as_str()).map_err(xml_error)?;
 
-        text_element(writer, XML_ELEM_SERVICE_SCPD_URL, self.scpd_url.as_str())
-            .map_err(xml_error)?;
+        text_element(
+            writer,
+            XML_ELEM_SERVICE_SCPD_URL,
+            self.scpd_url.to_string().as_str(),
+        )
+        .map_err(xml_error)?;
 
         text_element(
             writer,
             XML_ELEM_SERVICE_CONTROL_URL,
-            self.control_url.as_str(),
+            self.control_url.to_string().as_str(),
         )
         .map_err(xml_error)?;
 
         text_element(
             writer,
             XML_ELEM_SERVICE_EVENT_URL,
-            self.event_sub_url.as_str(),
+            self.event_sub_url.to_string().as_str(),
         )
         .map_err(xml_error)?;
 
@@ -248,10 +312,278 @@ impl<T: Write> Writable<T> for Service {
     }
 }
 
+// ------------------------------------------------------------------------------------------------
+
+impl DeviceRoot {
+    ///
+    /// Return the base URL against which relative URLs in this description are resolved, given
+    /// the `LOCATION` from which the description was retrieved. For UPnP 1.0 this is `URLBase`,
+    /// if present; it is deprecated in later versions and so `location` is always used.
+    ///
+    pub fn base_url(&self, location: &URL) -> URL {
+        match (&self.spec_version, &self.url_base) {
+            (SpecVersion::V10, Some(url_base)) => url_base.resolve(location),
+            _ => location.clone(),
+        }
+    }
+
+    ///
+    /// Return a copy of this description with all URLs, in all embedded devices and services,
+    /// resolved to absolute URLs; see `base_url` for how the base is chosen.
+    ///
+    pub fn resolve(&self, location: &URL) -> Self {
+        let base = self.base_url(location);
+        DeviceRoot {
+            device: self.device.resolve(&base),
+            ..self.clone()
+        }
+    }
+
+    ///
+    /// Return a copy of this description, to be served from `location`, with all relative URLs
+    /// resolved to absolute URLs against it. For UPnP 1.0 `URLBase` is set to the scheme and
+    /// authority of `location`, for later versions it is removed. URLs that are already absolute
+    /// are left unchanged, so a description served from more than one address should only use
+    /// relative URLs.
+    ///
+    pub fn located_at(&self, location: &URL) -> Self {
+        let url_base = match self.spec_version {
+            SpecVersion::V10 => URL::from_str("/").ok().map(|root| root.resolve(location)),
+            _ => None,
+        };
+        DeviceRoot {
+            url_base,
+            device: self.device.resolve(location),
+            ..self.clone()
+        }
+    }
+}
+
+impl Device {
+    ///
+    /// Return a copy of this device, and its icons, services, and embedded devices, with all
+    /// URLs resolved against `base`.
+    ///
+    pub fn resolve(&self, base: &URL) -> Self {
+        Device {
+            manufacturer_url: self.manufacturer_url.as_ref().map(|url| url.resolve(base)),
+            model_url: self.model_url.as_ref().map(|url| url.resolve(base)),
+            icon_list: self
+                .icon_list
+                .iter()
+                .map(|icon| icon.resolve(base))
+                .collect(),
+            service_list: self
+                .service_list
+                .iter()
+                .map(|service| service.resolve(base))
+                .collect(),
+            device_list: self
+                .device_list
+                .iter()
+                .map(|device| device.resolve(base))
+                .collect(),
+            presentation_url: self.presentation_url.as_ref().map(|url| url.resolve(base)),
+            ..self.clone()
+        }
+    }
+}
+
+impl Icon {
+    ///
+    /// Return a copy of this icon with its URL resolved against `base`.
+    ///
+    pub fn resolve(&self, base: &URL) -> Self {
+        Icon {
+            url: self.url.resolve(base),
+            ..self.clone()
+        }
+    }
+}
+
+impl Service {
+    ///
+    /// Return a copy of this service with its SCPD, control, and event URLs resolved against
+    /// `base`.
+    ///
+    pub fn resolve(&self, base: &URL) -> Self {
+        Service {
+            scpd_url: self.scpd_url.resolve(base),
+            control_url: self.control_url.resolve(base),
+            event_sub_url: self.event_sub_url.resolve(base),
+            ..self.clone()
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl FromStr for DeviceRoot {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DeviceRoot::try_from(&read_document(s)?)
+    }
+}
+
+impl TryFrom<&Node> for DeviceRoot {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        if node.local_name() != XML_ELEM_ROOT {
+            error!("try_from - expecting root element, not '{}'", node.name);
+            return invalid_field_value(XML_ELEM_ROOT, &node.name).into();
+        }
+        let namespace_prefix = format!("{}:", XML_ATTR_NAMESPACE);
+        Ok(DeviceRoot {
+            spec_version: SpecVersion::try_from(node.required_child(XML_ELEM_SPEC_VERSION)?)?,
+            config_id: parse_config_id(node)?,
+            namespaces: node
+                .attributes
+                .iter()
+                .filter(|(name, _)| name.starts_with(&namespace_prefix))
+                .map(|(name, value)| (name[namespace_prefix.len()..].to_string(), value.clone()))
+                .collect(),
+            url_base: parse_optional_url(node, XML_ELEM_URL_BASE)?,
+            device: Device::try_from(node.required_child(XML_ELEM_DEVICE)?)?,
+        })
+    }
+}
+
+impl TryFrom<&Node> for Device {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        lazy_static! {
+            static ref STANDARD: Vec<&'static str> = vec![
+                XML_ELEM_DEVICE_TYPE,
+                XML_ELEM_FRIENDLY_NAME,
+                XML_ELEM_MANUFACTURER,
+                XML_ELEM_MANUFACTURER_URL,
+                XML_ELEM_MODEL_DESCR,
+                XML_ELEM_MODEL_NAME,
+                XML_ELEM_MODEL_NUMBER,
+                XML_ELEM_MODEL_URL,
+                XML_ELEM_SERIAL_NUMBER,
+                XML_ELEM_UDN,
+                XML_ELEM_UPC,
+                XML_ELEM_ICON_LIST,
+                XML_ELEM_SERVICE_LIST,
+                XML_ELEM_DEVICE_LIST,
+                XML_ELEM_PRESENTATION_URL,
+            ];
+        }
+        Ok(Device {
+            device_type: TypeID::from_str(node.required_child_text(XML_ELEM_DEVICE_TYPE)?)?,
+            friendly_name: node.required_child_text(XML_ELEM_FRIENDLY_NAME)?.clone(),
+            manufacturer: node.required_child_text(XML_ELEM_MANUFACTURER)?.clone(),
+            manufacturer_url: parse_optional_url(node, XML_ELEM_MANUFACTURER_URL)?,
+            model_description: node.child_text(XML_ELEM_MODEL_DESCR).cloned(),
+            model_name: node.required_child_text(XML_ELEM_MODEL_NAME)?.clone(),
+            model_number: node.child_text(XML_ELEM_MODEL_NUMBER).cloned(),
+            model_url: parse_optional_url(node, XML_ELEM_MODEL_URL)?,
+            serial_number: node.child_text(XML_ELEM_SERIAL_NUMBER).cloned(),
+            unique_device_name: node.required_child_text(XML_ELEM_UDN)?.clone(),
+            upc: node.child_text(XML_ELEM_UPC).cloned(),
+            icon_list: child_list(node, XML_ELEM_ICON_LIST, XML_ELEM_ICON)?,
+            service_list: child_list(node, XML_ELEM_SERVICE_LIST, XML_ELEM_SERVICE)?,
+            device_list: child_list(node, XML_ELEM_DEVICE_LIST, XML_ELEM_DEVICE)?,
+            presentation_url: parse_optional_url(node, XML_ELEM_PRESENTATION_URL)?,
+            extensions: node
+                .children
+                .iter()
+                .filter(|child| child.prefix().is_some() || !STANDARD.contains(&child.local_name()))
+                .cloned()
+                .collect(),
+        })
+    }
+}
+
+impl TryFrom<&Node> for Icon {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        Ok(Icon {
+            mime_type: node.required_child_text(XML_ELEM_ICON_MIME_TYPE)?.clone(),
+            width: parse_number(node, XML_ELEM_ICON_WIDTH)?,
+            height: parse_number(node, XML_ELEM_ICON_HEIGHT)?,
+            depth: parse_number(node, XML_ELEM_ICON_DEPTH)?,
+            url: parse_url(node, XML_ELEM_ICON_URL)?,
+        })
+    }
+}
+
+impl TryFrom<&Node> for Service {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        Ok(Service {
+            service_type: TypeID::from_str(node.required_child_text(XML_ELEM_SERVICE_TYPE)?)?,
+            service_id: node.required_child_text(XML_ELEM_SERVICE_ID)?.clone(),
+            scpd_url: parse_url(node, XML_ELEM_SERVICE_SCPD_URL)?,
+            control_url: parse_url(node, XML_ELEM_SERVICE_CONTROL_URL)?,
+            event_sub_url: parse_url(node, XML_ELEM_SERVICE_EVENT_URL)?,
+        })
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Types
 // ------------------------------------------------------------------------------------------------
 
+// ------------------------------------------------------------------------------------------------
+// Crate Functions
+// ------------------------------------------------------------------------------------------------
+
+pub(crate) fn parse_config_id(node: &Node) -> Result<Option<u32>, Error> {
+    match node.attribute(XML_ATTR_CONFIG_ID) {
+        None => Ok(None),
+        Some(value) => match value.trim().parse::<u32>() {
+            Ok(config_id) if config_id < (1 << 24) => Ok(Some(config_id)),
+            _ => {
+                error!("parse_config_id - invalid configId value '{}'", value);
+                invalid_field_value(XML_ATTR_CONFIG_ID, value).into()
+            }
+        },
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn parse_url(node: &Node, name: &str) -> Result<URL, Error> {
+    URL::from_str(node.required_child_text(name)?)
+}
+
+fn parse_optional_url(node: &Node, name: &str) -> Result<Option<URL>, Error> {
+    match node.child_text(name).map(|value| value.trim()) {
+        None | Some("") => Ok(None),
+        Some(value) => URL::from_str(value).map(Some),
+    }
+}
+
+fn parse_number(node: &Node, name: &str) -> Result<u16, Error> {
+    let value = node.required_child_text(name)?;
+    match value.trim().parse::<u16>() {
+        Ok(number) => Ok(number),
+        Err(_) => {
+            error!("parse_number - invalid value '{}' for '{}'", value, name);
+            invalid_field_value(name, value).into()
+        }
+    }
+}
+
+fn child_list<T>(node: &Node, list_name: &str, item_name: &str) -> Result<Vec<T>, Error>
+where
+    T: for<'a> TryFrom<&'a Node, Error = Error>,
+{
+    match node.child(list_name) {
+        None => Ok(Vec::new()),
+        Some(list) => list.children_named(item_name).map(T::try_from).collect(),
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
@@ -302,16 +634,18 @@ mod tests {
     fn test_xml_serialize() {
         let device = DeviceRoot {
             spec_version: SpecVersion::V10,
-            url_base: "http://10.59.104.28:49152/".to_string(),
+            config_id: None,
+            namespaces: vec![],
+            url_base: Some("http://10.59.104.28:49152/".parse().unwrap()),
             device: Device {
-                device_type: TypeID::new_device("Basic".to_string(), "1".to_string()),
+                device_type: TypeID::new_device("Basic".to_string(), 1),
                 friendly_name: "AXIS P3301 - 00408CA45086".to_string(),
                 manufacturer: "AXIS".to_string(),
-                manufacturer_url: Some("http://www.axis.com/".to_string()),
+                manufacturer_url: Some("http://www.axis.com/".parse().unwrap()),
                 model_description: Some("AXIS P3301 Network Fixed Dome Camera".to_string()),
                 model_name: "AXIS P3301".to_string(),
                 model_number: Some("P3301".to_string()),
-                model_url: Some("http://www.axis.com/".to_string()),
+                model_url: Some("http://www.axis.com/".parse().unwrap()),
                 serial_number: Some("00408CA45086".to_string()),
                 unique_device_name: "uuid:Upnp-BasicDevice-1_0-00408CA45086".to_string(),
                 upc: None,
@@ -320,15 +654,16 @@ mod tests {
                     service_type: TypeID::new_service_with_domain(
                         "axis-com".to_string(),
                         "BasicService".to_string(),
-                        "1".to_string(),
+                        1,
                     ),
                     service_id: "urn:axis-com:serviceId:BasicServiceId".to_string(),
-                    scpd_url: "/scpd_basic.xml".to_string(),
-                    control_url: "/upnp/control/BasicServiceId".to_string(),
-                    event_sub_url: "/upnp/event/BasicServiceId".to_string(),
+                    scpd_url: "/scpd_basic.xml".parse().unwrap(),
+                    control_url: "/upnp/control/BasicServiceId".parse().unwrap(),
+                    event_sub_url: "/upnp/event/BasicServiceId".parse().unwrap(),
                 }],
                 device_list: vec![],
-                presentation_url: Some("http://10.59.104.28:80/".to_string()),
+                presentation_url: Some("http://10.59.104.28:80/".parse().unwrap()),
+                extensions: vec![],
             },
         };
         println!("\n{:#?}\n", device);
@@ -339,4 +674,165 @@ mod tests {
 
         assert_eq!(xml, EX_DEVICE);
     }
+
+    const EX_DLNA_DEVICE: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0" xmlns:dlna="urn:schemas-dlna-org:device-1-0" xmlns:sec="http://www.sec.co.kr/dlna" configId="1337">
+  <specVersion><major>2</major><minor>0</minor></specVersion>
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:MediaServer:1</deviceType>
+    <friendlyName>Living Room</friendlyName>
+    <manufacturer>Example</manufacturer>
+    <modelName>Media Server</modelName>
+    <UDN>uuid:4d696e69-444c-164e-9d41-b827eb54e939</UDN>
+    <dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC>
+    <sec:ProductCap>smi,DCM10,getMediaInfo.sec</sec:ProductCap>
+    <iconList>
+      <icon><mimetype>image/png</mimetype><width>48</width><height>48</height><depth>24</depth><url>/icon.png</url></icon>
+    </iconList>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:ContentDirectory:1</serviceType>
+        <serviceId>urn:upnp-org:serviceId:ContentDirectory</serviceId>
+        <SCPDURL>/cd.xml</SCPDURL>
+        <controlURL>/cd/control</controlURL>
+        <eventSubURL>/cd/event</eventSubURL>
+      </service>
+    </serviceList>
+  </device>
+</root>"#;
+
+    #[test]
+    fn test_xml_round_trip_with_extensions() {
+        let device: DeviceRoot = EX_DLNA_DEVICE.parse().unwrap();
+        assert_eq!(device.spec_version, SpecVersion::V20);
+        assert_eq!(device.config_id, Some(1337));
+        assert_eq!(device.namespaces.len(), 2);
+        assert_eq!(device.namespaces[0].0, "dlna");
+        assert_eq!(device.device.icon_list.len(), 1);
+        assert_eq!(device.device.service_list.len(), 1);
+        assert_eq!(
+            device.device.service_list[0].service_type.to_string(),
+            "urn:schemas-upnp-org:service:ContentDirectory:1"
+        );
+        let extensions: Vec<&str> = device
+            .device
+            .extensions
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(extensions, vec!["dlna:X_DLNADOC", "sec:ProductCap"]);
+
+        let written = to_writer(&device, Vec::new()).unwrap();
+        let xml = from_utf8(&written).unwrap();
+        assert!(xml.contains("xmlns:dlna=\"urn:schemas-dlna-org:device-1-0\""));
+        assert!(xml.contains("configId=\"1337\""));
+        assert!(xml.contains("<dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC>"));
+
+        let reparsed = from_reader(written.as_slice()).unwrap();
+        let rewritten = to_writer(&reparsed, Vec::new()).unwrap();
+        assert_eq!(from_utf8(&rewritten).unwrap(), xml);
+    }
+
+    #[test]
+    fn test_resolve_urls() {
+        let location = URL::from_str("http://192.168.1.20:8200/rootDesc.xml").unwrap();
+        let device: DeviceRoot = EX_DLNA_DEVICE.parse().unwrap();
+        let resolved = device.resolve(&location);
+        let service = &resolved.device.service_list[0];
+        assert_eq!(
+            service.scpd_url.to_string(),
+            "http://192.168.1.20:8200/cd.xml"
+        );
+        assert_eq!(
+            service.control_url.to_string(),
+            "http://192.168.1.20:8200/cd/control"
+        );
+        assert_eq!(
+            resolved.device.icon_list[0].url.to_string(),
+            "http://192.168.1.20:8200/icon.png"
+        );
+
+        let mut device: DeviceRoot = EX_DEVICE.parse().unwrap();
+        assert_eq!(
+            device.base_url(&location).to_string(),
+            "http://10.59.104.28:49152/"
+        );
+        device.spec_version = SpecVersion::V11;
+        assert_eq!(device.base_url(&location), location);
+    }
+
+    #[test]
+    fn test_located_at() {
+        let location = URL::from_str("http://10.0.3.1:49152/description.xml").unwrap();
+        let device: DeviceRoot = EX_DEVICE.parse().unwrap();
+        let located = device.located_at(&location);
+        assert_eq!(
+            located.url_base.as_ref().unwrap().to_string(),
+            "http://10.0.3.1:49152/"
+        );
+        assert_eq!(
+            located.device.service_list[0].scpd_url.to_string(),
+            "http://10.0.3.1:49152/scpd_basic.xml"
+        );
+        assert_eq!(
+            located
+                .device
+                .presentation_url
+                .as_ref()
+                .unwrap()
+                .to_string(),
+            "http://10.59.104.28:80/"
+        );
+
+        let mut device: DeviceRoot = EX_DLNA_DEVICE.parse().unwrap();
+        device.spec_version = SpecVersion::V11;
+        let written = to_writer_for(&device, &location, Vec::new()).unwrap();
+        let written = from_utf8(&written).unwrap();
+        assert!(!written.contains("<URLBase>"));
+        assert!(written.contains("<SCPDURL>http://10.0.3.1:49152/cd.xml</SCPDURL>"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_device() {
+        let device: DeviceRoot = serde_json::from_str(
+            r#"{
+                "spec_version": "1.1",
+                "config_id": 1,
+                "url_base": null,
+                "device": {
+                    "device_type": "urn:schemas-upnp-org:device:BinaryLight:1",
+                    "friendly_name": "Hall Light",
+                    "manufacturer": "Example",
+                    "model_name": "Light",
+                    "unique_device_name": "uuid:0b5a84b6-3c45-4b2a-9d5e-2b1a3c4d5e6f",
+                    "service_list": [{
+                        "service_type": "urn:schemas-upnp-org:service:SwitchPower:1",
+                        "service_id": "urn:upnp-org:serviceId:SwitchPower",
+                        "scpd_url": "/switch.xml",
+                        "control_url": "/switch/control",
+                        "event_sub_url": "/switch/event"
+                    }]
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(device.spec_version, SpecVersion::V11);
+        assert!(device.device.icon_list.is_empty());
+        assert_eq!(
+            device.device.service_list[0].service_type,
+            TypeID::new_service("SwitchPower".to_string(), 1)
+        );
+
+        let value = serde_json::to_value(&device).unwrap();
+        assert_eq!(value["spec_version"], "1.1");
+        assert_eq!(
+            value["device"]["device_type"],
+            "urn:schemas-upnp-org:device:BinaryLight:1"
+        );
+        assert_eq!(
+            value["device"]["service_list"][0]["scpd_url"],
+            "/switch.xml"
+        );
+    }
 }
@@ -4,23 +4,30 @@ This module implements the UPnP device and service descriptions using the UPnP t
 use crate::discovery::search::SearchTarget;
 use crate::error::{invalid_value_for_type, unsupported_operation, Error};
 use crate::UPNP_DOMAIN;
+use regex::Regex;
 use std::fmt::{Display, Error as FmtError, Formatter};
+use std::str::FromStr;
+use tracing::error;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
-#[derive(Clone, Debug)]
+///
+/// The type of a device or service, in the form `urn:{domain}:device:{name}:{version}` or
+/// `urn:{domain}:service:{name}:{version}`; the version is always a positive integer.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum TypeID {
     Device {
         domain: String,
         name: String,
-        version: String,
+        version: u32,
     },
     Service {
         domain: String,
         name: String,
-        version: String,
+        version: u32,
     },
 }
 
@@ -33,7 +40,7 @@ pub enum TypeID {
 // ------------------------------------------------------------------------------------------------
 
 impl TypeID {
-    pub fn new_device(name: String, version: String) -> Self {
+    pub fn new_device(name: String, version: u32) -> Self {
         TypeID::Device {
             domain: UPNP_DOMAIN.to_string(),
             name,
@@ -41,7 +48,7 @@ impl TypeID {
         }
     }
 
-    pub fn new_device_with_domain(domain: String, name: String, version: String) -> Self {
+    pub fn new_device_with_domain(domain: String, name: String, version: u32) -> Self {
         TypeID::Device {
             domain,
             name,
@@ -49,7 +56,7 @@ impl TypeID {
         }
     }
 
-    pub fn new_service(name: String, version: String) -> Self {
+    pub fn new_service(name: String, version: u32) -> Self {
         TypeID::Service {
             domain: UPNP_DOMAIN.to_string(),
             name,
@@ -57,7 +64,7 @@ impl TypeID {
         }
     }
 
-    pub fn new_service_with_domain(domain: String, name: String, version: String) -> Self {
+    pub fn new_service_with_domain(domain: String, name: String, version: u32) -> Self {
         TypeID::Service {
             domain,
             name,
@@ -93,10 +100,62 @@ impl TypeID {
         }
     }
 
+    pub fn domain(&self) -> &String {
+        match self {
+            TypeID::Device { domain, .. } => domain,
+            TypeID::Service { domain, .. } => domain,
+        }
+    }
+
+    pub fn name(&self) -> &String {
+        match self {
+            TypeID::Device { name, .. } => name,
+            TypeID::Service { name, .. } => name,
+        }
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            TypeID::Device { version, .. } => *version,
+            TypeID::Service { version, .. } => *version,
+        }
+    }
+
+    pub fn is_device(&self) -> bool {
+        matches!(self, TypeID::Device { .. })
+    }
+
+    pub fn is_service(&self) -> bool {
+        matches!(self, TypeID::Service { .. })
+    }
+
+    ///
+    /// Returns `true` if this type may be used where `required` is expected. The UDA requires
+    /// that later versions of a device or service type are backward compatible with earlier
+    /// ones, so this is the case if both have the same kind, domain, and name, and this type's
+    /// version is the same as, or later than, the required version.
+    ///
+    pub fn is_compatible_with(&self, required: &TypeID) -> bool {
+        self.is_device() == required.is_device()
+            && self.domain() == required.domain()
+            && self.name() == required.name()
+            && self.version() >= required.version()
+    }
+
+    ///
+    /// The identifier for a single instance of this type, `urn:upnp-org:serviceId:{name}` for
+    /// a standard service. The UDA reserves `upnp-org` for standard types, so vendor types use
+    /// their own domain, with any `.` replaced by `-`, as in `urn:example-com:serviceId:{name}`.
+    ///
     pub fn default_id(&self) -> String {
+        let domain = if self.domain() == UPNP_DOMAIN {
+            "upnp-org".to_string()
+        } else {
+            self.domain().replace('.', "-")
+        };
         match self {
-            TypeID::Device { name, .. } => format!("urn:upnp-org:deviceId:{}", name),
-            TypeID::Service { name, .. } => format!("urn:upnp-org:serviceId:{}", name),
+            TypeID::Device { name, .. } => format!("urn:{}:deviceId:{}", domain, name),
+            TypeID::Service { name, .. } => format!("urn:{}:serviceId:{}", domain, name),
         }
     }
 }
@@ -118,6 +177,47 @@ impl Display for TypeID {
     }
 }
 
+impl From<&TypeID> for SearchTarget {
+    fn from(type_id: &TypeID) -> Self {
+        let name = format!("{}:{}", type_id.name(), type_id.version());
+        match (type_id.is_device(), type_id.domain() == UPNP_DOMAIN) {
+            (true, true) => SearchTarget::DeviceType(name),
+            (true, false) => SearchTarget::DomainDeviceType(type_id.domain().clone(), name),
+            (false, true) => SearchTarget::ServiceType(name),
+            (false, false) => SearchTarget::DomainServiceType(type_id.domain().clone(), name),
+        }
+    }
+}
+
+impl FromStr for TypeID {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref TYPE_ID: Regex =
+                Regex::new(r"^urn:([^:]+):(device|service):([^:]+):(\d+)$").unwrap();
+        }
+        match TYPE_ID.captures(s.trim()) {
+            None => {
+                error!("from_str - invalid type identifier '{}'", s);
+                invalid_value_for_type("TypeID", s).into()
+            }
+            Some(captures) => {
+                let domain = captures.get(1).unwrap().as_str().to_string();
+                let name = captures.get(3).unwrap().as_str().to_string();
+                let version = parse_version(captures.get(4).unwrap().as_str())?;
+                Ok(if captures.get(2).unwrap().as_str() == "device" {
+                    TypeID::new_device_with_domain(domain, name, version)
+                } else {
+                    TypeID::new_service_with_domain(domain, name, version)
+                })
+            }
+        }
+    }
+}
+
+serde_as_string!(TypeID);
+
 // ------------------------------------------------------------------------------------------------
 // Private Types
 // ------------------------------------------------------------------------------------------------
@@ -126,13 +226,20 @@ impl Display for TypeID {
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
-fn split_type_and_version(type_name: String) -> Result<(String, String), Error> {
-    match type_name.find(':') {
+fn split_type_and_version(type_name: String) -> Result<(String, u32), Error> {
+    match type_name.rfind(':') {
         None => invalid_value_for_type("type_and_version", type_name).into(),
-        Some(sep) => {
-            let (name, ver) = type_name.split_at(sep);
-            Ok((name.to_string(), ver.to_string()))
-        }
+        Some(sep) => Ok((
+            type_name[..sep].to_string(),
+            parse_version(&type_name[sep + 1..])?,
+        )),
+    }
+}
+
+fn parse_version(version: &str) -> Result<u32, Error> {
+    match version.parse::<u32>() {
+        Ok(version) if version > 0 => Ok(version),
+        _ => invalid_value_for_type("version", version).into(),
     }
 }
 
@@ -140,6 +247,91 @@ fn split_type_and_version(type_name: String) -> Result<(String, String), Error>
 // Modules
 // ------------------------------------------------------------------------------------------------
 
+pub mod builder;
+
 pub mod device;
 
 pub mod service;
+
+pub mod validate;
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_type_id_round_trip() {
+        for s in &[
+            "urn:schemas-upnp-org:device:MediaServer:1",
+            "urn:schemas-upnp-org:service:ContentDirectory:4",
+            "urn:axis-com:service:BasicService:1",
+        ] {
+            assert_eq!(TypeID::from_str(s).unwrap().to_string(), *s);
+        }
+        let type_id = TypeID::from_str("urn:schemas-upnp-org:service:ContentDirectory:4").unwrap();
+        assert!(type_id.is_service());
+        assert_eq!(type_id.name(), "ContentDirectory");
+        assert_eq!(type_id.version(), 4);
+
+        assert!(TypeID::from_str("urn:schemas-upnp-org:service:ContentDirectory").is_err());
+        assert!(TypeID::from_str("urn:schemas-upnp-org:service:ContentDirectory:x").is_err());
+        assert!(TypeID::from_str("urn:schemas-upnp-org:thing:ContentDirectory:1").is_err());
+    }
+
+    #[test]
+    fn test_type_id_default_id() {
+        assert_eq!(
+            TypeID::new_service("SwitchPower".to_string(), 1).default_id(),
+            "urn:upnp-org:serviceId:SwitchPower"
+        );
+        assert_eq!(
+            TypeID::from_str("urn:axis-com:service:BasicService:1")
+                .unwrap()
+                .default_id(),
+            "urn:axis-com:serviceId:BasicService"
+        );
+        assert_eq!(
+            TypeID::new_device_with_domain("example.com".to_string(), "Light".to_string(), 1)
+                .default_id(),
+            "urn:example-com:deviceId:Light"
+        );
+    }
+
+    #[test]
+    fn test_type_id_compatibility() {
+        let v1 = TypeID::new_service("ContentDirectory".to_string(), 1);
+        let v2 = TypeID::new_service("ContentDirectory".to_string(), 2);
+        assert!(v2.is_compatible_with(&v1));
+        assert!(v1.is_compatible_with(&v1));
+        assert!(!v1.is_compatible_with(&v2));
+        assert!(!TypeID::new_device("ContentDirectory".to_string(), 2).is_compatible_with(&v1));
+        assert!(!TypeID::new_service_with_domain(
+            "example-com".to_string(),
+            "ContentDirectory".to_string(),
+            2
+        )
+        .is_compatible_with(&v1));
+    }
+
+    #[test]
+    fn test_type_id_from_search_target() {
+        let st = SearchTarget::from_str("urn:schemas-upnp-org:device:MediaServer:2").unwrap();
+        assert_eq!(
+            TypeID::device_from(st).unwrap(),
+            TypeID::new_device("MediaServer".to_string(), 2)
+        );
+        for s in &[
+            "urn:schemas-upnp-org:device:MediaServer:2",
+            "urn:schemas-upnp-org:service:ContentDirectory:1",
+            "urn:dial-multiscreen-org:service:dial:1",
+        ] {
+            let type_id = TypeID::from_str(s).unwrap();
+            assert_eq!(SearchTarget::from(&type_id).to_string(), *s);
+        }
+    }
+}
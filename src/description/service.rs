@@ -2,30 +2,42 @@
 What's this all about then?
 */
 
+use crate::common::http;
+use crate::common::uri::URL;
+use crate::common::xml::read::{read_document, Node};
 use crate::common::xml::write::*;
-use crate::error::{xml_error, Error};
+use crate::description::device::parse_config_id;
+use crate::error::{invalid_field_value, xml_error, Error};
 use crate::syntax::{
-    XML_ATTR_SEND_EVENTS, XML_ELEM_ACTION, XML_ELEM_ACTION_LIST, XML_ELEM_ALLOWED_LIST,
+    XML_ATTR_CONFIG_ID, XML_ATTR_MULTICAST, XML_ATTR_NAMESPACE, XML_ATTR_SEND_EVENTS,
+    XML_ATTR_TYPE, XML_ELEM_ACTION, XML_ELEM_ACTION_LIST, XML_ELEM_ALLOWED_LIST,
     XML_ELEM_ALLOWED_RANGE, XML_ELEM_ALLOWED_VALUE, XML_ELEM_ARGUMENT, XML_ELEM_ARGUMENT_LIST,
     XML_ELEM_DATA_TYPE, XML_ELEM_DEFAULT_VALUE, XML_ELEM_DIRECTION, XML_ELEM_MAXIMUM,
     XML_ELEM_MINIMUM, XML_ELEM_NAME, XML_ELEM_REL_STATE_VARIABLE, XML_ELEM_RETVAL, XML_ELEM_SPCD,
-    XML_ELEM_STATE_TABLE, XML_ELEM_STATE_VARIABLE, XML_ELEM_STEP, XML_NS_SERVICE,
+    XML_ELEM_SPEC_VERSION, XML_ELEM_STATE_TABLE, XML_ELEM_STATE_VARIABLE, XML_ELEM_STEP,
+    XML_NS_SERVICE,
 };
 use crate::SpecVersion;
 use quick_xml::Writer;
-use std::io::Write;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::str::FromStr;
+use tracing::error;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Direction {
     In,
     Out,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Argument {
     pub name: String,
     pub direction: Direction,
@@ -34,12 +46,16 @@ pub struct Argument {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Action {
     pub name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub argument_list: Vec<Argument>,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum AllowedValue {
     List {
         values: Vec<String>,
@@ -52,18 +68,28 @@ pub enum AllowedValue {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StateVariable {
     pub send_events: bool,
+    /// Whether changes are also multicast as events, UPnP 1.1 and later.
+    pub multicast: bool,
     pub name: String,
     pub data_type: String,
+    /// The `type` attribute of `dataType`, naming an extended, schema-defined, data type.
+    pub extended_data_type: Option<String>,
     pub default_value: Option<String>,
     pub allowed_values: Option<AllowedValue>,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Spcd {
     pub spec_version: SpecVersion,
+    /// The `configId` attribute of the `scpd` element, required for UPnP 1.1 and later.
+    pub config_id: Option<u32>,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub action_list: Vec<Action>,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub service_state_table: Vec<StateVariable>,
 }
 
@@ -75,6 +101,25 @@ pub fn to_writer<T: Write>(root: &Spcd, writer: T) -> Result<T, Error> {
     root.write_root(writer)
 }
 
+///
+/// Read a complete service description document from the provided reader.
+///
+pub fn from_reader<R: Read>(mut reader: R) -> Result<Spcd, Error> {
+    let mut xml = String::new();
+    let _ = reader
+        .read_to_string(&mut xml)
+        .map_err(|e| xml_error(e.into()))?;
+    xml.parse()
+}
+
+///
+/// Fetch the service description at `scpd_url`, usually the resolved `SCPDURL` of a service in
+/// a device description.
+///
+pub fn fetch(scpd_url: &URL) -> Result<Spcd, Error> {
+    http::fetch(scpd_url)
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -161,19 +206,32 @@ impl<T: Write> Writable<T> for AllowedValue {
 
 impl<T: Write> Writable<T> for StateVariable {
     fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
-        let variable = start_element_with(
-            writer,
-            XML_ELEM_STATE_VARIABLE,
-            vec![(
-                XML_ATTR_SEND_EVENTS,
-                if self.send_events { "yes" } else { "no" },
-            )],
-        )
-        .map_err(xml_error)?;
+        let mut attributes = vec![(
+            XML_ATTR_SEND_EVENTS,
+            if self.send_events { "yes" } else { "no" },
+        )];
+        if self.multicast {
+            attributes.push((XML_ATTR_MULTICAST, "yes"));
+        }
+        let variable =
+            start_element_with(writer, XML_ELEM_STATE_VARIABLE, attributes).map_err(xml_error)?;
 
         text_element(writer, XML_ELEM_NAME, self.name.as_str()).map_err(xml_error)?;
 
-        text_element(writer, XML_ELEM_DATA_TYPE, self.data_type.as_str()).map_err(xml_error)?;
+        match &self.extended_data_type {
+            None => text_element(writer, XML_ELEM_DATA_TYPE, self.data_type.as_str())
+                .map_err(xml_error)?,
+            Some(extended) => {
+                let data_type = start_element_with(
+                    writer,
+                    XML_ELEM_DATA_TYPE,
+                    vec![(XML_ATTR_TYPE, extended.as_str())],
+                )
+                .map_err(xml_error)?;
+                text(writer, self.data_type.as_str()).map_err(xml_error)?;
+                data_type.end(writer).map_err(xml_error)?;
+            }
+        }
 
         if let Some(default_value) = &self.default_value {
             text_element(writer, XML_ELEM_DEFAULT_VALUE, default_value.as_str())
@@ -192,8 +250,12 @@ impl<T: Write> RootWritable<T> for Spcd {}
 
 impl<T: Write> Writable<T> for Spcd {
     fn write(&self, writer: &mut Writer<T>) -> Result<(), Error> {
-        let root =
-            start_ns_element(writer, XML_ELEM_SPCD, XML_NS_SERVICE, None).map_err(xml_error)?;
+        let config_id = self.config_id.map(|config_id| config_id.to_string());
+        let mut attributes = vec![(XML_ATTR_NAMESPACE, XML_NS_SERVICE)];
+        if let Some(config_id) = &config_id {
+            attributes.push((XML_ATTR_CONFIG_ID, config_id.as_str()));
+        }
+        let root = start_element_with(writer, XML_ELEM_SPCD, attributes).map_err(xml_error)?;
 
         self.spec_version.write(writer)?;
 
@@ -215,6 +277,134 @@ impl<T: Write> Writable<T> for Spcd {
     }
 }
 
+// ------------------------------------------------------------------------------------------------
+
+impl Spcd {
+    ///
+    /// Return the action with the given name, if present.
+    ///
+    pub fn action(&self, name: &str) -> Option<&Action> {
+        self.action_list.iter().find(|action| action.name == name)
+    }
+
+    ///
+    /// Return the state variable with the given name, if present; this is commonly used to find
+    /// the type of an argument from its `related_state_variable`.
+    ///
+    pub fn state_variable(&self, name: &str) -> Option<&StateVariable> {
+        self.service_state_table
+            .iter()
+            .find(|variable| variable.name == name)
+    }
+}
+
+impl FromStr for Spcd {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Spcd::try_from(&read_document(s)?)
+    }
+}
+
+impl TryFrom<&Node> for Spcd {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        if node.local_name() != XML_ELEM_SPCD {
+            error!("try_from - expecting scpd element, not '{}'", node.name);
+            return invalid_field_value(XML_ELEM_SPCD, &node.name).into();
+        }
+        Ok(Spcd {
+            spec_version: SpecVersion::try_from(node.required_child(XML_ELEM_SPEC_VERSION)?)?,
+            config_id: parse_config_id(node)?,
+            action_list: match node.child(XML_ELEM_ACTION_LIST) {
+                None => Vec::new(),
+                Some(list) => list
+                    .children_named(XML_ELEM_ACTION)
+                    .map(Action::try_from)
+                    .collect::<Result<_, _>>()?,
+            },
+            service_state_table: node
+                .required_child(XML_ELEM_STATE_TABLE)?
+                .children_named(XML_ELEM_STATE_VARIABLE)
+                .map(StateVariable::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl TryFrom<&Node> for Action {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        Ok(Action {
+            name: node.required_child_text(XML_ELEM_NAME)?.clone(),
+            argument_list: match node.child(XML_ELEM_ARGUMENT_LIST) {
+                None => Vec::new(),
+                Some(list) => list
+                    .children_named(XML_ELEM_ARGUMENT)
+                    .map(Argument::try_from)
+                    .collect::<Result<_, _>>()?,
+            },
+        })
+    }
+}
+
+impl TryFrom<&Node> for Argument {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        let direction = node.required_child_text(XML_ELEM_DIRECTION)?;
+        Ok(Argument {
+            name: node.required_child_text(XML_ELEM_NAME)?.clone(),
+            direction: match direction.trim() {
+                "in" => Direction::In,
+                "out" => Direction::Out,
+                _ => {
+                    error!("try_from - invalid argument direction '{}'", direction);
+                    return invalid_field_value(XML_ELEM_DIRECTION, direction).into();
+                }
+            },
+            return_value: node.child(XML_ELEM_RETVAL).is_some(),
+            related_state_variable: node
+                .required_child_text(XML_ELEM_REL_STATE_VARIABLE)?
+                .clone(),
+        })
+    }
+}
+
+impl TryFrom<&Node> for StateVariable {
+    type Error = Error;
+
+    fn try_from(node: &Node) -> Result<Self, Self::Error> {
+        let data_type = node.required_child(XML_ELEM_DATA_TYPE)?;
+        Ok(StateVariable {
+            send_events: parse_yes_no(node, XML_ATTR_SEND_EVENTS, true)?,
+            multicast: parse_yes_no(node, XML_ATTR_MULTICAST, false)?,
+            name: node.required_child_text(XML_ELEM_NAME)?.clone(),
+            data_type: data_type.text.clone(),
+            extended_data_type: data_type.attribute(XML_ATTR_TYPE).cloned(),
+            default_value: node.child_text(XML_ELEM_DEFAULT_VALUE).cloned(),
+            allowed_values: if let Some(list) = node.child(XML_ELEM_ALLOWED_LIST) {
+                Some(AllowedValue::List {
+                    values: list
+                        .children_named(XML_ELEM_ALLOWED_VALUE)
+                        .map(|value| value.text.clone())
+                        .collect(),
+                })
+            } else if let Some(range) = node.child(XML_ELEM_ALLOWED_RANGE) {
+                Some(AllowedValue::Range {
+                    minimum: range.required_child_text(XML_ELEM_MINIMUM)?.clone(),
+                    maximum: range.required_child_text(XML_ELEM_MAXIMUM)?.clone(),
+                    step: range.child_text(XML_ELEM_STEP).cloned(),
+                })
+            } else {
+                None
+            },
+        })
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Types
 // ------------------------------------------------------------------------------------------------
@@ -223,6 +413,77 @@ impl<T: Write> Writable<T> for Spcd {
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+fn parse_yes_no(node: &Node, name: &str, default: bool) -> Result<bool, Error> {
+    match node.attribute(name).map(|value| value.trim()) {
+        None => Ok(default),
+        Some("yes") => Ok(true),
+        Some("no") => Ok(false),
+        Some(value) => {
+            error!("parse_yes_no - invalid value '{}' for '{}'", value, name);
+            invalid_field_value(name, value).into()
+        }
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::str::from_utf8;
+
+    const EX_SCPD: &str = r#"<?xml version="1.0"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0" configId="7">
+  <specVersion><major>2</major><minor>0</minor></specVersion>
+  <actionList>
+    <action>
+      <name>GetLevel</name>
+      <argumentList>
+        <argument><name>Level</name><direction>out</direction><retval/><relatedStateVariable>Level</relatedStateVariable></argument>
+      </argumentList>
+    </action>
+  </actionList>
+  <serviceStateTable>
+    <stateVariable sendEvents="no" multicast="yes">
+      <name>Level</name>
+      <dataType>ui1</dataType>
+      <defaultValue>0</defaultValue>
+      <allowedValueRange><minimum>0</minimum><maximum>100</maximum><step>1</step></allowedValueRange>
+    </stateVariable>
+    <stateVariable sendEvents="no">
+      <name>A_ARG_TYPE_Info</name>
+      <dataType type="av:AVTransportInfo">string</dataType>
+    </stateVariable>
+  </serviceStateTable>
+</scpd>"#;
+
+    #[test]
+    fn test_xml_round_trip() {
+        let spcd: Spcd = EX_SCPD.parse().unwrap();
+        assert_eq!(spcd.spec_version, SpecVersion::V20);
+        assert_eq!(spcd.config_id, Some(7));
+        assert_eq!(spcd.action_list.len(), 1);
+        assert!(spcd.action_list[0].argument_list[0].return_value);
+        let level = &spcd.service_state_table[0];
+        assert!(!level.send_events);
+        assert!(level.multicast);
+        let info = &spcd.service_state_table[1];
+        assert_eq!(info.data_type, "string");
+        assert_eq!(
+            info.extended_data_type.as_deref(),
+            Some("av:AVTransportInfo")
+        );
+
+        let written = to_writer(&spcd, Vec::new()).unwrap();
+        let xml = from_utf8(&written).unwrap();
+        assert!(xml.contains("<stateVariable sendEvents=\"no\" multicast=\"yes\">"));
+        assert!(xml.contains("<dataType type=\"av:AVTransportInfo\">string</dataType>"));
+
+        let reparsed = from_reader(written.as_slice()).unwrap();
+        let rewritten = to_writer(&reparsed, Vec::new()).unwrap();
+        assert_eq!(from_utf8(&rewritten).unwrap(), xml);
+    }
+}
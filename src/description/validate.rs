@@ -0,0 +1,740 @@
+/*!
+This module provides validation of device and service descriptions against the rules of the
+_UPnP Device Architecture_ version they declare. Rather than failing on the first problem, the
+validation functions return a list of findings, each with a severity, so that a caller can decide
+which are acceptable.
+*/
+
+use crate::description::device::{Device, DeviceRoot, Icon, Service};
+use crate::description::service::{Action, AllowedValue, Direction, Spcd, StateVariable};
+use crate::SpecVersion;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fmt::{Display, Error as FmtError, Formatter};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The severity of a validation finding.
+///
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Severity {
+    /// Informational only, the description is valid.
+    Info,
+    /// The description breaks a recommendation ("should") of the specification.
+    Warning,
+    /// The description breaks a requirement ("must") of the specification.
+    Error,
+}
+
+///
+/// A single validation finding.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Finding {
+    /// How serious the finding is.
+    pub severity: Severity,
+    /// The location of the offending element, e.g. `root/device/serviceList/service[1]`.
+    pub path: String,
+    /// A description of the problem.
+    pub message: String,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Validate a device description, and all embedded devices and services, against the rules of
+/// the specification version declared in `root.spec_version`.
+///
+pub fn validate_device_root(root: &DeviceRoot) -> Vec<Finding> {
+    let mut findings = Findings::default();
+    let path = "root";
+
+    if root.spec_version >= SpecVersion::V11 && root.url_base.is_some() {
+        findings.warning(
+            path,
+            "URLBase is deprecated since UPnP 1.1 and should not be included",
+        );
+    }
+    if root.spec_version >= SpecVersion::V11 && root.config_id.is_none() {
+        findings.error(path, "configId is required since UPnP 1.1");
+    }
+
+    let mut udns: HashSet<String> = Default::default();
+    check_device(
+        &root.device,
+        &format!("{}/device", path),
+        &mut udns,
+        &mut findings,
+    );
+
+    findings.into()
+}
+
+///
+/// Validate a service description against the rules of the specification version declared in
+/// `spcd.spec_version`.
+///
+pub fn validate_spcd(spcd: &Spcd) -> Vec<Finding> {
+    let mut findings = Findings::default();
+    let path = "scpd";
+
+    if spcd.spec_version >= SpecVersion::V11 && spcd.config_id.is_none() {
+        findings.error(path, "configId is required since UPnP 1.1");
+    }
+
+    let mut action_names: HashSet<&String> = Default::default();
+    for (index, action) in spcd.action_list.iter().enumerate() {
+        let path = format!("{}/actionList/action[{}]", path, index);
+        check_action(action, &spcd.service_state_table, &path, &mut findings);
+        if !action_names.insert(&action.name) {
+            findings.error(&path, format!("duplicate action name '{}'", action.name));
+        }
+    }
+
+    if spcd.service_state_table.is_empty() {
+        findings.error(
+            path,
+            "the service state table must contain at least one state variable",
+        );
+    }
+
+    let mut variable_names: HashSet<&String> = Default::default();
+    for (index, variable) in spcd.service_state_table.iter().enumerate() {
+        let path = format!("{}/serviceStateTable/stateVariable[{}]", path, index);
+        check_name(&variable.name, &path, "stateVariable", &mut findings);
+        if !variable_names.insert(&variable.name) {
+            findings.error(
+                &path,
+                format!("duplicate state variable name '{}'", variable.name),
+            );
+        }
+        check_state_variable(variable, spcd.spec_version, &path, &mut findings);
+    }
+
+    findings.into()
+}
+
+///
+/// Validate a single device, and all embedded devices and services, outside of a description;
+/// the rules that depend on the specification version are checked by `validate_device_root`.
+///
+pub fn validate_device(device: &Device) -> Vec<Finding> {
+    let mut findings = Findings::default();
+    let mut udns: HashSet<String> = Default::default();
+    check_device(device, "device", &mut udns, &mut findings);
+    findings.into()
+}
+
+///
+/// Validate a single service entry of a device description.
+///
+pub fn validate_service(service: &Service) -> Vec<Finding> {
+    let mut findings = Findings::default();
+    check_service(service, "service", &mut findings);
+    findings.into()
+}
+
+///
+/// Validate a single icon entry of a device description.
+///
+pub fn validate_icon(icon: &Icon) -> Vec<Finding> {
+    let mut findings = Findings::default();
+    check_icon(icon, "icon", &mut findings);
+    findings.into()
+}
+
+///
+/// Validate a single action, where each argument must name a state variable in
+/// `service_state_table`.
+///
+pub fn validate_action(action: &Action, service_state_table: &[StateVariable]) -> Vec<Finding> {
+    let mut findings = Findings::default();
+    check_action(action, service_state_table, "action", &mut findings);
+    findings.into()
+}
+
+///
+/// Validate a single state variable against the rules of the specification version
+/// `spec_version`.
+///
+pub fn validate_state_variable(
+    variable: &StateVariable,
+    spec_version: SpecVersion,
+) -> Vec<Finding> {
+    let mut findings = Findings::default();
+    check_state_variable(variable, spec_version, "stateVariable", &mut findings);
+    findings.into()
+}
+
+///
+/// Returns `true` if any of the findings has the severity `Severity::Error`.
+///
+pub fn has_errors(findings: &[Finding]) -> bool {
+    findings.iter().any(|f| f.severity == Severity::Error)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(
+            f,
+            "{}",
+            match self {
+                Severity::Info => "info",
+                Severity::Warning => "warning",
+                Severity::Error => "error",
+            }
+        )
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}: {}: {}", self.severity, self.path, self.message)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug, Default)]
+struct Findings(Vec<Finding>);
+
+impl Findings {
+    fn add<S: Into<String>>(&mut self, severity: Severity, path: &str, message: S) {
+        self.0.push(Finding {
+            severity,
+            path: path.to_string(),
+            message: message.into(),
+        })
+    }
+
+    fn error<S: Into<String>>(&mut self, path: &str, message: S) {
+        self.add(Severity::Error, path, message)
+    }
+
+    fn warning<S: Into<String>>(&mut self, path: &str, message: S) {
+        self.add(Severity::Warning, path, message)
+    }
+}
+
+impl From<Findings> for Vec<Finding> {
+    fn from(findings: Findings) -> Self {
+        findings.0
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+lazy_static! {
+    static ref DEVICE_TYPE: Regex =
+        Regex::new(r"^urn:[A-Za-z0-9\-\.]+:device:[A-Za-z0-9\-_]{1,64}:\d+$").unwrap();
+    static ref SERVICE_TYPE: Regex =
+        Regex::new(r"^urn:[A-Za-z0-9\-\.]+:service:[A-Za-z0-9\-_]{1,64}:\d+$").unwrap();
+    static ref SERVICE_ID: Regex =
+        Regex::new(r"^urn:[A-Za-z0-9\-\.]+:serviceId:[A-Za-z0-9\-_]{1,64}$").unwrap();
+    static ref UPC: Regex = Regex::new(r"^\d{12}$").unwrap();
+}
+
+const NUMERIC_TYPES: [&str; 14] = [
+    "ui1",
+    "ui2",
+    "ui4",
+    "ui8",
+    "i1",
+    "i2",
+    "i4",
+    "i8",
+    "int",
+    "r4",
+    "r8",
+    "number",
+    "fixed.14.4",
+    "float",
+];
+
+const OTHER_TYPES: [&str; 12] = [
+    "char",
+    "string",
+    "date",
+    "dateTime",
+    "dateTime.tz",
+    "time",
+    "time.tz",
+    "boolean",
+    "bin.base64",
+    "bin.hex",
+    "uri",
+    "uuid",
+];
+
+const V20_TYPES: [&str; 2] = ["ui8", "i8"];
+
+fn check_device(device: &Device, path: &str, udns: &mut HashSet<String>, findings: &mut Findings) {
+    let device_type = device.device_type.to_string();
+    if !DEVICE_TYPE.is_match(&device_type) {
+        findings.error(
+            path,
+            format!(
+                "deviceType '{}' is not a valid device type URN",
+                device_type
+            ),
+        );
+    }
+
+    check_required_text(&device.friendly_name, path, "friendlyName", 64, findings);
+    check_required_text(&device.manufacturer, path, "manufacturer", 64, findings);
+    check_required_text(&device.model_name, path, "modelName", 32, findings);
+    check_optional_text(
+        &device.model_description,
+        path,
+        "modelDescription",
+        128,
+        findings,
+    );
+    check_optional_text(&device.model_number, path, "modelNumber", 32, findings);
+    check_optional_text(&device.serial_number, path, "serialNumber", 64, findings);
+
+    if !device.unique_device_name.starts_with("uuid:") {
+        findings.error(
+            path,
+            format!(
+                "UDN '{}' must begin with 'uuid:'",
+                device.unique_device_name
+            ),
+        );
+    } else if device.unique_device_name.len() == "uuid:".len() {
+        findings.error(path, "UDN must include a UUID following 'uuid:'");
+    }
+    if !udns.insert(device.unique_device_name.clone()) {
+        findings.error(
+            path,
+            format!(
+                "UDN '{}' is used by more than one device",
+                device.unique_device_name
+            ),
+        );
+    }
+
+    if let Some(upc) = &device.upc {
+        if !UPC.is_match(upc) {
+            findings.warning(path, format!("UPC '{}' should be a 12-digit number", upc));
+        }
+    }
+
+    for (index, icon) in device.icon_list.iter().enumerate() {
+        let path = format!("{}/iconList/icon[{}]", path, index);
+        check_icon(icon, &path, findings);
+    }
+
+    let mut service_ids: HashSet<&String> = Default::default();
+    for (index, service) in device.service_list.iter().enumerate() {
+        let path = format!("{}/serviceList/service[{}]", path, index);
+        check_service(service, &path, findings);
+        if !service_ids.insert(&service.service_id) {
+            findings.error(
+                &path,
+                format!(
+                    "serviceId '{}' is not unique within the device",
+                    service.service_id
+                ),
+            );
+        }
+    }
+
+    for (index, embedded) in device.device_list.iter().enumerate() {
+        let path = format!("{}/deviceList/device[{}]", path, index);
+        check_device(embedded, &path, udns, findings);
+    }
+}
+
+fn check_service(service: &Service, path: &str, findings: &mut Findings) {
+    let service_type = service.service_type.to_string();
+    if !SERVICE_TYPE.is_match(&service_type) {
+        findings.error(
+            path,
+            format!(
+                "serviceType '{}' is not a valid service type URN",
+                service_type
+            ),
+        );
+    }
+    if !SERVICE_ID.is_match(&service.service_id) {
+        findings.error(
+            path,
+            format!(
+                "serviceId '{}' is not a valid service identifier URN",
+                service.service_id
+            ),
+        );
+    }
+    for (value, name) in &[
+        (&service.scpd_url, "SCPDURL"),
+        (&service.control_url, "controlURL"),
+        (&service.event_sub_url, "eventSubURL"),
+    ] {
+        if value.is_empty() {
+            findings.error(path, format!("{} is required", name));
+        }
+    }
+}
+
+fn check_icon(icon: &Icon, path: &str, findings: &mut Findings) {
+    if icon.mime_type.trim().is_empty() {
+        findings.error(path, "mimetype is required");
+    }
+    if icon.width == 0 || icon.height == 0 {
+        findings.error(path, "width and height must be greater than zero");
+    }
+    if icon.depth == 0 {
+        findings.error(path, "depth must be greater than zero");
+    }
+    if icon.url.is_empty() {
+        findings.error(path, "url is required");
+    }
+}
+
+fn check_action(
+    action: &Action,
+    service_state_table: &[StateVariable],
+    path: &str,
+    findings: &mut Findings,
+) {
+    check_name(&action.name, path, "action", findings);
+
+    let mut argument_names: HashSet<&String> = Default::default();
+    let mut seen_out = false;
+    for (index, argument) in action.argument_list.iter().enumerate() {
+        let path = format!("{}/argumentList/argument[{}]", path, index);
+        check_name(&argument.name, &path, "argument", findings);
+        if !argument_names.insert(&argument.name) {
+            findings.error(
+                &path,
+                format!("duplicate argument name '{}'", argument.name),
+            );
+        }
+        if !service_state_table
+            .iter()
+            .any(|v| v.name == argument.related_state_variable)
+        {
+            findings.error(
+                &path,
+                format!(
+                    "relatedStateVariable '{}' is not in the service state table",
+                    argument.related_state_variable
+                ),
+            );
+        }
+        match argument.direction {
+            Direction::In => {
+                if seen_out {
+                    findings.error(&path, "in arguments must be listed before out arguments");
+                }
+                if argument.return_value {
+                    findings.error(&path, "retval may only be used on an out argument");
+                }
+            }
+            Direction::Out => {
+                if argument.return_value && seen_out {
+                    findings.error(&path, "retval may only be used on the first out argument");
+                }
+                seen_out = true;
+            }
+        }
+    }
+}
+
+fn check_state_variable(
+    variable: &StateVariable,
+    spec_version: SpecVersion,
+    path: &str,
+    findings: &mut Findings,
+) {
+    let data_type = variable.data_type.as_str();
+    let is_numeric = NUMERIC_TYPES.contains(&data_type);
+    if !NUMERIC_TYPES.contains(&data_type) && !OTHER_TYPES.contains(&data_type) {
+        findings.error(path, format!("unknown dataType '{}'", data_type));
+        return;
+    }
+    if spec_version < SpecVersion::V20 && V20_TYPES.contains(&data_type) {
+        findings.error(
+            path,
+            format!(
+                "dataType '{}' requires UPnP 2.0 (declared {})",
+                data_type, spec_version
+            ),
+        );
+    }
+
+    match &variable.allowed_values {
+        None => {}
+        Some(AllowedValue::List { values }) => {
+            if data_type != "string" {
+                findings.error(
+                    path,
+                    format!(
+                        "allowedValueList may only be used with string, not '{}'",
+                        data_type
+                    ),
+                );
+            }
+            if values.is_empty() {
+                findings.error(path, "allowedValueList must contain at least one value");
+            }
+            if let Some(default_value) = &variable.default_value {
+                if !values.contains(default_value) {
+                    findings.warning(
+                        path,
+                        format!(
+                            "defaultValue '{}' is not in the allowedValueList",
+                            default_value
+                        ),
+                    );
+                }
+            }
+        }
+        Some(AllowedValue::Range {
+            minimum,
+            maximum,
+            step,
+        }) => {
+            if !is_numeric {
+                findings.error(
+                    path,
+                    format!(
+                        "allowedValueRange may only be used with numeric types, not '{}'",
+                        data_type
+                    ),
+                );
+                return;
+            }
+            let minimum = parse_number(minimum, "minimum", path, findings);
+            let maximum = parse_number(maximum, "maximum", path, findings);
+            if let (Some(minimum), Some(maximum)) = (minimum, maximum) {
+                if minimum > maximum {
+                    findings.error(path, "allowedValueRange minimum is greater than maximum");
+                }
+                if let Some(default_value) = &variable.default_value {
+                    if let Ok(value) = default_value.trim().parse::<f64>() {
+                        if value < minimum || value > maximum {
+                            findings.warning(
+                                path,
+                                format!(
+                                    "defaultValue '{}' is outside the allowedValueRange",
+                                    default_value
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+            if let Some(step) = step {
+                if let Some(step) = parse_number(step, "step", path, findings) {
+                    if step <= 0.0 {
+                        findings.error(path, "allowedValueRange step must be greater than zero");
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn parse_number(value: &str, name: &str, path: &str, findings: &mut Findings) -> Option<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            findings.error(
+                path,
+                format!("allowedValueRange {} '{}' is not a number", name, value),
+            );
+            None
+        }
+    }
+}
+
+fn check_name(name: &str, path: &str, what: &str, findings: &mut Findings) {
+    if name.trim().is_empty() {
+        findings.error(path, format!("{} name is required", what));
+    } else if name.len() >= 32 {
+        findings.warning(
+            path,
+            format!("{} name '{}' should be less than 32 characters", what, name),
+        );
+    }
+}
+
+fn check_required_text(value: &str, path: &str, name: &str, limit: usize, findings: &mut Findings) {
+    if value.trim().is_empty() {
+        findings.error(path, format!("{} is required", name));
+    } else if value.chars().count() >= limit {
+        findings.warning(
+            path,
+            format!("{} should be less than {} characters", name, limit),
+        );
+    }
+}
+
+fn check_optional_text(
+    value: &Option<String>,
+    path: &str,
+    name: &str,
+    limit: usize,
+    findings: &mut Findings,
+) {
+    if let Some(value) = value {
+        if value.chars().count() >= limit {
+            findings.warning(
+                path,
+                format!("{} should be less than {} characters", name, limit),
+            );
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::description::device::Service;
+    use crate::description::service::{Action, Argument};
+    use crate::description::TypeID;
+
+    fn service(id: &str) -> Service {
+        Service {
+            service_type: TypeID::new_service("SwitchPower".to_string(), 1),
+            service_id: id.to_string(),
+            scpd_url: "/scpd.xml".parse().unwrap(),
+            control_url: "/control".parse().unwrap(),
+            event_sub_url: "/event".parse().unwrap(),
+        }
+    }
+
+    fn device_root() -> DeviceRoot {
+        DeviceRoot {
+            spec_version: SpecVersion::V11,
+            config_id: Some(1),
+            namespaces: vec![],
+            url_base: None,
+            device: Device {
+                device_type: TypeID::new_device("BinaryLight".to_string(), 1),
+                friendly_name: "Kitchen Light".to_string(),
+                manufacturer: "Example".to_string(),
+                manufacturer_url: None,
+                model_description: None,
+                model_name: "Light".to_string(),
+                model_number: None,
+                model_url: None,
+                serial_number: None,
+                unique_device_name: "uuid:2fac1234-31f8-11b4-a222-08002b34c003".to_string(),
+                upc: None,
+                icon_list: vec![],
+                service_list: vec![service("urn:upnp-org:serviceId:SwitchPower")],
+                device_list: vec![],
+                presentation_url: None,
+                extensions: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_valid_device() {
+        let findings = validate_device_root(&device_root());
+        assert!(findings.is_empty(), "{:#?}", findings);
+    }
+
+    #[test]
+    fn test_invalid_device() {
+        let mut root = device_root();
+        root.url_base = Some("http://10.0.0.1/".parse().unwrap());
+        root.device.friendly_name = "x".repeat(70);
+        root.device.unique_device_name = "2fac1234-31f8-11b4-a222-08002b34c003".to_string();
+        root.device
+            .service_list
+            .push(service("urn:upnp-org:serviceId:SwitchPower"));
+        root.device.service_list.push(service("SwitchPower"));
+
+        let findings = validate_device_root(&root);
+        assert!(has_errors(&findings));
+        let messages: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+        assert_eq!(messages.len(), 5, "{:#?}", messages);
+        assert!(messages[0].starts_with("warning: root: URLBase"));
+        assert!(messages[1].starts_with("warning: root/device: friendlyName"));
+        assert!(messages[2].starts_with("error: root/device: UDN"));
+        assert!(messages[3].starts_with("error: root/device/serviceList/service[1]: serviceId"));
+        assert!(messages[4].starts_with("error: root/device/serviceList/service[2]: serviceId"));
+    }
+
+    #[test]
+    fn test_invalid_spcd() {
+        let spcd = Spcd {
+            spec_version: SpecVersion::V10,
+            config_id: None,
+            action_list: vec![Action {
+                name: "GetStatus".to_string(),
+                argument_list: vec![
+                    Argument {
+                        name: "ResultStatus".to_string(),
+                        direction: Direction::Out,
+                        return_value: true,
+                        related_state_variable: "Status".to_string(),
+                    },
+                    Argument {
+                        name: "Other".to_string(),
+                        direction: Direction::Out,
+                        return_value: true,
+                        related_state_variable: "Missing".to_string(),
+                    },
+                ],
+            }],
+            service_state_table: vec![
+                StateVariable {
+                    send_events: true,
+                    multicast: false,
+                    name: "Status".to_string(),
+                    data_type: "boolean".to_string(),
+                    extended_data_type: None,
+                    default_value: None,
+                    allowed_values: None,
+                },
+                StateVariable {
+                    send_events: false,
+                    multicast: false,
+                    name: "Level".to_string(),
+                    data_type: "ui8".to_string(),
+                    extended_data_type: None,
+                    default_value: None,
+                    allowed_values: Some(AllowedValue::Range {
+                        minimum: "100".to_string(),
+                        maximum: "0".to_string(),
+                        step: None,
+                    }),
+                },
+            ],
+        };
+
+        let findings = validate_spcd(&spcd);
+        let messages: Vec<String> = findings.iter().map(|f| f.to_string()).collect();
+        assert_eq!(messages.len(), 4, "{:#?}", messages);
+        assert!(messages[0].contains("relatedStateVariable 'Missing'"));
+        assert!(messages[1].contains("retval may only be used on the first out argument"));
+        assert!(messages[2].contains("requires UPnP 2.0"));
+        assert!(messages[3].contains("minimum is greater than maximum"));
+    }
+}
@@ -0,0 +1,499 @@
+/*!
+This module protects a device's search responder from being used to flood the network, or to
+reflect traffic at a third party.
+
+# Specification
+
+A search response is larger than the search that caused it, and a device returns one for each of
+its advertisements that matches, so a single spoofed `M-SEARCH` can cause many times its size in
+traffic to be sent to the forged source address. SSDP is only meant for the local network; a
+multicast search has a TTL of 4 at most and a device is not expected to answer control points
+that are not on one of its own networks.
+
+A `SearchGuard` checks each search before it is answered, dropping it if:
+
+1. its source address is not on one of the allowed networks, by default the networks of the
+   host's own interfaces,
+2. the source has sent more than its share of searches recently,
+3. answering it would exceed the budget of responses the device sends across all sources, or
+4. the request is invalid; a multicast search must have an `MX` value of at least 1, and any
+   search must have a `MAN` value of `"ssdp:discover"`.
+
+Each dropped search is counted, by reason, in the guard's `GuardCounters`.
+
+# Example
+
+```rust
+use upnp_rs::discovery::guard::{GuardOptions, SearchGuard};
+
+let guard = SearchGuard::new(GuardOptions {
+    allowed_networks: vec!["192.168.1.0/24".parse().unwrap(), "10.0.0.0/8".parse().unwrap()],
+    ..Default::default()
+});
+assert_eq!(guard.counters().dropped(), 0);
+```
+*/
+
+use crate::discovery::search::SearchRequest;
+use ipnetwork::IpNetwork;
+use pnet::datalink;
+use std::collections::HashMap;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The limits a `SearchGuard` enforces.
+///
+#[derive(Clone, Debug)]
+pub struct GuardOptions {
+    /// The networks searches are answered from; if empty, the networks of the host's own
+    /// interfaces are used, and refreshed as they change. Default: empty.
+    pub allowed_networks: Vec<IpNetwork>,
+    /// The number of searches answered from any one source address in each `source_interval`.
+    /// Default: 20.
+    pub max_searches_per_source: u32,
+    /// Default: 10 seconds.
+    pub source_interval: Duration,
+    /// The number of responses sent, to all sources, in each `response_interval`. Default: 200.
+    pub max_responses: u32,
+    /// Default: 1 second.
+    pub response_interval: Duration,
+}
+
+///
+/// The reason a search was dropped rather than answered.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    /// The source address is not on an allowed network.
+    NotAllowed,
+    /// The source address has sent too many searches.
+    RateLimited,
+    /// Answering would exceed the response budget.
+    OverBudget,
+    /// The packet was not a valid search request.
+    Invalid,
+}
+
+///
+/// The number of searches answered and dropped, by reason, since the guard was created.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GuardCounters {
+    pub accepted: u64,
+    pub not_allowed: u64,
+    pub rate_limited: u64,
+    pub over_budget: u64,
+    pub invalid: u64,
+}
+
+///
+/// Decides whether each search received should be answered, see the module documentation.
+///
+#[derive(Debug)]
+pub struct SearchGuard {
+    options: GuardOptions,
+    local_networks: Vec<IpNetwork>,
+    local_networks_at: Instant,
+    sources: HashMap<IpAddr, Window>,
+    responses: Window,
+    counters: GuardCounters,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Default for GuardOptions {
+    fn default() -> Self {
+        Self {
+            allowed_networks: Default::default(),
+            max_searches_per_source: 20,
+            source_interval: Duration::from_secs(10),
+            max_responses: 200,
+            response_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(
+            f,
+            "{}",
+            match self {
+                Rejection::NotAllowed => "source not on an allowed network",
+                Rejection::RateLimited => "source rate limit exceeded",
+                Rejection::OverBudget => "response budget exceeded",
+                Rejection::Invalid => "invalid request",
+            }
+        )
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl GuardCounters {
+    ///
+    /// The total number of searches, and invalid packets, dropped.
+    ///
+    pub fn dropped(&self) -> u64 {
+        self.not_allowed + self.rate_limited + self.over_budget + self.invalid
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl SearchGuard {
+    ///
+    /// Create a new guard enforcing the limits in `options`.
+    ///
+    pub fn new(options: GuardOptions) -> Self {
+        let now = Instant::now();
+        Self {
+            options,
+            local_networks: local_networks(),
+            local_networks_at: now,
+            sources: Default::default(),
+            responses: Window::new(now),
+            counters: Default::default(),
+        }
+    }
+
+    ///
+    /// The limits this guard enforces.
+    ///
+    pub fn options(&self) -> &GuardOptions {
+        &self.options
+    }
+
+    ///
+    /// The number of searches answered and dropped so far.
+    ///
+    pub fn counters(&self) -> GuardCounters {
+        self.counters
+    }
+
+    ///
+    /// Decide whether to answer `request`, received from `from`, with `responses` responses;
+    /// if it is answered the responses are taken from the budget.
+    ///
+    pub fn check(
+        &mut self,
+        from: &SocketAddr,
+        request: &SearchRequest,
+        responses: usize,
+    ) -> Result<(), Rejection> {
+        self.check_at(Instant::now(), from, request, responses)
+    }
+
+    ///
+    /// Record a packet, received from `from`, that was not a valid request.
+    ///
+    pub fn invalid(&mut self, from: &SocketAddr) -> Rejection {
+        warn!("invalid - dropping invalid packet from {}", from);
+        self.counters.invalid += 1;
+        Rejection::Invalid
+    }
+
+    fn check_at(
+        &mut self,
+        now: Instant,
+        from: &SocketAddr,
+        request: &SearchRequest,
+        responses: usize,
+    ) -> Result<(), Rejection> {
+        let result = self.rejection_at(now, from, request, responses);
+        match result {
+            Ok(()) => self.counters.accepted += 1,
+            Err(rejection) => {
+                info!("check - dropping search from {}: {}", from, rejection);
+                match rejection {
+                    Rejection::NotAllowed => self.counters.not_allowed += 1,
+                    Rejection::RateLimited => self.counters.rate_limited += 1,
+                    Rejection::OverBudget => self.counters.over_budget += 1,
+                    Rejection::Invalid => self.counters.invalid += 1,
+                }
+            }
+        }
+        result
+    }
+
+    fn rejection_at(
+        &mut self,
+        now: Instant,
+        from: &SocketAddr,
+        request: &SearchRequest,
+        responses: usize,
+    ) -> Result<(), Rejection> {
+        if is_multicast_search(request) && request.max_wait_time.unwrap_or_default() < 1 {
+            return Err(Rejection::Invalid);
+        }
+        if !self.is_allowed_at(now, &from.ip()) {
+            return Err(Rejection::NotAllowed);
+        }
+        let source_interval = self.options.source_interval;
+        if self.sources.len() >= MAX_SOURCES {
+            self.sources
+                .retain(|_, window| !window.has_ended(now, source_interval));
+        }
+        if !self
+            .sources
+            .entry(from.ip())
+            .or_insert_with(|| Window::new(now))
+            .take(
+                now,
+                source_interval,
+                self.options.max_searches_per_source,
+                1,
+            )
+        {
+            return Err(Rejection::RateLimited);
+        }
+        if !self.responses.take(
+            now,
+            self.options.response_interval,
+            self.options.max_responses,
+            responses as u32,
+        ) {
+            // The search is not answered, so it does not count against its source.
+            if let Some(window) = self.sources.get_mut(&from.ip()) {
+                window.give_back(1);
+            }
+            return Err(Rejection::OverBudget);
+        }
+        Ok(())
+    }
+
+    fn is_allowed_at(&mut self, now: Instant, address: &IpAddr) -> bool {
+        if !self.options.allowed_networks.is_empty() {
+            return self
+                .options
+                .allowed_networks
+                .iter()
+                .any(|network| network.contains(*address));
+        }
+        // Interfaces may have changed address since the networks were last read.
+        if now.duration_since(self.local_networks_at) >= LOCAL_NETWORKS_REFRESH {
+            self.local_networks = local_networks();
+            self.local_networks_at = now;
+        }
+        self.local_networks
+            .iter()
+            .any(|network| network.contains(*address))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Window {
+    fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            count: 0,
+        }
+    }
+
+    fn has_ended(&self, now: Instant, interval: Duration) -> bool {
+        now.duration_since(self.started) >= interval
+    }
+
+    ///
+    /// Take `amount` from the `limit` allowed in each `interval`, returning `false`, and taking
+    /// nothing, if there is not enough left.
+    ///
+    fn take(&mut self, now: Instant, interval: Duration, limit: u32, amount: u32) -> bool {
+        if self.has_ended(now, interval) {
+            *self = Window::new(now);
+        }
+        if self.count.saturating_add(amount) > limit {
+            false
+        } else {
+            self.count += amount;
+            true
+        }
+    }
+
+    ///
+    /// Return `amount`, taken earlier in this interval, to the limit.
+    ///
+    fn give_back(&mut self, amount: u32) {
+        self.count = self.count.saturating_sub(amount);
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A count of events in a fixed interval starting at `started`.
+///
+#[derive(Clone, Debug)]
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+const LOCAL_NETWORKS_REFRESH: Duration = Duration::from_secs(30);
+
+/// Beyond this many sources, those whose interval has ended are forgotten.
+const MAX_SOURCES: usize = 1024;
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn local_networks() -> Vec<IpNetwork> {
+    datalink::interfaces()
+        .iter()
+        .flat_map(|interface| interface.ips.iter())
+        .filter_map(|network| IpNetwork::new(network.ip(), network.prefix()).ok())
+        .collect()
+}
+
+pub(crate) fn is_multicast_search(request: &SearchRequest) -> bool {
+    request
+        .host
+        .parse::<SocketAddr>()
+        .map(|host| host.ip().is_multicast())
+        .unwrap_or(true)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::search::SearchTarget;
+    use pretty_assertions::assert_eq;
+
+    fn search(host: &str, max_wait_time: Option<u8>) -> SearchRequest {
+        SearchRequest {
+            search_target: SearchTarget::All,
+            host: host.to_string(),
+            max_wait_time,
+            user_agent: None,
+            control_point: None,
+            other_headers: Default::default(),
+        }
+    }
+
+    fn lan_guard() -> SearchGuard {
+        SearchGuard::new(GuardOptions {
+            allowed_networks: vec!["192.168.1.0/24".parse().unwrap()],
+            max_searches_per_source: 2,
+            max_responses: 10,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_allowed_networks() {
+        let mut guard = lan_guard();
+        let request = search("239.255.255.250:1900", Some(2));
+        let now = Instant::now();
+        assert_eq!(
+            guard.check_at(now, &"192.168.1.12:50000".parse().unwrap(), &request, 1),
+            Ok(())
+        );
+        assert_eq!(
+            guard.check_at(now, &"203.0.113.9:50000".parse().unwrap(), &request, 1),
+            Err(Rejection::NotAllowed)
+        );
+
+        let mut guard = SearchGuard::new(GuardOptions::default());
+        assert_eq!(
+            guard.check_at(now, &"127.0.0.1:50000".parse().unwrap(), &request, 1),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        let mut guard = lan_guard();
+        let request = search("239.255.255.250:1900", Some(2));
+        let first: SocketAddr = "192.168.1.12:50000".parse().unwrap();
+        let second: SocketAddr = "192.168.1.13:50000".parse().unwrap();
+        let now = Instant::now();
+        assert_eq!(guard.check_at(now, &first, &request, 4), Ok(()));
+        assert_eq!(guard.check_at(now, &first, &request, 4), Ok(()));
+        assert_eq!(
+            guard.check_at(now, &first, &request, 1),
+            Err(Rejection::RateLimited)
+        );
+        assert_eq!(
+            guard.check_at(now, &second, &request, 4),
+            Err(Rejection::OverBudget)
+        );
+        assert_eq!(guard.check_at(now, &second, &request, 2), Ok(()));
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(guard.check_at(later, &first, &request, 4), Ok(()));
+
+        assert_eq!(
+            guard.counters(),
+            GuardCounters {
+                accepted: 4,
+                not_allowed: 0,
+                rate_limited: 1,
+                over_budget: 1,
+                invalid: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_over_budget_not_rate_limited() {
+        let mut guard = lan_guard();
+        let request = search("239.255.255.250:1900", Some(2));
+        let from: SocketAddr = "192.168.1.12:50000".parse().unwrap();
+        let now = Instant::now();
+        assert_eq!(guard.check_at(now, &from, &request, 10), Ok(()));
+        assert_eq!(
+            guard.check_at(now, &from, &request, 1),
+            Err(Rejection::OverBudget)
+        );
+        assert_eq!(
+            guard.check_at(now, &from, &request, 1),
+            Err(Rejection::OverBudget)
+        );
+
+        // A new response interval, but the same source interval.
+        let later = now + Duration::from_secs(1);
+        assert_eq!(guard.check_at(later, &from, &request, 1), Ok(()));
+        assert_eq!(guard.counters().rate_limited, 0);
+    }
+
+    #[test]
+    fn test_invalid_max_wait() {
+        let mut guard = lan_guard();
+        let from: SocketAddr = "192.168.1.12:50000".parse().unwrap();
+        let now = Instant::now();
+        assert_eq!(
+            guard.check_at(now, &from, &search("239.255.255.250:1900", None), 1),
+            Err(Rejection::Invalid)
+        );
+        assert_eq!(
+            guard.check_at(now, &from, &search("239.255.255.250:1900", Some(0)), 1),
+            Err(Rejection::Invalid)
+        );
+        assert_eq!(
+            guard.check_at(now, &from, &search("192.168.1.10:1900", None), 1),
+            Ok(())
+        );
+        let _ = guard.invalid(&from);
+        assert_eq!(guard.counters().invalid, 3);
+        assert_eq!(guard.counters().dropped(), 3);
+    }
+}
@@ -0,0 +1,519 @@
+/*!
+This module manages the two values a hosted device uses to tell control points that something
+about it has changed: `BOOTID.UPNP.ORG` and `CONFIGID.UPNP.ORG`.
+
+# Specification
+
+The `BOOTID.UPNP.ORG` value MUST be increased each time a device (re)joins the network, and when a
+multi-homed device adds an interface, or an interface changes address; it MUST NOT change in any
+other case, so that a control point can rely on a change to mean that the device has lost any
+state it held, such as event subscriptions. It is a non-negative 31-bit integer. A device changing
+its boot ID while running first sends `ssdp:update`, with the current value in `BOOTID.UPNP.ORG`
+and the new value in `NEXTBOOTID.UPNP.ORG`, for each advertisement and then re-advertises with the
+new value.
+
+The `CONFIGID.UPNP.ORG` value identifies the current set of device and service descriptions and
+MUST change whenever any of them change; it is a non-negative integer less than 2^24, the higher
+values being reserved.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::description::device::DeviceRoot;
+use upnp_rs::description::service::Spcd;
+use upnp_rs::discovery::identity::{DeviceIdentity, FileBootIdStore};
+use std::str::FromStr;
+
+# fn descriptions() -> (DeviceRoot, Vec<Spcd>) { unimplemented!() }
+let (mut root, mut services) = descriptions();
+let mut identity = DeviceIdentity::start(Box::new(FileBootIdStore::new("/var/lib/light/boot_id")))
+    .unwrap();
+let _ = identity.assign_config_id(&mut root, &mut services).unwrap();
+let location = URL::from_str("http://192.168.1.10:49152/description.xml").unwrap();
+let advertisements = identity.advertisements_for(&root, &location).unwrap();
+```
+*/
+
+use crate::common::httpu::Transport;
+use crate::common::uri::URL;
+use crate::description::device::{to_writer as device_to_writer, DeviceRoot};
+use crate::description::service::{to_writer as service_to_writer, Spcd};
+use crate::discovery::notify::{
+    advertisements_for, device_available_using, device_update_using, Device, Options,
+};
+use crate::error::Error;
+use crate::SpecVersion;
+use std::fmt::Debug;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Persistent storage for a device's boot ID, so that the value increases across restarts.
+///
+pub trait BootIdStore: Debug + Send {
+    ///
+    /// Return the last boot ID saved, or `None` if there is none.
+    ///
+    fn load(&self) -> Result<Option<u32>, Error>;
+
+    ///
+    /// Save `boot_id`, replacing any earlier value.
+    ///
+    fn save(&self, boot_id: u32) -> Result<(), Error>;
+}
+
+///
+/// Stores the boot ID, as a decimal number, in a single file; this is the default store.
+///
+#[derive(Clone, Debug)]
+pub struct FileBootIdStore {
+    path: PathBuf,
+}
+
+///
+/// Stores the boot ID in memory only, for devices with no persistent storage and for testing.
+///
+#[derive(Debug, Default)]
+pub struct MemoryBootIdStore {
+    boot_id: Mutex<Option<u32>>,
+}
+
+///
+/// The boot and configuration IDs of a hosted device, see the
+/// [module documentation](index.html).
+///
+#[derive(Debug)]
+pub struct DeviceIdentity {
+    store: Box<dyn BootIdStore>,
+    boot_id: u32,
+    config_id: u32,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the boot ID that follows `boot_id`, wrapping at the largest 31-bit value.
+///
+pub fn next_boot_id(boot_id: u32) -> u32 {
+    if boot_id >= MAX_BOOT_ID {
+        0
+    } else {
+        boot_id + 1
+    }
+}
+
+///
+/// Return a configuration ID computed from the device description and all of its service
+/// descriptions, so that it changes exactly when one of them does. Any `configId` already present
+/// is ignored; the services should be provided in the same order each time.
+///
+pub fn config_id_for(root: &DeviceRoot, services: &[Spcd]) -> Result<u32, Error> {
+    let mut root = root.clone();
+    root.config_id = None;
+    let mut hash = FNV_OFFSET_BASIS;
+    hash = fnv_1a(hash, &device_to_writer(&root, Vec::new())?);
+    for service in services {
+        let mut service = service.clone();
+        service.config_id = None;
+        hash = fnv_1a(hash, &service_to_writer(&service, Vec::new())?);
+    }
+    Ok(hash & MAX_CONFIG_ID)
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl FileBootIdStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl BootIdStore for FileBootIdStore {
+    fn load(&self) -> Result<Option<u32>, Error> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => match content.trim().parse::<u32>() {
+                Ok(boot_id) => Ok(Some(boot_id)),
+                Err(_) => {
+                    // A damaged file is not fatal, the device starts with a new boot ID.
+                    error!(
+                        "load - ignoring invalid boot ID '{}' in {:?}",
+                        content.trim(),
+                        self.path
+                    );
+                    Ok(None)
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => {
+                error!("load - could not read {:?}: {}", self.path, e);
+                Err(e.into())
+            }
+        }
+    }
+
+    fn save(&self, boot_id: u32) -> Result<(), Error> {
+        fs::write(&self.path, boot_id.to_string()).map_err(|e| {
+            error!("save - could not write {:?}: {}", self.path, e);
+            e.into()
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl MemoryBootIdStore {
+    pub fn new(boot_id: u32) -> Self {
+        Self {
+            boot_id: Mutex::new(Some(boot_id)),
+        }
+    }
+}
+
+impl BootIdStore for MemoryBootIdStore {
+    fn load(&self) -> Result<Option<u32>, Error> {
+        Ok(*self.boot_id.lock().unwrap())
+    }
+
+    fn save(&self, boot_id: u32) -> Result<(), Error> {
+        *self.boot_id.lock().unwrap() = Some(boot_id);
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl DeviceIdentity {
+    ///
+    /// Start the device, choosing a boot ID greater than the one saved in `store` and saving it.
+    /// If there is no saved value the boot ID is taken from the current time, which is also
+    /// likely to be greater than any value used before.
+    ///
+    pub fn start(store: Box<dyn BootIdStore>) -> Result<Self, Error> {
+        let boot_id = match store.load()? {
+            Some(boot_id) => next_boot_id(boot_id),
+            None => initial_boot_id(),
+        };
+        store.save(boot_id)?;
+        info!("start - device starting with boot ID {}", boot_id);
+        Ok(Self {
+            store,
+            boot_id,
+            config_id: Default::default(),
+        })
+    }
+
+    pub fn boot_id(&self) -> u32 {
+        self.boot_id
+    }
+
+    pub fn config_id(&self) -> u32 {
+        self.config_id
+    }
+
+    ///
+    /// Compute the configuration ID, using `config_id_for`, and set it in the device description
+    /// and in each service description that is UPnP 1.1 or later.
+    ///
+    pub fn assign_config_id(
+        &mut self,
+        root: &mut DeviceRoot,
+        services: &mut [Spcd],
+    ) -> Result<u32, Error> {
+        self.config_id = config_id_for(root, services)?;
+        if root.spec_version >= SpecVersion::V11 {
+            root.config_id = Some(self.config_id);
+        }
+        for service in services.iter_mut() {
+            if service.spec_version >= SpecVersion::V11 {
+                service.config_id = Some(self.config_id);
+            }
+        }
+        Ok(self.config_id)
+    }
+
+    ///
+    /// Return the advertisements for `root`, as `notify::advertisements_for`, with the current
+    /// boot ID.
+    ///
+    pub fn advertisements_for(
+        &self,
+        root: &DeviceRoot,
+        location: &URL,
+    ) -> Result<Vec<Device>, Error> {
+        advertisements_for(root, location, self.boot_id)
+    }
+
+    ///
+    /// Send `ssdp:update` for each advertisement, announcing the next boot ID, without changing
+    /// the current one. A multi-homed device sends these on each of its unaffected interfaces
+    /// before calling `commit_next_boot_id`.
+    ///
+    pub fn announce_next_boot_id_using(
+        &self,
+        advertisements: &[Device],
+        options: &Options,
+        transport: &dyn Transport,
+    ) -> Result<(), Error> {
+        let next_boot_id = next_boot_id(self.boot_id);
+        for advertisement in advertisements {
+            device_update_using(advertisement, next_boot_id, options.clone(), transport)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Move to, and save, the next boot ID and set it in each of `advertisements`; these should
+    /// then be re-advertised on every interface.
+    ///
+    pub fn commit_next_boot_id(&mut self, advertisements: &mut [Device]) -> Result<u32, Error> {
+        let boot_id = next_boot_id(self.boot_id);
+        self.store.save(boot_id)?;
+        self.boot_id = boot_id;
+        for advertisement in advertisements.iter_mut() {
+            advertisement.boot_id = boot_id;
+        }
+        info!("commit_next_boot_id - device now using boot ID {}", boot_id);
+        Ok(boot_id)
+    }
+
+    ///
+    /// Perform the complete sequence required when an interface is added or changes address, on a
+    /// device with a single interface: send `ssdp:update` for each advertisement, move to the next
+    /// boot ID, and send `ssdp:alive` for each advertisement with the new value. UPnP 1.0 has no
+    /// boot ID, and so only re-advertises.
+    ///
+    pub fn interface_changed_using(
+        &mut self,
+        advertisements: &mut [Device],
+        options: &Options,
+        transport: &dyn Transport,
+    ) -> Result<(), Error> {
+        self.change_boot_id_using(advertisements, None, options, transport)
+    }
+
+    ///
+    /// Perform the same sequence as `interface_changed_using` when the device's address has
+    /// changed, but re-advertise with the new `location`; the `ssdp:update` messages still carry
+    /// the previous location. The `common::monitor` module reports such changes.
+    ///
+    pub fn relocate_using(
+        &mut self,
+        advertisements: &mut [Device],
+        location: &URL,
+        options: &Options,
+        transport: &dyn Transport,
+    ) -> Result<(), Error> {
+        self.change_boot_id_using(advertisements, Some(location), options, transport)
+    }
+
+    fn change_boot_id_using(
+        &mut self,
+        advertisements: &mut [Device],
+        location: Option<&URL>,
+        options: &Options,
+        transport: &dyn Transport,
+    ) -> Result<(), Error> {
+        if options.spec_version >= SpecVersion::V11 {
+            self.announce_next_boot_id_using(advertisements, options, transport)?;
+        }
+        let _ = self.commit_next_boot_id(advertisements)?;
+        for advertisement in advertisements.iter_mut() {
+            if let Some(location) = location {
+                advertisement.location = location.clone();
+            }
+            device_available_using(advertisement, options.clone(), transport)?;
+        }
+        Ok(())
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+const MAX_BOOT_ID: u32 = (1 << 31) - 1;
+
+const MAX_CONFIG_ID: u32 = (1 << 24) - 1;
+
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+
+const FNV_PRIME: u32 = 0x0100_0193;
+
+fn initial_boot_id() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() as u32 & MAX_BOOT_ID)
+        .unwrap_or_default()
+}
+
+///
+/// The 32-bit FNV-1a hash; unlike the standard library's hashers its values are stable across
+/// releases, and so may be compared with those computed by an earlier run.
+///
+fn fnv_1a(hash: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::httpu::MemoryTransport;
+    use crate::description::builder::{
+        ActionBuilder, DeviceBuilder, DeviceRootBuilder, ServiceBuilder, SpcdBuilder,
+        StateVariableBuilder,
+    };
+    use crate::description::TypeID;
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
+
+    fn descriptions() -> (DeviceRoot, Vec<Spcd>) {
+        let root = DeviceRootBuilder::new(
+            DeviceBuilder::new(TypeID::new_device("BinaryLight".to_string(), 1), "Light")
+                .manufacturer("Example")
+                .model_name("Light")
+                .unique_device_name("uuid:2f402f80-da50-11e1-9b23-00178829d301")
+                .service(
+                    ServiceBuilder::new(TypeID::new_service("SwitchPower".to_string(), 1))
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .spec_version(SpecVersion::V11)
+        .build()
+        .unwrap();
+        let target = StateVariableBuilder::new("Target", "boolean")
+            .build()
+            .unwrap();
+        let service = SpcdBuilder::new()
+            .spec_version(SpecVersion::V11)
+            .action(
+                ActionBuilder::new("SetTarget", std::slice::from_ref(&target))
+                    .in_argument("newTargetValue", "Target")
+                    .build()
+                    .unwrap(),
+            )
+            .state_variable(target)
+            .build()
+            .unwrap();
+        (root, vec![service])
+    }
+
+    #[test]
+    fn test_boot_id_persists() {
+        let identity = DeviceIdentity::start(Box::new(MemoryBootIdStore::new(41))).unwrap();
+        assert_eq!(identity.boot_id(), 42);
+        assert_eq!(identity.store.load().unwrap(), Some(42));
+
+        let identity =
+            DeviceIdentity::start(Box::new(MemoryBootIdStore::new(MAX_BOOT_ID))).unwrap();
+        assert_eq!(identity.boot_id(), 0);
+
+        let identity = DeviceIdentity::start(Box::new(MemoryBootIdStore::default())).unwrap();
+        assert!(identity.boot_id() > 0);
+    }
+
+    #[test]
+    fn test_file_boot_id_store() {
+        let path = std::env::temp_dir().join(format!("upnp-rs-boot-id-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let store = FileBootIdStore::new(&path);
+        assert_eq!(store.load().unwrap(), None);
+        store.save(7).unwrap();
+        assert_eq!(store.load().unwrap(), Some(7));
+        let identity = DeviceIdentity::start(Box::new(store)).unwrap();
+        assert_eq!(identity.boot_id(), 8);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "8");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_config_id_follows_descriptions() {
+        let (mut root, mut services) = descriptions();
+        let mut identity = DeviceIdentity::start(Box::new(MemoryBootIdStore::new(1))).unwrap();
+        let config_id = identity.assign_config_id(&mut root, &mut services).unwrap();
+        assert!(config_id <= MAX_CONFIG_ID);
+        assert_eq!(root.config_id, Some(config_id));
+        assert_eq!(services[0].config_id, Some(config_id));
+        assert_eq!(config_id_for(&root, &services).unwrap(), config_id);
+
+        services[0].service_state_table[0].send_events = false;
+        assert_ne!(config_id_for(&root, &services).unwrap(), config_id);
+    }
+
+    #[test]
+    fn test_interface_changed() {
+        let (root, _) = descriptions();
+        let location = URL::from_str("http://192.168.1.10:49152/description.xml").unwrap();
+        let mut identity = DeviceIdentity::start(Box::new(MemoryBootIdStore::new(4))).unwrap();
+        let mut advertisements = identity.advertisements_for(&root, &location).unwrap();
+        assert!(advertisements.iter().all(|a| a.boot_id == 5));
+
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        identity
+            .interface_changed_using(
+                &mut advertisements,
+                &Options::default_for(SpecVersion::V11),
+                &transport,
+            )
+            .unwrap();
+        assert_eq!(identity.boot_id(), 6);
+        assert!(advertisements.iter().all(|a| a.boot_id == 6));
+
+        let sent: Vec<String> = transport
+            .sent()
+            .iter()
+            .map(|(_, message)| String::from_utf8(message.clone()).unwrap())
+            .collect();
+        let count = advertisements.len();
+        assert_eq!(sent.len(), count * 2);
+        assert!(sent[..count]
+            .iter()
+            .all(|message| message.contains("NTS:ssdp:update")
+                && message.contains("BOOTID.UPNP.ORG:5")
+                && message.contains("NEXTBOOTID.UPNP.ORG:6")));
+        assert!(sent[count..]
+            .iter()
+            .all(|message| message.contains("NTS:ssdp:alive")
+                && message.contains("BOOTID.UPNP.ORG:6")));
+
+        let moved = URL::from_str("http://10.0.0.7:49152/description.xml").unwrap();
+        identity
+            .relocate_using(
+                &mut advertisements,
+                &moved,
+                &Options::default_for(SpecVersion::V11),
+                &transport,
+            )
+            .unwrap();
+        assert_eq!(identity.boot_id(), 7);
+        assert!(advertisements.iter().all(|a| a.location == moved));
+        let sent = transport.sent();
+        let (_, last) = sent.last().unwrap();
+        assert!(String::from_utf8(last.clone())
+            .unwrap()
+            .contains("LOCATION:http://10.0.0.7:49152/description.xml"));
+    }
+}
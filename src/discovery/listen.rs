@@ -0,0 +1,371 @@
+/*!
+This module provides a listener that joins the SSDP multicast group and returns each device
+notification (`NOTIFY`) and control point search (`M-SEARCH`) sent to the group.
+
+# Example
+
+```rust,no_run
+use upnp_rs::discovery::listen::{Listener, Message, Options};
+use std::time::Duration;
+
+let listener = Listener::new(&Options::default()).unwrap();
+loop {
+    if let Some((from, message)) = listener.next_message(Duration::from_secs(5)).unwrap() {
+        match message {
+            Message::Notification(notification) => {
+                println!("{} {} {}", from, notification.sub_type, notification.service_name)
+            }
+            Message::Search(search) => println!("{} M-SEARCH {}", from, search.search_target),
+        }
+    }
+}
+```
+*/
+
+use crate::common::httpu::{
+    create_listener_socket, Options as MulticastOptions, Request, Transport, UdpTransport,
+    DEFAULT_BUFFER_SIZE,
+};
+use crate::common::interface::IP;
+use crate::discovery::multicast_address;
+use crate::discovery::notify::Notification;
+use crate::discovery::search::SearchRequest;
+use crate::error::{invalid_field_value, invalid_value_for_type, Error};
+use crate::syntax::{
+    HTTP_METHOD_NOTIFY, HTTP_METHOD_SEARCH, MULTICAST_ADDRESS, MULTICAST_ADDRESS_V6_LINK_LOCAL,
+};
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tracing::{error, info, trace, warn};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// This type encapsulates the values used to join the multicast group.
+///
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Options {
+    /// A specific network interface to join the group on; if not specified the system chooses
+    /// a default interface. Default: `None`.
+    pub network_interface: Option<String>,
+    /// Denotes whether to listen on the IPv4 or IPv6 group; this is only used to select the
+    /// default address. Default: `None`, which is IPv4.
+    pub network_version: Option<IP>,
+    /// Multicast address, default: 239.255.255.250, or FF02::C for IPv6
+    pub address: Option<String>,
+    /// Multicast port, default: 1900
+    pub port: Option<u16>,
+    /// The size of the buffer each message is read into, default: 8192 bytes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub buffer_size: Option<usize>,
+}
+
+///
+/// A message received on the multicast group.
+///
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+#[allow(clippy::large_enum_variant)]
+pub enum Message {
+    /// A device notification, `ssdp:alive`, `ssdp:byebye`, or `ssdp:update`.
+    Notification(Notification),
+    /// A search from a control point.
+    Search(SearchRequest),
+}
+
+///
+/// Receives messages sent to the SSDP multicast group.
+///
+#[derive(Debug)]
+pub struct Listener {
+    transport: Box<dyn Transport>,
+    options: Option<Options>,
+    buffer_size: usize,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Options {
+    ///
+    /// The multicast group address, and port, to listen on.
+    ///
+    pub fn group_address(&self) -> Result<SocketAddr, Error> {
+        let address = self
+            .address
+            .as_deref()
+            .unwrap_or(match self.network_version {
+                Some(IP::V6) => MULTICAST_ADDRESS_V6_LINK_LOCAL,
+                _ => MULTICAST_ADDRESS,
+            });
+        multicast_address(Some(address), self.port)
+    }
+}
+
+impl From<&Options> for MulticastOptions {
+    fn from(options: &Options) -> Self {
+        MulticastOptions {
+            network_interface: options.network_interface.clone(),
+            network_version: options.network_version.clone(),
+            buffer_size: options.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE),
+            ..Default::default()
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl TryFrom<&Request> for Message {
+    type Error = Error;
+
+    fn try_from(request: &Request) -> Result<Self, Self::Error> {
+        match request.method().as_str() {
+            HTTP_METHOD_NOTIFY => Ok(Message::Notification(Notification::try_from(request)?)),
+            HTTP_METHOD_SEARCH => Ok(Message::Search(SearchRequest::try_from(request)?)),
+            method => {
+                error!("try_from - unexpected request method '{}'", method);
+                invalid_value_for_type("Message", method).into()
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Listener {
+    ///
+    /// Create a new listener, joining the multicast group described by `options`.
+    ///
+    pub fn new(options: &Options) -> Result<Self, Error> {
+        info!("new - options: {:?}", options);
+        if options.buffer_size == Some(0) {
+            error!("new - buffer_size must not be zero");
+            return invalid_field_value("buffer_size", "0").into();
+        }
+        let multicast_options: MulticastOptions = options.into();
+        let socket = create_listener_socket(&options.group_address()?, &multicast_options)?;
+        Ok(Self {
+            transport: Box::new(UdpTransport::from(socket)),
+            options: Some(options.clone()),
+            buffer_size: multicast_options.buffer_size,
+        })
+    }
+
+    ///
+    /// Create a listener that receives messages from the provided transport rather than a newly
+    /// created UDP socket.
+    ///
+    pub fn with_transport(transport: Box<dyn Transport>) -> Self {
+        Self {
+            transport,
+            options: None,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+        }
+    }
+
+    ///
+    /// Replace the size of the buffer each message is read into; anything larger is truncated.
+    ///
+    pub fn set_buffer_size(&mut self, buffer_size: usize) {
+        self.buffer_size = buffer_size.max(1);
+    }
+
+    ///
+    /// Join the multicast group again, with a new socket, after the network interfaces have
+    /// changed; a group joined on an interface that has since lost its address, or on the
+    /// system's default interface that has since been replaced, no longer receives messages.
+    /// A listener created `with_transport` is left unchanged.
+    ///
+    pub fn rejoin(&mut self) -> Result<(), Error> {
+        match &self.options {
+            Some(options) => {
+                info!("rejoin - options: {:?}", options);
+                let socket = create_listener_socket(&options.group_address()?, &options.into())?;
+                self.transport = Box::new(UdpTransport::from(socket));
+            }
+            None => trace!("rejoin - listener has a provided transport, not rejoining"),
+        }
+        Ok(())
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.transport.local_addr()
+    }
+
+    ///
+    /// The transport messages are received on, also used to send unicast replies.
+    ///
+    pub(crate) fn transport(&self) -> &dyn Transport {
+        self.transport.as_ref()
+    }
+
+    ///
+    /// Wait up to `timeout` for the next message, returning `None` if no message arrived in
+    /// time. Packets that are not valid SSDP requests, such as search responses that may also be
+    /// seen on the group port, are logged and skipped.
+    ///
+    pub fn next_message(&self, timeout: Duration) -> Result<Option<(SocketAddr, Message)>, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.next_packet(deadline.saturating_duration_since(Instant::now()))? {
+                Some((from, Ok(message))) => return Ok(Some((from, message))),
+                Some((from, Err(e))) => {
+                    warn!("next_message - ignoring packet from {}: {}", from, e)
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    ///
+    /// Wait up to `timeout` for the next packet, returning the message it contains or the
+    /// reason it is not a valid SSDP request.
+    ///
+    pub(crate) fn next_packet(&self, timeout: Duration) -> Result<Option<Packet>, Error> {
+        if timeout.is_zero() {
+            return Ok(None);
+        }
+        let mut buf = vec![0u8; self.buffer_size];
+        // A zero timeout would make the socket block forever.
+        match self
+            .transport
+            .recv_from(&mut buf, timeout.max(Duration::from_millis(1)))?
+        {
+            Some((received, from)) => {
+                trace!("next_packet - received {} bytes from {:?}", received, from);
+                Ok(Some((
+                    from,
+                    Request::try_from(&buf[..received])
+                        .map_err(Error::from)
+                        .and_then(|request| Message::try_from(&request)),
+                )))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A packet received on the group, and either the message it contains or the reason it is not
+/// a valid SSDP request.
+///
+pub(crate) type Packet = (SocketAddr, Result<Message, Error>);
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::httpu::MemoryTransport;
+    use crate::discovery::notify::NotificationSubType;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_next_message_skips_invalid_packets() {
+        let transport = MemoryTransport::new("0.0.0.0:1900".parse().unwrap());
+        transport
+            .push_response(
+                "192.168.1.30:1900".parse().unwrap(),
+                b"HTTP/1.1 200 OK\r\nST: upnp:rootdevice\r\n\r\n",
+            )
+            .push_response(
+                "192.168.1.30:1900".parse().unwrap(),
+                b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nNT: upnp:rootdevice\r\nNTS: ssdp:byebye\r\nUSN: uuid:2f402f80-da50-11e1-9b23-00178829d301::upnp:rootdevice\r\n\r\n",
+            )
+            .push_response(
+                "192.168.1.12:50000".parse().unwrap(),
+                b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: ssdp:all\r\n\r\n",
+            );
+        let listener = Listener::with_transport(Box::new(transport));
+
+        let (from, message) = listener
+            .next_message(Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(from.to_string(), "192.168.1.30:1900");
+        match message {
+            Message::Notification(notification) => {
+                assert_eq!(notification.sub_type, NotificationSubType::ByeBye)
+            }
+            _ => panic!("expected a notification"),
+        }
+
+        let (from, message) = listener
+            .next_message(Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(from.to_string(), "192.168.1.12:50000");
+        assert!(matches!(message, Message::Search(_)));
+
+        assert!(listener
+            .next_message(Duration::from_secs(1))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_buffer_size() {
+        const EX_NOTIFY: &[u8] = b"NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nNT: upnp:rootdevice\r\nNTS: ssdp:byebye\r\nUSN: uuid:2f402f80-da50-11e1-9b23-00178829d301::upnp:rootdevice\r\n\r\n";
+        let transport = MemoryTransport::new("0.0.0.0:1900".parse().unwrap());
+        transport
+            .push_response("192.168.1.30:1900".parse().unwrap(), EX_NOTIFY)
+            .push_response("192.168.1.30:1900".parse().unwrap(), EX_NOTIFY);
+        let mut listener = Listener::with_transport(Box::new(transport));
+
+        listener.set_buffer_size(64);
+        let (_, result) = listener
+            .next_packet(Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert!(result.is_err());
+
+        listener.set_buffer_size(EX_NOTIFY.len());
+        let (_, result) = listener
+            .next_packet(Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert!(matches!(result, Ok(Message::Notification(_))));
+
+        let options = Options {
+            buffer_size: Some(0),
+            ..Default::default()
+        };
+        assert!(Listener::new(&options).is_err());
+        assert_eq!(
+            MulticastOptions::from(&Options {
+                buffer_size: Some(512),
+                ..Default::default()
+            })
+            .buffer_size,
+            512
+        );
+    }
+
+    #[test]
+    fn test_group_address() {
+        assert_eq!(
+            Options::default().group_address().unwrap().to_string(),
+            "239.255.255.250:1900"
+        );
+        let options = Options {
+            network_version: Some(IP::V6),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.group_address().unwrap().to_string(),
+            "[ff02::c]:1900"
+        );
+    }
+}
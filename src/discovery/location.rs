@@ -0,0 +1,256 @@
+/*!
+This module provides `LOCATION` values for devices with more than one network interface.
+
+# Specification
+
+For devices that have multiple network interfaces, advertisements and search responses are sent
+on all interfaces enabled for UPnP networking, and the URL specified in the `LOCATION` field value
+MUST be reachable on the interface on which the message is sent. The same device description may
+therefore be fetched from different addresses, and any absolute URLs within it must also use the
+address of the interface the request arrived on.
+
+A `LocationTemplate` is a description URL with the placeholder `{host}` in place of the address,
+for example `http://{host}:49152/description.xml`; the placeholder is replaced with the address of
+each interface as messages are sent.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::description::device::DeviceRoot;
+use upnp_rs::discovery::location::{device_available_on, LocationTemplate};
+use upnp_rs::discovery::notify::{advertisements_for, Options};
+use upnp_rs::SpecVersion;
+use std::str::FromStr;
+
+# fn description() -> DeviceRoot { unimplemented!() }
+let root: DeviceRoot = description();
+let template = LocationTemplate::from_str("http://{host}:49152/description.xml").unwrap();
+let location = URL::from_str("http://127.0.0.1:49152/description.xml").unwrap();
+let advertisements = advertisements_for(&root, &location, 1).unwrap();
+device_available_on(
+    &advertisements,
+    &template,
+    &["eth0".to_string(), "eth1".to_string(), "wlan0".to_string()],
+    Options::default_for(SpecVersion::V11),
+)
+.unwrap();
+```
+*/
+
+use crate::common::httpu::{Transport, UdpTransport};
+use crate::common::interface::{ip_address_for_interface, local_address_for, IP};
+use crate::common::uri::URL;
+use crate::discovery::multicast_address;
+use crate::discovery::notify::{device_available_using, device_unavailable_using, Device, Options};
+use crate::error::{invalid_value_for_type, Error};
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use tracing::{error, info};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// A description URL with a `{host}` placeholder for the address of the interface it is sent on.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocationTemplate {
+    template: String,
+}
+
+///
+/// The placeholder, in a `LocationTemplate`, for the address of the interface.
+///
+pub const HOST_PLACEHOLDER: &str = "{host}";
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return a copy of `advertisements` with `location` as the `LOCATION` of each.
+///
+pub fn located_at(advertisements: &[Device], location: &URL) -> Vec<Device> {
+    advertisements
+        .iter()
+        .map(|advertisement| Device {
+            location: location.clone(),
+            ..advertisement.clone()
+        })
+        .collect()
+}
+
+///
+/// Send the `ssdp:alive` notification for each of `advertisements` on each of the named
+/// `interfaces`, with the `LOCATION` of each message taken from `template` and the first address
+/// of the interface; the `network_interface` in `options` is ignored.
+///
+pub fn device_available_on(
+    advertisements: &[Device],
+    template: &LocationTemplate,
+    interfaces: &[String],
+    options: Options,
+) -> Result<(), Error> {
+    send_on(interfaces, options, |interface, options, transport| {
+        let location = template.location_on(interface, &options.network_version)?;
+        for advertisement in located_at(advertisements, &location) {
+            device_available_using(&advertisement, options.clone(), transport)?;
+        }
+        Ok(())
+    })
+}
+
+///
+/// Send the `ssdp:byebye` notification for each of `advertisements` on each of the named
+/// `interfaces`; the `network_interface` in `options` is ignored.
+///
+pub fn device_unavailable_on(
+    advertisements: &[Device],
+    interfaces: &[String],
+    options: Options,
+) -> Result<(), Error> {
+    send_on(interfaces, options, |_, options, transport| {
+        for advertisement in advertisements {
+            device_unavailable_using(advertisement, options.clone(), transport)?;
+        }
+        Ok(())
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl LocationTemplate {
+    ///
+    /// Return the `LOCATION` for the interface with the address `address`.
+    ///
+    pub fn location_for(&self, address: &IpAddr) -> Result<URL, Error> {
+        let host = match address {
+            IpAddr::V4(address) => address.to_string(),
+            IpAddr::V6(address) => format!("[{}]", address),
+        };
+        let location = self.template.replace(HOST_PLACEHOLDER, &host);
+        match URL::from_str(&location) {
+            Ok(location) if location.is_absolute() => Ok(location),
+            _ => {
+                error!("location_for - '{}' is not an absolute URL", location);
+                invalid_value_for_type("URL", location).into()
+            }
+        }
+    }
+
+    ///
+    /// Return the `LOCATION` for the named interface, using its first address of the IP version
+    /// `version`, if specified.
+    ///
+    pub fn location_on(&self, interface: &str, version: &Option<IP>) -> Result<URL, Error> {
+        match ip_address_for_interface(&Some(interface.to_string()), version) {
+            Some(address) => self.location_for(&address),
+            None => {
+                error!("location_on - interface '{}' has no address", interface);
+                invalid_value_for_type("NetworkInterface", interface).into()
+            }
+        }
+    }
+
+    ///
+    /// Return the `LOCATION` for the interface on the route to `remote`, usually the control
+    /// point a search response is sent to.
+    ///
+    pub fn location_to(&self, remote: &SocketAddr) -> Result<URL, Error> {
+        self.location_for(&local_address_for(remote)?)
+    }
+}
+
+impl Display for LocationTemplate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{}", self.template)
+    }
+}
+
+impl FromStr for LocationTemplate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.contains(HOST_PLACEHOLDER) {
+            error!("from_str - '{}' has no {} placeholder", s, HOST_PLACEHOLDER);
+            return invalid_value_for_type("LocationTemplate", s).into();
+        }
+        let template = Self {
+            template: s.to_string(),
+        };
+        let _ = template.location_for(&IpAddr::from([127, 0, 0, 1]))?;
+        Ok(template)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+fn send_on<F>(interfaces: &[String], options: Options, send: F) -> Result<(), Error>
+where
+    F: Fn(&str, &Options, &dyn Transport) -> Result<(), Error>,
+{
+    for interface in interfaces {
+        info!("send_on - sending on interface '{}'", interface);
+        let options = Options {
+            network_interface: Some(interface.clone()),
+            ..options.clone()
+        };
+        let transport = UdpTransport::new(
+            &multicast_address(options.address.as_deref(), options.port)?,
+            &options.clone().into(),
+        )?;
+        send(interface, &options, &transport)?;
+    }
+    Ok(())
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::net::Ipv6Addr;
+
+    #[test]
+    fn test_location_for() {
+        let template = LocationTemplate::from_str("http://{host}:49152/description.xml").unwrap();
+        assert_eq!(
+            template
+                .location_for(&"192.168.2.1".parse().unwrap())
+                .unwrap()
+                .to_string(),
+            "http://192.168.2.1:49152/description.xml"
+        );
+        assert_eq!(
+            template
+                .location_for(&IpAddr::V6(Ipv6Addr::LOCALHOST))
+                .unwrap()
+                .to_string(),
+            "http://[::1]:49152/description.xml"
+        );
+        assert_eq!(
+            template
+                .location_to(&"127.0.0.1:50000".parse().unwrap())
+                .unwrap()
+                .to_string(),
+            "http://127.0.0.1:49152/description.xml"
+        );
+        assert_eq!(template.to_string(), "http://{host}:49152/description.xml");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(LocationTemplate::from_str("http://192.168.2.1:49152/description.xml").is_err());
+        assert!(LocationTemplate::from_str("/{host}/description.xml").is_err());
+    }
+}
@@ -61,9 +61,16 @@ section explains the format of version information in discovery messages and spe
 on discovery messages to maintain compatibility with advances in minor versions.
 */
 
+use crate::common::httpu::Headers;
+use crate::error::{invalid_value_for_type, Error};
+use crate::syntax::{HTTP_HEADER_NLS, HTTP_HEADER_OPT, MULTICAST_ADDRESS, MULTICAST_PORT};
 use crate::{SpecVersion, UPNP_STRING};
 use os_version::{detect, OsVersion};
-use std::fmt::{Display, Error, Formatter};
+use regex::Regex;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use tracing::error;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -74,6 +81,7 @@ use std::fmt::{Display, Error, Formatter};
 /// but the `friendly_name` field is required by the 2.0 specification.
 ///
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ControlPoint {
     /// Specifies the friendly name of the control point. The friendly name is vendor specific.
     pub friendly_name: String,
@@ -93,6 +101,7 @@ pub struct ControlPoint {
 /// Field value MUST begin with the following "product tokens" (defined by HTTP/1.1).
 ///
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProductVersion {
     name: String,
     version: String,
@@ -103,12 +112,30 @@ pub struct ProductVersion {
 /// `CACHE-CONTROL` headers.
 ///
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProductVersions {
     product: ProductVersion,
     upnp: ProductVersion,
     platform: ProductVersion,
 }
 
+///
+/// An extension declared, using the HTTP Extension Framework, in the `OPT` header of a message.
+/// Many devices send the UPnP extension `http://schemas.upnp.org/upnp/1/0/` with the namespace
+/// `01`, along with a `01-NLS` header carrying a network location signature that changes each
+/// time the device restarts.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Extension {
+    /// The URI identifying the extension.
+    pub uri: String,
+    /// The prefix used for the headers of the extension, if declared.
+    pub namespace: Option<String>,
+    /// The value of the `NLS` header, with the declared prefix, if present.
+    pub network_location_signature: Option<String>,
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -122,7 +149,7 @@ lazy_static! {
 }
 
 impl Display for ProductVersion {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
         write!(f, "{}/{}", self.name, self.version)
     }
 }
@@ -183,9 +210,54 @@ impl Default for ProductVersions {
     }
 }
 
+///
+/// Writes the value in the order UDA requires for `SERVER` and `USER-AGENT`, the platform, the
+/// UPnP version, then the product; for example `Linux/5.10 UPnP/1.1 Example/1.0`.
+///
 impl Display for ProductVersions {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        write!(f, "{} {} {}", self.product, self.upnp, self.platform)
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(f, "{} {} {}", self.platform, self.upnp, self.product)
+    }
+}
+
+///
+/// Parses a `SERVER` or `USER-AGENT` value, such as `Linux/3.14.0 UPnP/1.0 IpBridge/1.40.0`.
+/// Devices vary in how closely they follow the required form, so the value is split around the
+/// `UPnP/` version token, the platform before it and product after it may be missing, commas
+/// may separate the tokens, and names and versions are split at the first `/`.
+///
+impl FromStr for ProductVersions {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref PRODUCTS: Regex =
+                Regex::new(r"^\s*(?:(.*?)[\s,]+)?(?i:(UPnP))/(\d+\.\d+)(?:[\s,]+(.*?))?[\s,]*$")
+                    .unwrap();
+        }
+        match PRODUCTS.captures(s) {
+            Some(captures) => {
+                let product_version = |index: usize| {
+                    let token = captures
+                        .get(index)
+                        .map(|x| x.as_str().trim_end_matches(','))
+                        .unwrap_or_default();
+                    match token.split_once('/') {
+                        Some((name, version)) => ProductVersion::for_product(name, version),
+                        None => ProductVersion::for_product(token, ""),
+                    }
+                };
+                Ok(Self {
+                    product: product_version(4),
+                    upnp: ProductVersion::for_product(
+                        captures.get(2).unwrap().as_str(),
+                        captures.get(3).unwrap().as_str(),
+                    ),
+                    platform: product_version(1),
+                })
+            }
+            None => invalid_value_for_type("ProductVersions", s).into(),
+        }
     }
 }
 
@@ -211,10 +283,91 @@ impl ProductVersions {
     }
 }
 
+impl ProductVersions {
+    ///
+    /// The UPnP version in the `UPnP/` token, as the latest version this crate supports that it
+    /// is compatible with; a later minor version, such as 1.2, is a superset of earlier minor
+    /// versions of the same major version.
+    ///
+    pub fn spec_version(&self) -> Option<SpecVersion> {
+        let (major, minor) = self.upnp.version.split_once('.')?;
+        match (major.parse::<u16>().ok()?, minor.parse::<u16>().ok()?) {
+            (1, 0) => Some(SpecVersion::V10),
+            (1, _) => Some(SpecVersion::V11),
+            (2, _) => Some(SpecVersion::V20),
+            _ => None,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Extension {
+    ///
+    /// The extension declared in the `OPT` header of `headers`, if any, with the value of its
+    /// `NLS` header.
+    ///
+    pub fn from_headers(headers: &Headers) -> Option<Self> {
+        lazy_static! {
+            static ref OPT: Regex =
+                Regex::new(r#"^\s*"?([^";]*?)"?\s*(?:;\s*ns\s*=\s*([0-9A-Za-z]+))?\s*$"#).unwrap();
+        }
+        let captures = OPT.captures(headers.get(HTTP_HEADER_OPT)?)?;
+        let uri = captures.get(1).unwrap().as_str();
+        if uri.is_empty() {
+            return None;
+        }
+        let namespace = captures.get(2).map(|x| x.as_str().to_string());
+        let network_location_signature = namespace
+            .as_ref()
+            .and_then(|namespace| headers.get(&format!("{}-{}", namespace, HTTP_HEADER_NLS)))
+            .cloned();
+        Some(Self {
+            uri: uri.to_string(),
+            namespace,
+            network_location_signature,
+        })
+    }
+
+    ///
+    /// The names of the headers this extension was parsed from.
+    ///
+    pub fn header_names(&self) -> Vec<String> {
+        let mut names = vec![HTTP_HEADER_OPT.to_string()];
+        if let Some(namespace) = &self.namespace {
+            names.push(format!("{}-{}", namespace, HTTP_HEADER_NLS));
+        }
+        names
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// The multicast group `address`, default 239.255.255.250, and `port`, default 1900, to send
+/// to. The address must be an IP address; an IPv6 address may be given with or without
+/// brackets.
+///
+pub(crate) fn multicast_address(
+    address: Option<&str>,
+    port: Option<u16>,
+) -> Result<SocketAddr, Error> {
+    let address = address.unwrap_or(MULTICAST_ADDRESS);
+    let ip = address.trim_start_matches('[').trim_end_matches(']');
+    match ip.parse::<IpAddr>() {
+        Ok(ip) => Ok(SocketAddr::new(ip, port.unwrap_or(MULTICAST_PORT))),
+        Err(_) => {
+            error!(
+                "multicast_address - invalid multicast address '{}'",
+                address
+            );
+            invalid_value_for_type("IpAddr", address).into()
+        }
+    }
+}
+
 fn platform_name() -> String {
     let version = detect().expect("Could not detect platform name/version");
     match version {
@@ -244,3 +397,15 @@ fn platform_version() -> String {
 pub mod search;
 
 pub mod notify;
+
+pub mod identity;
+
+pub mod location;
+
+pub mod listen;
+
+pub mod respond;
+
+pub mod guard;
+
+pub mod registry;
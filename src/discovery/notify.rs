@@ -1,18 +1,37 @@
 /*!
 This module provides three functions that provide 1) device available, 2) device updated, and
-3) device leaving notifications over multicast UDP.
+3) device leaving notifications over multicast UDP. It also provides the `Notification` type that
+parses these notifications when received by a control point.
 */
-use crate::common::httpu::{multicast_once, Options as MulticastOptions, RequestBuilder};
+use crate::common::headers;
+use crate::common::httpu::{
+    multicast_once_using, Headers, Options as MulticastOptions, Request, RequestBuilder, Transport,
+    UdpTransport,
+};
 use crate::common::interface::IP;
 use crate::common::uri::{URI, URL};
 use crate::common::user_agent::user_agent_string;
-use crate::discovery::search::SearchTarget;
-use crate::discovery::ProductVersion;
-use crate::error::{unsupported_version, Error};
+use crate::description::device::DeviceRoot;
+use crate::description::TypeID;
+use crate::discovery::search::{search_address, SearchTarget};
+use crate::discovery::{multicast_address, ProductVersion};
+use crate::error::{
+    invalid_value_for_type, missing_required_field, unsupported_version, Error, MessageFormatError,
+};
 use crate::syntax::{
-    HTTP_HEADER_BOOTID, HTTP_HEADER_CACHE_CONTROL, HTTP_HEADER_CONFIGID, HTTP_HEADER_HOST, HTTP_HEADER_LOCATION, HTTP_HEADER_NEXT_BOOTID, HTTP_HEADER_NT, HTTP_HEADER_NTS, HTTP_HEADER_SEARCH_PORT, HTTP_HEADER_SERVER, HTTP_HEADER_USN, HTTP_METHOD_NOTIFY, MULTICAST_ADDRESS, MULTICAST_PORT, NTS_ALIVE, NTS_BYE, NTS_UPDATE
+    HTTP_HEADER_BOOTID, HTTP_HEADER_CACHE_CONTROL, HTTP_HEADER_CONFIGID, HTTP_HEADER_HOST,
+    HTTP_HEADER_LOCATION, HTTP_HEADER_NEXT_BOOTID, HTTP_HEADER_NT, HTTP_HEADER_NTS,
+    HTTP_HEADER_SEARCH_PORT, HTTP_HEADER_SECURE_LOCATION, HTTP_HEADER_SERVER, HTTP_HEADER_USN,
+    HTTP_METHOD_NOTIFY, MULTICAST_ADDRESS, MULTICAST_PORT, NTS_ALIVE, NTS_BYE, NTS_UPDATE,
 };
 use crate::SpecVersion;
+use regex::Regex;
+use std::convert::TryFrom;
+use std::fmt::{Display, Error as FmtError, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::error;
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -22,6 +41,7 @@ use crate::SpecVersion;
 /// Description of a device sent in _alive_ and _update_ messages.
 ///
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Device {
     pub notification_type: SearchTarget,
     pub service_name: URI,
@@ -29,7 +49,68 @@ pub struct Device {
     pub boot_id: u32,
     pub config_id: u64,
     pub search_port: Option<u16>,
-    pub secure_location: Option<String>,
+    pub secure_location: Option<URL>,
+}
+
+///
+/// The kinds of discovery message a device sends, each identifying its device by UDN
+/// (`uuid:{device-UUID}`) and each with its own pairing of `NT` and `USN` values. Messages built
+/// from a `MessageKind` always carry a consistent pair.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MessageKind {
+    /// `NT: upnp:rootdevice`, `USN: uuid:{device-UUID}::upnp:rootdevice`; sent once, for the
+    /// root device.
+    RootDevice(String),
+    /// `NT: uuid:{device-UUID}`, `USN: uuid:{device-UUID}`; sent once for each device.
+    Device(String),
+    /// `NT: urn:{domain}:device:{deviceType:v}`,
+    /// `USN: uuid:{device-UUID}::urn:{domain}:device:{deviceType:v}`; sent once for each device.
+    DeviceType(String, TypeID),
+    /// `NT: urn:{domain}:service:{serviceType:v}`,
+    /// `USN: uuid:{device-UUID}::urn:{domain}:service:{serviceType:v}`; sent once for each
+    /// distinct service type in each device.
+    ServiceType(String, TypeID),
+}
+
+///
+/// The kind of notification, carried in the `NTS` header.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NotificationSubType {
+    /// Corresponds to the value `ssdp:alive`
+    Alive,
+    /// Corresponds to the value `ssdp:byebye`
+    ByeBye,
+    /// Corresponds to the value `ssdp:update`
+    Update,
+}
+
+///
+/// A notification received from a device, as parsed from a `NOTIFY` request. Headers that are
+/// not required by a particular sub-type, or by the specification version the device uses, are
+/// optional.
+///
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Notification {
+    pub notification_type: SearchTarget,
+    pub sub_type: NotificationSubType,
+    pub service_name: URI,
+    pub location: Option<URL>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "crate::common::seconds::option")
+    )]
+    pub max_age: Option<Duration>,
+    pub server: Option<String>,
+    pub boot_id: Option<u32>,
+    pub next_boot_id: Option<u32>,
+    pub config_id: Option<u64>,
+    pub search_port: Option<u16>,
+    pub secure_location: Option<URL>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub other_headers: Headers,
 }
 
 ///
@@ -37,6 +118,7 @@ pub struct Device {
 /// send.
 ///
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Options {
     /// The specification that will be used to construct sent messages and to verify responses.
     /// Default: `SpecVersion:V10`.
@@ -58,6 +140,27 @@ pub struct Options {
     pub address: Option<String>,
     /// Multicast port, default: 1900
     pub port: Option<u16>,
+    /// A local address to send from, in place of the address of `network_interface`.
+    /// Default: `None`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub source_address: Option<IpAddr>,
+    /// Whether notifications are also delivered to the sending host, so that local control
+    /// points see them. Default: `false`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub loop_back_also: bool,
+    /// Set `SO_REUSEADDR` on the socket. Default: `false`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reuse_address: bool,
+    /// Set `SO_REUSEPORT` on the socket, where supported. Default: `false`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reuse_port: bool,
+    /// The Differentiated Services code point, `0..=63`, for sent packets. Default: `None`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dscp: Option<u8>,
+    /// Headers added to each sent `NOTIFY`, after the standard ones, such as vendor or DLNA
+    /// headers; these replace any standard header of the same name. Default: none.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_headers: Headers,
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -82,16 +185,32 @@ components:
 # Parameters
 
 * `device` - details of the device to publish as a part of the notification message. Not all device
-     fields may be used in all notifications.
+  fields may be used in all notifications.
 * `options` - protocol options such as the specification version to use and any network
-     configuration values.
+  configuration values.
 
 */
-pub fn device_available(device: &mut Device, options: Options) -> Result<(), Error> {
-    let next_boot_id = device.boot_id + 1;
+pub fn device_available(device: &Device, options: Options) -> Result<(), Error> {
+    let transport = UdpTransport::new(
+        &multicast_address(options.address.as_deref(), options.port)?,
+        &options.clone().into(),
+    )?;
+    device_available_using(device, options, &transport)
+}
+
+///
+/// Provides the same notification as `device_available`, but sends the message using the provided
+/// transport rather than a newly created UDP socket.
+///
+pub fn device_available_using(
+    device: &Device,
+    options: Options,
+    transport: &dyn Transport,
+) -> Result<(), Error> {
+    let to_address = multicast_address(options.address.as_deref(), options.port)?;
     let mut message_builder = RequestBuilder::new(HTTP_METHOD_NOTIFY);
     message_builder
-        .add_header(HTTP_HEADER_HOST, format!("{}:{}", options.address.as_deref().unwrap_or(MULTICAST_ADDRESS), options.port.unwrap_or(MULTICAST_PORT)).as_str())
+        .add_header(HTTP_HEADER_HOST, &to_address.to_string())
         .add_header(
             HTTP_HEADER_CACHE_CONTROL,
             &format!("max-age={}", options.max_age),
@@ -116,17 +235,12 @@ pub fn device_available(device: &mut Device, options: Options) -> Result<(), Err
 
     if options.spec_version >= SpecVersion::V20 {
         if let Some(secure_location) = &device.secure_location {
-            message_builder.add_header(HTTP_HEADER_USN, secure_location);
+            message_builder.add_header(HTTP_HEADER_SECURE_LOCATION, &secure_location.to_string());
         }
     }
 
-    multicast_once(
-        &message_builder.into(),
-        &format!("{}:{}", options.address.as_deref().unwrap_or(MULTICAST_ADDRESS), options.port.unwrap_or(MULTICAST_PORT)).parse().unwrap(),
-        &options.into(),
-    )?;
-
-    device.boot_id = next_boot_id;
+    message_builder.extra_headers(&options.extra_headers);
+    multicast_once_using(&message_builder.into(), &to_address, transport)?;
     Ok(())
 }
 
@@ -160,19 +274,41 @@ interface on which the advertisement is sent.
 # Parameters
 
 * `device` - details of the device to publish as a part of the notification message. Not all device
-     fields may be used in all notifications.
+  fields may be used in all notifications.
+* `next_boot_id` - the `BOOTID.UPNP.ORG` value the device will use once all updates are sent; the
+  device's own `boot_id` is sent as the current value and is not changed.
 * `options` - protocol options such as the specification version to use and any network
-     configuration values.
+  configuration values.
+
+The [`identity`](../identity/index.html) module manages the boot ID and sends the complete
+sequence of messages.
 
 */
-pub fn device_update(device: &mut Device, options: Options) -> Result<(), Error> {
+pub fn device_update(device: &Device, next_boot_id: u32, options: Options) -> Result<(), Error> {
+    let transport = UdpTransport::new(
+        &multicast_address(options.address.as_deref(), options.port)?,
+        &options.clone().into(),
+    )?;
+    device_update_using(device, next_boot_id, options, &transport)
+}
+
+///
+/// Provides the same notification as `device_update`, but sends the message using the provided
+/// transport rather than a newly created UDP socket.
+///
+pub fn device_update_using(
+    device: &Device,
+    next_boot_id: u32,
+    options: Options,
+    transport: &dyn Transport,
+) -> Result<(), Error> {
+    let to_address = multicast_address(options.address.as_deref(), options.port)?;
     if options.spec_version == SpecVersion::V10 {
         unsupported_version(options.spec_version).into()
     } else {
-        let next_boot_id = device.boot_id + 1;
         let mut message_builder = RequestBuilder::new(HTTP_METHOD_NOTIFY);
         message_builder
-            .add_header(HTTP_HEADER_HOST, format!("{}:{}", options.address.as_deref().unwrap_or(MULTICAST_ADDRESS), options.port.unwrap_or(MULTICAST_PORT)).as_str())
+            .add_header(HTTP_HEADER_HOST, &to_address.to_string())
             .add_header(HTTP_HEADER_LOCATION, &device.location.to_string())
             .add_header(HTTP_HEADER_NT, &device.notification_type.to_string())
             .add_header(HTTP_HEADER_NTS, NTS_UPDATE)
@@ -187,16 +323,13 @@ pub fn device_update(device: &mut Device, options: Options) -> Result<(), Error>
 
         if options.spec_version >= SpecVersion::V20 {
             if let Some(secure_location) = &device.secure_location {
-                message_builder.add_header(HTTP_HEADER_USN, secure_location);
+                message_builder
+                    .add_header(HTTP_HEADER_SECURE_LOCATION, &secure_location.to_string());
             }
         }
 
-        multicast_once(
-            &message_builder.into(),
-            &format!("{}:{}", options.address.as_deref().unwrap_or(MULTICAST_ADDRESS), options.port.unwrap_or(MULTICAST_PORT)).parse().unwrap(),
-            &options.into(),
-        )?;
-        device.boot_id = next_boot_id;
+        message_builder.extra_headers(&options.extra_headers);
+        multicast_once_using(&message_builder.into(), &to_address, transport)?;
         Ok(())
     }
 }
@@ -220,16 +353,32 @@ request must have method `NOTIFY` and `ssdp:byeby`e in the `NTS` header in the f
 # Parameters
 
 * `device` - details of the device to publish as a part of the notification message. Not all device
-     fields may be used in all notifications.
+  fields may be used in all notifications.
 * `options` - protocol options such as the specification version to use and any network
-     configuration values.
+  configuration values.
 
 */
-pub fn device_unavailable(device: &mut Device, options: Options) -> Result<(), Error> {
-    let next_boot_id = device.boot_id + 1;
+pub fn device_unavailable(device: &Device, options: Options) -> Result<(), Error> {
+    let transport = UdpTransport::new(
+        &multicast_address(options.address.as_deref(), options.port)?,
+        &options.clone().into(),
+    )?;
+    device_unavailable_using(device, options, &transport)
+}
+
+///
+/// Provides the same notification as `device_unavailable`, but sends the message using the provided
+/// transport rather than a newly created UDP socket.
+///
+pub fn device_unavailable_using(
+    device: &Device,
+    options: Options,
+    transport: &dyn Transport,
+) -> Result<(), Error> {
+    let to_address = multicast_address(options.address.as_deref(), options.port)?;
     let mut message_builder = RequestBuilder::new(HTTP_METHOD_NOTIFY);
     message_builder
-        .add_header(HTTP_HEADER_HOST, format!("{}:{}", options.address.as_deref().unwrap_or(MULTICAST_ADDRESS), options.port.unwrap_or(MULTICAST_PORT)).as_str())
+        .add_header(HTTP_HEADER_HOST, &to_address.to_string())
         .add_header(HTTP_HEADER_NT, &device.notification_type.to_string())
         .add_header(HTTP_HEADER_NTS, NTS_BYE)
         .add_header(HTTP_HEADER_USN, &device.service_name.to_string());
@@ -240,19 +389,348 @@ pub fn device_unavailable(device: &mut Device, options: Options) -> Result<(), E
             .add_header(HTTP_HEADER_CONFIGID, &device.config_id.to_string());
     }
 
-    multicast_once(
-        &message_builder.into(),
-        &format!("{}:{}", options.address.as_deref().unwrap_or(MULTICAST_ADDRESS), options.port.unwrap_or(MULTICAST_PORT)).parse().unwrap(),
-        &options.into(),
-    )?;
-    device.boot_id = next_boot_id;
+    message_builder.extra_headers(&options.extra_headers);
+    multicast_once_using(&message_builder.into(), &to_address, transport)?;
     Ok(())
 }
 
+///
+/// Return the complete set of advertisements for the root device described by `root`, whose
+/// description is at `location`. As required by the UDA, this is three for the root device, two
+/// for each embedded device, and one for each distinct service type in each device; these are
+/// the messages to send for each of the notification functions above and the candidates for a
+/// search response.
+///
+pub fn advertisements_for(
+    root: &DeviceRoot,
+    location: &URL,
+    boot_id: u32,
+) -> Result<Vec<Device>, Error> {
+    let config_id = root.config_id.map(u64::from).unwrap_or_default();
+    message_kinds_for(root)
+        .iter()
+        .map(|kind| Device::new(kind, location, boot_id, config_id))
+        .collect()
+}
+
+///
+/// Return the kind of each message that the root device described by `root` sends, in the order
+/// sent, as used by `advertisements_for`.
+///
+pub fn message_kinds_for(root: &DeviceRoot) -> Vec<MessageKind> {
+    let mut kinds = vec![MessageKind::RootDevice(
+        root.device.unique_device_name.clone(),
+    )];
+    let mut devices = vec![&root.device];
+    while let Some(device) = devices.pop() {
+        let udn = &device.unique_device_name;
+        kinds.push(MessageKind::Device(udn.clone()));
+        kinds.push(MessageKind::DeviceType(
+            udn.clone(),
+            device.device_type.clone(),
+        ));
+        let mut service_types: Vec<&TypeID> = Default::default();
+        for service in &device.service_list {
+            if !service_types.contains(&&service.service_type) {
+                service_types.push(&service.service_type);
+                kinds.push(MessageKind::ServiceType(
+                    udn.clone(),
+                    service.service_type.clone(),
+                ));
+            }
+        }
+        devices.extend(device.device_list.iter().rev());
+    }
+    kinds
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
 
+impl Device {
+    ///
+    /// Construct the advertisement of kind `kind`, with its `NT` and `USN` values taken from the
+    /// kind.
+    ///
+    pub fn new(
+        kind: &MessageKind,
+        location: &URL,
+        boot_id: u32,
+        config_id: u64,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            notification_type: kind.notification_type(),
+            service_name: kind.service_name()?,
+            location: location.clone(),
+            boot_id,
+            config_id,
+            search_port: None,
+            secure_location: None,
+        })
+    }
+
+    ///
+    /// The kind of this advertisement, an error if its `NT` and `USN` values are not a
+    /// consistent pair.
+    ///
+    pub fn kind(&self) -> Result<MessageKind, Error> {
+        MessageKind::from_parts(&self.notification_type, &self.service_name)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl MessageKind {
+    ///
+    /// Return the kind of message with the `NT` value `notification_type` and the `USN` value
+    /// `service_name`, an error if they are not one of the pairs a device sends.
+    ///
+    pub fn from_parts(notification_type: &SearchTarget, service_name: &URI) -> Result<Self, Error> {
+        let service_name = service_name.to_string();
+        let udn = match service_name.split_once("::") {
+            Some((udn, _)) => udn.to_string(),
+            None => service_name.clone(),
+        };
+        let kind = match notification_type {
+            SearchTarget::RootDevice => Some(MessageKind::RootDevice(udn)),
+            SearchTarget::Device(_) => Some(MessageKind::Device(udn)),
+            SearchTarget::DeviceType(_) | SearchTarget::DomainDeviceType(_, _) => {
+                TypeID::device_from(notification_type.clone())
+                    .ok()
+                    .map(|device_type| MessageKind::DeviceType(udn, device_type))
+            }
+            SearchTarget::ServiceType(_) | SearchTarget::DomainServiceType(_, _) => {
+                TypeID::service_from(notification_type.clone())
+                    .ok()
+                    .map(|service_type| MessageKind::ServiceType(udn, service_type))
+            }
+            _ => None,
+        };
+        match kind {
+            Some(kind)
+                if kind.unique_device_name().starts_with("uuid:")
+                    && kind.notification_type() == *notification_type
+                    && kind.usn() == service_name =>
+            {
+                Ok(kind)
+            }
+            _ => {
+                error!(
+                    "from_parts - NT '{}' and USN '{}' are not a consistent pair",
+                    notification_type, service_name
+                );
+                invalid_value_for_type("MessageKind", service_name).into()
+            }
+        }
+    }
+
+    ///
+    /// The UDN, `uuid:{device-UUID}`, of the device the message is about.
+    ///
+    pub fn unique_device_name(&self) -> &String {
+        match self {
+            MessageKind::RootDevice(udn) => udn,
+            MessageKind::Device(udn) => udn,
+            MessageKind::DeviceType(udn, _) => udn,
+            MessageKind::ServiceType(udn, _) => udn,
+        }
+    }
+
+    ///
+    /// The `NT` value for this kind of message.
+    ///
+    pub fn notification_type(&self) -> SearchTarget {
+        match self {
+            MessageKind::RootDevice(_) => SearchTarget::RootDevice,
+            MessageKind::Device(udn) => {
+                SearchTarget::Device(udn.trim_start_matches("uuid:").to_string())
+            }
+            MessageKind::DeviceType(_, type_id) => SearchTarget::from(type_id),
+            MessageKind::ServiceType(_, type_id) => SearchTarget::from(type_id),
+        }
+    }
+
+    ///
+    /// The `USN` value for this kind of message.
+    ///
+    pub fn service_name(&self) -> Result<URI, Error> {
+        URI::from_str(&self.usn())
+    }
+
+    fn usn(&self) -> String {
+        match self {
+            MessageKind::RootDevice(udn) => format!("{}::{}", udn, SearchTarget::RootDevice),
+            MessageKind::Device(udn) => udn.to_string(),
+            MessageKind::DeviceType(udn, type_id) => format!("{}::{}", udn, type_id),
+            MessageKind::ServiceType(udn, type_id) => format!("{}::{}", udn, type_id),
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for NotificationSubType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), FmtError> {
+        write!(
+            f,
+            "{}",
+            match self {
+                NotificationSubType::Alive => NTS_ALIVE,
+                NotificationSubType::ByeBye => NTS_BYE,
+                NotificationSubType::Update => NTS_UPDATE,
+            }
+        )
+    }
+}
+
+impl FromStr for NotificationSubType {
+    type Err = MessageFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            NTS_ALIVE => Ok(NotificationSubType::Alive),
+            NTS_BYE => Ok(NotificationSubType::ByeBye),
+            NTS_UPDATE => Ok(NotificationSubType::Update),
+            _ => {
+                error!(
+                    "from_str - could not parse '{}' as a notification sub-type",
+                    s
+                );
+                invalid_value_for_type("NotificationSubType", s).into()
+            }
+        }
+    }
+}
+
+serde_as_string!(NotificationSubType);
+
+// ------------------------------------------------------------------------------------------------
+
+const REQUIRED_HEADERS: [&str; 3] = [HTTP_HEADER_NT, HTTP_HEADER_NTS, HTTP_HEADER_USN];
+
+const REQUIRED_HEADERS_ALIVE: [&str; 2] = [HTTP_HEADER_CACHE_CONTROL, HTTP_HEADER_LOCATION];
+
+const KNOWN_HEADERS: [&str; 11] = [
+    HTTP_HEADER_BOOTID,
+    HTTP_HEADER_CACHE_CONTROL,
+    HTTP_HEADER_CONFIGID,
+    HTTP_HEADER_LOCATION,
+    HTTP_HEADER_NEXT_BOOTID,
+    HTTP_HEADER_NT,
+    HTTP_HEADER_NTS,
+    HTTP_HEADER_SEARCH_PORT,
+    HTTP_HEADER_SECURE_LOCATION,
+    HTTP_HEADER_SERVER,
+    HTTP_HEADER_USN,
+];
+
+impl TryFrom<&Request> for Notification {
+    type Error = Error;
+
+    fn try_from(request: &Request) -> Result<Self, Self::Error> {
+        lazy_static! {
+            static ref MAX_AGE: Regex = Regex::new(r"max-age[ ]*=[ ]*(\d+)").unwrap();
+        }
+        if request.method() != HTTP_METHOD_NOTIFY {
+            error!(
+                "try_from - request method '{}' is not NOTIFY",
+                request.method()
+            );
+            return invalid_value_for_type("Notification", request.method()).into();
+        }
+        let headers = request.headers();
+        headers::check_required(headers, &REQUIRED_HEADERS)?;
+
+        let sub_type = headers::check_parsed_value::<NotificationSubType>(
+            headers.get(HTTP_HEADER_NTS).unwrap(),
+            HTTP_HEADER_NTS,
+        )?;
+        if sub_type == NotificationSubType::Alive {
+            headers::check_required(headers, &REQUIRED_HEADERS_ALIVE)?;
+        }
+
+        let max_age = match headers.get(HTTP_HEADER_CACHE_CONTROL) {
+            None => None,
+            Some(value) => Some(Duration::from_secs(headers::check_parsed_value::<u64>(
+                &headers::check_regex(value, HTTP_HEADER_CACHE_CONTROL, &MAX_AGE)?,
+                HTTP_HEADER_CACHE_CONTROL,
+            )?)),
+        };
+
+        let notification_type = headers::check_parsed_value::<SearchTarget>(
+            headers.get(HTTP_HEADER_NT).unwrap(),
+            HTTP_HEADER_NT,
+        )?;
+        let service_name = headers::check_parsed_value::<URI>(
+            headers.get(HTTP_HEADER_USN).unwrap(),
+            HTTP_HEADER_USN,
+        )?;
+
+        let remaining_headers = headers.without(&KNOWN_HEADERS);
+
+        Ok(Notification {
+            notification_type,
+            sub_type,
+            service_name,
+            location: headers::check_optional_url(
+                headers.get(HTTP_HEADER_LOCATION),
+                HTTP_HEADER_LOCATION,
+            )?,
+            max_age,
+            server: headers.get(HTTP_HEADER_SERVER).cloned(),
+            boot_id: headers::check_optional_value(
+                headers.get(HTTP_HEADER_BOOTID),
+                HTTP_HEADER_BOOTID,
+            )?,
+            next_boot_id: headers::check_optional_value(
+                headers.get(HTTP_HEADER_NEXT_BOOTID),
+                HTTP_HEADER_NEXT_BOOTID,
+            )?,
+            config_id: headers::check_optional_value(
+                headers.get(HTTP_HEADER_CONFIGID),
+                HTTP_HEADER_CONFIGID,
+            )?,
+            search_port: headers::check_optional_value(
+                headers.get(HTTP_HEADER_SEARCH_PORT),
+                HTTP_HEADER_SEARCH_PORT,
+            )?,
+            secure_location: headers::check_optional_url(
+                headers.get(HTTP_HEADER_SECURE_LOCATION),
+                HTTP_HEADER_SECURE_LOCATION,
+            )?,
+            other_headers: remaining_headers,
+        })
+    }
+}
+
+impl Notification {
+    ///
+    /// The kind of this notification, an error if its `NT` and `USN` values are not a
+    /// consistent pair.
+    ///
+    pub fn kind(&self) -> Result<MessageKind, Error> {
+        MessageKind::from_parts(&self.notification_type, &self.service_name)
+    }
+
+    ///
+    /// The address to send a unicast search to for the notifying device, see
+    /// `search::search_address`; `ssdp:byebye` notifications have no `LOCATION` and so no
+    /// address.
+    ///
+    pub fn search_address(&self) -> Result<SocketAddr, Error> {
+        match &self.location {
+            Some(location) => search_address(location, self.search_port),
+            None => {
+                error!("search_address - notification has no LOCATION");
+                missing_required_field(HTTP_HEADER_LOCATION).into()
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
 const CACHE_CONTROL_MAX_AGE: u16 = 1800;
 
 impl Options {
@@ -270,7 +748,12 @@ impl Options {
             product_and_version: None,
             address: Some(MULTICAST_ADDRESS.to_string()),
             port: Some(MULTICAST_PORT),
-            
+            source_address: None,
+            loop_back_also: false,
+            reuse_address: false,
+            reuse_port: false,
+            dscp: None,
+            extra_headers: Default::default(),
         }
     }
 }
@@ -280,8 +763,223 @@ impl From<Options> for MulticastOptions {
         MulticastOptions {
             network_interface: options.network_interface,
             network_version: options.network_version,
+            source_address: options.source_address,
             packet_ttl: options.packet_ttl,
+            loop_back_also: options.loop_back_also,
+            reuse_address: options.reuse_address,
+            reuse_port: options.reuse_port,
+            dscp: options.dscp,
             ..Default::default()
         }
     }
 }
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::httpu::MemoryTransport;
+    use std::str::FromStr;
+
+    const EX_DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0" configId="7">
+  <specVersion><major>2</major><minor>0</minor></specVersion>
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:MediaServer:1</deviceType>
+    <friendlyName>Media</friendlyName>
+    <manufacturer>Example</manufacturer>
+    <modelName>M1</modelName>
+    <UDN>uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d5</UDN>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:ContentDirectory:1</serviceType>
+        <serviceId>urn:upnp-org:serviceId:ContentDirectory</serviceId>
+        <SCPDURL>/cd.xml</SCPDURL>
+        <controlURL>/cd/control</controlURL>
+        <eventSubURL>/cd/event</eventSubURL>
+      </service>
+    </serviceList>
+    <deviceList>
+      <device>
+        <deviceType>urn:example-com:device:Light:1</deviceType>
+        <friendlyName>Light</friendlyName>
+        <manufacturer>Example</manufacturer>
+        <modelName>L1</modelName>
+        <UDN>uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d6</UDN>
+        <serviceList>
+          <service>
+            <serviceType>urn:example-com:service:Dimming:1</serviceType>
+            <serviceId>urn:example-com:serviceId:Dimming</serviceId>
+            <SCPDURL>/dim.xml</SCPDURL>
+            <controlURL>/dim/control</controlURL>
+            <eventSubURL>/dim/event</eventSubURL>
+          </service>
+        </serviceList>
+      </device>
+    </deviceList>
+  </device>
+</root>"#;
+
+    fn root_device(boot_id: u32) -> Device {
+        Device::new(
+            &MessageKind::RootDevice("uuid:2f402f80-da50-11e1-9b23-00178829d301".to_string()),
+            &URL::from_str("http://192.168.1.10:49152/description.xml").unwrap(),
+            boot_id,
+            1,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_device_available_using_memory_transport() {
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        let device = root_device(1);
+
+        let mut options = Options::default_for(SpecVersion::V10);
+        options
+            .extra_headers
+            .append("X-DLNA-Example", "DLNADOC/1.50");
+        device_available_using(&device, options, &transport).unwrap();
+
+        let sent = transport.sent();
+        assert_eq!(sent.len(), 1);
+        let (to_address, request) = sent.first().unwrap();
+        assert_eq!(to_address.to_string(), "239.255.255.250:1900");
+        let request = String::from_utf8(request.clone()).unwrap();
+        assert!(request.starts_with("NOTIFY * HTTP/1.1\r\n"));
+        assert!(request.contains("NTS:ssdp:alive"));
+        assert!(request.contains("LOCATION:http://192.168.1.10:49152/description.xml"));
+        assert!(request.ends_with("\r\nX-DLNA-Example:DLNADOC/1.50\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_boot_id_unchanged_by_notifications() {
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        let device = root_device(3);
+        let options = Options::default_for(SpecVersion::V11);
+
+        device_available_using(&device, options.clone(), &transport).unwrap();
+        device_update_using(&device, 4, options.clone(), &transport).unwrap();
+        device_unavailable_using(&device, options, &transport).unwrap();
+
+        let sent: Vec<String> = transport
+            .sent()
+            .iter()
+            .map(|(_, request)| String::from_utf8(request.clone()).unwrap())
+            .collect();
+        assert_eq!(sent.len(), 3);
+        assert!(sent
+            .iter()
+            .all(|request| request.contains("\r\nBOOTID.UPNP.ORG:3\r\n")));
+        assert!(sent[1].contains("NEXTBOOTID.UPNP.ORG:4"));
+    }
+
+    #[test]
+    fn test_parse_notification() {
+        let request = Request::try_from(
+            "NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nCACHE-CONTROL: max-age=1800\r\nLOCATION: http://192.168.1.10:49152/description.xml\r\nNT: urn:schemas-upnp-org:service:SwitchPower:1\r\nNTS: ssdp:alive\r\nSERVER: Linux/5.4 UPnP/1.1 Example/1.0\r\nUSN: uuid:2f402f80-da50-11e1-9b23-00178829d301::urn:schemas-upnp-org:service:SwitchPower:1\r\nBOOTID.UPNP.ORG: 7\r\nCONFIGID.UPNP.ORG: 3\r\nX-VENDOR: one\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let notification = Notification::try_from(&request).unwrap();
+        assert_eq!(notification.sub_type, NotificationSubType::Alive);
+        assert_eq!(
+            notification.notification_type.to_string(),
+            "urn:schemas-upnp-org:service:SwitchPower:1"
+        );
+        assert_eq!(notification.max_age, Some(Duration::from_secs(1800)));
+        assert_eq!(notification.boot_id, Some(7));
+        assert_eq!(notification.config_id, Some(3));
+        assert_eq!(notification.other_headers.get("X-VENDOR").unwrap(), "one");
+
+        let request = Request::try_from(
+            "NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nNT: upnp:rootdevice\r\nNTS: ssdp:byebye\r\nUSN: uuid:2f402f80-da50-11e1-9b23-00178829d301::upnp:rootdevice\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let notification = Notification::try_from(&request).unwrap();
+        assert_eq!(notification.sub_type, NotificationSubType::ByeBye);
+        assert!(notification.location.is_none());
+
+        let request = Request::try_from(
+            "NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nNT: upnp:rootdevice\r\nNTS: ssdp:alive\r\nUSN: uuid:2f402f80-da50-11e1-9b23-00178829d301::upnp:rootdevice\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert!(Notification::try_from(&request).is_err());
+    }
+
+    #[test]
+    fn test_message_kinds_round_trip() {
+        let root = DeviceRoot::from_str(EX_DESCRIPTION).unwrap();
+        let kinds = message_kinds_for(&root);
+        assert_eq!(kinds.len(), 7);
+        assert_eq!(
+            kinds[5],
+            MessageKind::DeviceType(
+                "uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d6".to_string(),
+                TypeID::from_str("urn:example-com:device:Light:1").unwrap()
+            )
+        );
+
+        let location = URL::from_str("http://192.168.1.10:49152/description.xml").unwrap();
+        let mut advertisements = advertisements_for(&root, &location, 3).unwrap();
+        for advertisement in advertisements.iter_mut() {
+            advertisement.secure_location =
+                Some(URL::from_str("https://192.168.1.10:49443/description.xml").unwrap());
+        }
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        let options = Options::default_for(SpecVersion::V20);
+        for advertisement in &advertisements {
+            device_available_using(advertisement, options.clone(), &transport).unwrap();
+            device_update_using(advertisement, 4, options.clone(), &transport).unwrap();
+            device_unavailable_using(advertisement, options.clone(), &transport).unwrap();
+        }
+
+        let notifications: Vec<Notification> = transport
+            .sent()
+            .iter()
+            .map(|(_, bytes)| {
+                Notification::try_from(&Request::try_from(bytes.as_slice()).unwrap()).unwrap()
+            })
+            .collect();
+        assert_eq!(notifications.len(), kinds.len() * 3);
+        for (kind, sent) in kinds.iter().zip(notifications.chunks(3)) {
+            for notification in sent {
+                assert_eq!(&notification.kind().unwrap(), kind);
+                assert_eq!(notification.boot_id, Some(3));
+                assert_eq!(notification.config_id, Some(7));
+            }
+            assert_eq!(sent[0].sub_type, NotificationSubType::Alive);
+            assert_eq!(sent[0].secure_location, advertisements[0].secure_location);
+            assert_eq!(sent[1].sub_type, NotificationSubType::Update);
+            assert_eq!(sent[1].next_boot_id, Some(4));
+            assert_eq!(sent[2].sub_type, NotificationSubType::ByeBye);
+        }
+    }
+
+    #[test]
+    fn test_inconsistent_message_kinds() {
+        let service_name =
+            URI::from_str("uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d5::upnp:rootdevice").unwrap();
+        assert!(MessageKind::from_parts(&SearchTarget::RootDevice, &service_name).is_ok());
+        assert!(MessageKind::from_parts(
+            &SearchTarget::Device("6c1b0f2e-0000-1000-8000-0013a2b3c4d5".to_string()),
+            &service_name
+        )
+        .is_err());
+        assert!(MessageKind::from_parts(
+            &SearchTarget::from_str("urn:schemas-upnp-org:device:MediaServer:1").unwrap(),
+            &service_name
+        )
+        .is_err());
+        assert!(MessageKind::from_parts(
+            &SearchTarget::RootDevice,
+            &URI::from_str("uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d5").unwrap()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,691 @@
+/*!
+This module provides a registry, for control points, that tracks which devices are present on the
+network. It combines the responses to searches with the notifications devices send to the
+multicast group and reports each change in a device's presence as a
+[`RegistryEvent`](enum.RegistryEvent.html).
+
+Devices are keyed by their UDN, the `uuid:{device-UUID}` prefix of each `USN`, and the registry
+records the `LOCATION`, `BOOTID.UPNP.ORG`, `CONFIGID.UPNP.ORG`, and the device and service types
+advertised for each. A device is removed when it sends `ssdp:byebye` or when its advertisements
+are not renewed before their `max-age` expires.
+
+Embedded devices advertise with their own UDN, and so are separate entries in the registry; as all
+of the devices in a description share the same `LOCATION` the
+[`embedded_devices`](struct.Registry.html#method.embedded_devices) method returns those that
+belong to a root device.
+
+# Example
+
+```rust,no_run
+use upnp_rs::discovery::listen::{Listener, Options as ListenOptions};
+use upnp_rs::discovery::registry::{Registry, RegistryEvent};
+use upnp_rs::discovery::search::Options;
+use upnp_rs::SpecVersion;
+use std::time::Duration;
+
+let mut registry = Registry::new();
+let listener = Listener::new(&ListenOptions::default()).unwrap();
+let mut events = registry.search(Options::default_for(SpecVersion::V11)).unwrap();
+loop {
+    for event in events.drain(..) {
+        match event {
+            RegistryEvent::DeviceAdded(device) | RegistryEvent::DeviceUpdated(device) => {
+                println!("fetch description from {}", device.location)
+            }
+            RegistryEvent::DeviceRebooted(device) => {
+                println!("re-subscribe to {}", device.unique_device_name)
+            }
+            RegistryEvent::DeviceRemoved(device) => {
+                println!("{} has gone", device.unique_device_name)
+            }
+        }
+    }
+    events = registry.listen(&listener, Duration::from_secs(5)).unwrap();
+}
+```
+*/
+
+use crate::common::httpu::Transport;
+use crate::common::uri::{URI, URL};
+use crate::discovery::listen::{Listener, Message};
+use crate::discovery::notify::{Notification, NotificationSubType};
+use crate::discovery::search::{
+    search_address, search_once, search_once_using, Options, Response, SearchTarget,
+};
+use crate::error::Error;
+use std::collections::{BTreeSet, HashMap};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tracing::{info, trace, warn};
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// What the registry knows about a single device, as advertised by the device itself.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegisteredDevice {
+    /// The device's UDN, of the form `uuid:{device-UUID}`.
+    pub unique_device_name: String,
+    /// The URL of the device description.
+    pub location: URL,
+    /// The `BOOTID.UPNP.ORG` value, UPnP 1.1 and later devices only.
+    pub boot_id: Option<u32>,
+    /// The `CONFIGID.UPNP.ORG` value, UPnP 1.1 and later devices only.
+    pub config_id: Option<u64>,
+    /// The `SEARCHPORT.UPNP.ORG` value, if the device listens for unicast searches on a port
+    /// other than 1900.
+    pub search_port: Option<u16>,
+    /// The `SERVER` value, if the device sent one.
+    pub server: Option<String>,
+    /// Denotes that the device has advertised itself as a root device.
+    pub root_device: bool,
+    /// The device types advertised under this UDN.
+    pub device_types: BTreeSet<String>,
+    /// The service types advertised under this UDN.
+    pub service_types: BTreeSet<String>,
+    /// The time at which the device will be removed unless it renews its advertisements.
+    pub expires: Instant,
+}
+
+///
+/// A change in the presence of a device, each carries the state of the device after the change,
+/// or immediately before it was removed.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum RegistryEvent {
+    /// A device was seen for the first time.
+    DeviceAdded(RegisteredDevice),
+    /// A device's `LOCATION` or `CONFIGID.UPNP.ORG` changed and so its description, and those of
+    /// its services, should be fetched again.
+    DeviceUpdated(RegisteredDevice),
+    /// A device's `BOOTID.UPNP.ORG` changed without a preceding `ssdp:update`, it has restarted
+    /// and any event subscriptions will have been lost.
+    DeviceRebooted(RegisteredDevice),
+    /// A device sent `ssdp:byebye`, or its advertisements expired.
+    DeviceRemoved(RegisteredDevice),
+}
+
+///
+/// The set of devices currently present, see the [module documentation](index.html).
+///
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    devices: HashMap<String, RegisteredDevice>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl RegisteredDevice {
+    pub fn is_expired(&self) -> bool {
+        self.expires <= Instant::now()
+    }
+
+    ///
+    /// The address to send a unicast search to for this device, see `search::search_address`.
+    ///
+    pub fn search_address(&self) -> Result<SocketAddr, Error> {
+        search_address(&self.location, self.search_port)
+    }
+
+    fn add_target(&mut self, target: &SearchTarget) {
+        match target {
+            SearchTarget::RootDevice => self.root_device = true,
+            SearchTarget::DeviceType(_) | SearchTarget::DomainDeviceType(_, _) => {
+                let _ = self.device_types.insert(target.to_string());
+            }
+            SearchTarget::ServiceType(_) | SearchTarget::DomainServiceType(_, _) => {
+                let _ = self.service_types.insert(target.to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Registry {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn device(&self, unique_device_name: &str) -> Option<&RegisteredDevice> {
+        self.devices.get(unique_device_name)
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = &RegisteredDevice> {
+        self.devices.values()
+    }
+
+    ///
+    /// Return all devices that have advertised themselves as root devices.
+    ///
+    pub fn root_devices(&self) -> impl Iterator<Item = &RegisteredDevice> {
+        self.devices.values().filter(|device| device.root_device)
+    }
+
+    ///
+    /// Return the devices, other than the root device itself, that share the description
+    /// `LOCATION` of the root device `unique_device_name`.
+    ///
+    pub fn embedded_devices(&self, unique_device_name: &str) -> Vec<&RegisteredDevice> {
+        match self.devices.get(unique_device_name) {
+            None => Vec::new(),
+            Some(root) => self
+                .devices
+                .values()
+                .filter(|device| {
+                    device.location == root.location
+                        && device.unique_device_name != root.unique_device_name
+                })
+                .collect(),
+        }
+    }
+
+    ///
+    /// Perform a multicast search, using `search_once`, and add the responses to the registry.
+    /// Expired devices are removed at the same time.
+    ///
+    pub fn search(&mut self, options: Options) -> Result<Vec<RegistryEvent>, Error> {
+        let responses = search_once(options)?;
+        Ok(self.responses_received(&responses, Instant::now()))
+    }
+
+    ///
+    /// Perform a multicast search, as `search`, but send and receive messages using the provided
+    /// transport rather than a newly created UDP socket.
+    ///
+    pub fn search_using(
+        &mut self,
+        options: Options,
+        transport: &dyn Transport,
+    ) -> Result<Vec<RegistryEvent>, Error> {
+        let responses = search_once_using(options, transport)?;
+        Ok(self.responses_received(&responses, Instant::now()))
+    }
+
+    ///
+    /// Wait up to `timeout` for the next message on the multicast group, add it to the registry
+    /// if it is a notification, and remove any devices that have expired.
+    ///
+    pub fn listen(
+        &mut self,
+        listener: &Listener,
+        timeout: Duration,
+    ) -> Result<Vec<RegistryEvent>, Error> {
+        let mut events = match listener.next_message(timeout)? {
+            Some((_, message)) => self.message_received(&message),
+            None => Vec::new(),
+        };
+        events.extend(self.expire());
+        Ok(events)
+    }
+
+    ///
+    /// Add a message received from the multicast group; searches from other control points are
+    /// ignored.
+    ///
+    pub fn message_received(&mut self, message: &Message) -> Vec<RegistryEvent> {
+        match message {
+            Message::Notification(notification) => self.notification_received(notification),
+            Message::Search(_) => Vec::new(),
+        }
+    }
+
+    ///
+    /// Add a notification received from a device.
+    ///
+    pub fn notification_received(&mut self, notification: &Notification) -> Vec<RegistryEvent> {
+        self.notification_received_at(notification, Instant::now())
+    }
+
+    ///
+    /// Add a response to a search.
+    ///
+    pub fn response_received(&mut self, response: &Response) -> Vec<RegistryEvent> {
+        self.response_received_at(response, Instant::now())
+    }
+
+    ///
+    /// Remove all devices whose advertisements have expired.
+    ///
+    pub fn expire(&mut self) -> Vec<RegistryEvent> {
+        self.expire_at(Instant::now())
+    }
+
+    fn responses_received(&mut self, responses: &[Response], now: Instant) -> Vec<RegistryEvent> {
+        let mut events: Vec<RegistryEvent> = responses
+            .iter()
+            .flat_map(|response| self.response_received_at(response, now))
+            .collect();
+        events.extend(self.expire_at(now));
+        events
+    }
+
+    fn notification_received_at(
+        &mut self,
+        notification: &Notification,
+        now: Instant,
+    ) -> Vec<RegistryEvent> {
+        let unique_device_name = match unique_device_name(&notification.service_name) {
+            Some(unique_device_name) => unique_device_name,
+            None => return Vec::new(),
+        };
+        match notification.sub_type {
+            NotificationSubType::ByeBye => match self.devices.remove(&unique_device_name) {
+                Some(device) => {
+                    info!(
+                        "notification_received - device {} has left",
+                        unique_device_name
+                    );
+                    vec![RegistryEvent::DeviceRemoved(device)]
+                }
+                None => Vec::new(),
+            },
+            NotificationSubType::Alive => match &notification.location {
+                Some(location) => self.advertised(
+                    unique_device_name,
+                    Advertisement {
+                        target: &notification.notification_type,
+                        location,
+                        boot_id: notification.boot_id,
+                        config_id: notification.config_id,
+                        search_port: notification.search_port,
+                        server: notification.server.clone(),
+                        max_age: notification.max_age,
+                    },
+                    now,
+                ),
+                None => {
+                    warn!(
+                        "notification_received - ignoring alive with no location from {}",
+                        unique_device_name
+                    );
+                    Vec::new()
+                }
+            },
+            NotificationSubType::Update => {
+                let device = match self.devices.get_mut(&unique_device_name) {
+                    Some(device) => device,
+                    None => {
+                        trace!(
+                            "notification_received - ignoring update for unknown device {}",
+                            unique_device_name
+                        );
+                        return Vec::new();
+                    }
+                };
+                // The device is announcing its next boot ID, not that it has rebooted, so the
+                // new value is simply recorded.
+                if let Some(next_boot_id) = notification.next_boot_id {
+                    device.boot_id = Some(next_boot_id);
+                }
+                let mut changed = false;
+                if let Some(location) = &notification.location {
+                    changed |= *location != device.location;
+                    device.location = location.clone();
+                }
+                if notification.config_id.is_some() {
+                    changed |= notification.config_id != device.config_id;
+                    device.config_id = notification.config_id;
+                }
+                if notification.search_port.is_some() {
+                    device.search_port = notification.search_port;
+                }
+                if changed {
+                    vec![RegistryEvent::DeviceUpdated(device.clone())]
+                } else {
+                    Vec::new()
+                }
+            }
+        }
+    }
+
+    fn response_received_at(&mut self, response: &Response, now: Instant) -> Vec<RegistryEvent> {
+        match unique_device_name(&response.service_name) {
+            Some(unique_device_name) => self.advertised(
+                unique_device_name,
+                Advertisement {
+                    target: &response.search_target,
+                    location: &response.location,
+                    boot_id: response.boot_id,
+                    config_id: response.config_id,
+                    search_port: response.search_port,
+                    server: Some(response.versions.to_string()),
+                    max_age: Some(response.max_age),
+                },
+                now,
+            ),
+            None => Vec::new(),
+        }
+    }
+
+    fn expire_at(&mut self, now: Instant) -> Vec<RegistryEvent> {
+        let expired: Vec<String> = self
+            .devices
+            .values()
+            .filter(|device| device.expires <= now)
+            .map(|device| device.unique_device_name.clone())
+            .collect();
+        expired
+            .iter()
+            .filter_map(|unique_device_name| self.devices.remove(unique_device_name))
+            .map(|device| {
+                info!("expire - device {} has expired", device.unique_device_name);
+                RegistryEvent::DeviceRemoved(device)
+            })
+            .collect()
+    }
+
+    fn advertised(
+        &mut self,
+        unique_device_name: String,
+        advertisement: Advertisement<'_>,
+        now: Instant,
+    ) -> Vec<RegistryEvent> {
+        let expires = now + advertisement.max_age.unwrap_or(DEFAULT_MAX_AGE);
+        let mut events = Vec::new();
+        match self.devices.get_mut(&unique_device_name) {
+            None => {
+                info!("advertised - device {} added", unique_device_name);
+                let mut device = RegisteredDevice {
+                    unique_device_name: unique_device_name.clone(),
+                    location: advertisement.location.clone(),
+                    boot_id: advertisement.boot_id,
+                    config_id: advertisement.config_id,
+                    search_port: advertisement.search_port,
+                    server: advertisement.server,
+                    root_device: false,
+                    device_types: Default::default(),
+                    service_types: Default::default(),
+                    expires,
+                };
+                device.add_target(advertisement.target);
+                events.push(RegistryEvent::DeviceAdded(device.clone()));
+                let _ = self.devices.insert(unique_device_name, device);
+            }
+            Some(device) => {
+                device.expires = expires;
+                device.add_target(advertisement.target);
+                if advertisement.server.is_some() {
+                    device.server = advertisement.server;
+                }
+                if advertisement.search_port.is_some() {
+                    device.search_port = advertisement.search_port;
+                }
+                let mut rebooted = false;
+                if let Some(boot_id) = advertisement.boot_id {
+                    rebooted = device.boot_id.is_some_and(|current| current != boot_id);
+                    device.boot_id = Some(boot_id);
+                }
+                let mut changed = *advertisement.location != device.location;
+                device.location = advertisement.location.clone();
+                if advertisement.config_id.is_some() {
+                    changed |= advertisement.config_id != device.config_id;
+                    device.config_id = advertisement.config_id;
+                }
+                if rebooted {
+                    info!("advertised - device {} rebooted", unique_device_name);
+                    events.push(RegistryEvent::DeviceRebooted(device.clone()));
+                }
+                if changed {
+                    info!("advertised - device {} updated", unique_device_name);
+                    events.push(RegistryEvent::DeviceUpdated(device.clone()));
+                }
+            }
+        }
+        events
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(1800);
+
+///
+/// The values common to an `ssdp:alive` notification and a search response.
+///
+struct Advertisement<'a> {
+    target: &'a SearchTarget,
+    location: &'a URL,
+    boot_id: Option<u32>,
+    config_id: Option<u64>,
+    search_port: Option<u16>,
+    server: Option<String>,
+    max_age: Option<Duration>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// The UDN is the `uuid:{device-UUID}` portion of a USN, before any `::` separator.
+///
+fn unique_device_name(service_name: &URI) -> Option<String> {
+    let service_name = service_name.to_string();
+    let unique_device_name = service_name.split("::").next().unwrap_or_default();
+    if unique_device_name.starts_with("uuid:") && unique_device_name.len() > 5 {
+        Some(unique_device_name.to_string())
+    } else {
+        warn!(
+            "unique_device_name - ignoring USN '{}' with no device UUID",
+            service_name
+        );
+        None
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::httpu::Request;
+    use pretty_assertions::assert_eq;
+    use std::convert::TryFrom;
+
+    const ROOT: &str = "uuid:2f402f80-da50-11e1-9b23-00178829d301";
+
+    const EMBEDDED: &str = "uuid:2f402f80-da50-11e1-9b23-00178829d302";
+
+    fn notification(sub_type: &str, usn: &str, nt: &str, extra: &str) -> Notification {
+        let request = Request::try_from(
+            format!(
+                "NOTIFY * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nCACHE-CONTROL: max-age=100\r\nNT: {}\r\nNTS: {}\r\nUSN: {}\r\n{}\r\n",
+                nt, sub_type, usn, extra
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        Notification::try_from(&request).unwrap()
+    }
+
+    fn alive(usn: &str, nt: &str, location: &str, boot_id: u32, config_id: u64) -> Notification {
+        notification(
+            "ssdp:alive",
+            usn,
+            nt,
+            &format!(
+                "LOCATION: {}\r\nSERVER: Linux/5.4 UPnP/1.1 Example/1.0\r\nBOOTID.UPNP.ORG: {}\r\nCONFIGID.UPNP.ORG: {}\r\n",
+                location, boot_id, config_id
+            ),
+        )
+    }
+
+    fn names(events: &[RegistryEvent]) -> Vec<&str> {
+        events
+            .iter()
+            .map(|event| match event {
+                RegistryEvent::DeviceAdded(_) => "added",
+                RegistryEvent::DeviceUpdated(_) => "updated",
+                RegistryEvent::DeviceRebooted(_) => "rebooted",
+                RegistryEvent::DeviceRemoved(_) => "removed",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_device_life_cycle() {
+        let location = "http://192.168.1.10:49152/description.xml";
+        let root_usn = format!("{}::upnp:rootdevice", ROOT);
+        let mut registry = Registry::new();
+        let now = Instant::now();
+
+        let events = registry
+            .notification_received_at(&alive(&root_usn, "upnp:rootdevice", location, 1, 1), now);
+        assert_eq!(names(&events), vec!["added"]);
+        let events = registry.notification_received_at(
+            &alive(
+                &format!("{}::urn:schemas-upnp-org:service:SwitchPower:1", ROOT),
+                "urn:schemas-upnp-org:service:SwitchPower:1",
+                location,
+                1,
+                1,
+            ),
+            now,
+        );
+        assert!(events.is_empty());
+
+        let device = registry.device(ROOT).unwrap();
+        assert!(device.root_device);
+        assert_eq!(device.boot_id, Some(1));
+        assert_eq!(
+            device.service_types.iter().collect::<Vec<&String>>(),
+            vec!["urn:schemas-upnp-org:service:SwitchPower:1"]
+        );
+        assert_eq!(device.expires, now + Duration::from_secs(100));
+
+        // A new configuration means the description must be fetched again.
+        let events = registry
+            .notification_received_at(&alive(&root_usn, "upnp:rootdevice", location, 1, 2), now);
+        assert_eq!(names(&events), vec!["updated"]);
+
+        // An update announcing the next boot ID is not a reboot.
+        let events = registry.notification_received_at(
+            &notification(
+                "ssdp:update",
+                &root_usn,
+                "upnp:rootdevice",
+                &format!(
+                    "LOCATION: {}\r\nBOOTID.UPNP.ORG: 1\r\nNEXTBOOTID.UPNP.ORG: 2\r\nCONFIGID.UPNP.ORG: 2\r\nSEARCHPORT.UPNP.ORG: 1901\r\n",
+                    location
+                ),
+            ),
+            now,
+        );
+        assert!(events.is_empty());
+        assert_eq!(
+            registry.device(ROOT).unwrap().search_address().unwrap(),
+            "192.168.1.10:1901".parse().unwrap()
+        );
+        let events = registry
+            .notification_received_at(&alive(&root_usn, "upnp:rootdevice", location, 2, 2), now);
+        assert!(events.is_empty());
+
+        let events = registry.notification_received_at(
+            &alive(
+                &root_usn,
+                "upnp:rootdevice",
+                "http://192.168.1.11:49152/description.xml",
+                3,
+                2,
+            ),
+            now,
+        );
+        assert_eq!(names(&events), vec!["rebooted", "updated"]);
+
+        let events = registry.notification_received_at(
+            &notification("ssdp:byebye", &root_usn, "upnp:rootdevice", ""),
+            now,
+        );
+        assert_eq!(names(&events), vec!["removed"]);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_embedded_devices_and_expiry() {
+        let location = "http://192.168.1.10:49152/description.xml";
+        let mut registry = Registry::new();
+        let now = Instant::now();
+
+        let _ = registry.notification_received_at(
+            &alive(
+                &format!("{}::upnp:rootdevice", ROOT),
+                "upnp:rootdevice",
+                location,
+                1,
+                1,
+            ),
+            now,
+        );
+        let _ = registry.notification_received_at(
+            &alive(
+                &format!("{}::urn:schemas-upnp-org:device:BinaryLight:1", EMBEDDED),
+                "urn:schemas-upnp-org:device:BinaryLight:1",
+                location,
+                1,
+                1,
+            ),
+            now + Duration::from_secs(50),
+        );
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.root_devices().count(), 1);
+        let embedded = registry.embedded_devices(ROOT);
+        assert_eq!(embedded.len(), 1);
+        assert_eq!(embedded[0].unique_device_name, EMBEDDED);
+        assert!(embedded[0]
+            .device_types
+            .contains("urn:schemas-upnp-org:device:BinaryLight:1"));
+
+        assert!(registry.expire_at(now + Duration::from_secs(99)).is_empty());
+        let events = registry.expire_at(now + Duration::from_secs(100));
+        assert_eq!(names(&events), vec!["removed"]);
+        assert!(registry.device(ROOT).is_none());
+        assert!(registry.device(EMBEDDED).is_some());
+    }
+
+    #[test]
+    fn test_search_using_memory_transport() {
+        use crate::common::httpu::MemoryTransport;
+        use crate::SpecVersion;
+
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        transport.push_response(
+            "192.168.1.30:1900".parse().unwrap(),
+            format!(
+                "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=100\r\nDATE: Fri, 02 Oct 2020 16:35:21 GMT\r\nEXT:\r\nLOCATION: http://192.168.1.30:49152/description.xml\r\nSERVER: Linux/3.14.0 UPnP/1.0 IpBridge/1.40.0\r\nST: upnp:rootdevice\r\nUSN: {}::upnp:rootdevice\r\n\r\n",
+                ROOT
+            )
+            .as_bytes(),
+        );
+        let mut registry = Registry::new();
+        let events = registry
+            .search_using(Options::default_for(SpecVersion::V10), &transport)
+            .unwrap();
+        assert_eq!(names(&events), vec!["added"]);
+        let device = registry.device(ROOT).unwrap();
+        assert_eq!(
+            device.location.to_string(),
+            "http://192.168.1.30:49152/description.xml"
+        );
+        assert_eq!(device.boot_id, None);
+        assert!(device.root_device);
+    }
+}
@@ -0,0 +1,674 @@
+/*!
+This module provides the device side of search; a `Responder` joins the SSDP multicast group and
+answers each `M-SEARCH` request with one response, sent unicast to the control point, for each of
+the device's advertisements that matches the search target. Responses to a multicast search are
+each sent after a random delay of up to the `MX` seconds the control point asked for. Searches
+are first checked by a [`SearchGuard`](../guard/struct.SearchGuard.html); by default only
+searches from the host's own networks are answered, and both the searches from each source and
+the total responses sent are rate limited.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::description::device::DeviceRoot;
+use upnp_rs::discovery::listen::Options as ListenOptions;
+use upnp_rs::discovery::notify::{advertisements_for, Options};
+use upnp_rs::discovery::respond::Responder;
+use upnp_rs::SpecVersion;
+use std::str::FromStr;
+use std::time::Duration;
+
+# fn description() -> DeviceRoot { unimplemented!() }
+let root: DeviceRoot = description();
+let location = URL::from_str("http://192.168.1.10:49152/description.xml").unwrap();
+let responder = Responder::new(
+    &ListenOptions::default(),
+    advertisements_for(&root, &location, 1).unwrap(),
+    Options::default_for(SpecVersion::V11),
+)
+.unwrap();
+loop {
+    if let Some((from, search, sent)) = responder.next_search(Duration::from_secs(5)).unwrap() {
+        println!("{} searched for {}, sent {} responses", from, search.search_target, sent);
+    }
+}
+```
+*/
+
+use crate::common::http::http_date;
+use crate::common::httpu::{Response, ResponseBuilder};
+use crate::common::user_agent::user_agent_string;
+use crate::discovery::guard::{is_multicast_search, GuardCounters, GuardOptions, SearchGuard};
+use crate::discovery::listen::{Listener, Message, Options as ListenOptions};
+use crate::discovery::location::{located_at, LocationTemplate};
+use crate::discovery::notify::{Device, Options};
+use crate::discovery::search::{SearchRequest, SearchTarget};
+use crate::error::Error;
+use crate::syntax::{
+    HTTP_HEADER_BOOTID, HTTP_HEADER_CACHE_CONTROL, HTTP_HEADER_CONFIGID, HTTP_HEADER_DATE,
+    HTTP_HEADER_EXT, HTTP_HEADER_LOCATION, HTTP_HEADER_SEARCH_PORT, HTTP_HEADER_SECURE_LOCATION,
+    HTTP_HEADER_SERVER, HTTP_HEADER_ST, HTTP_HEADER_USN,
+};
+use crate::SpecVersion;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, trace, warn};
+use uuid::Uuid;
+
+// ------------------------------------------------------------------------------------------------
+// Public Types
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Answers searches on the multicast group on behalf of a set of advertisements, usually those
+/// returned by `notify::advertisements_for`.
+///
+#[derive(Debug)]
+pub struct Responder {
+    listener: Listener,
+    advertisements: Vec<Device>,
+    location_template: Option<LocationTemplate>,
+    guard: Mutex<SearchGuard>,
+    pending: Mutex<Vec<PendingResponse>>,
+    options: Options,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Public Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Return the responses to send for `request`, one for each advertisement that matches the
+/// search target. For `ssdp:all` each response carries the advertisement's own notification
+/// type, otherwise the search target as sent, so that a search for an earlier version of a type
+/// is answered with that version.
+///
+pub fn search_responses(
+    advertisements: &[Device],
+    request: &SearchRequest,
+    options: &Options,
+) -> Vec<Response> {
+    matching(advertisements, request)
+        .map(|advertisement| {
+            let search_target = match request.search_target {
+                SearchTarget::All => &advertisement.notification_type,
+                _ => &request.search_target,
+            };
+            search_response(advertisement, search_target, options)
+        })
+        .collect()
+}
+
+///
+/// Return the response for a single advertisement, with `search_target` as the `ST` value.
+///
+pub fn search_response(
+    advertisement: &Device,
+    search_target: &SearchTarget,
+    options: &Options,
+) -> Response {
+    let mut builder = ResponseBuilder::ok();
+    builder
+        .add_header(
+            HTTP_HEADER_CACHE_CONTROL,
+            &format!("max-age={}", options.max_age),
+        )
+        .add_header(HTTP_HEADER_DATE, &http_date(SystemTime::now()))
+        .add_header(HTTP_HEADER_EXT, "")
+        .add_header(HTTP_HEADER_LOCATION, &advertisement.location.to_string())
+        .add_header(
+            HTTP_HEADER_SERVER,
+            &user_agent_string(options.spec_version, options.product_and_version.clone()),
+        )
+        .add_header(HTTP_HEADER_ST, &search_target.to_string())
+        .add_header(HTTP_HEADER_USN, &advertisement.service_name.to_string());
+    if options.spec_version >= SpecVersion::V11 {
+        builder
+            .add_header(HTTP_HEADER_BOOTID, &advertisement.boot_id.to_string())
+            .add_header(HTTP_HEADER_CONFIGID, &advertisement.config_id.to_string());
+        if let Some(search_port) = &advertisement.search_port {
+            builder.add_header(HTTP_HEADER_SEARCH_PORT, &search_port.to_string());
+        }
+    }
+    if options.spec_version >= SpecVersion::V20 {
+        if let Some(secure_location) = &advertisement.secure_location {
+            builder.add_header(HTTP_HEADER_SECURE_LOCATION, &secure_location.to_string());
+        }
+    }
+    builder.into()
+}
+
+// ------------------------------------------------------------------------------------------------
+// Implementations
+// ------------------------------------------------------------------------------------------------
+
+impl Responder {
+    ///
+    /// Create a new responder, joining the multicast group described by `listen_options`, that
+    /// answers for `advertisements`.
+    ///
+    pub fn new(
+        listen_options: &ListenOptions,
+        advertisements: Vec<Device>,
+        options: Options,
+    ) -> Result<Self, Error> {
+        Ok(Self::with_listener(
+            Listener::new(listen_options)?,
+            advertisements,
+            options,
+        ))
+    }
+
+    ///
+    /// Create a responder that receives searches from, and sends responses using, the provided
+    /// listener.
+    ///
+    pub fn with_listener(
+        listener: Listener,
+        advertisements: Vec<Device>,
+        options: Options,
+    ) -> Self {
+        Self {
+            listener,
+            advertisements,
+            location_template: None,
+            guard: Mutex::new(SearchGuard::new(GuardOptions::default())),
+            pending: Default::default(),
+            options,
+        }
+    }
+
+    ///
+    /// The advertisements this responder answers for.
+    ///
+    pub fn advertisements(&self) -> &Vec<Device> {
+        &self.advertisements
+    }
+
+    ///
+    /// Replace the advertisements this responder answers for, for example when they have a new
+    /// `LOCATION` or boot ID.
+    ///
+    pub fn set_advertisements(&mut self, advertisements: Vec<Device>) {
+        self.advertisements = advertisements;
+    }
+
+    ///
+    /// Take the `LOCATION` of each response from `template`, with the address of the interface
+    /// on the route to the control point, rather than from the advertisement; this is needed
+    /// when the device is reachable on more than one interface. If the address cannot be
+    /// determined the advertisement's own `LOCATION` is sent.
+    ///
+    pub fn set_location_template(&mut self, template: Option<LocationTemplate>) {
+        self.location_template = template;
+    }
+
+    ///
+    /// Replace the limits used to decide which searches are answered; the counters are reset.
+    ///
+    pub fn set_guard_options(&mut self, options: GuardOptions) {
+        self.guard = Mutex::new(SearchGuard::new(options));
+    }
+
+    ///
+    /// The number of searches answered and dropped, by reason, so far.
+    ///
+    pub fn counters(&self) -> GuardCounters {
+        self.guard.lock().unwrap().counters()
+    }
+
+    ///
+    /// Join the multicast group again after the network interfaces have changed, see
+    /// `Listener::rejoin`.
+    ///
+    pub fn rejoin(&mut self) -> Result<(), Error> {
+        self.listener.rejoin()
+    }
+
+    ///
+    /// Wait up to `timeout` for the next search and answer it, returning the address of the
+    /// control point, its request, and the number of responses sent or scheduled; `None` is
+    /// returned if no search arrived in time. Notifications seen on the group are skipped, as
+    /// are searches, and invalid packets, dropped by the guard. Responses to earlier searches
+    /// are sent while waiting, as their delay expires, so this should be called in a loop.
+    ///
+    pub fn next_search(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<(SocketAddr, SearchRequest, usize)>, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let now = Instant::now();
+            let _ = self.send_pending(Some(now));
+            let remaining = deadline.saturating_duration_since(now);
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            let next_due = self.next_due();
+            let wait = match next_due {
+                Some(due) => remaining.min(due.saturating_duration_since(now)),
+                None => remaining,
+            };
+            match self.listener.next_packet(wait)? {
+                Some((from, Ok(Message::Search(request)))) => {
+                    if let Some(sent) = self.respond(&from, &request) {
+                        return Ok(Some((from, request, sent)));
+                    }
+                }
+                Some((from, Ok(Message::Notification(_)))) => {
+                    trace!("next_search - skipping notification from {}", from)
+                }
+                Some((from, Err(_))) => {
+                    let _ = self.guard.lock().unwrap().invalid(&from);
+                }
+                None => match next_due {
+                    // The transport may return early, so wait out any remaining delay.
+                    Some(due) => {
+                        thread::sleep(due.min(deadline).saturating_duration_since(Instant::now()))
+                    }
+                    None => return Ok(None),
+                },
+            }
+        }
+    }
+
+    ///
+    /// Send all responses still waiting for their delay to expire, returning the number sent;
+    /// for example before the responder is dropped.
+    ///
+    pub fn flush(&self) -> usize {
+        self.send_pending(None)
+    }
+
+    fn respond(&self, to_address: &SocketAddr, request: &SearchRequest) -> Option<usize> {
+        // The guard runs before any work, such as finding the route for LOCATION, is done.
+        let count = matching(&self.advertisements, request).count();
+        if self
+            .guard
+            .lock()
+            .unwrap()
+            .check(to_address, request, count)
+            .is_err()
+        {
+            return None;
+        }
+        let responses = match &self.location_template {
+            Some(template) => match template.location_to(to_address) {
+                Ok(location) => search_responses(
+                    &located_at(&self.advertisements, &location),
+                    request,
+                    &self.options,
+                ),
+                Err(e) => {
+                    warn!("respond - no location for {}: {}", to_address, e);
+                    search_responses(&self.advertisements, request, &self.options)
+                }
+            },
+            None => search_responses(&self.advertisements, request, &self.options),
+        };
+        if is_multicast_search(request) {
+            let max_delay = max_response_delay(request, &self.options);
+            info!(
+                "respond - scheduling {} responses for {} to {} within {:?}",
+                responses.len(),
+                request.search_target,
+                to_address,
+                max_delay
+            );
+            let now = Instant::now();
+            self.pending
+                .lock()
+                .unwrap()
+                .extend(responses.iter().map(|response| PendingResponse {
+                    send_at: now + random_delay(max_delay),
+                    to_address: *to_address,
+                    bytes: response.into(),
+                }));
+            Some(responses.len())
+        } else {
+            // A unicast search is answered immediately, it has no MX.
+            info!(
+                "respond - sending {} responses for {} to {}",
+                responses.len(),
+                request.search_target,
+                to_address
+            );
+            Some(
+                responses
+                    .iter()
+                    .filter(|response| {
+                        let bytes: Vec<u8> = (*response).into();
+                        self.send(to_address, &bytes)
+                    })
+                    .count(),
+            )
+        }
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.pending
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|pending| pending.send_at)
+            .min()
+    }
+
+    fn send_pending(&self, due_by: Option<Instant>) -> usize {
+        let due: Vec<PendingResponse> = {
+            let mut pending = self.pending.lock().unwrap();
+            let (due, later) = pending
+                .drain(..)
+                .partition(|pending| due_by.is_none_or(|due_by| pending.send_at <= due_by));
+            *pending = later;
+            due
+        };
+        due.iter()
+            .filter(|pending| self.send(&pending.to_address, &pending.bytes))
+            .count()
+    }
+
+    fn send(&self, to_address: &SocketAddr, bytes: &[u8]) -> bool {
+        match self.listener.transport().send_to(bytes, to_address) {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("send - could not send response to {}: {}", to_address, e);
+                false
+            }
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Types
+// ------------------------------------------------------------------------------------------------
+
+#[derive(Debug)]
+struct PendingResponse {
+    send_at: Instant,
+    to_address: SocketAddr,
+    bytes: Vec<u8>,
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Functions
+// ------------------------------------------------------------------------------------------------
+
+///
+/// UDA 1.1 and later tell devices to treat an `MX` above 5 as 5.
+///
+fn max_response_delay(request: &SearchRequest, options: &Options) -> Duration {
+    let max_wait_time = request.max_wait_time.unwrap_or(1);
+    Duration::from_secs(u64::from(if options.spec_version >= SpecVersion::V11 {
+        max_wait_time.min(5)
+    } else {
+        max_wait_time
+    }))
+}
+
+///
+/// A random delay between zero and `max_delay`, to the millisecond; the random bits come from a
+/// version 4 UUID, as used for new device names.
+///
+fn random_delay(max_delay: Duration) -> Duration {
+    let millis = Uuid::new_v4().as_u128() % (max_delay.as_millis() + 1);
+    Duration::from_millis(millis as u64)
+}
+
+fn matching<'a>(
+    advertisements: &'a [Device],
+    request: &'a SearchRequest,
+) -> impl Iterator<Item = &'a Device> {
+    advertisements.iter().filter(move |advertisement| {
+        request
+            .search_target
+            .matches(&advertisement.notification_type)
+    })
+}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::httpu::MemoryTransport;
+    use crate::common::uri::URL;
+    use crate::description::device::DeviceRoot;
+    use crate::discovery::notify::advertisements_for;
+    use pretty_assertions::assert_eq;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    const EX_DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0" configId="7">
+  <specVersion><major>1</major><minor>1</minor></specVersion>
+  <device>
+    <deviceType>urn:schemas-upnp-org:device:BinaryLight:1</deviceType>
+    <friendlyName>Light</friendlyName>
+    <manufacturer>Example</manufacturer>
+    <modelName>L1</modelName>
+    <UDN>uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d5</UDN>
+    <serviceList>
+      <service>
+        <serviceType>urn:schemas-upnp-org:service:SwitchPower:1</serviceType>
+        <serviceId>urn:upnp-org:serviceId:SwitchPower</serviceId>
+        <SCPDURL>/SwitchPower.xml</SCPDURL>
+        <controlURL>/control/SwitchPower</controlURL>
+        <eventSubURL>/event/SwitchPower</eventSubURL>
+      </service>
+    </serviceList>
+  </device>
+</root>"#;
+
+    fn responses_to(search: &[u8]) -> Vec<(SocketAddr, String)> {
+        responses_from("192.168.1.12:50000", search, None)
+    }
+
+    fn responses_from(
+        from: &str,
+        search: &[u8],
+        template: Option<LocationTemplate>,
+    ) -> Vec<(SocketAddr, String)> {
+        let root = DeviceRoot::from_str(EX_DESCRIPTION).unwrap();
+        let location = URL::from_str("http://192.168.1.10:49152/description.xml").unwrap();
+        let advertisements = advertisements_for(&root, &location, 3).unwrap();
+        let transport = Arc::new(MemoryTransport::new("0.0.0.0:1900".parse().unwrap()));
+        let _ = transport.push_response(from.parse().unwrap(), search);
+        let mut responder = Responder::with_listener(
+            Listener::with_transport(Box::new(transport.clone())),
+            advertisements,
+            Options::default_for(SpecVersion::V11),
+        );
+        responder.set_location_template(template);
+        responder.set_guard_options(GuardOptions {
+            allowed_networks: vec![
+                "192.168.1.0/24".parse().unwrap(),
+                "127.0.0.0/8".parse().unwrap(),
+            ],
+            ..Default::default()
+        });
+        let (_, _, sent) = responder
+            .next_search(Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(responder.flush(), sent);
+        let sent_messages = transport.sent();
+        assert_eq!(sent, sent_messages.len());
+        sent_messages
+            .into_iter()
+            .map(|(to, bytes)| (to, String::from_utf8(bytes).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_advertisements_for() {
+        let root = DeviceRoot::from_str(EX_DESCRIPTION).unwrap();
+        let location = URL::from_str("http://192.168.1.10:49152/description.xml").unwrap();
+        let advertisements = advertisements_for(&root, &location, 3).unwrap();
+        assert_eq!(
+            advertisements
+                .iter()
+                .map(|a| (a.notification_type.to_string(), a.service_name.to_string()))
+                .collect::<Vec<(String, String)>>(),
+            vec![
+                (
+                    "upnp:rootdevice".to_string(),
+                    "uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d5::upnp:rootdevice".to_string()
+                ),
+                (
+                    "uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d5".to_string(),
+                    "uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d5".to_string()
+                ),
+                (
+                    "urn:schemas-upnp-org:device:BinaryLight:1".to_string(),
+                    "uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d5::urn:schemas-upnp-org:device:BinaryLight:1".to_string()
+                ),
+                (
+                    "urn:schemas-upnp-org:service:SwitchPower:1".to_string(),
+                    "uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d5::urn:schemas-upnp-org:service:SwitchPower:1".to_string()
+                ),
+            ]
+        );
+        assert!(advertisements
+            .iter()
+            .all(|a| a.config_id == 7 && a.boot_id == 3));
+    }
+
+    #[test]
+    fn test_respond_to_search() {
+        let all = responses_to(
+            b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: ssdp:all\r\n\r\n",
+        );
+        assert_eq!(all.len(), 4);
+        assert!(all
+            .iter()
+            .all(|(to, _)| to.to_string() == "192.168.1.12:50000"));
+
+        let typed = responses_to(
+            b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: urn:schemas-upnp-org:service:SwitchPower:1\r\n\r\n",
+        );
+        assert_eq!(typed.len(), 1);
+        let response = &typed[0].1;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("ST:urn:schemas-upnp-org:service:SwitchPower:1\r\n"));
+        assert!(response.contains(
+            "USN:uuid:6c1b0f2e-0000-1000-8000-0013a2b3c4d5::urn:schemas-upnp-org:service:SwitchPower:1\r\n"
+        ));
+        assert!(response.contains("LOCATION:http://192.168.1.10:49152/description.xml\r\n"));
+        assert!(response.contains("BOOTID.UPNP.ORG:3\r\n"));
+
+        let none = responses_to(
+            b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: urn:schemas-upnp-org:service:Dimming:1\r\n\r\n",
+        );
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_multicast_responses_are_delayed() {
+        let root = DeviceRoot::from_str(EX_DESCRIPTION).unwrap();
+        let location = URL::from_str("http://192.168.1.10:49152/description.xml").unwrap();
+        let transport = Arc::new(MemoryTransport::new("0.0.0.0:1900".parse().unwrap()));
+        let _ = transport
+            .push_response(
+                "192.168.1.12:50000".parse().unwrap(),
+                b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: ssdp:all\r\n\r\n",
+            )
+            .push_response(
+                "192.168.1.12:50000".parse().unwrap(),
+                b"M-SEARCH * HTTP/1.1\r\nHOST: 192.168.1.10:1900\r\nMAN: \"ssdp:discover\"\r\nST: upnp:rootdevice\r\n\r\n",
+            );
+        let mut responder = Responder::with_listener(
+            Listener::with_transport(Box::new(transport.clone())),
+            advertisements_for(&root, &location, 3).unwrap(),
+            Options::default_for(SpecVersion::V11),
+        );
+        responder.set_guard_options(GuardOptions {
+            allowed_networks: vec!["192.168.1.0/24".parse().unwrap()],
+            ..Default::default()
+        });
+        let started = Instant::now();
+        let (_, _, scheduled) = responder
+            .next_search(Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(scheduled, 4);
+        assert!(transport.sent().is_empty());
+
+        let (_, _, sent) = responder
+            .next_search(Duration::from_secs(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(sent, 1);
+        assert!(!transport.sent().is_empty());
+
+        assert!(responder
+            .next_search(Duration::from_millis(1500))
+            .unwrap()
+            .is_none());
+        assert_eq!(transport.sent().len(), 5);
+        assert!(started.elapsed() <= Duration::from_millis(1500));
+        assert_eq!(responder.flush(), 0);
+    }
+
+    #[test]
+    fn test_respond_with_location_template() {
+        let responses = responses_from(
+            "127.0.0.1:50000",
+            b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: upnp:rootdevice\r\n\r\n",
+            Some(LocationTemplate::from_str("http://{host}:49152/description.xml").unwrap()),
+        );
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0]
+            .1
+            .contains("LOCATION:http://127.0.0.1:49152/description.xml\r\n"));
+    }
+
+    #[test]
+    fn test_guard_drops_searches() {
+        let root = DeviceRoot::from_str(EX_DESCRIPTION).unwrap();
+        let location = URL::from_str("http://192.168.1.10:49152/description.xml").unwrap();
+        let transport = Arc::new(MemoryTransport::new("0.0.0.0:1900".parse().unwrap()));
+        let _ = transport
+            .push_response(
+                "203.0.113.9:1900".parse().unwrap(),
+                b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: ssdp:all\r\n\r\n",
+            )
+            .push_response(
+                "192.168.1.12:50000".parse().unwrap(),
+                b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:explore\"\r\nMX: 2\r\nST: ssdp:all\r\n\r\n",
+            )
+            .push_response(
+                "192.168.1.12:50000".parse().unwrap(),
+                b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nST: ssdp:all\r\n\r\n",
+            );
+        let mut responder = Responder::with_listener(
+            Listener::with_transport(Box::new(transport.clone())),
+            advertisements_for(&root, &location, 3).unwrap(),
+            Options::default_for(SpecVersion::V11),
+        );
+        responder.set_guard_options(GuardOptions {
+            allowed_networks: vec!["192.168.1.0/24".parse().unwrap()],
+            ..Default::default()
+        });
+        assert!(responder
+            .next_search(Duration::from_secs(1))
+            .unwrap()
+            .is_none());
+        assert!(transport.sent().is_empty());
+        assert_eq!(
+            responder.counters(),
+            GuardCounters {
+                accepted: 0,
+                not_allowed: 1,
+                rate_limited: 0,
+                over_budget: 0,
+                invalid: 2,
+            }
+        );
+    }
+}
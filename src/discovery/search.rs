@@ -9,13 +9,18 @@ TBD
 
 */
 use crate::common::headers;
+use crate::common::http::{parse_http_date, read_response};
 use crate::common::httpu::{
-    multicast, Options as MulticastOptions, RequestBuilder, Response as MulticastResponse,
+    multicast_using, Headers, Options as MulticastOptions, Request, RequestBuilder,
+    Response as MulticastResponse, Transport, UdpTransport, DEFAULT_BUFFER_SIZE,
 };
-use crate::common::interface::IP;
+use crate::common::interface::{ip_address_for_interface, IP};
 use crate::common::uri::{URI, URL};
 use crate::common::user_agent::user_agent_string;
-use crate::discovery::{ControlPoint, ProductVersion, ProductVersions};
+use crate::description::TypeID;
+use crate::discovery::{
+    multicast_address, ControlPoint, Extension, ProductVersion, ProductVersions,
+};
 use crate::error::{
     invalid_field_value, invalid_header_value, invalid_value_for_type, missing_required_field,
     unsupported_operation, unsupported_version, Error, MessageFormatError,
@@ -24,19 +29,20 @@ use crate::syntax::{
     HTTP_EXTENSION, HTTP_HEADER_BOOTID, HTTP_HEADER_CACHE_CONTROL, HTTP_HEADER_CONFIGID,
     HTTP_HEADER_CP_FN, HTTP_HEADER_CP_UUID, HTTP_HEADER_DATE, HTTP_HEADER_EXT, HTTP_HEADER_HOST,
     HTTP_HEADER_LOCATION, HTTP_HEADER_MAN, HTTP_HEADER_MX, HTTP_HEADER_SEARCH_PORT,
-    HTTP_HEADER_SERVER, HTTP_HEADER_ST, HTTP_HEADER_TCP_PORT, HTTP_HEADER_USER_AGENT,
-    HTTP_HEADER_USN, HTTP_METHOD_SEARCH, MULTICAST_ADDRESS, MULTICAST_PORT,
+    HTTP_HEADER_SECURE_LOCATION, HTTP_HEADER_SERVER, HTTP_HEADER_ST, HTTP_HEADER_TCP_PORT,
+    HTTP_HEADER_USER_AGENT, HTTP_HEADER_USN, HTTP_METHOD_SEARCH, MULTICAST_ADDRESS, MULTICAST_PORT,
 };
 use crate::SpecVersion;
 use regex::Regex;
 use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
 use std::fmt::{Display, Error as FmtError, Formatter};
-use std::net::SocketAddr;
+use std::io::ErrorKind as IOErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, ToSocketAddrs};
 use std::str::FromStr;
-use std::time::{Duration, SystemTime};
-use tracing::{error, info, trace};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{error, info, trace, warn};
 
 // ------------------------------------------------------------------------------------------------
 // Public Types
@@ -48,7 +54,7 @@ use tracing::{error, info, trace};
 /// This type does not separate out the version of a device or service type, it does ensure
 /// that the ':' separator character is present in the combined value.
 ///
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SearchTarget {
     /// Corresponds to the value `ssdp:all`
     All,
@@ -88,6 +94,7 @@ type CallbackFn = fn(&Response) -> bool;
 /// The `Options::for_control_point` will set the control point as well as the version number.
 ///
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Options {
     /// The specification that will be used to construct sent messages and to verify responses.
     /// Default: `SpecVersion:V10`.
@@ -119,6 +126,38 @@ pub struct Options {
     pub port: Option<u16>,
     /// Multicast bind port, default: random
     pub bind_port: Option<u16>,
+    /// A local address to bind to, in place of the address of `network_interface`.
+    /// Default: `None`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub source_address: Option<IpAddr>,
+    /// The time to wait for each response, which may be less than a second; if not specified
+    /// `max_wait_time` is used. Default: `None`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "crate::common::seconds::fractional")
+    )]
+    pub recv_timeout: Option<Duration>,
+    /// Whether the search is also delivered to the sending host, so that local devices may
+    /// respond. Default: `false`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub loop_back_also: bool,
+    /// The size of the buffer each response is read into, default: 8192 bytes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub buffer_size: Option<usize>,
+    /// Set `SO_REUSEADDR` on the socket, useful with a fixed `bind_port`. Default: `false`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reuse_address: bool,
+    /// Set `SO_REUSEPORT` on the socket, where supported. Default: `false`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reuse_port: bool,
+    /// The Differentiated Services code point, `0..=63`, for sent packets. Default: `None`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dscp: Option<u8>,
+    /// Headers added to each sent `M-SEARCH`, after the standard ones, such as `X-User-Agent`
+    /// or the `OPT` and `01-NLS` pair; these replace any standard header of the same name.
+    /// Default: none.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extra_headers: Headers,
 }
 
 #[derive(Clone, Debug)]
@@ -145,17 +184,63 @@ pub struct ResponseCache {
 /// A Single device response.
 ///
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Response {
+    #[cfg_attr(feature = "serde", serde(with = "crate::common::seconds"))]
     pub max_age: Duration,
-    pub date: String,
+    /// The time the response was generated, from the `DATE` header, if sent and valid.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "crate::common::seconds::since_epoch")
+    )]
+    pub date: Option<SystemTime>,
     pub versions: ProductVersions,
     pub search_target: SearchTarget,
     pub service_name: URI,
     pub location: URL,
-    pub boot_id: u64,
+    /// The `https` base URL sent by devices implementing Device Protection.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub secure_location: Option<URL>,
+    /// The `BOOTID.UPNP.ORG` value, read only from devices supporting UPnP 1.1 or later, as are
+    /// `config_id` and `search_port`.
+    pub boot_id: Option<u32>,
     pub config_id: Option<u64>,
     pub search_port: Option<u16>,
-    pub other_headers: HashMap<String, String>,
+    /// The extension declared in the `OPT` header, and its `NLS` value, if sent.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub extension: Option<Extension>,
+    /// Any headers not parsed into the fields above, in the order received.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub other_headers: Headers,
+    /// The address the response was received from, if it came from the network.
+    pub from: Option<SocketAddr>,
+}
+
+///
+/// Accepts search responses sent over TCP, by UPnP 2.0 devices, to the port a control point
+/// sends in the `TCPPORT.UPNP.ORG` header of its searches.
+///
+#[derive(Debug)]
+pub struct TcpResponseListener {
+    listener: TcpListener,
+}
+
+///
+/// A search request sent by a control point, as parsed from an `M-SEARCH` request. This is
+/// used by anything listening to, or answering, searches on the multicast group.
+///
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchRequest {
+    pub search_target: SearchTarget,
+    pub host: String,
+    /// The `MX` value, required for multicast searches but not for unicast ones.
+    pub max_wait_time: Option<u8>,
+    pub user_agent: Option<String>,
+    /// Present only if the control point sent the UPnP 2.0 `CPFN.UPNP.ORG` header.
+    pub control_point: Option<ControlPoint>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub other_headers: Headers,
 }
 
 // ------------------------------------------------------------------------------------------------
@@ -203,51 +288,39 @@ pub fn search(options: Options) -> Result<ResponseCache, Error> {
 ///
 pub fn search_once(options: Options) -> Result<Vec<Response>, Error> {
     info!("search_once - options: {:?}", options);
+    let transport = UdpTransport::new(
+        &multicast_address(options.address.as_deref(), options.port)?,
+        &options.clone().into(),
+    )?;
+    search_once_using(options, &transport)
+}
+
+///
+/// Perform a multicast search, as `search_once`, but send and receive messages using the provided
+/// transport rather than a newly created UDP socket.
+///
+/// # Parameters
+///
+/// * `options` - protocol options such as the specification version to use and any network
+///   configuration values.
+/// * `transport` - the transport used to send the search request and receive responses.
+///
+pub fn search_once_using(
+    options: Options,
+    transport: &dyn Transport,
+) -> Result<Vec<Response>, Error> {
+    info!("search_once_using - options: {:?}", options);
     options.validate()?;
-    let mut message_builder = RequestBuilder::new(HTTP_METHOD_SEARCH);
-    // All headers from the original 1.0 specification.
-    message_builder
-        .add_header(HTTP_HEADER_HOST, format!("{}:{}", options.address.as_deref().unwrap_or(MULTICAST_ADDRESS), options.port.unwrap_or(MULTICAST_PORT)).as_str())
-        .add_header(HTTP_HEADER_MAN, HTTP_EXTENSION)
-        .add_header(HTTP_HEADER_MX, &format!("{}", options.max_wait_time))
-        .add_header(HTTP_HEADER_ST, &options.search_target.to_string());
-    // Headers added by 1.1 specification
-    if options.spec_version >= SpecVersion::V11 {
-        message_builder.add_header(
-            HTTP_HEADER_USER_AGENT,
-            &user_agent_string(options.spec_version, options.product_and_version.clone()),
-        );
-    }
-    // Headers added by 2.0 specification
-    if options.spec_version >= SpecVersion::V20 {
-        match &options.control_point {
-            Some(cp) => {
-                message_builder.add_header(HTTP_HEADER_CP_FN, &cp.friendly_name);
-                if let Some(uuid) = &cp.uuid {
-                    message_builder.add_header(HTTP_HEADER_CP_UUID, uuid);
-                }
-                if let Some(port) = cp.port {
-                    message_builder.add_header(HTTP_HEADER_TCP_PORT, &port.to_string());
-                }
-            }
-            None => {
-                error!("search_once - missing control point, required for UPnP/2.0");
-                return missing_required_field("control_point").into();
-            }
-        }
-    }
-    trace!("search_once - {:?}", &message_builder);
-    let raw_responses = multicast(
-        &message_builder.into(),
-        &format!("{}:{}", options.address.as_deref().unwrap_or(MULTICAST_ADDRESS), options.port.unwrap_or(MULTICAST_PORT)).parse().unwrap(),
-        &options.into(),
+    let to_address = multicast_address(options.address.as_deref(), options.port)?;
+    let message = search_request(
+        &options,
+        &to_address.to_string(),
+        Some(options.max_wait_time),
     )?;
+    trace!("search_once_using - {:?}", &message);
+    let raw_responses = multicast_using(&message, &to_address, transport, &options.clone().into())?;
 
-    let mut responses: Vec<Response> = Vec::new();
-    for raw_response in raw_responses {
-        responses.push(raw_response.try_into()?);
-    }
-    Ok(responses)
+    Ok(responses_from(raw_responses))
 }
 
 ///
@@ -259,7 +332,11 @@ pub fn search_once(options: Options) -> Result<Vec<Response>, Error> {
 ///
 /// # Specification
 ///
-/// TBD
+/// A unicast search is sent to a single device, at port 1900 or at the port the device sent in
+/// the `SEARCHPORT.UPNP.ORG` header of its notifications and search responses; see
+/// [`search_address`](fn.search_address.html). The `HOST` header is the address of the device,
+/// and there is no `MX` header as the device responds immediately; `max_wait_time` is still used
+/// as the time to wait for responses. Unicast search is only supported from UPnP 1.1.
 ///
 /// # Parameters
 ///
@@ -276,27 +353,111 @@ pub fn search_once_to_device(
         "search_once_to_device - options: {:?}, device_address: {:?}",
         options, device_address
     );
+    let mut multicast_options: MulticastOptions = options.clone().into();
+    if multicast_options.network_version.is_none() {
+        multicast_options.network_version = Some(if device_address.is_ipv6() {
+            IP::V6
+        } else {
+            IP::V4
+        });
+    }
+    let transport = UdpTransport::new(&device_address, &multicast_options)?;
+    search_once_to_device_using(options, device_address, &transport)
+}
+
+///
+/// Perform a unicast search, as `search_once_to_device`, but send and receive messages using
+/// the provided transport rather than a newly created UDP socket.
+///
+pub fn search_once_to_device_using(
+    options: Options,
+    device_address: SocketAddr,
+    transport: &dyn Transport,
+) -> Result<Vec<Response>, Error> {
+    info!(
+        "search_once_to_device_using - options: {:?}, device_address: {:?}",
+        options, device_address
+    );
     options.validate()?;
     if options.spec_version >= SpecVersion::V11 {
-        let mut message_builder = RequestBuilder::new(HTTP_METHOD_SEARCH);
-        message_builder
-            .add_header(HTTP_HEADER_HOST, format!("{}:{}", options.address.as_deref().unwrap_or(MULTICAST_ADDRESS), options.port.unwrap_or(MULTICAST_PORT)).as_str())
-            .add_header(HTTP_HEADER_MAN, HTTP_EXTENSION)
-            .add_header(HTTP_HEADER_ST, &options.search_target.to_string())
-            .add_header(
-                HTTP_HEADER_USER_AGENT,
-                &user_agent_string(options.spec_version, options.product_and_version.clone()),
-            );
+        let message = search_request(&options, &device_address.to_string(), None)?;
+        trace!("search_once_to_device_using - {:?}", &message);
+        let raw_responses = multicast_using(
+            &message,
+            &device_address,
+            transport,
+            &options.clone().into(),
+        )?;
+        Ok(responses_from(raw_responses))
+    } else {
+        unsupported_version(options.spec_version).into()
+    }
+}
 
-        let raw_responses = multicast(&message_builder.into(), &device_address, &options.into())?;
+///
+/// Perform a multicast search, as `search_once`, also accepting responses sent over TCP to
+/// `listener`; the port of the listener is sent in the `TCPPORT.UPNP.ORG` header, replacing any
+/// port in the control point options. This requires UPnP 2.0, and so a control point.
+///
+pub fn search_once_with_tcp(
+    options: Options,
+    listener: &TcpResponseListener,
+) -> Result<Vec<Response>, Error> {
+    info!("search_once_with_tcp - options: {:?}", options);
+    let transport = UdpTransport::new(
+        &multicast_address(options.address.as_deref(), options.port)?,
+        &options.clone().into(),
+    )?;
+    search_once_with_tcp_using(options, listener, &transport)
+}
 
-        let mut responses: Vec<Response> = Vec::new();
-        for raw_response in raw_responses {
-            responses.push(raw_response.try_into()?);
+///
+/// Perform a multicast search, as `search_once_with_tcp`, but send and receive the multicast
+/// messages using the provided transport rather than a newly created UDP socket.
+///
+pub fn search_once_with_tcp_using(
+    options: Options,
+    listener: &TcpResponseListener,
+    transport: &dyn Transport,
+) -> Result<Vec<Response>, Error> {
+    info!("search_once_with_tcp_using - options: {:?}", options);
+    if options.spec_version < SpecVersion::V20 {
+        error!("search_once_with_tcp_using - TCPPORT.UPNP.ORG requires UPnP/2.0");
+        return unsupported_version(options.spec_version).into();
+    }
+    let mut options = options;
+    match &mut options.control_point {
+        Some(control_point) => control_point.port = Some(listener.port()?),
+        None => {
+            error!("search_once_with_tcp_using - control_point required for TCPPORT.UPNP.ORG");
+            return missing_required_field("ControlPoint").into();
+        }
+    }
+    let mut responses = search_once_using(options, transport)?;
+    // Devices have had the whole wait time to connect, so only those already queued remain.
+    responses.extend(listener.responses(TCP_DRAIN_TIMEOUT)?);
+    Ok(responses)
+}
+
+///
+/// Return the address to send a unicast search to for the device whose description is at
+/// `location`; this is the host of `location` and the `SEARCHPORT.UPNP.ORG` value the device
+/// sent, if any, or port 1900.
+///
+pub fn search_address(location: &URL, search_port: Option<u16>) -> Result<SocketAddr, Error> {
+    let port = search_port.unwrap_or(MULTICAST_PORT);
+    match location.host() {
+        Some(host) => match (host, port).to_socket_addrs()?.next() {
+            Some(address) => Ok(address),
+            None => {
+                error!("search_address - no address for host '{}'", host);
+                invalid_value_for_type("SocketAddr", host).into()
+            }
+        },
+        None => {
+            error!("search_address - LOCATION '{}' has no host", location);
+            invalid_header_value(HTTP_HEADER_LOCATION, location.to_string()).into()
         }
-        Ok(responses)
-    } else {
-        unsupported_version(options.spec_version).into()
     }
 }
 
@@ -310,7 +471,7 @@ impl Display for SearchTarget {
             f,
             "{}",
             match self {
-                SearchTarget::All => "ssdp::all".to_string(),
+                SearchTarget::All => "ssdp:all".to_string(),
                 SearchTarget::RootDevice => "upnp:rootdevice".to_string(),
                 SearchTarget::Device(device) => format!("uuid:{}", device),
                 SearchTarget::DeviceType(device) =>
@@ -335,7 +496,8 @@ impl FromStr for SearchTarget {
             static ref DOMAIN_URN: Regex =
                 Regex::new(r"^urn:([^:]+):(device|service):(.+)$").unwrap();
         }
-        if s == "ssdp::all" {
+        // "ssdp::all" was written by earlier versions of this crate, and is still accepted.
+        if s == "ssdp:all" || s == "ssdp::all" {
             Ok(SearchTarget::All)
         } else if s == "upnp:rootdevice" {
             Ok(SearchTarget::RootDevice)
@@ -347,8 +509,8 @@ impl FromStr for SearchTarget {
             Ok(SearchTarget::DeviceType(device_type.to_string()))
         } else if let Some(service_type) = s.strip_prefix("urn:schemas-upnp-org:service:") {
             Ok(SearchTarget::ServiceType(service_type.to_string()))
-        } else if let Some(domain) = s.strip_prefix("urn:") {
-            match DOMAIN_URN.captures(domain) {
+        } else if s.starts_with("urn:") {
+            match DOMAIN_URN.captures(s) {
                 Some(captures) => {
                     if captures.get(2).unwrap().as_str() == "device" {
                         Ok(SearchTarget::DomainDeviceType(
@@ -374,6 +536,39 @@ impl FromStr for SearchTarget {
     }
 }
 
+serde_as_string!(SearchTarget);
+
+impl SearchTarget {
+    ///
+    /// Return the device or service type identified by this search target, if it is a type
+    /// target with a valid, numeric, version.
+    ///
+    pub fn type_id(&self) -> Option<TypeID> {
+        match self {
+            SearchTarget::DeviceType(_) | SearchTarget::DomainDeviceType(_, _) => {
+                TypeID::device_from(self.clone()).ok()
+            }
+            SearchTarget::ServiceType(_) | SearchTarget::DomainServiceType(_, _) => {
+                TypeID::service_from(self.clone()).ok()
+            }
+            _ => None,
+        }
+    }
+
+    ///
+    /// Returns `true` if a device or service advertising `target`, in an `NT` or `ST` header,
+    /// satisfies a search for this target. `ssdp:all` matches any target, type targets match
+    /// any compatible type (see `TypeID::is_compatible_with`), and all others must be equal.
+    ///
+    pub fn matches(&self, target: &SearchTarget) -> bool {
+        match (self, self.type_id(), target.type_id()) {
+            (SearchTarget::All, _, _) => true,
+            (_, Some(required), Some(actual)) => actual.is_compatible_with(&required),
+            _ => self.to_string() == target.to_string(),
+        }
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 
 impl Options {
@@ -397,6 +592,14 @@ impl Options {
             address: Some(MULTICAST_ADDRESS.to_string()),
             port: Some(MULTICAST_PORT),
             bind_port: None,
+            source_address: None,
+            recv_timeout: None,
+            loop_back_also: false,
+            buffer_size: None,
+            reuse_address: false,
+            reuse_port: false,
+            dscp: None,
+            extra_headers: Default::default(),
         }
     }
 
@@ -423,6 +626,14 @@ impl Options {
             );
             return invalid_field_value("max_wait_time", self.max_wait_time.to_string()).into();
         }
+        if let Some(dscp) = self.dscp.filter(|dscp| *dscp > 63) {
+            error!("validate - dscp must be between 0..63 ({})", dscp);
+            return invalid_field_value("dscp", dscp.to_string()).into();
+        }
+        if self.buffer_size == Some(0) {
+            error!("validate - buffer_size must not be zero");
+            return invalid_field_value("buffer_size", "0").into();
+        }
         if self.spec_version >= SpecVersion::V11 {
             if let Some(user_agent) = &self.product_and_version {
                 if user_agent.name.contains('/') || !UA_VERSION.is_match(&user_agent.version) {
@@ -452,130 +663,231 @@ impl Options {
 
 impl From<Options> for MulticastOptions {
     fn from(options: Options) -> Self {
+        let max_wait_time = Duration::from_secs(options.max_wait_time as u64);
         MulticastOptions {
             network_interface: options.network_interface,
             network_version: options.network_version,
-            packet_ttl: options.packet_ttl,
-            recv_timeout: options.max_wait_time as u64,
+            source_address: options.source_address,
             local_port: options.bind_port.unwrap_or_default(),
-            ..Default::default()
+            recv_timeout: options.recv_timeout.unwrap_or(max_wait_time),
+            packet_ttl: options.packet_ttl,
+            loop_back_also: options.loop_back_also,
+            buffer_size: options.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE),
+            reuse_address: options.reuse_address,
+            reuse_port: options.reuse_port,
+            dscp: options.dscp,
         }
     }
 }
 // ------------------------------------------------------------------------------------------------
 
-const REQUIRED_HEADERS_V10: [&str; 7] = [
+const REQUIRED_HEADERS_V10: [&str; 6] = [
+    HTTP_HEADER_CACHE_CONTROL,
+    HTTP_HEADER_EXT,
+    HTTP_HEADER_LOCATION,
+    HTTP_HEADER_SERVER,
+    HTTP_HEADER_ST,
+    HTTP_HEADER_USN,
+];
+
+const KNOWN_HEADERS_V10: [&str; 8] = [
     HTTP_HEADER_CACHE_CONTROL,
     HTTP_HEADER_DATE,
     HTTP_HEADER_EXT,
     HTTP_HEADER_LOCATION,
+    HTTP_HEADER_SECURE_LOCATION,
     HTTP_HEADER_SERVER,
     HTTP_HEADER_ST,
     HTTP_HEADER_USN,
 ];
 
+const KNOWN_HEADERS_V11: [&str; 3] = [
+    HTTP_HEADER_BOOTID,
+    HTTP_HEADER_CONFIGID,
+    HTTP_HEADER_SEARCH_PORT,
+];
+
 impl TryFrom<MulticastResponse> for Response {
     type Error = Error;
 
     fn try_from(response: MulticastResponse) -> Result<Self, Self::Error> {
         lazy_static! {
-            static ref UA_ALL: Regex =
-                Regex::new(r"^([^/ ]+)([/ ]([\d\.]+))?,?[ ]+([^/]+)/([\d\.]+)[ ]*[^/]*/?[\d\.]*,?[ ]+([^/]+)/V?([\d\.]+)$")
-                    .unwrap();
+            static ref MAX_AGE: Regex = Regex::new(r"max-age[ ]*=[ ]*(\d+)").unwrap();
         }
-        headers::check_required(&response.headers, &REQUIRED_HEADERS_V10)?;
-        headers::check_empty(
-            response.headers.get(HTTP_HEADER_EXT).unwrap(),
-            HTTP_HEADER_EXT,
-        )?;
+        if !response.status().is_success() {
+            error!(
+                "search response returned error status '{}'",
+                response.status().code()
+            );
+            return invalid_header_value("STATUS", response.status().code().to_string()).into();
+        }
+        let headers = &response.headers;
+        headers::check_required(headers, &REQUIRED_HEADERS_V10)?;
+        headers::check_empty(headers.get(HTTP_HEADER_EXT).unwrap(), HTTP_HEADER_EXT)?;
 
-        let server = response.headers.get(HTTP_HEADER_SERVER).unwrap();
-        let versions = match UA_ALL.captures(server) {
-            Some(captures) => ProductVersions {
-                product: ProductVersion {
-                    name: captures.get(6).map(|x| x.as_str()).unwrap_or_default().to_string(),
-                    version: captures.get(7).map(|x| x.as_str()).unwrap_or_default().to_string(),
-                },
-                upnp: ProductVersion {
-                    name: captures.get(4).map(|x| x.as_str()).unwrap_or_default().to_string(),
-                    version: captures.get(5).map(|x| x.as_str()).unwrap_or_default().to_string(),
-                },
-                platform: ProductVersion {
-                    name: captures.get(1).map(|x| x.as_str()).unwrap_or_default().to_string(),
-                    version: captures.get(3).map(|x| x.as_str()).unwrap_or_default().to_string(),
-                },
-            },
-            None => {
-                error!("invalid value for server header '{}", server);
+        let server = headers.get(HTTP_HEADER_SERVER).unwrap();
+        let versions = match ProductVersions::from_str(server) {
+            Ok(versions) => versions,
+            Err(_) => {
+                error!("try_from - invalid value for server header '{}'", server);
                 return invalid_field_value(HTTP_HEADER_SERVER, server).into();
             }
         };
 
         let max_age = headers::check_parsed_value::<u64>(
             &headers::check_regex(
-                response.headers.get(HTTP_HEADER_CACHE_CONTROL).unwrap(),
+                headers.get(HTTP_HEADER_CACHE_CONTROL).unwrap(),
                 HTTP_HEADER_CACHE_CONTROL,
-                &Regex::new(r"max-age[ ]*=[ ]*(\d+)").unwrap(),
+                &MAX_AGE,
             )?,
             HTTP_HEADER_CACHE_CONTROL,
         )?;
 
-        let date = headers::check_not_empty(
-            response.headers.get(HTTP_HEADER_DATE),
-            "Thu, 01 Jan 1970 00:00:00 GMT",
-        );
-
-        let location = headers::check_not_empty(
-            response.headers.get(HTTP_HEADER_LOCATION),
-            "http://www.example.org",
-        );
+        // DATE is only recommended, and a value that can't be parsed is treated as absent.
+        let date = headers.get(HTTP_HEADER_DATE).and_then(|date| {
+            let parsed = parse_http_date(date);
+            if parsed.is_none() {
+                warn!("try_from - ignoring invalid DATE '{}'", date);
+            }
+            parsed
+        });
 
-        let service_name =
-            headers::check_not_empty(response.headers.get(HTTP_HEADER_USN), "raw:undefined");
-
-        let search_target =
-            headers::check_not_empty(response.headers.get(HTTP_HEADER_ST), "raw:undefined");
-
-        let mut boot_id = 0u64;
-        let mut config_id: Option<u64> = None;
-        let mut search_port: Option<u16> = None;
-        if versions.upnp.version == SpecVersion::V20.to_string() {
-            boot_id = headers::check_parsed_value::<u64>(
-                response
-                    .headers
-                    .get(HTTP_HEADER_BOOTID)
-                    .unwrap_or(&"0".to_string()),
-                HTTP_HEADER_BOOTID,
-            )?;
-            if let Some(s) = response.headers.get(HTTP_HEADER_CONFIGID) {
-                config_id = s.parse::<u64>().ok();
+        let location = headers.get(HTTP_HEADER_LOCATION).unwrap();
+        let location = match URL::from_str(location) {
+            Ok(url) if url.is_absolute() => url,
+            _ => {
+                error!("try_from - LOCATION '{}' is not an absolute URL", location);
+                return invalid_header_value(HTTP_HEADER_LOCATION, location).into();
             }
-            if let Some(s) = response.headers.get(HTTP_HEADER_SEARCH_PORT) {
-                search_port = s.parse::<u16>().ok();
+        };
+        let secure_location = headers::check_optional_url(
+            headers.get(HTTP_HEADER_SECURE_LOCATION),
+            HTTP_HEADER_SECURE_LOCATION,
+        )?;
+
+        let service_name = headers.get(HTTP_HEADER_USN).unwrap();
+        let search_target = headers.get(HTTP_HEADER_ST).unwrap();
+
+        // The headers added by 1.1 are only read from devices that claim to support it.
+        let mut known_headers: Vec<String> =
+            KNOWN_HEADERS_V10.iter().map(|h| h.to_string()).collect();
+        let (boot_id, config_id, search_port) = match versions.spec_version() {
+            Some(version) if version >= SpecVersion::V11 => {
+                known_headers.extend(KNOWN_HEADERS_V11.iter().map(|h| h.to_string()));
+                (
+                    headers::check_optional_value::<u32>(
+                        headers.get(HTTP_HEADER_BOOTID),
+                        HTTP_HEADER_BOOTID,
+                    )?,
+                    headers::check_optional_value::<u64>(
+                        headers.get(HTTP_HEADER_CONFIGID),
+                        HTTP_HEADER_CONFIGID,
+                    )?,
+                    headers::check_optional_value::<u16>(
+                        headers.get(HTTP_HEADER_SEARCH_PORT),
+                        HTTP_HEADER_SEARCH_PORT,
+                    )?,
+                )
             }
-        }
+            _ => (None, None, None),
+        };
 
-        let remaining_headers: HashMap<String, String> = response
-            .headers
-            .clone()
-            .iter()
-            .filter(|(k, _)| !REQUIRED_HEADERS_V10.contains(&k.as_str()))
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
+        let extension = Extension::from_headers(headers);
+        if let Some(extension) = &extension {
+            known_headers.extend(extension.header_names());
+        }
+        let known_headers: Vec<&str> = known_headers.iter().map(String::as_str).collect();
+        let remaining_headers = headers.without(&known_headers);
 
         Ok(Response {
             max_age: Duration::from_secs(max_age),
             date,
             versions,
-            location: URI::from_str(&location)
-                .map_err(|_| invalid_header_value(HTTP_HEADER_LOCATION, &location))?,
-            search_target: SearchTarget::from_str(&search_target)
+            location,
+            secure_location,
+            search_target: SearchTarget::from_str(search_target)
                 .map_err(|_| invalid_field_value("SearchTarget", search_target))?,
-            service_name: URI::from_str(&service_name)
+            service_name: URI::from_str(service_name)
                 .map_err(|_| invalid_field_value("URI", service_name))?,
             boot_id,
             config_id,
             search_port,
+            extension,
+            other_headers: remaining_headers,
+            from: None,
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+const REQUIRED_HEADERS_SEARCH: [&str; 3] = [HTTP_HEADER_HOST, HTTP_HEADER_MAN, HTTP_HEADER_ST];
+
+const KNOWN_HEADERS_SEARCH: [&str; 8] = [
+    HTTP_HEADER_CP_FN,
+    HTTP_HEADER_CP_UUID,
+    HTTP_HEADER_HOST,
+    HTTP_HEADER_MAN,
+    HTTP_HEADER_MX,
+    HTTP_HEADER_ST,
+    HTTP_HEADER_TCP_PORT,
+    HTTP_HEADER_USER_AGENT,
+];
+
+impl TryFrom<&Request> for SearchRequest {
+    type Error = Error;
+
+    fn try_from(request: &Request) -> Result<Self, Self::Error> {
+        if request.method() != HTTP_METHOD_SEARCH {
+            error!(
+                "try_from - request method '{}' is not M-SEARCH",
+                request.method()
+            );
+            return invalid_value_for_type("SearchRequest", request.method()).into();
+        }
+        let headers = request.headers();
+        headers::check_required(headers, &REQUIRED_HEADERS_SEARCH)?;
+
+        let man = headers.get(HTTP_HEADER_MAN).unwrap();
+        if man != HTTP_EXTENSION {
+            error!(
+                "try_from - MAN header must be {}, not '{}'",
+                HTTP_EXTENSION, man
+            );
+            return invalid_header_value(HTTP_HEADER_MAN, man).into();
+        }
+
+        let max_wait_time = match headers.get(HTTP_HEADER_MX) {
+            None => None,
+            Some(value) => Some(headers::check_parsed_value::<u8>(value, HTTP_HEADER_MX)?),
+        };
+
+        let control_point = match headers.get(HTTP_HEADER_CP_FN) {
+            None => None,
+            Some(friendly_name) => Some(ControlPoint {
+                friendly_name: friendly_name.clone(),
+                uuid: headers.get(HTTP_HEADER_CP_UUID).cloned(),
+                port: match headers.get(HTTP_HEADER_TCP_PORT) {
+                    None => None,
+                    Some(value) => Some(headers::check_parsed_value::<u16>(
+                        value,
+                        HTTP_HEADER_TCP_PORT,
+                    )?),
+                },
+            }),
+        };
+
+        let remaining_headers = headers.without(&KNOWN_HEADERS_SEARCH);
+
+        Ok(SearchRequest {
+            search_target: headers::check_parsed_value::<SearchTarget>(
+                headers.get(HTTP_HEADER_ST).unwrap(),
+                HTTP_HEADER_ST,
+            )?,
+            host: headers.get(HTTP_HEADER_HOST).unwrap().clone(),
+            max_wait_time,
+            user_agent: headers.get(HTTP_HEADER_USER_AGENT).cloned(),
+            control_point,
             other_headers: remaining_headers,
         })
     }
@@ -597,10 +909,741 @@ impl ResponseCache {
     }
 }
 
+// ------------------------------------------------------------------------------------------------
+
+impl Response {
+    ///
+    /// The address to send a unicast search to for the responding device, see `search_address`.
+    ///
+    pub fn search_address(&self) -> Result<SocketAddr, Error> {
+        search_address(&self.location, self.search_port)
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+const TCP_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+const TCP_ACCEPT_INTERVAL: Duration = Duration::from_millis(20);
+
+const TCP_READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+impl TcpResponseListener {
+    ///
+    /// Create a new listener on the interface, and IP version, given in `options`, and on the
+    /// port of its control point, if set, or otherwise a random port.
+    ///
+    pub fn new(options: &Options) -> Result<Self, Error> {
+        let address =
+            match ip_address_for_interface(&options.network_interface, &options.network_version) {
+                Some(address) => address,
+                None => match options.network_version {
+                    Some(IP::V6) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                    _ => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                },
+            };
+        let port = options
+            .control_point
+            .as_ref()
+            .and_then(|control_point| control_point.port)
+            .unwrap_or_default();
+        Self::bind(&SocketAddr::new(address, port))
+    }
+
+    ///
+    /// Create a new listener bound to `address`.
+    ///
+    pub fn bind(address: &SocketAddr) -> Result<Self, Error> {
+        info!("bind - address: {}", address);
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener })
+    }
+
+    ///
+    /// The port to send in the `TCPPORT.UPNP.ORG` header.
+    ///
+    pub fn port(&self) -> Result<u16, Error> {
+        Ok(self.local_addr()?.port())
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    ///
+    /// Wait up to `timeout` for the next response, returning `None` if no response arrived in
+    /// time. Connections that do not carry a valid search response are logged and skipped, and
+    /// reading a response never runs past `timeout`.
+    ///
+    pub fn next_response(&self, timeout: Duration) -> Result<Option<Response>, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.listener.accept() {
+                Ok((mut stream, from)) => {
+                    trace!("next_response - connection from {}", from);
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        warn!(
+                            "next_response - timed out, dropping connection from {}",
+                            from
+                        );
+                        return Ok(None);
+                    }
+                    stream.set_nonblocking(false)?;
+                    stream.set_read_timeout(Some(remaining.min(TCP_READ_TIMEOUT)))?;
+                    match read_response(&mut stream).and_then(Response::try_from) {
+                        Ok(mut response) => {
+                            response.from = Some(from);
+                            return Ok(Some(response));
+                        }
+                        Err(e) => warn!("next_response - ignoring response from {}: {}", from, e),
+                    }
+                }
+                Err(e) if e.kind() == IOErrorKind::WouldBlock => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Ok(None);
+                    }
+                    thread::sleep(remaining.min(TCP_ACCEPT_INTERVAL));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    ///
+    /// Return all the responses that arrive within `timeout`.
+    ///
+    pub fn responses(&self, timeout: Duration) -> Result<Vec<Response>, Error> {
+        let deadline = Instant::now() + timeout;
+        let mut responses: Vec<Response> = Default::default();
+        while let Some(response) =
+            self.next_response(deadline.saturating_duration_since(Instant::now()))?
+        {
+            responses.push(response);
+        }
+        Ok(responses)
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Convert the raw responses to a search, skipping any that are not valid search responses so
+/// that one misbehaving device does not discard the responses of all the others.
+///
+fn responses_from(raw_responses: Vec<(SocketAddr, MulticastResponse)>) -> Vec<Response> {
+    raw_responses
+        .into_iter()
+        .filter_map(
+            |(from, raw_response)| match Response::try_from(raw_response) {
+                Ok(mut response) => {
+                    response.from = Some(from);
+                    Some(response)
+                }
+                Err(e) => {
+                    warn!("responses_from - ignoring response from {}: {}", from, e);
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+///
+/// Build an `M-SEARCH` request to `host`; `max_wait_time` is sent, as `MX`, only for multicast
+/// searches.
+///
+fn search_request(
+    options: &Options,
+    host: &str,
+    max_wait_time: Option<u8>,
+) -> Result<Request, Error> {
+    let mut message_builder = RequestBuilder::new(HTTP_METHOD_SEARCH);
+    // All headers from the original 1.0 specification.
+    message_builder
+        .add_header(HTTP_HEADER_HOST, host)
+        .add_header(HTTP_HEADER_MAN, HTTP_EXTENSION);
+    if let Some(max_wait_time) = max_wait_time {
+        message_builder.add_header(HTTP_HEADER_MX, &format!("{}", max_wait_time));
+    }
+    message_builder.add_header(HTTP_HEADER_ST, &options.search_target.to_string());
+    // Headers added by 1.1 specification
+    if options.spec_version >= SpecVersion::V11 {
+        message_builder.add_header(
+            HTTP_HEADER_USER_AGENT,
+            &user_agent_string(options.spec_version, options.product_and_version.clone()),
+        );
+    }
+    // Headers added by 2.0 specification
+    if options.spec_version >= SpecVersion::V20 {
+        match &options.control_point {
+            Some(cp) => {
+                message_builder.add_header(HTTP_HEADER_CP_FN, &cp.friendly_name);
+                if let Some(uuid) = &cp.uuid {
+                    message_builder.add_header(HTTP_HEADER_CP_UUID, uuid);
+                }
+                if let Some(port) = cp.port {
+                    message_builder.add_header(HTTP_HEADER_TCP_PORT, &port.to_string());
+                }
+            }
+            None => {
+                error!("search_request - missing control point, required for UPnP/2.0");
+                return missing_required_field("control_point").into();
+            }
+        }
+    }
+    message_builder.extra_headers(&options.extra_headers);
+    Ok(message_builder.into())
+}
+
 //fn callback_wrapper(inner: &CallbackFn) -> bool {
 //    false
 //}
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::httpu::MemoryTransport;
+    use pretty_assertions::assert_eq;
+    use std::time::UNIX_EPOCH;
+
+    const EX_RESPONSE: &str = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=100\r\nDATE: Fri, 02 Oct 2020 16:35:21 GMT\r\nEXT:\r\nLOCATION: http://192.168.1.30:49152/description.xml\r\nSERVER: Linux/3.14.0 UPnP/1.0 IpBridge/1.40.0\r\nST: upnp:rootdevice\r\nUSN: uuid:2f402f80-da50-11e1-9b23-00178829d301::upnp:rootdevice\r\n\r\n";
+
+    #[test]
+    fn test_search_once_using_memory_transport() {
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        transport.push_response("192.168.1.30:1900".parse().unwrap(), EX_RESPONSE.as_bytes());
+
+        let responses = search_once_using(Options::default_for(SpecVersion::V10), &transport);
+        assert!(responses.is_ok());
+        let responses = responses.unwrap();
+        assert_eq!(responses.len(), 1);
+        let response = responses.first().unwrap();
+        assert_eq!(response.max_age, Duration::from_secs(100));
+        assert_eq!(
+            response.location.to_string(),
+            "http://192.168.1.30:49152/description.xml"
+        );
+        assert_eq!(response.versions.product_version().name(), "IpBridge");
+        assert!(transport.is_drained());
+
+        let sent = transport.sent();
+        assert_eq!(sent.len(), 1);
+        let (to_address, request) = sent.first().unwrap();
+        assert_eq!(to_address.to_string(), "239.255.255.250:1900");
+        let request = String::from_utf8(request.clone()).unwrap();
+        assert!(request.starts_with("M-SEARCH * HTTP/1.1\r\n"));
+        assert!(request.contains("ST:upnp:rootdevice"));
+        assert!(request.contains("MX:2"));
+        assert!(request.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_search_once_using_skips_invalid_responses() {
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        transport.push_response("192.168.1.30:1900".parse().unwrap(), EX_RESPONSE.as_bytes());
+        transport.push_response(
+            "192.168.1.31:1900".parse().unwrap(),
+            EX_RESPONSE
+                .replace("200 OK", "500 Internal Server Error")
+                .as_bytes(),
+        );
+        transport.push_response(
+            "192.168.1.32:1900".parse().unwrap(),
+            EX_RESPONSE
+                .replace(
+                    "http://192.168.1.30:49152/description.xml",
+                    "/description.xml",
+                )
+                .as_bytes(),
+        );
+        transport.push_response("192.168.1.33:1900".parse().unwrap(), EX_RESPONSE.as_bytes());
+
+        let responses =
+            search_once_using(Options::default_for(SpecVersion::V10), &transport).unwrap();
+        let from: Vec<String> = responses
+            .iter()
+            .map(|response| response.from.unwrap().to_string())
+            .collect();
+        assert_eq!(from, vec!["192.168.1.30:1900", "192.168.1.33:1900"]);
+    }
+
+    #[test]
+    fn test_search_once_using_ipv6_group() {
+        let transport = MemoryTransport::new("[fe80::1]:50000".parse().unwrap());
+        let mut options = Options::default_for(SpecVersion::V10);
+        options.address = Some("FF02::C".to_string());
+        assert!(search_once_using(options.clone(), &transport)
+            .unwrap()
+            .is_empty());
+
+        let sent = transport.sent();
+        let (to_address, request) = sent.first().unwrap();
+        assert_eq!(to_address.to_string(), "[ff02::c]:1900");
+        let request = String::from_utf8(request.clone()).unwrap();
+        assert!(request.contains("HOST:[ff02::c]:1900\r\n"));
+
+        options.address = Some("ssdp.example.com".to_string());
+        assert!(search_once_using(options, &transport).is_err());
+    }
+
+    #[test]
+    fn test_search_once_using_no_responses() {
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+
+        let responses = search_once_using(Options::default_for(SpecVersion::V10), &transport);
+        assert!(responses.unwrap().is_empty());
+        assert_eq!(transport.sent().len(), 1);
+    }
+
+    #[test]
+    fn test_socket_options() {
+        let mut options = Options::default_for(SpecVersion::V11);
+        let multicast_options: MulticastOptions = options.clone().into();
+        assert_eq!(multicast_options.recv_timeout, Duration::from_secs(2));
+        assert_eq!(multicast_options.packet_ttl, 2);
+        assert_eq!(multicast_options.buffer_size, DEFAULT_BUFFER_SIZE);
+
+        options.source_address = Some(Ipv4Addr::LOCALHOST.into());
+        options.recv_timeout = Some(Duration::from_millis(250));
+        options.loop_back_also = true;
+        options.reuse_address = true;
+        options.dscp = Some(46);
+        let multicast_options: MulticastOptions = options.clone().into();
+        assert_eq!(
+            multicast_options.source_address,
+            Some(Ipv4Addr::LOCALHOST.into())
+        );
+        assert_eq!(multicast_options.recv_timeout, Duration::from_millis(250));
+        assert!(multicast_options.loop_back_also);
+        assert!(multicast_options.reuse_address);
+        assert!(!multicast_options.reuse_port);
+        assert_eq!(multicast_options.dscp, Some(46));
+        assert!(options.validate().is_ok());
+
+        options.dscp = Some(64);
+        assert!(options.validate().is_err());
+        options.dscp = None;
+        options.buffer_size = Some(0);
+        assert!(options.validate().is_err());
+    }
+
+    #[test]
+    fn test_large_response_not_truncated() {
+        let padding = format!("X-PADDING: {}\r\n\r\n", "p".repeat(3000));
+        let response = EX_RESPONSE.replacen("\r\n\r\n", &format!("\r\n{}", padding), 1);
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        transport.push_response("192.168.1.30:1900".parse().unwrap(), response.as_bytes());
+
+        let responses =
+            search_once_using(Options::default_for(SpecVersion::V10), &transport).unwrap();
+        assert_eq!(
+            responses[0].other_headers.get("X-PADDING").unwrap().len(),
+            3000
+        );
+
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        transport.push_response("192.168.1.30:1900".parse().unwrap(), response.as_bytes());
+        let mut options = Options::default_for(SpecVersion::V10);
+        options.buffer_size = Some(1500);
+        // The datagram is cut at the buffer size, part way through the padding header.
+        let kept = 1500 - response.find("X-PADDING: ").unwrap() - "X-PADDING: ".len();
+        let responses = search_once_using(options, &transport).unwrap();
+        assert_eq!(
+            responses[0].other_headers.get("X-PADDING").unwrap().len(),
+            kept
+        );
+    }
+
+    #[test]
+    fn test_extra_and_other_headers() {
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        transport.push_response(
+            "192.168.1.30:1900".parse().unwrap(),
+            EX_RESPONSE
+                .replace(
+                    "\r\n\r\n",
+                    "\r\nX-Vendor: one\r\nOPT: \"http://schemas.upnp.org/upnp/1/0/\"; ns=01\r\nx-vendor: two\r\n\r\n",
+                )
+                .as_bytes(),
+        );
+        let mut options = Options::default_for(SpecVersion::V11);
+        options.extra_headers = vec![
+            ("USER-AGENT".to_string(), "Example/1.0".to_string()),
+            ("X-User-Agent".to_string(), "redsonic".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        let responses = search_once_using(options, &transport).unwrap();
+        let other_headers = &responses[0].other_headers;
+        let names: Vec<&String> = other_headers.iter().map(|(k, _)| k).collect();
+        assert_eq!(names, vec!["X-Vendor", "x-vendor"]);
+        assert_eq!(
+            responses[0].extension.as_ref().unwrap().uri,
+            "http://schemas.upnp.org/upnp/1/0/"
+        );
+        assert_eq!(other_headers.get_all("X-VENDOR"), vec!["one", "two"]);
+
+        let sent = transport.sent();
+        let request = String::from_utf8(sent[0].1.clone()).unwrap();
+        assert!(request.ends_with("\r\nUSER-AGENT:Example/1.0\r\nX-User-Agent:redsonic\r\n\r\n"));
+        assert_eq!(request.matches("USER-AGENT:").count(), 1);
+    }
+
+    #[test]
+    fn test_search_once_to_device_using() {
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        transport.push_response("192.168.1.30:1901".parse().unwrap(), EX_RESPONSE.as_bytes());
+        let device_address = search_address(
+            &URL::from_str("http://192.168.1.30:49152/description.xml").unwrap(),
+            Some(1901),
+        )
+        .unwrap();
+        assert_eq!(device_address.to_string(), "192.168.1.30:1901");
+
+        let responses = search_once_to_device_using(
+            Options::default_for(SpecVersion::V11),
+            device_address,
+            &transport,
+        )
+        .unwrap();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].search_address().unwrap().port(), 1900);
+
+        let sent = transport.sent();
+        let (to_address, request) = sent.first().unwrap();
+        assert_eq!(to_address, &device_address);
+        let request = String::from_utf8(request.clone()).unwrap();
+        assert!(request.contains("HOST:192.168.1.30:1901\r\n"));
+        assert!(!request.contains("MX:"));
+        assert!(request.contains("USER-AGENT:"));
+
+        assert!(search_once_to_device_using(
+            Options::default_for(SpecVersion::V10),
+            device_address,
+            &transport
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_tcp_response_listener() {
+        let listener = TcpResponseListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = listener.local_addr().unwrap();
+        let device = thread::spawn(move || {
+            let mut stream = std::net::TcpStream::connect(address).unwrap();
+            std::io::Write::write_all(&mut stream, EX_RESPONSE.as_bytes()).unwrap();
+        });
+        let response = listener
+            .next_response(Duration::from_secs(2))
+            .unwrap()
+            .unwrap();
+        device.join().unwrap();
+        assert_eq!(
+            response.service_name.to_string(),
+            "uuid:2f402f80-da50-11e1-9b23-00178829d301::upnp:rootdevice"
+        );
+        assert_eq!(response.from.unwrap().ip(), address.ip());
+        assert!(listener
+            .next_response(Duration::from_millis(50))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_tcp_response_listener_deadline() {
+        let listener = TcpResponseListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = listener.local_addr().unwrap();
+        // Connected, but never sends a response.
+        let _silent = std::net::TcpStream::connect(address).unwrap();
+        let mut invalid = std::net::TcpStream::connect(address).unwrap();
+        std::io::Write::write_all(&mut invalid, b"HTTP/1.1 200 OK\r\n\r\n").unwrap();
+
+        let started = Instant::now();
+        assert!(listener
+            .next_response(Duration::from_millis(200))
+            .unwrap()
+            .is_none());
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_search_once_with_tcp_using() {
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        transport.push_response("192.168.1.30:1900".parse().unwrap(), EX_RESPONSE.as_bytes());
+        let listener = TcpResponseListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let mut device = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        std::io::Write::write_all(
+            &mut device,
+            EX_RESPONSE
+                .replace("192.168.1.30", "192.168.1.31")
+                .as_bytes(),
+        )
+        .unwrap();
+        drop(device);
+
+        let options = Options::for_control_point(ControlPoint {
+            friendly_name: "Test".to_string(),
+            uuid: None,
+            port: None,
+        });
+        let responses = search_once_with_tcp_using(options, &listener, &transport).unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0].from.unwrap().to_string(), "192.168.1.30:1900");
+        assert_eq!(
+            responses[1].location.to_string(),
+            "http://192.168.1.31:49152/description.xml"
+        );
+
+        let sent = transport.sent();
+        let request = String::from_utf8(sent[0].1.clone()).unwrap();
+        assert!(request.contains(&format!(
+            "TCPPORT.UPNP.ORG:{}\r\n",
+            listener.port().unwrap()
+        )));
+
+        assert!(search_once_with_tcp_using(
+            Options::default_for(SpecVersion::V20),
+            &listener,
+            &transport
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_search_target_matching() {
+        let all = SearchTarget::from_str("ssdp:all").unwrap();
+        assert_eq!(all.to_string(), "ssdp:all");
+        assert!(matches!(
+            SearchTarget::from_str("ssdp::all").unwrap(),
+            SearchTarget::All
+        ));
+
+        let cd_v1 =
+            SearchTarget::from_str("urn:schemas-upnp-org:service:ContentDirectory:1").unwrap();
+        let cd_v2 =
+            SearchTarget::from_str("urn:schemas-upnp-org:service:ContentDirectory:2").unwrap();
+        assert!(all.matches(&cd_v1));
+        assert!(cd_v1.matches(&cd_v2));
+        assert!(!cd_v2.matches(&cd_v1));
+        assert!(!cd_v1.matches(&SearchTarget::RootDevice));
+        assert!(SearchTarget::RootDevice.matches(&SearchTarget::RootDevice));
+
+        let light = SearchTarget::from_str("urn:example-com:device:Light:1").unwrap();
+        assert_eq!(
+            light,
+            SearchTarget::DomainDeviceType("example-com".to_string(), "Light:1".to_string())
+        );
+        assert_eq!(light.to_string(), "urn:example-com:device:Light:1");
+    }
+
+    #[test]
+    fn test_parse_search_request() {
+        let request = Request::try_from(
+            "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 3\r\nST: upnp:rootdevice\r\nCPFN.UPNP.ORG: Living Room\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let search = SearchRequest::try_from(&request).unwrap();
+        assert!(matches!(search.search_target, SearchTarget::RootDevice));
+        assert_eq!(search.max_wait_time, Some(3));
+        assert_eq!(
+            search.control_point.unwrap().friendly_name,
+            "Living Room".to_string()
+        );
+
+        let request = Request::try_from(
+            "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: ssdp:discover\r\nST: ssdp:all\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert!(SearchRequest::try_from(&request).is_err());
+    }
+
+    fn parse_response(packet: &str) -> Result<Response, Error> {
+        Response::try_from(MulticastResponse::try_from(packet.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_parse_captured_responses() {
+        // Philips Hue bridge, UPnP 1.0.
+        let response = parse_response(
+            "HTTP/1.1 200 OK\r\nHOST: 239.255.255.250:1900\r\nEXT:\r\nCACHE-CONTROL: max-age=100\r\nLOCATION: http://192.168.1.30:80/description.xml\r\nSERVER: Linux/3.14.0 UPnP/1.0 IpBridge/1.40.0\r\nhue-bridgeid: 001788FFFE29D301\r\nST: upnp:rootdevice\r\nUSN: uuid:2f402f80-da50-11e1-9b23-00178829d301::upnp:rootdevice\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(response.date, None);
+        assert_eq!(
+            response.versions.platform_version().to_string(),
+            "Linux/3.14.0"
+        );
+        assert_eq!(response.versions.spec_version(), Some(SpecVersion::V10));
+        assert_eq!(
+            response.versions.product_version().to_string(),
+            "IpBridge/1.40.0"
+        );
+        assert_eq!(response.boot_id, None);
+        assert_eq!(
+            response.other_headers.get("HUE-BRIDGEID").unwrap(),
+            "001788FFFE29D301"
+        );
+
+        // libupnp based media renderer, with the HTTP Extension Framework headers.
+        let response = parse_response(
+            "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nDATE: Sat, 03 Oct 2020 14:15:21 GMT\r\nEXT:\r\nLOCATION: http://192.168.1.40:49153/description.xml\r\nOPT: \"http://schemas.upnp.org/upnp/1/0/\"; ns=01\r\n01-NLS: 1f2e6d4e-1dd2-11b2-a2c8-b0c5a1e6d2f1\r\nSERVER: Linux/4.19.0, UPnP/1.0, Portable SDK for UPnP devices/1.8.4\r\nX-User-Agent: redsonic\r\nST: urn:schemas-upnp-org:device:MediaRenderer:1\r\nUSN: uuid:5f9ec1b3-ed59-79bb-4530-745f9b2a9a4c::urn:schemas-upnp-org:device:MediaRenderer:1\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            response.date,
+            Some(UNIX_EPOCH + Duration::from_secs(1_601_734_521))
+        );
+        assert_eq!(
+            response.versions.product_version().name(),
+            "Portable SDK for UPnP devices"
+        );
+        assert_eq!(response.versions.product_version().version(), "1.8.4");
+        assert_eq!(
+            response.extension,
+            Some(Extension {
+                uri: "http://schemas.upnp.org/upnp/1/0/".to_string(),
+                namespace: Some("01".to_string()),
+                network_location_signature: Some(
+                    "1f2e6d4e-1dd2-11b2-a2c8-b0c5a1e6d2f1".to_string()
+                ),
+            })
+        );
+        let names: Vec<&String> = response.other_headers.iter().map(|(k, _)| k).collect();
+        assert_eq!(names, vec!["X-User-Agent"]);
+
+        // Sonos speaker; it sends BOOTID.UPNP.ORG but claims only UPnP 1.0.
+        let response = parse_response(
+            "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age = 1800\r\nEXT:\r\nLOCATION: http://192.168.1.45:1400/xml/device_description.xml\r\nSERVER: Linux UPnP/1.0 Sonos/57.3-79090 (ZPS9)\r\nST: urn:schemas-upnp-org:device:ZonePlayer:1\r\nUSN: uuid:RINCON_B8E93781B4A801400::urn:schemas-upnp-org:device:ZonePlayer:1\r\nX-RINCON-HOUSEHOLD: Sonos_abcdefghijklmnopqrstuvwxyz\r\nX-RINCON-BOOTSEQ: 71\r\nBOOTID.UPNP.ORG: 71\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(response.max_age, Duration::from_secs(1800));
+        assert_eq!(response.versions.platform_version().name(), "Linux");
+        assert_eq!(response.versions.platform_version().version(), "");
+        assert_eq!(response.versions.product_version().name(), "Sonos");
+        assert_eq!(response.boot_id, None);
+        assert_eq!(response.other_headers.get("BOOTID.UPNP.ORG").unwrap(), "71");
+
+        // MiniUPnPd internet gateway, UPnP 1.1.
+        let response = parse_response(
+            "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\nUSN: uuid:bd0c8c6b-33ca-4d3c-9a2a-2d5e1a2b7b1c::urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\nEXT:\r\nSERVER: FreeBSD/12.1 UPnP/1.1 MiniUPnPd/2.1\r\nLOCATION: http://192.168.1.1:2189/rootDesc.xml\r\nOPT: \"http://schemas.upnp.org/upnp/1/0/\"; ns=01\r\n01-NLS: 1601656521\r\nBOOTID.UPNP.ORG: 1601656521\r\nCONFIGID.UPNP.ORG: 1337\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(response.versions.spec_version(), Some(SpecVersion::V11));
+        assert_eq!(response.boot_id, Some(1_601_656_521));
+        assert_eq!(response.config_id, Some(1337));
+        assert_eq!(response.search_port, None);
+        assert!(response.other_headers.is_empty());
+
+        // UPnP 2.0 device implementing Device Protection.
+        let response = parse_response(
+            "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=1800\r\nDATE: Fri, 02 Oct 2020 16:35:21 GMT\r\nEXT:\r\nLOCATION: http://192.168.1.50:49152/description.xml\r\nSERVER: Linux/5.4 UPnP/2.0 Example/1.0\r\nST: upnp:rootdevice\r\nUSN: uuid:0b1c2d3e-4f50-6172-8394-a5b6c7d8e9f0::upnp:rootdevice\r\nBOOTID.UPNP.ORG: 12\r\nCONFIGID.UPNP.ORG: 3\r\nSEARCHPORT.UPNP.ORG: 49200\r\nSECURELOCATION.UPNP.ORG: https://192.168.1.50:49443/description.xml\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(response.boot_id, Some(12));
+        assert_eq!(response.search_port, Some(49200));
+        assert_eq!(
+            response.secure_location.unwrap().to_string(),
+            "https://192.168.1.50:49443/description.xml"
+        );
+
+        // A SERVER value without a UPnP version, and an invalid BOOTID.UPNP.ORG, are errors.
+        assert!(parse_response(&EX_RESPONSE.replace("UPnP/1.0 ", "")).is_err());
+        assert!(parse_response(
+            &EX_RESPONSE
+                .replace("UPnP/1.0", "UPnP/1.1")
+                .replace("\r\n\r\n", "\r\nBOOTID.UPNP.ORG: one\r\n\r\n")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_user_agent_round_trip() {
+        let server = user_agent_string(
+            SpecVersion::V11,
+            Some(ProductVersion::for_product("Example", "1.0")),
+        );
+        let versions = ProductVersions::from_str(&server).unwrap();
+        assert_eq!(versions.product_version().to_string(), "Example/1.0");
+        assert_eq!(versions.upnp_version().to_string(), "UPnP/1.1");
+        assert_eq!(versions.to_string(), server);
+    }
+
+    #[test]
+    fn test_product_versions_order() {
+        let versions = ProductVersions::new(
+            ProductVersion::for_product("Example", "1.0"),
+            ProductVersion::for_upnp_version(SpecVersion::V11),
+            ProductVersion::for_product("Linux", "5.10"),
+        );
+        assert_eq!(versions.to_string(), "Linux/5.10 UPnP/1.1 Example/1.0");
+
+        let server = user_agent_string(
+            SpecVersion::V11,
+            Some(ProductVersion::for_product("Example", "1.0")),
+        );
+        assert_eq!(
+            server,
+            format!("{} UPnP/1.1 Example/1.0", ProductVersion::for_platform())
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_response() {
+        let transport = MemoryTransport::new("192.168.1.10:50000".parse().unwrap());
+        transport.push_response("192.168.1.30:1900".parse().unwrap(), EX_RESPONSE.as_bytes());
+        let responses =
+            search_once_using(Options::default_for(SpecVersion::V10), &transport).unwrap();
+
+        let value = serde_json::to_value(&responses[0]).unwrap();
+        assert_eq!(value["max_age"], 100);
+        assert_eq!(value["search_target"], "upnp:rootdevice");
+        assert_eq!(
+            value["location"],
+            "http://192.168.1.30:49152/description.xml"
+        );
+        assert_eq!(value["from"], "192.168.1.30:1900");
+        assert_eq!(value["date"], 1_601_656_521);
+        assert_eq!(value["versions"]["product"]["name"], "IpBridge");
+
+        let response: Response = serde_json::from_value(value).unwrap();
+        assert_eq!(response.max_age, Duration::from_secs(100));
+        assert_eq!(response.date, responses[0].date);
+        assert_eq!(
+            response.service_name.to_string(),
+            "uuid:2f402f80-da50-11e1-9b23-00178829d301::upnp:rootdevice"
+        );
+        assert!(serde_json::from_str::<SearchTarget>("\"urn:example-com:bogus\"").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_socket_options() {
+        let mut options = Options::default_for(SpecVersion::V10);
+        options.recv_timeout = Some(Duration::from_millis(250));
+        let value = serde_json::to_value(&options).unwrap();
+        assert_eq!(value["recv_timeout"], 0.25);
+        let options: Options = serde_json::from_value(value).unwrap();
+        assert_eq!(options.recv_timeout, Some(Duration::from_millis(250)));
+
+        let mut value = serde_json::to_value(Options::default_for(SpecVersion::V10)).unwrap();
+        for field in ["source_address", "recv_timeout", "loop_back_also", "dscp"] {
+            let _ = value.as_object_mut().unwrap().remove(field);
+        }
+        let options: Options = serde_json::from_value(value).unwrap();
+        assert_eq!(options.recv_timeout, None);
+        assert!(!options.loop_back_also);
+    }
+}
@@ -6,11 +6,14 @@ More detailed description, with
 
 use quick_xml::Error as XMLError;
 use reqwest::Error as HTTPError;
-use std::fmt::Display;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::io::Error as IOError;
 use std::str::Utf8Error;
+use std::sync::RwLock;
 use thiserror::Error;
 
+use crate::description::validate::Finding;
 use crate::SpecVersion;
 
 // ------------------------------------------------------------------------------------------------
@@ -39,6 +42,77 @@ pub enum Error {
 
     #[error("An operation you attempted is not supported (Operation: `{operation}`)")]
     UnsupportedOperation { operation: String },
+
+    #[error(transparent)]
+    Upnp(#[from] UpnpError),
+
+    #[error("The description is not valid ({} error(s), the first: `{}`)", .findings.len(), .findings.first().map(|f| f.to_string()).unwrap_or_default())]
+    InvalidDescription { findings: Vec<Finding> },
+}
+
+///
+/// The `errorCode` values a device may return in a `<UPnPError>` SOAP fault. The standard codes
+/// are defined by the UDA; codes 700-799 are defined by each device control protocol (DCP) for
+/// its own actions, and 800-899 are left to vendors.
+///
+/// Action-specific codes are only meaningful alongside the service that returned them, the
+/// associated constants below name some commonly used ones, for example
+/// `UpnpErrorCode::CONFLICT_IN_MAPPING_ENTRY`, and may be used as patterns.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UpnpErrorCode {
+    /// 401: No action by that name at this service.
+    InvalidAction,
+    /// 402: Not enough, or too many, in arguments; or arguments with the wrong name or type.
+    InvalidArgs,
+    /// 501: The action failed; may be returned if the current state of the service prevents
+    /// invoking the action.
+    ActionFailed,
+    /// 600: The argument value is invalid.
+    ArgumentValueInvalid,
+    /// 601: An argument value is less than the minimum or more than the maximum value of the
+    /// allowed value range, or is not in the allowed value list.
+    ArgumentValueOutOfRange,
+    /// 602: The requested action is optional and is not implemented by the device.
+    OptionalActionNotImplemented,
+    /// 603: The device does not have sufficient memory available to complete the action.
+    OutOfMemory,
+    /// 604: The device has encountered an error condition which it cannot resolve itself.
+    HumanInterventionRequired,
+    /// 605: A string argument is too long for the device to handle properly.
+    StringArgumentTooLong,
+    /// 606: The action requested requires authorization and the sender was not authorized.
+    ActionNotAuthorized,
+    /// 607: The sender's signature failed to verify.
+    SignatureFailure,
+    /// 608: The action requested requires a digital signature and there was none provided.
+    SignatureMissing,
+    /// 609: This action requires confidentiality but the action was not delivered encrypted.
+    NotEncrypted,
+    /// 610: The sequence provided was not valid.
+    InvalidSequence,
+    /// 611: The controlURL within the freshness element does not match the controlURL of the
+    /// action actually invoked.
+    InvalidControlUrl,
+    /// 612: The session key reference is to a non-existent session.
+    NoSuchSession,
+    /// 700-799: Defined by the DCP of the service that returned it.
+    ActionSpecific(u16),
+    /// 800-899: Defined by the vendor of the device.
+    VendorDefined(u16),
+    /// Any other code, including those reserved for future use by the UDA.
+    Other(u16),
+}
+
+///
+/// An error reported by a device in response to a control action; this corresponds to the
+/// `<UPnPError>` element in the detail of a SOAP fault.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Error)]
+#[error("The device reported UPnP error {code} (Description: `{description}`)")]
+pub struct UpnpError {
+    code: UpnpErrorCode,
+    description: String,
 }
 
 #[derive(Clone, Copy, Debug, Error)]
@@ -205,10 +279,41 @@ where
     }
 }
 
+pub fn invalid_description(findings: Vec<Finding>) -> Error {
+    Error::InvalidDescription { findings }
+}
+
 pub fn xml_error(e: XMLError) -> Error {
     Error::MessageFormat(MessageFormatError::XmlFormat(e))
 }
 
+pub fn upnp_error(code: UpnpErrorCode) -> UpnpError {
+    UpnpError::new(code)
+}
+
+///
+/// Register the description of an action-specific, or vendor-defined, error code for the named
+/// service type, for example `"WANIPConnection"`. Any existing registration is replaced.
+///
+pub fn register_upnp_error(service_name: &str, code: u16, description: &str) {
+    let _ = UPNP_ERROR_REGISTRY
+        .write()
+        .unwrap()
+        .insert((service_name.to_string(), code), description.to_string());
+}
+
+///
+/// Return the registered description of an action-specific, or vendor-defined, error code for
+/// the named service type.
+///
+pub fn registered_upnp_error(service_name: &str, code: u16) -> Option<String> {
+    UPNP_ERROR_REGISTRY
+        .read()
+        .unwrap()
+        .get(&(service_name.to_string(), code))
+        .cloned()
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
@@ -247,6 +352,245 @@ impl<T> From<Error> for Result<T, Error> {
     }
 }
 
+impl<T> From<UpnpError> for Result<T, Error> {
+    fn from(e: UpnpError) -> Self {
+        Err(Error::Upnp(e))
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl Display for UpnpErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl From<u16> for UpnpErrorCode {
+    fn from(code: u16) -> Self {
+        match code {
+            401 => UpnpErrorCode::InvalidAction,
+            402 => UpnpErrorCode::InvalidArgs,
+            501 => UpnpErrorCode::ActionFailed,
+            600 => UpnpErrorCode::ArgumentValueInvalid,
+            601 => UpnpErrorCode::ArgumentValueOutOfRange,
+            602 => UpnpErrorCode::OptionalActionNotImplemented,
+            603 => UpnpErrorCode::OutOfMemory,
+            604 => UpnpErrorCode::HumanInterventionRequired,
+            605 => UpnpErrorCode::StringArgumentTooLong,
+            606 => UpnpErrorCode::ActionNotAuthorized,
+            607 => UpnpErrorCode::SignatureFailure,
+            608 => UpnpErrorCode::SignatureMissing,
+            609 => UpnpErrorCode::NotEncrypted,
+            610 => UpnpErrorCode::InvalidSequence,
+            611 => UpnpErrorCode::InvalidControlUrl,
+            612 => UpnpErrorCode::NoSuchSession,
+            700..=799 => UpnpErrorCode::ActionSpecific(code),
+            800..=899 => UpnpErrorCode::VendorDefined(code),
+            _ => UpnpErrorCode::Other(code),
+        }
+    }
+}
+
+impl From<UpnpErrorCode> for u16 {
+    fn from(code: UpnpErrorCode) -> Self {
+        code.code()
+    }
+}
+
+impl UpnpErrorCode {
+    /// WANIPConnection, WANPPPConnection: the array index is out of bounds.
+    pub const SPECIFIED_ARRAY_INDEX_INVALID: Self = UpnpErrorCode::ActionSpecific(713);
+    /// WANIPConnection, WANPPPConnection: the specified value does not exist in the array.
+    pub const NO_SUCH_ENTRY_IN_ARRAY: Self = UpnpErrorCode::ActionSpecific(714);
+    /// WANIPConnection, WANPPPConnection: the port mapping entry conflicts with a mapping
+    /// assigned previously to another client.
+    pub const CONFLICT_IN_MAPPING_ENTRY: Self = UpnpErrorCode::ActionSpecific(718);
+    /// WANIPConnection, WANPPPConnection: internal and external port values must be the same.
+    pub const SAME_PORT_VALUES_REQUIRED: Self = UpnpErrorCode::ActionSpecific(724);
+    /// WANIPConnection, WANPPPConnection: the NAT implementation only supports permanent lease
+    /// times on port mappings.
+    pub const ONLY_PERMANENT_LEASES_SUPPORTED: Self = UpnpErrorCode::ActionSpecific(725);
+    /// ContentDirectory: the specified object does not exist.
+    pub const NO_SUCH_OBJECT: Self = UpnpErrorCode::ActionSpecific(701);
+    /// ContentDirectory: the specified container does not exist.
+    pub const NO_SUCH_CONTAINER: Self = UpnpErrorCode::ActionSpecific(710);
+
+    ///
+    /// The numeric value of this code, as used in the `errorCode` element.
+    ///
+    pub fn code(&self) -> u16 {
+        match self {
+            UpnpErrorCode::InvalidAction => 401,
+            UpnpErrorCode::InvalidArgs => 402,
+            UpnpErrorCode::ActionFailed => 501,
+            UpnpErrorCode::ArgumentValueInvalid => 600,
+            UpnpErrorCode::ArgumentValueOutOfRange => 601,
+            UpnpErrorCode::OptionalActionNotImplemented => 602,
+            UpnpErrorCode::OutOfMemory => 603,
+            UpnpErrorCode::HumanInterventionRequired => 604,
+            UpnpErrorCode::StringArgumentTooLong => 605,
+            UpnpErrorCode::ActionNotAuthorized => 606,
+            UpnpErrorCode::SignatureFailure => 607,
+            UpnpErrorCode::SignatureMissing => 608,
+            UpnpErrorCode::NotEncrypted => 609,
+            UpnpErrorCode::InvalidSequence => 610,
+            UpnpErrorCode::InvalidControlUrl => 611,
+            UpnpErrorCode::NoSuchSession => 612,
+            UpnpErrorCode::ActionSpecific(code) => *code,
+            UpnpErrorCode::VendorDefined(code) => *code,
+            UpnpErrorCode::Other(code) => *code,
+        }
+    }
+
+    ///
+    /// The description given to this code by the UDA, if it is a standard code.
+    ///
+    pub fn standard_description(&self) -> Option<&'static str> {
+        match self {
+            UpnpErrorCode::InvalidAction => Some("Invalid Action"),
+            UpnpErrorCode::InvalidArgs => Some("Invalid Args"),
+            UpnpErrorCode::ActionFailed => Some("Action Failed"),
+            UpnpErrorCode::ArgumentValueInvalid => Some("Argument Value Invalid"),
+            UpnpErrorCode::ArgumentValueOutOfRange => Some("Argument Value Out of Range"),
+            UpnpErrorCode::OptionalActionNotImplemented => Some("Optional Action Not Implemented"),
+            UpnpErrorCode::OutOfMemory => Some("Out of Memory"),
+            UpnpErrorCode::HumanInterventionRequired => Some("Human Intervention Required"),
+            UpnpErrorCode::StringArgumentTooLong => Some("String Argument Too Long"),
+            UpnpErrorCode::ActionNotAuthorized => Some("Action not authorized"),
+            UpnpErrorCode::SignatureFailure => Some("Signature failure"),
+            UpnpErrorCode::SignatureMissing => Some("Signature missing"),
+            UpnpErrorCode::NotEncrypted => Some("Not encrypted"),
+            UpnpErrorCode::InvalidSequence => Some("Invalid sequence"),
+            UpnpErrorCode::InvalidControlUrl => Some("Invalid control URL"),
+            UpnpErrorCode::NoSuchSession => Some("No such session"),
+            _ => None,
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl UpnpError {
+    ///
+    /// Create a new error with the standard description for `code`, or a generic one if
+    /// `code` is not a standard code.
+    ///
+    pub fn new(code: UpnpErrorCode) -> Self {
+        let description = match code.standard_description() {
+            Some(description) => description.to_string(),
+            None => format!("Error {}", code),
+        };
+        Self { code, description }
+    }
+
+    ///
+    /// Create a new error, for the named service type, using any description registered with
+    /// `register_upnp_error`.
+    ///
+    pub fn for_service(service_name: &str, code: u16) -> Self {
+        match registered_upnp_error(service_name, code) {
+            Some(description) => Self::with_description(code.into(), &description),
+            None => Self::new(code.into()),
+        }
+    }
+
+    pub fn with_description(code: UpnpErrorCode, description: &str) -> Self {
+        Self {
+            code,
+            description: description.to_string(),
+        }
+    }
+
+    pub fn code(&self) -> UpnpErrorCode {
+        self.code
+    }
+
+    pub fn description(&self) -> &String {
+        &self.description
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+// Private Values
+// ------------------------------------------------------------------------------------------------
+
+lazy_static! {
+    static ref UPNP_ERROR_REGISTRY: RwLock<HashMap<(String, u16), String>> = {
+        let mut registry: HashMap<(String, u16), String> = Default::default();
+        for service_name in &["WANIPConnection", "WANPPPConnection"] {
+            for (code, description) in &[
+                (713, "SpecifiedArrayIndexInvalid"),
+                (714, "NoSuchEntryInArray"),
+                (715, "WildCardNotPermittedInSrcIP"),
+                (716, "WildCardNotPermittedInExtPort"),
+                (718, "ConflictInMappingEntry"),
+                (724, "SamePortValuesRequired"),
+                (725, "OnlyPermanentLeasesSupported"),
+                (726, "RemoteHostOnlySupportsWildcard"),
+                (727, "ExternalPortOnlySupportsWildcard"),
+            ] {
+                let _ = registry.insert((service_name.to_string(), *code), description.to_string());
+            }
+        }
+        for (code, description) in &[
+            (701, "No such object"),
+            (709, "Unsupported or invalid sort criteria"),
+            (710, "No such container"),
+        ] {
+            let _ = registry.insert(
+                ("ContentDirectory".to_string(), *code),
+                description.to_string(),
+            );
+        }
+        RwLock::new(registry)
+    };
+}
+
 // ------------------------------------------------------------------------------------------------
 // Modules
 // ------------------------------------------------------------------------------------------------
+
+// ------------------------------------------------------------------------------------------------
+// Unit Tests
+// ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_upnp_error_codes() {
+        for code in &[401u16, 402, 501, 600, 605, 612, 718, 801, 650] {
+            assert_eq!(UpnpErrorCode::from(*code).code(), *code);
+        }
+        assert_eq!(UpnpErrorCode::from(402), UpnpErrorCode::InvalidArgs);
+        assert_eq!(UpnpErrorCode::from(650), UpnpErrorCode::Other(650));
+        assert_eq!(UpnpErrorCode::from(801), UpnpErrorCode::VendorDefined(801));
+        assert_eq!(
+            UpnpError::new(UpnpErrorCode::InvalidAction).description(),
+            "Invalid Action"
+        );
+    }
+
+    #[test]
+    fn test_upnp_error_registry() {
+        let error = UpnpError::for_service("WANIPConnection", 718);
+        assert!(matches!(
+            error.code(),
+            UpnpErrorCode::CONFLICT_IN_MAPPING_ENTRY
+        ));
+        assert_eq!(error.description(), "ConflictInMappingEntry");
+
+        register_upnp_error("X_Example", 801, "Lamp is broken");
+        assert_eq!(
+            UpnpError::for_service("X_Example", 801).description(),
+            "Lamp is broken"
+        );
+        assert_eq!(
+            UpnpError::for_service("X_Example", 802).description(),
+            "Error 802"
+        );
+    }
+}
@@ -1,28 +1,481 @@
 /*!
 This module implements the UPnP device and service eventing capabilities via the _General Event
 Notification Architecture_ (GENA) protocol.
+
+A control point subscribes to a service by sending a `SUBSCRIBE` request to the service's event
+URL, naming one or more callback URLs; the device assigns a subscription identifier (SID) and
+then sends a `NOTIFY` request, carrying a property set, to the callback each time an evented state
+variable changes. Subscriptions expire unless renewed, and should be cancelled when no longer
+required. The `subscribe`, `renew`, and `unsubscribe` functions manage the subscription while the
+`CallbackListener` receives the resulting events.
+
+# Example
+
+```rust,no_run
+use upnp_rs::common::uri::URL;
+use upnp_rs::eventing::{subscribe, unsubscribe, CallbackListener};
+use std::str::FromStr;
+use std::time::Duration;
+
+let event_url = URL::from_str("http://192.168.1.10:49152/upnp/event/power").unwrap();
+let listener = CallbackListener::new("0.0.0.0:0".parse().unwrap()).unwrap();
+let callback = listener.callback_url_for(&event_url).unwrap();
+let subscription =
+    subscribe(&event_url, &[callback], Some(Duration::from_secs(1800))).unwrap();
+if let Some(event) = listener.next_event(Duration::from_secs(30)).unwrap() {
+    println!("{:?}", event.properties);
+}
+unsubscribe(&subscription).unwrap();
+```
 */
 
+use crate::common::headers;
+use crate::common::http::{read_request, write_response};
+use crate::common::httpu::{Request, Response, ResponseBuilder};
+use crate::common::interface::local_address_for;
+use crate::common::uri::URL;
+use crate::common::xml::read::read_document;
+use crate::error::{invalid_field_value, invalid_header_value, invalid_value_for_type, Error};
+use crate::syntax::{
+    GENA_NTS_PROPERTY_CHANGE, GENA_NT_EVENT, GENA_TIMEOUT_INFINITE, GENA_TIMEOUT_PREFIX,
+    HTTP_HEADER_CALLBACK, HTTP_HEADER_NT, HTTP_HEADER_NTS, HTTP_HEADER_SEQ, HTTP_HEADER_SID,
+    HTTP_HEADER_TIMEOUT, HTTP_METHOD_NOTIFY, HTTP_METHOD_SUBSCRIBE, HTTP_METHOD_UNSUBSCRIBE,
+    XML_ELEM_PROPERTY, XML_ELEM_PROPERTY_SET,
+};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::Method;
+use std::convert::TryFrom;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, TcpListener, ToSocketAddrs};
+use std::str::{from_utf8, FromStr};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
 // ------------------------------------------------------------------------------------------------
 // Public Types
 // ------------------------------------------------------------------------------------------------
 
+///
+/// An active subscription, as returned by the device in response to a subscribe or renew
+/// request.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Subscription {
+    /// The event URL of the service subscribed to.
+    pub event_url: URL,
+    /// The Subscription Identifier assigned by the device.
+    pub sid: String,
+    /// The actual duration of the subscription, `None` denotes a subscription that does not
+    /// expire.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "crate::common::seconds::option")
+    )]
+    pub timeout: Option<Duration>,
+}
+
+///
+/// A single event message, sent by the device to the callback URL of a subscription.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Event {
+    /// The Subscription Identifier this event belongs to.
+    pub sid: String,
+    /// The event key, `0` for the initial event sent on subscription.
+    pub seq: u32,
+    /// The evented state variables, and their new values, in document order.
+    pub properties: Vec<(String, String)>,
+}
+
+///
+/// A simple HTTP server that receives event messages sent to a callback URL.
+///
+#[derive(Debug)]
+pub struct CallbackListener {
+    listener: TcpListener,
+}
+
 // ------------------------------------------------------------------------------------------------
 // Public Functions
 // ------------------------------------------------------------------------------------------------
 
+///
+/// Subscribe to the service at `event_url`, asking the device to send events to the first of
+/// `callbacks` that it can reach. The requested `timeout` is a hint, the device returns the
+/// actual duration in the resulting subscription; `None` requests an infinite subscription.
+///
+pub fn subscribe(
+    event_url: &URL,
+    callbacks: &[URL],
+    timeout: Option<Duration>,
+) -> Result<Subscription, Error> {
+    info!("subscribe - subscribing to {}", event_url);
+    let callback = callbacks
+        .iter()
+        .map(|callback| format!("<{}>", callback))
+        .collect::<String>();
+    let request = new_request(HTTP_METHOD_SUBSCRIBE, event_url)
+        .header(HTTP_HEADER_CALLBACK, callback)
+        .header(HTTP_HEADER_NT, GENA_NT_EVENT)
+        .header(HTTP_HEADER_TIMEOUT, timeout_to_string(timeout));
+    send_subscription_request(HTTP_METHOD_SUBSCRIBE, event_url, request)
+}
+
+///
+/// Renew `subscription` before it expires, returning the subscription with its new timeout.
+///
+pub fn renew(
+    subscription: &Subscription,
+    timeout: Option<Duration>,
+) -> Result<Subscription, Error> {
+    info!("renew - renewing subscription {}", subscription.sid);
+    let request = new_request(HTTP_METHOD_SUBSCRIBE, &subscription.event_url)
+        .header(HTTP_HEADER_SID, &subscription.sid)
+        .header(HTTP_HEADER_TIMEOUT, timeout_to_string(timeout));
+    send_subscription_request(HTTP_METHOD_SUBSCRIBE, &subscription.event_url, request)
+}
+
+///
+/// Cancel `subscription`, the device sends no further events for it.
+///
+pub fn unsubscribe(subscription: &Subscription) -> Result<(), Error> {
+    info!("unsubscribe - cancelling subscription {}", subscription.sid);
+    let response = new_request(HTTP_METHOD_UNSUBSCRIBE, &subscription.event_url)
+        .header(HTTP_HEADER_SID, &subscription.sid)
+        .send()?;
+    if !response.status().is_success() {
+        error!(
+            "unsubscribe - {} returned status {}",
+            subscription.event_url,
+            response.status()
+        );
+        return Err(Error::OperationFailed {
+            operation: format!("{} {}", HTTP_METHOD_UNSUBSCRIBE, subscription.event_url),
+            status: response.status().to_string(),
+        });
+    }
+    Ok(())
+}
+
 // ------------------------------------------------------------------------------------------------
 // Implementations
 // ------------------------------------------------------------------------------------------------
 
+impl TryFrom<&Request> for Event {
+    type Error = Error;
+
+    fn try_from(request: &Request) -> Result<Self, Self::Error> {
+        if request.method() != HTTP_METHOD_NOTIFY {
+            error!(
+                "try_from - request method '{}' is not NOTIFY",
+                request.method()
+            );
+            return invalid_value_for_type("Event", request.method()).into();
+        }
+        let headers = request.headers();
+        headers::check_required(headers, &REQUIRED_HEADERS)?;
+        let nt = headers.get(HTTP_HEADER_NT).unwrap();
+        if nt != GENA_NT_EVENT {
+            error!("try_from - NT '{}' is not '{}'", nt, GENA_NT_EVENT);
+            return invalid_header_value(HTTP_HEADER_NT, nt).into();
+        }
+        let nts = headers.get(HTTP_HEADER_NTS).unwrap();
+        if nts != GENA_NTS_PROPERTY_CHANGE {
+            error!(
+                "try_from - NTS '{}' is not '{}'",
+                nts, GENA_NTS_PROPERTY_CHANGE
+            );
+            return invalid_header_value(HTTP_HEADER_NTS, nts).into();
+        }
+        let seq = headers::check_parsed_value::<u32>(
+            headers.get(HTTP_HEADER_SEQ).unwrap(),
+            HTTP_HEADER_SEQ,
+        )?;
+        let body = match request.body() {
+            Some(body) => match from_utf8(body) {
+                Ok(body) => body,
+                Err(e) => {
+                    error!("try_from - event body is not valid UTF-8: {}", e);
+                    return invalid_field_value(XML_ELEM_PROPERTY_SET, "").into();
+                }
+            },
+            None => "",
+        };
+        Ok(Event {
+            sid: headers.get(HTTP_HEADER_SID).unwrap().clone(),
+            seq,
+            properties: properties_from_str(body)?,
+        })
+    }
+}
+
+// ------------------------------------------------------------------------------------------------
+
+impl CallbackListener {
+    ///
+    /// Create a new listener bound to `address`; use port `0` to have the operating system
+    /// choose a free port.
+    ///
+    pub fn new(address: SocketAddr) -> Result<Self, Error> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        info!("new - listening for events on {}", listener.local_addr()?);
+        Ok(Self { listener })
+    }
+
+    ///
+    /// The address this listener is bound to.
+    ///
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    ///
+    /// Return the callback URL a device serving `event_url` should use to reach this listener.
+    /// If the listener is bound to an unspecified address the local address used to route to
+    /// the device is used.
+    ///
+    pub fn callback_url_for(&self, event_url: &URL) -> Result<URL, Error> {
+        let local = self.local_addr()?;
+        let address = if local.ip().is_unspecified() {
+            route_address_to(event_url)?
+        } else {
+            local.ip()
+        };
+        let host = match address {
+            IpAddr::V4(address) => address.to_string(),
+            IpAddr::V6(address) => format!("[{}]", address),
+        };
+        URL::from_str(&format!("http://{}:{}/", host, local.port()))
+    }
+
+    ///
+    /// Wait up to `timeout` for the next event message, returning `None` if none arrived. Each
+    /// valid message is acknowledged with `200 OK`, invalid messages are rejected and skipped.
+    ///
+    pub fn next_event(&self, timeout: Duration) -> Result<Option<Event>, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.listener.accept() {
+                Ok((mut stream, from)) => {
+                    stream.set_nonblocking(false)?;
+                    stream.set_read_timeout(Some(STREAM_TIMEOUT))?;
+                    let event =
+                        read_request(&mut stream).and_then(|request| Event::try_from(&request));
+                    let response: Response = match &event {
+                        Ok(_) => ResponseBuilder::ok().into(),
+                        Err(e) => {
+                            warn!("next_event - ignoring invalid message from {}: {}", from, e);
+                            ResponseBuilder::new(412, "Precondition Failed").into()
+                        }
+                    };
+                    if let Err(e) = write_response(&mut stream, &response) {
+                        warn!("next_event - could not respond to {}: {}", from, e);
+                    }
+                    if let Ok(event) = event {
+                        return Ok(Some(event));
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(None);
+                    }
+                    sleep(POLL_INTERVAL.min(deadline - now));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
 // ------------------------------------------------------------------------------------------------
 // Private Types
 // ------------------------------------------------------------------------------------------------
 
+const REQUIRED_HEADERS: [&str; 4] = [
+    HTTP_HEADER_NT,
+    HTTP_HEADER_NTS,
+    HTTP_HEADER_SID,
+    HTTP_HEADER_SEQ,
+];
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const STREAM_TIMEOUT: Duration = Duration::from_secs(5);
+
 // ------------------------------------------------------------------------------------------------
 // Private Functions
 // ------------------------------------------------------------------------------------------------
 
+fn new_request(method: &str, event_url: &URL) -> RequestBuilder {
+    Client::new().request(
+        Method::from_bytes(method.as_bytes()).unwrap(),
+        event_url.to_string(),
+    )
+}
+
+fn send_subscription_request(
+    method: &str,
+    event_url: &URL,
+    request: RequestBuilder,
+) -> Result<Subscription, Error> {
+    let response = request.send()?;
+    info!("send_subscription_request - received {:?}", &response);
+    if !response.status().is_success() {
+        error!(
+            "send_subscription_request - {} {} returned status {}",
+            method,
+            event_url,
+            response.status()
+        );
+        return Err(Error::OperationFailed {
+            operation: format!("{} {}", method, event_url),
+            status: response.status().to_string(),
+        });
+    }
+    let headers = response.headers();
+    let sid = match headers
+        .get(HTTP_HEADER_SID)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(sid) if !sid.trim().is_empty() => sid.trim().to_string(),
+        _ => {
+            error!("send_subscription_request - response has no SID");
+            return invalid_header_value(HTTP_HEADER_SID, "").into();
+        }
+    };
+    let timeout = match headers
+        .get(HTTP_HEADER_TIMEOUT)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(timeout) => timeout_from_str(timeout)?,
+        None => {
+            error!("send_subscription_request - response has no TIMEOUT");
+            return invalid_header_value(HTTP_HEADER_TIMEOUT, "").into();
+        }
+    };
+    Ok(Subscription {
+        event_url: event_url.clone(),
+        sid,
+        timeout,
+    })
+}
+
+fn timeout_to_string(timeout: Option<Duration>) -> String {
+    match timeout {
+        Some(timeout) => format!("{}{}", GENA_TIMEOUT_PREFIX, timeout.as_secs()),
+        None => format!("{}{}", GENA_TIMEOUT_PREFIX, GENA_TIMEOUT_INFINITE),
+    }
+}
+
+fn timeout_from_str(s: &str) -> Result<Option<Duration>, Error> {
+    let value = s.trim();
+    match value.get(..GENA_TIMEOUT_PREFIX.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(GENA_TIMEOUT_PREFIX) => {
+            let seconds = &value[GENA_TIMEOUT_PREFIX.len()..];
+            if seconds.eq_ignore_ascii_case(GENA_TIMEOUT_INFINITE) {
+                Ok(None)
+            } else {
+                Ok(Some(Duration::from_secs(
+                    headers::check_parsed_value::<u64>(seconds, HTTP_HEADER_TIMEOUT)?,
+                )))
+            }
+        }
+        _ => {
+            error!("timeout_from_str - invalid TIMEOUT value '{}'", s);
+            invalid_header_value(HTTP_HEADER_TIMEOUT, s).into()
+        }
+    }
+}
+
+fn properties_from_str(xml: &str) -> Result<Vec<(String, String)>, Error> {
+    let property_set = read_document(xml)?;
+    if property_set.local_name() != XML_ELEM_PROPERTY_SET {
+        error!(
+            "properties_from_str - document element '{}' is not '{}'",
+            property_set.name, XML_ELEM_PROPERTY_SET
+        );
+        return invalid_field_value(XML_ELEM_PROPERTY_SET, &property_set.name).into();
+    }
+    Ok(property_set
+        .children_named(XML_ELEM_PROPERTY)
+        .flat_map(|property| property.children.iter())
+        .map(|variable| (variable.local_name().to_string(), variable.text.clone()))
+        .collect())
+}
+
+fn route_address_to(url: &URL) -> Result<IpAddr, Error> {
+    let host = url.host().unwrap_or_default();
+    let port = url.port().unwrap_or(80);
+    let remote = match (host, port).to_socket_addrs()?.next() {
+        Some(remote) => remote,
+        None => {
+            error!("route_address_to - could not resolve host '{}'", host);
+            return invalid_field_value("host", host).into();
+        }
+    };
+    local_address_for(&remote)
+}
+
 // ------------------------------------------------------------------------------------------------
 // Unit Tests
 // ------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const EX_EVENT: &[u8] = b"NOTIFY / HTTP/1.1\r
+HOST: 192.168.1.20:49200\r
+CONTENT-TYPE: text/xml; charset=\"utf-8\"\r
+CONTENT-LENGTH: 188\r
+NT: upnp:event\r
+NTS: upnp:propchange\r
+SID: uuid:5e5f8e06-0000-1000-8000-0013a2b3c4d5\r
+SEQ: 3\r
+\r
+<?xml version=\"1.0\"?>
+<e:propertyset xmlns:e=\"urn:schemas-upnp-org:event-1-0\">
+  <e:property><Status>1</Status></e:property>
+  <e:property><Target>0</Target></e:property>
+</e:propertyset>
+";
+
+    #[test]
+    fn test_event_from_request() {
+        let request = Request::try_from(EX_EVENT).unwrap();
+        let event = Event::try_from(&request).unwrap();
+        assert_eq!(
+            event,
+            Event {
+                sid: "uuid:5e5f8e06-0000-1000-8000-0013a2b3c4d5".to_string(),
+                seq: 3,
+                properties: vec![
+                    ("Status".to_string(), "1".to_string()),
+                    ("Target".to_string(), "0".to_string()),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_timeout_values() {
+        assert_eq!(
+            timeout_from_str("Second-1800").unwrap(),
+            Some(Duration::from_secs(1800))
+        );
+        assert_eq!(timeout_from_str("second-infinite").unwrap(), None);
+        assert!(timeout_from_str("1800").is_err());
+        assert!(timeout_from_str("Second-soon").is_err());
+        assert_eq!(
+            timeout_to_string(Some(Duration::from_secs(300))),
+            "Second-300"
+        );
+        assert_eq!(timeout_to_string(None), "Second-infinite");
+    }
+}
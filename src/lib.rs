@@ -43,6 +43,22 @@
 * }
 * ```
 *
+* # Features
+*
+* * `serde` - implements `Serialize` and `Deserialize` for the discovery, description, and
+*   eventing types so that, for example, search results can be stored or device descriptions
+*   loaded from JSON or YAML. Structures use their field names as-is, fields holding lists and
+*   header maps may be omitted when deserializing, and the following types have fixed string or
+*   numeric representations:
+*   * `SpecVersion` as `"1.0"`, `"1.1"`, or `"2.0"`.
+*   * `SearchTarget` as the `ST`/`NT` header value, e.g. `"upnp:rootdevice"` or
+*     `"urn:schemas-upnp-org:device:MediaServer:1"`.
+*   * `TypeID` as its URN, e.g. `"urn:schemas-upnp-org:service:ContentDirectory:1"`.
+*   * `URI` and `URL` as the URI reference string.
+*   * `NotificationSubType` as the `NTS` header value, e.g. `"ssdp:alive"`.
+*   * Durations, such as `max_age`, as a whole number of seconds.
+*   * Other enumerations, such as `Direction`, as their lower case variant name.
+*
 * # Documentation
 *
 * Where possible any documentation for fields, functions, and values will be taken directly from the
@@ -139,6 +155,41 @@ impl FromStr for SpecVersion {
     }
 }
 
+// ------------------------------------------------------------------------------------------------
+// Private Macros
+// ------------------------------------------------------------------------------------------------
+
+///
+/// Implement `Serialize` and `Deserialize`, when the `serde` feature is enabled, for a type
+/// that is represented as a string using its `Display` and `FromStr` implementations.
+///
+macro_rules! serde_as_string {
+    ($type:ty) => {
+        #[cfg(feature = "serde")]
+        impl ::serde::Serialize for $type {
+            fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: ::serde::Serializer,
+            {
+                serializer.collect_str(self)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> ::serde::Deserialize<'de> for $type {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::serde::Deserializer<'de>,
+            {
+                let s = <String as ::serde::Deserialize<'de>>::deserialize(deserializer)?;
+                <$type as ::std::str::FromStr>::from_str(&s).map_err(::serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+serde_as_string!(SpecVersion);
+
 // ------------------------------------------------------------------------------------------------
 // Modules
 // ------------------------------------------------------------------------------------------------
@@ -19,6 +19,11 @@ default SSDP port number of `1900`.
 pub const MULTICAST_ADDRESS: &str = "239.255.255.250";
 pub const MULTICAST_PORT: u16 = 1900;
 
+/**
+The link-local scoped IPv6 multicast address reserved for SSDP.
+*/
+pub const MULTICAST_ADDRESS_V6_LINK_LOCAL: &str = "FF02::C";
+
 // ------------------------------------------------------------------------------------------------
 // Public Values -- HTTP (core)
 // ------------------------------------------------------------------------------------------------
@@ -92,6 +97,17 @@ when received.
 */
 pub const HTTP_HEADER_CACHE_CONTROL: &str = "CACHE-CONTROL";
 
+/**
+The size of the message body, in bytes. Required in any message that includes a body.
+*/
+pub const HTTP_HEADER_CONTENT_LENGTH: &str = "CONTENT-LENGTH";
+
+/**
+The media type of the message body, for control messages this is always `text/xml` with the
+`utf-8` character set.
+*/
+pub const HTTP_HEADER_CONTENT_TYPE: &str = "CONTENT-TYPE";
+
 /**
 The CONFIGID.UPNP.ORG field value MUST be a non-negative, 31-bit integer, ASCII encoded, decimal,
 without leading zeros (leading zeroes, if present, MUST be ignored by the recipient) that MUST
@@ -185,6 +201,19 @@ by the recipient) and MUST be greater than the field value of the BOOTID.UPNP.OR
 */
 pub const HTTP_HEADER_NEXT_BOOTID: &str = "NEXTBOOTID.UPNP.ORG";
 
+/**
+Declares, using the HTTP Extension Framework (RFC 2774), an extension used by the message and the
+namespace prefix for its headers, as in `"http://schemas.upnp.org/upnp/1/0/"; ns=01`. Not defined
+by UDA, but sent by many devices along with the `NLS` header.
+*/
+pub const HTTP_HEADER_OPT: &str = "OPT";
+
+/**
+The network location signature, a value that changes whenever the device restarts; sent with the
+namespace prefix declared in `OPT`, as in `01-NLS`.
+*/
+pub const HTTP_HEADER_NLS: &str = "NLS";
+
 /**
 If a device does not send the SEARCHPORT.UPNP.ORG header field, it MUST respond to unicast M-SEARCH
 messages on port 1900. Only if port 1900 is unavailable MAY a device select a different port to
@@ -273,10 +302,17 @@ pub const XML_ATTR_NAMESPACE: &str = "xmlns";
 // Public Values -- XML
 // ------------------------------------------------------------------------------------------------
 
+/**
+The namespaces for device and service descriptions. Note that these are used unchanged by all
+versions of the UDA, the version of a description is determined by its `specVersion` element.
+*/
 pub const XML_NS_DEVICE: &str = "urn:schemas-upnp-org:device-1-0";
 pub const XML_NS_SERVICE: &str = "urn:schemas-upnp-org:service-1-0";
 
+pub const XML_ATTR_CONFIG_ID: &str = "configId";
+pub const XML_ATTR_MULTICAST: &str = "multicast";
 pub const XML_ATTR_SEND_EVENTS: &str = "sendEvents";
+pub const XML_ATTR_TYPE: &str = "type";
 
 pub const XML_ELEM_ACTION: &str = "action";
 pub const XML_ELEM_ACTION_LIST: &str = "actionList";
@@ -322,7 +358,7 @@ pub const XML_ELEM_SERVICE_LIST: &str = "serviceList";
 pub const XML_ELEM_SERVICE_SCPD_URL: &str = "SCPDURL";
 pub const XML_ELEM_SERVICE_TYPE: &str = "serviceType";
 pub const XML_ELEM_REL_STATE_VARIABLE: &str = "relatedStateVariable";
-pub const XML_ELEM_SPCD: &str = "spcd";
+pub const XML_ELEM_SPCD: &str = "scpd";
 pub const XML_ELEM_SPEC_VERSION: &str = "specVersion";
 pub const XML_ELEM_STATE_TABLE: &str = "serviceStateTable";
 pub const XML_ELEM_STATE_VARIABLE: &str = "stateVariable";
@@ -335,7 +371,85 @@ pub const XML_ELEM_URL_BASE: &str = "URLBase";
 // Public Values -- SOAP
 // ------------------------------------------------------------------------------------------------
 
-pub const SOAP_NS_ENVELOPE: &str = "http://schemas.xmlsoap.org/common.soap/envelope/";
-pub const SOAP_NS_ENCODING: &str = "http://schemas.xmlsoap.org/common.soap/encoding/";
+pub const SOAP_NS_ENVELOPE: &str = "http://schemas.xmlsoap.org/soap/envelope/";
+pub const SOAP_NS_ENCODING: &str = "http://schemas.xmlsoap.org/soap/encoding/";
+pub const SOAP_NS_CONTROL: &str = "urn:schemas-upnp-org:control-1-0";
+
+pub const SOAP_PREFIX: &str = "s";
+/// The prefix conventionally bound to the service type namespace in action requests and
+/// responses.
+pub const SOAP_ACTION_PREFIX: &str = "u";
+/// Appended to the action name to form the element name in action responses.
+pub const SOAP_RESPONSE_SUFFIX: &str = "Response";
+
+pub const SOAP_ATTR_ENCODING_STYLE: &str = "encodingStyle";
+
+pub const SOAP_ELEM_BODY: &str = "Body";
+pub const SOAP_ELEM_DETAIL: &str = "detail";
+pub const SOAP_ELEM_ENVELOPE: &str = "Envelope";
+pub const SOAP_ELEM_ERROR_CODE: &str = "errorCode";
+pub const SOAP_ELEM_ERROR_DESCRIPTION: &str = "errorDescription";
+pub const SOAP_ELEM_FAULT: &str = "Fault";
+pub const SOAP_ELEM_FAULT_CODE: &str = "faultcode";
+pub const SOAP_ELEM_FAULT_STRING: &str = "faultstring";
+pub const SOAP_ELEM_UPNP_ERROR: &str = "UPnPError";
+
+/// The `faultcode` value for all UPnP errors, the fault is always the client's.
+pub const SOAP_FAULT_CODE_CLIENT: &str = "Client";
+/// The `faultstring` value for all UPnP errors.
+pub const SOAP_FAULT_STRING_UPNP: &str = "UPnPError";
 
 pub const SOAP_HTTP_HEADER_ACTION: &str = "SOAPACTION";
+
+pub const SOAP_CONTENT_TYPE: &str = "text/xml; charset=\"utf-8\"";
+
+// ------------------------------------------------------------------------------------------------
+// Public Values -- GENA
+// ------------------------------------------------------------------------------------------------
+
+/**
+From § 4.1 Eventing: Subscription, sent by a control point to subscribe, or renew a subscription.
+*/
+pub const HTTP_METHOD_SUBSCRIBE: &str = "SUBSCRIBE";
+
+/**
+From § 4.1 Eventing: Subscription, sent by a control point to cancel a subscription.
+*/
+pub const HTTP_METHOD_UNSUBSCRIBE: &str = "UNSUBSCRIBE";
+
+/**
+Field value contains the location to send events to, one or more URLs each enclosed in angle
+brackets; the device tries each in turn until one succeeds.
+*/
+pub const HTTP_HEADER_CALLBACK: &str = "CALLBACK";
+
+/**
+Field value contains the event key, a 32-bit unsigned integer that is `0` for the initial event
+message and is incremented for each subsequent message, wrapping to `1` rather than `0`.
+*/
+pub const HTTP_HEADER_SEQ: &str = "SEQ";
+
+/**
+Field value contains the Subscription Identifier, assigned by the device in its response to a
+subscription request and used by the control point to renew or cancel the subscription.
+*/
+pub const HTTP_HEADER_SID: &str = "SID";
+
+/**
+Field value contains the requested, or actual, duration until the subscription expires, in the
+form `Second-` followed by an integer number of seconds, or `Second-infinite`.
+*/
+pub const HTTP_HEADER_TIMEOUT: &str = "TIMEOUT";
+
+pub const GENA_NT_EVENT: &str = "upnp:event";
+
+pub const GENA_NTS_PROPERTY_CHANGE: &str = "upnp:propchange";
+
+pub const GENA_TIMEOUT_PREFIX: &str = "Second-";
+
+pub const GENA_TIMEOUT_INFINITE: &str = "infinite";
+
+pub const XML_NS_EVENT: &str = "urn:schemas-upnp-org:event-1-0";
+
+pub const XML_ELEM_PROPERTY: &str = "property";
+pub const XML_ELEM_PROPERTY_SET: &str = "propertyset";